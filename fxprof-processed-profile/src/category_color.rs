@@ -1,3 +1,4 @@
+use serde::de::{Deserialize, Deserializer, Error};
 use serde::ser::{Serialize, Serializer};
 
 /// One of the available colors for a category.
@@ -18,6 +19,33 @@ pub enum CategoryColor {
     DarkGray,
 }
 
+impl<'de> Deserialize<'de> for CategoryColor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let color = String::deserialize(deserializer)?;
+        let color = match color.as_str() {
+            "transparent" => CategoryColor::Transparent,
+            "purple" => CategoryColor::Purple,
+            "green" => CategoryColor::Green,
+            "orange" => CategoryColor::Orange,
+            "yellow" => CategoryColor::Yellow,
+            "lightblue" => CategoryColor::LightBlue,
+            "grey" => CategoryColor::Grey,
+            "blue" => CategoryColor::Blue,
+            "brown" => CategoryColor::Brown,
+            "lightgreen" => CategoryColor::LightGreen,
+            "red" => CategoryColor::Red,
+            "lightred" => CategoryColor::LightRed,
+            "darkgray" => CategoryColor::DarkGray,
+            other => {
+                return Err(D::Error::custom(format!(
+                    "unknown category color {other:?}"
+                )))
+            }
+        };
+        Ok(color)
+    }
+}
+
 impl Serialize for CategoryColor {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
         match self {
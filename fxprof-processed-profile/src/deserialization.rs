@@ -0,0 +1,800 @@
+use std::convert::TryFrom;
+
+use debugid::DebugId;
+use serde::de::{Deserialize, Deserializer, Error};
+use serde_json::Value;
+
+use crate::category::{
+    Category, CategoryHandle, CategoryPairHandle, Subcategory, SubcategoryIndex,
+};
+use crate::category_color::CategoryColor;
+use crate::counters::Counter;
+use crate::cpu_delta::CpuDelta;
+use crate::fast_hash_map::FastHashMap;
+use crate::frame_table::{FrameTable, InternalFrame, InternalFrameLocation};
+use crate::func_table::{FuncIndex, FuncTable};
+use crate::global_lib_table::{GlobalLibIndex, GlobalLibTable};
+use crate::lib_info::Lib;
+use crate::marker_table::MarkerTable;
+use crate::native_allocations::NativeAllocationTable;
+use crate::native_symbols::{NativeSymbolIndex, NativeSymbols};
+use crate::process::{Process, ThreadHandle};
+use crate::profile::{PausedRangeReason, PREPROCESSED_PROFILE_VERSION};
+use crate::resource_table::{ResourceIndex, ResourceTable};
+use crate::sample_table::{SampleTable, WeightType};
+use crate::stack_table::StackTable;
+use crate::string_table::StringIndex;
+use crate::thread::{ProcessHandle, Thread, ThreadTables};
+use crate::thread_string_table::{ThreadInternalStringIndex, ThreadStringTable};
+use crate::{MarkerTiming, Profile, ReferenceTimestamp, SamplingInterval, Timestamp};
+
+/// Reads a profile in the processed profile format, as it is written by the
+/// [`Serialize`](serde::Serialize) implementation of [`Profile`].
+///
+/// Only `preprocessedProfileVersion` 46 is supported. Information which
+/// [`Profile`] doesn't model, such as the `line` column of the frame table or
+/// all but the first sample group of a counter, is dropped. Library address ranges aren't part of the
+/// format, so samples which are added to the deserialized profile can only
+/// refer to libraries which are added again with [`Profile::add_lib`].
+impl<'de> Deserialize<'de> for Profile {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = RawProfile::deserialize(deserializer)?;
+        raw.into_profile()
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct RawProfile {
+    meta: RawMeta,
+    libs: Vec<RawLib>,
+    threads: Vec<RawThread>,
+    #[serde(default)]
+    counters: Vec<RawCounter>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawMeta {
+    categories: Vec<RawCategory>,
+    interval: f64,
+    preprocessed_profile_version: u32,
+    product: String,
+    start_time: f64,
+    #[serde(default)]
+    marker_schema: Vec<Value>,
+    #[serde(default)]
+    extra: Vec<RawMetaExtra>,
+    #[serde(default)]
+    paused_ranges: Vec<RawPausedRange>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawPausedRange {
+    start_time: Option<f64>,
+    end_time: Option<f64>,
+    reason: String,
+}
+
+#[derive(serde::Deserialize)]
+struct RawMetaExtra {
+    label: String,
+    entries: Vec<RawMetaExtraEntry>,
+}
+
+#[derive(serde::Deserialize)]
+struct RawMetaExtraEntry {
+    label: String,
+    value: Value,
+}
+
+#[derive(serde::Deserialize)]
+struct RawCategory {
+    name: String,
+    color: CategoryColor,
+    subcategories: Vec<String>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawLib {
+    name: String,
+    path: String,
+    debug_name: String,
+    debug_path: String,
+    breakpad_id: String,
+    code_id: Option<String>,
+    arch: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawThread {
+    frame_table: RawFrameTable,
+    func_table: RawFuncTable,
+    markers: RawMarkerTable,
+    name: String,
+    is_main_thread: bool,
+    native_symbols: RawNativeSymbols,
+    native_allocations: Option<RawNativeAllocationTable>,
+    #[serde(deserialize_with = "deserialize_id")]
+    pid: String,
+    process_name: Option<String>,
+    process_shutdown_time: Option<f64>,
+    process_startup_time: f64,
+    register_time: f64,
+    resource_table: RawResourceTable,
+    samples: RawSampleTable,
+    stack_table: RawStackTable,
+    string_array: Vec<String>,
+    #[serde(deserialize_with = "deserialize_id")]
+    tid: String,
+    unregister_time: Option<f64>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawFrameTable {
+    length: usize,
+    address: Vec<i64>,
+    category: Vec<Option<u16>>,
+    subcategory: Vec<Option<usize>>,
+    func: Vec<usize>,
+    native_symbol: Vec<Option<usize>>,
+}
+
+#[derive(serde::Deserialize)]
+struct RawFuncTable {
+    length: usize,
+    name: Vec<usize>,
+    resource: Vec<i64>,
+}
+
+#[derive(serde::Deserialize)]
+struct RawResourceTable {
+    length: usize,
+    lib: Vec<Option<usize>>,
+    name: Vec<usize>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawNativeSymbols {
+    length: usize,
+    address: Vec<u64>,
+    function_size: Vec<Option<u64>>,
+    lib_index: Vec<usize>,
+    name: Vec<usize>,
+}
+
+#[derive(serde::Deserialize)]
+struct RawStackTable {
+    length: usize,
+    prefix: Vec<Option<usize>>,
+    frame: Vec<usize>,
+    category: Vec<Option<u16>>,
+    subcategory: Vec<Option<usize>>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawSampleTable {
+    length: usize,
+    stack: Vec<Option<usize>>,
+    time: Vec<f64>,
+    weight: Option<Vec<Option<f64>>>,
+    weight_type: Option<String>,
+    #[serde(rename = "threadCPUDelta")]
+    thread_cpu_delta: Option<Vec<Option<f64>>>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawNativeAllocationTable {
+    length: usize,
+    memory_address: Vec<u64>,
+    stack: Vec<Option<usize>>,
+    time: Vec<f64>,
+    weight: Vec<i64>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawMarkerTable {
+    length: usize,
+    data: Vec<Value>,
+    end_time: Vec<Option<f64>>,
+    name: Vec<usize>,
+    phase: Vec<u8>,
+    start_time: Vec<Option<f64>>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawCounter {
+    name: String,
+    category: String,
+    description: String,
+    #[serde(deserialize_with = "deserialize_id")]
+    pid: String,
+    sample_groups: Vec<RawCounterSampleGroup>,
+}
+
+#[derive(serde::Deserialize)]
+struct RawCounterSampleGroup {
+    samples: RawCounterSamples,
+}
+
+#[derive(serde::Deserialize)]
+struct RawCounterSamples {
+    length: usize,
+    count: Vec<f64>,
+    number: Option<Vec<u32>>,
+    time: Vec<f64>,
+}
+
+/// Pids and tids are strings in profiles written by this crate, but older
+/// profiles can have them as numbers.
+fn deserialize_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum Id {
+        String(String),
+        Number(u64),
+    }
+    Ok(match Id::deserialize(deserializer)? {
+        Id::String(s) => s,
+        Id::Number(n) => n.to_string(),
+    })
+}
+
+/// Times are stored as nanoseconds since the reference timestamp, so times
+/// before `meta.startTime` can't be represented and are rejected.
+fn timestamp_from_millis<E: Error>(millis: f64) -> Result<Timestamp, E> {
+    if !millis.is_finite() || millis < 0.0 {
+        return Err(E::custom(format!(
+            "time {millis} is not a non-negative number of milliseconds since meta.startTime"
+        )));
+    }
+    // Round instead of truncating, so that serializing the profile again
+    // produces the same values.
+    Ok(Timestamp::from_nanos_since_reference(
+        (millis * 1_000_000.0).round() as u64,
+    ))
+}
+
+fn check_column_len<E: Error>(
+    table: &str,
+    column: &str,
+    actual: usize,
+    expected: usize,
+) -> Result<(), E> {
+    if actual != expected {
+        return Err(E::custom(format!(
+            "column {table}.{column} has {actual} elements, expected {expected}"
+        )));
+    }
+    Ok(())
+}
+
+fn check_index<E: Error>(what: &str, index: usize, len: usize) -> Result<usize, E> {
+    if index >= len {
+        return Err(E::custom(format!(
+            "{what} index {index} is out of range (length {len})"
+        )));
+    }
+    Ok(index)
+}
+
+/// Remembers the pid or tid so that pids and tids which are added later get
+/// a unique suffix, see `Profile::make_unique_pid_or_tid`.
+fn note_used_id(map: &mut FastHashMap<u32, u32>, id: &str) {
+    let (base, suffix) = match id.split_once('.') {
+        Some((base, suffix)) => (base, suffix.parse::<u32>().ok()),
+        None => (id, Some(0)),
+    };
+    if let (Ok(base), Some(suffix)) = (base.parse::<u32>(), suffix) {
+        let next_suffix = map.entry(base).or_insert(0);
+        *next_suffix = (*next_suffix).max(suffix + 1);
+    }
+}
+
+struct CategoryConverter<'a> {
+    categories: &'a [Category],
+}
+
+impl<'a> CategoryConverter<'a> {
+    fn convert<E: Error>(
+        &self,
+        category: Option<u16>,
+        subcategory: Option<usize>,
+    ) -> Result<(CategoryHandle, Subcategory), E> {
+        let category_index = category.unwrap_or(0);
+        let category_info = self
+            .categories
+            .get(category_index as usize)
+            .ok_or_else(|| E::custom(format!("category {category_index} does not exist")))?;
+        let category = CategoryHandle(category_index);
+        let subcategory = match subcategory {
+            // The subcategory after the last declared subcategory is the implicit
+            // "Other" subcategory.
+            Some(index) if index < category_info.subcategories.len() => {
+                Subcategory::Normal(SubcategoryIndex(index as u8))
+            }
+            Some(index) if index > category_info.subcategories.len() => {
+                return Err(E::custom(format!(
+                    "subcategory {index} does not exist in category {category_index}"
+                )))
+            }
+            _ => Subcategory::Other(category),
+        };
+        Ok((category, subcategory))
+    }
+}
+
+fn category_pair(category: CategoryHandle, subcategory: &Subcategory) -> CategoryPairHandle {
+    match subcategory {
+        Subcategory::Normal(index) => CategoryPairHandle(category, Some(*index)),
+        Subcategory::Other(_) => CategoryPairHandle(category, None),
+    }
+}
+
+impl RawProfile {
+    fn into_profile<E: Error>(self) -> Result<Profile, E> {
+        let RawProfile {
+            meta,
+            libs,
+            threads,
+            counters,
+        } = self;
+        if meta.preprocessed_profile_version != PREPROCESSED_PROFILE_VERSION {
+            return Err(E::custom(format!(
+                "unsupported preprocessedProfileVersion {}, expected {}",
+                meta.preprocessed_profile_version, PREPROCESSED_PROFILE_VERSION
+            )));
+        }
+
+        let categories: Vec<Category> = meta
+            .categories
+            .into_iter()
+            .map(|raw| {
+                let mut subcategories = raw.subcategories;
+                // The serializer appends the implicit "Other" subcategory.
+                if subcategories.last().map(String::as_str) == Some("Other") {
+                    subcategories.pop();
+                }
+                Category {
+                    name: raw.name,
+                    color: raw.color,
+                    subcategories,
+                }
+            })
+            .collect();
+        if categories.is_empty() {
+            return Err(E::custom("the profile has no categories"));
+        }
+
+        let libs = libs
+            .into_iter()
+            .map(|raw| {
+                let debug_id = DebugId::from_breakpad(&raw.breakpad_id)
+                    .map_err(|_| E::custom(format!("invalid breakpadId {:?}", raw.breakpad_id)))?;
+                Ok(Lib {
+                    name: raw.name,
+                    debug_name: raw.debug_name,
+                    path: raw.path,
+                    debug_path: raw.debug_path,
+                    arch: raw.arch,
+                    debug_id,
+                    code_id: raw.code_id,
+                    symbol_table: None,
+                })
+            })
+            .collect::<Result<Vec<_>, E>>()?;
+
+        let mut profile = Profile::new(
+            &meta.product,
+            ReferenceTimestamp::from_millis_since_unix_epoch(meta.start_time),
+            SamplingInterval::from_nanos((meta.interval * 1_000_000.0).round() as u64),
+        );
+        profile.categories = categories;
+        profile.global_libs = GlobalLibTable::from_libs(libs);
+        profile.sampled_event_name = meta
+            .extra
+            .iter()
+            .filter(|extra| extra.label == "Sampling")
+            .flat_map(|extra| &extra.entries)
+            .find(|entry| entry.label == "Event")
+            .and_then(|entry| entry.value.as_str())
+            .map(ToOwned::to_owned);
+        // Ranges which are open-ended, i.e. which have no start or end time, are dropped.
+        for range in meta.paused_ranges {
+            let reason = PausedRangeReason::parse(&range.reason).ok_or_else(|| {
+                E::custom(format!(
+                    "unsupported paused range reason {:?}",
+                    range.reason
+                ))
+            })?;
+            if let (Some(start), Some(end)) = (range.start_time, range.end_time) {
+                profile.add_paused_range(
+                    timestamp_from_millis(start)?,
+                    timestamp_from_millis(end)?,
+                    reason,
+                );
+            }
+        }
+
+        for schema in meta.marker_schema {
+            let name = match schema.get("name").and_then(Value::as_str) {
+                Some(name) => name.to_owned(),
+                None => return Err(E::custom("marker schema without a name")),
+            };
+            profile.deserialized_marker_schemas.insert(name, schema);
+        }
+
+        let mut processes_by_pid: FastHashMap<String, ProcessHandle> = FastHashMap::default();
+        for raw_thread in threads {
+            let process = match processes_by_pid.get(&raw_thread.pid) {
+                Some(process) => *process,
+                None => {
+                    let process = ProcessHandle(profile.processes.len());
+                    let mut process_info = Process::new(
+                        raw_thread.process_name.as_deref().unwrap_or(""),
+                        raw_thread.pid.clone(),
+                        timestamp_from_millis(raw_thread.process_startup_time)?,
+                    );
+                    if let Some(end_time) = raw_thread.process_shutdown_time {
+                        process_info.set_end_time(timestamp_from_millis(end_time)?);
+                    }
+                    note_used_id(&mut profile.used_pids, &raw_thread.pid);
+                    profile.processes.push(process_info);
+                    processes_by_pid.insert(raw_thread.pid.clone(), process);
+                    process
+                }
+            };
+            note_used_id(&mut profile.used_tids, &raw_thread.tid);
+            let thread = raw_thread.into_thread(
+                process,
+                &CategoryConverter {
+                    categories: &profile.categories,
+                },
+                profile.global_libs.len(),
+            )?;
+            let thread_handle = ThreadHandle(profile.threads.len());
+            profile.threads.push(thread);
+            profile.processes[process.0].add_thread(thread_handle);
+        }
+
+        for raw_counter in counters {
+            let process = *processes_by_pid.get(&raw_counter.pid).ok_or_else(|| {
+                E::custom(format!(
+                    "counter {} refers to unknown pid {}",
+                    raw_counter.name, raw_counter.pid
+                ))
+            })?;
+            let mut counter = Counter::new(
+                &raw_counter.name,
+                &raw_counter.category,
+                &raw_counter.description,
+                process,
+            );
+            if let Some(group) = raw_counter.sample_groups.into_iter().next() {
+                let samples = group.samples;
+                let len = samples.length;
+                check_column_len("counter.samples", "count", samples.count.len(), len)?;
+                check_column_len("counter.samples", "time", samples.time.len(), len)?;
+                let number = match samples.number {
+                    Some(number) => {
+                        check_column_len("counter.samples", "number", number.len(), len)?;
+                        number
+                    }
+                    None => vec![0; len],
+                };
+                for ((time, count), number) in
+                    samples.time.into_iter().zip(samples.count).zip(number)
+                {
+                    counter.add_sample(timestamp_from_millis(time)?, count, number);
+                }
+            }
+            profile.counters.push(counter);
+        }
+
+        Ok(profile)
+    }
+}
+
+impl RawThread {
+    fn into_thread<E: Error>(
+        self,
+        process: ProcessHandle,
+        categories: &CategoryConverter,
+        lib_count: usize,
+    ) -> Result<Thread, E> {
+        let string_count = self.string_array.len();
+        let string_table = ThreadStringTable::from_strings(self.string_array);
+        let string_index = |index: usize| -> Result<ThreadInternalStringIndex, E> {
+            check_index("string", index, string_count)?;
+            Ok(ThreadInternalStringIndex(StringIndex(index as u32)))
+        };
+        let lib_index = |index: usize| -> Result<GlobalLibIndex, E> {
+            check_index("lib", index, lib_count)?;
+            Ok(GlobalLibIndex(index))
+        };
+
+        // resourceTable
+        let raw = self.resource_table;
+        check_column_len("resourceTable", "lib", raw.lib.len(), raw.length)?;
+        check_column_len("resourceTable", "name", raw.name.len(), raw.length)?;
+        let resource_libs = raw
+            .lib
+            .into_iter()
+            .map(|lib| match lib {
+                Some(lib) => lib_index(lib),
+                None => Err(E::custom("only library resources are supported")),
+            })
+            .collect::<Result<Vec<_>, E>>()?;
+        let resource_names = raw
+            .name
+            .into_iter()
+            .map(string_index)
+            .collect::<Result<Vec<_>, E>>()?;
+        let resources = ResourceTable::from_columns(resource_libs.clone(), resource_names);
+
+        // funcTable
+        let raw = self.func_table;
+        check_column_len("funcTable", "name", raw.name.len(), raw.length)?;
+        check_column_len("funcTable", "resource", raw.resource.len(), raw.length)?;
+        let func_names = raw
+            .name
+            .into_iter()
+            .map(string_index)
+            .collect::<Result<Vec<_>, E>>()?;
+        let func_resources = raw
+            .resource
+            .into_iter()
+            .map(|resource| match usize::try_from(resource) {
+                Ok(resource) => {
+                    check_index("resource", resource, resource_libs.len())?;
+                    Ok(Some(ResourceIndex(resource as u32)))
+                }
+                Err(_) => Ok(None),
+            })
+            .collect::<Result<Vec<_>, E>>()?;
+        let func_count = func_names.len();
+
+        // nativeSymbols
+        let raw = self.native_symbols;
+        check_column_len("nativeSymbols", "address", raw.address.len(), raw.length)?;
+        check_column_len(
+            "nativeSymbols",
+            "functionSize",
+            raw.function_size.len(),
+            raw.length,
+        )?;
+        check_column_len("nativeSymbols", "libIndex", raw.lib_index.len(), raw.length)?;
+        check_column_len("nativeSymbols", "name", raw.name.len(), raw.length)?;
+        let native_symbol_count = raw.length;
+        let native_symbols = NativeSymbols::from_columns(
+            raw.address,
+            raw.function_size,
+            raw.lib_index
+                .into_iter()
+                .map(lib_index)
+                .collect::<Result<Vec<_>, E>>()?,
+            raw.name
+                .into_iter()
+                .map(string_index)
+                .collect::<Result<Vec<_>, E>>()?,
+        );
+
+        // frameTable
+        let raw = self.frame_table;
+        let len = raw.length;
+        check_column_len("frameTable", "address", raw.address.len(), len)?;
+        check_column_len("frameTable", "category", raw.category.len(), len)?;
+        check_column_len("frameTable", "subcategory", raw.subcategory.len(), len)?;
+        check_column_len("frameTable", "func", raw.func.len(), len)?;
+        check_column_len("frameTable", "nativeSymbol", raw.native_symbol.len(), len)?;
+        let mut frame_addresses = Vec::with_capacity(len);
+        let mut frame_categories = Vec::with_capacity(len);
+        let mut frame_subcategories = Vec::with_capacity(len);
+        let mut frame_funcs = Vec::with_capacity(len);
+        let mut frame_native_symbols = Vec::with_capacity(len);
+        let mut internal_frames = Vec::with_capacity(len);
+        for i in 0..len {
+            let address = u64::try_from(raw.address[i]).ok();
+            let (category, subcategory) =
+                categories.convert(raw.category[i], raw.subcategory[i])?;
+            let func = check_index("func", raw.func[i], func_count)?;
+            let native_symbol = match raw.native_symbol[i] {
+                Some(index) => {
+                    Some(NativeSymbolIndex(
+                        check_index("nativeSymbol", index, native_symbol_count)? as u32,
+                    ))
+                }
+                None => None,
+            };
+
+            // Reconstruct the key which the frame would have been created with,
+            // so that adding the same frame again reuses this frame.
+            let func_name = func_names[func];
+            let func_lib = func_resources[func].map(|resource| resource_libs[resource.0 as usize]);
+            let location = match (address, func_lib) {
+                (Some(address), Some(lib)) => InternalFrameLocation::AddressInLib(address, lib),
+                _ => {
+                    let name = string_table.get_string(func_name).unwrap_or_default();
+                    match name
+                        .strip_prefix("0x")
+                        .and_then(|hex| u64::from_str_radix(hex, 16).ok())
+                    {
+                        Some(address) => InternalFrameLocation::UnknownAddress(address),
+                        None => InternalFrameLocation::Label(func_name),
+                    }
+                }
+            };
+            internal_frames.push(InternalFrame {
+                location,
+                category_pair: category_pair(category, &subcategory),
+            });
+
+            frame_addresses.push(address);
+            frame_categories.push(category);
+            frame_subcategories.push(subcategory);
+            frame_funcs.push(FuncIndex(func as u32));
+            frame_native_symbols.push(native_symbol);
+        }
+        let frame_table = FrameTable::from_columns(
+            frame_addresses,
+            frame_categories,
+            frame_subcategories,
+            frame_funcs,
+            frame_native_symbols,
+            internal_frames,
+        );
+        let func_table = FuncTable::from_columns(func_names, func_resources);
+
+        // stackTable
+        let raw = self.stack_table;
+        let len = raw.length;
+        check_column_len("stackTable", "prefix", raw.prefix.len(), len)?;
+        check_column_len("stackTable", "frame", raw.frame.len(), len)?;
+        check_column_len("stackTable", "category", raw.category.len(), len)?;
+        check_column_len("stackTable", "subcategory", raw.subcategory.len(), len)?;
+        let mut stack_categories = Vec::with_capacity(len);
+        let mut stack_subcategories = Vec::with_capacity(len);
+        for i in 0..len {
+            if let Some(prefix) = raw.prefix[i] {
+                // Prefixes always come before the stacks that refer to them.
+                check_index("prefix", prefix, i)?;
+            }
+            check_index("frame", raw.frame[i], frame_table.len())?;
+            let (category, subcategory) =
+                categories.convert(raw.category[i], raw.subcategory[i])?;
+            stack_categories.push(category);
+            stack_subcategories.push(subcategory);
+        }
+        let stack_table =
+            StackTable::from_columns(raw.prefix, raw.frame, stack_categories, stack_subcategories);
+
+        // samples
+        let raw = self.samples;
+        let len = raw.length;
+        check_column_len("samples", "stack", raw.stack.len(), len)?;
+        check_column_len("samples", "time", raw.time.len(), len)?;
+        let weight_type = match &raw.weight_type {
+            Some(weight_type) => WeightType::parse(weight_type).ok_or_else(|| {
+                E::custom(format!("unsupported sample weight type {weight_type:?}"))
+            })?,
+            None => WeightType::Samples,
+        };
+        for stack in raw.stack.iter().flatten() {
+            check_index("stack", *stack, stack_table.len())?;
+        }
+        let weights = match raw.weight {
+            Some(weights) => {
+                check_column_len("samples", "weight", weights.len(), len)?;
+                weights
+                    .into_iter()
+                    .map(|w| w.map_or(1, |w| w.round() as i64))
+                    .collect()
+            }
+            None => vec![1; len],
+        };
+        let cpu_deltas = match raw.thread_cpu_delta {
+            Some(cpu_deltas) => {
+                check_column_len("samples", "threadCPUDelta", cpu_deltas.len(), len)?;
+                cpu_deltas
+                    .into_iter()
+                    .map(|micros| match micros {
+                        Some(micros) => CpuDelta::from_micros(micros.round() as u64),
+                        None => CpuDelta::ZERO,
+                    })
+                    .collect()
+            }
+            None => vec![CpuDelta::ZERO; len],
+        };
+        let samples = SampleTable::from_columns(
+            weight_type,
+            weights,
+            raw.time
+                .into_iter()
+                .map(timestamp_from_millis)
+                .collect::<Result<_, E>>()?,
+            raw.stack,
+            cpu_deltas,
+        );
+
+        // nativeAllocations
+        let native_allocations = match self.native_allocations {
+            Some(raw) => {
+                let len = raw.length;
+                check_column_len(
+                    "nativeAllocations",
+                    "memoryAddress",
+                    raw.memory_address.len(),
+                    len,
+                )?;
+                check_column_len("nativeAllocations", "stack", raw.stack.len(), len)?;
+                check_column_len("nativeAllocations", "time", raw.time.len(), len)?;
+                check_column_len("nativeAllocations", "weight", raw.weight.len(), len)?;
+                for stack in raw.stack.iter().flatten() {
+                    check_index("stack", *stack, stack_table.len())?;
+                }
+                NativeAllocationTable::from_columns(
+                    raw.time
+                        .into_iter()
+                        .map(timestamp_from_millis)
+                        .collect::<Result<_, E>>()?,
+                    raw.stack,
+                    raw.weight,
+                    raw.memory_address,
+                )
+            }
+            None => NativeAllocationTable::new(),
+        };
+
+        // markers
+        let raw = self.markers;
+        let len = raw.length;
+        check_column_len("markers", "data", raw.data.len(), len)?;
+        check_column_len("markers", "endTime", raw.end_time.len(), len)?;
+        check_column_len("markers", "name", raw.name.len(), len)?;
+        check_column_len("markers", "phase", raw.phase.len(), len)?;
+        check_column_len("markers", "startTime", raw.start_time.len(), len)?;
+        let mut markers = MarkerTable::new();
+        for (i, data) in raw.data.into_iter().enumerate() {
+            let start = timestamp_from_millis(raw.start_time[i].unwrap_or(0.0))?;
+            let end = timestamp_from_millis(raw.end_time[i].unwrap_or(0.0))?;
+            let timing = match raw.phase[i] {
+                0 => MarkerTiming::Instant(start),
+                1 => MarkerTiming::Interval(start, end),
+                2 => MarkerTiming::IntervalStart(start),
+                3 => MarkerTiming::IntervalEnd(end),
+                phase => return Err(E::custom(format!("unknown marker phase {phase}"))),
+            };
+            markers.add_marker(string_index(raw.name[i])?, timing, data);
+        }
+
+        Ok(Thread::from_tables(
+            process,
+            self.tid,
+            Some(self.name),
+            timestamp_from_millis(self.register_time)?,
+            self.unregister_time
+                .map(timestamp_from_millis)
+                .transpose()?,
+            self.is_main_thread,
+            ThreadTables {
+                stack_table,
+                frame_table,
+                func_table,
+                samples,
+                native_allocations,
+                markers,
+                resources,
+                native_symbols,
+                string_table,
+            },
+        ))
+    }
+}
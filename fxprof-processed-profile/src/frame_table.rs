@@ -13,7 +13,7 @@ use crate::thread_string_table::{ThreadInternalStringIndex, ThreadStringTable};
 
 #[derive(Debug, Clone, Default)]
 pub struct FrameTable {
-    addresses: Vec<Option<u32>>,
+    addresses: Vec<Option<u64>>,
     categories: Vec<CategoryHandle>,
     subcategories: Vec<Subcategory>,
     funcs: Vec<FuncIndex>,
@@ -26,6 +26,34 @@ impl FrameTable {
         Default::default()
     }
 
+    /// Create a frame table from existing columns. `internal_frames` contains
+    /// the deduplication key for each frame.
+    pub fn from_columns(
+        addresses: Vec<Option<u64>>,
+        categories: Vec<CategoryHandle>,
+        subcategories: Vec<Subcategory>,
+        funcs: Vec<FuncIndex>,
+        native_symbols: Vec<Option<NativeSymbolIndex>>,
+        internal_frames: Vec<InternalFrame>,
+    ) -> Self {
+        let mut internal_frame_to_frame_index = FastHashMap::default();
+        for (i, frame) in internal_frames.into_iter().enumerate() {
+            internal_frame_to_frame_index.entry(frame).or_insert(i);
+        }
+        Self {
+            addresses,
+            categories,
+            subcategories,
+            funcs,
+            native_symbols,
+            internal_frame_to_frame_index,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.addresses.len()
+    }
+
     pub fn index_for_frame(
         &mut self,
         string_table: &mut ThreadStringTable,
@@ -134,7 +162,7 @@ impl<'a> Serialize for SerializableFrameTable<'a> {
     }
 }
 
-struct SerializableFrameTableAddressColumn<'a>(&'a [Option<u32>]);
+struct SerializableFrameTableAddressColumn<'a>(&'a [Option<u64>]);
 
 impl<'a> Serialize for SerializableFrameTableAddressColumn<'a> {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
@@ -158,6 +186,6 @@ pub struct InternalFrame {
 #[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
 pub enum InternalFrameLocation {
     UnknownAddress(u64),
-    AddressInLib(u32, GlobalLibIndex),
+    AddressInLib(u64, GlobalLibIndex),
     Label(ThreadInternalStringIndex),
 }
@@ -18,6 +18,26 @@ impl FuncTable {
         Default::default()
     }
 
+    pub fn from_columns(
+        names: Vec<ThreadInternalStringIndex>,
+        resources: Vec<Option<ResourceIndex>>,
+    ) -> Self {
+        let mut func_name_and_resource_to_func_index = FastHashMap::default();
+        for (i, key) in names
+            .iter()
+            .cloned()
+            .zip(resources.iter().cloned())
+            .enumerate()
+        {
+            func_name_and_resource_to_func_index.entry(key).or_insert(i);
+        }
+        Self {
+            names,
+            resources,
+            func_name_and_resource_to_func_index,
+        }
+    }
+
     pub fn index_for_func(
         &mut self,
         name: ThreadInternalStringIndex,
@@ -39,7 +59,7 @@ impl FuncTable {
 }
 
 #[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash)]
-pub struct FuncIndex(u32);
+pub struct FuncIndex(pub(crate) u32);
 
 impl Serialize for FuncIndex {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
@@ -1,7 +1,10 @@
+use std::sync::Arc;
+
 use serde::ser::{Serialize, Serializer};
 
 use crate::fast_hash_map::FastHashMap;
 use crate::lib_info::Lib;
+use crate::SymbolTable;
 
 #[derive(Debug)]
 pub struct GlobalLibTable {
@@ -17,6 +20,20 @@ impl GlobalLibTable {
         }
     }
 
+    /// Create a lib table from an existing list of libs, keeping the lib
+    /// indexes intact.
+    pub fn from_libs(libs: Vec<Lib>) -> Self {
+        let mut lib_map = FastHashMap::default();
+        for (i, lib) in libs.iter().enumerate() {
+            lib_map.entry(lib.clone()).or_insert(GlobalLibIndex(i));
+        }
+        Self { libs, lib_map }
+    }
+
+    pub fn len(&self) -> usize {
+        self.libs.len()
+    }
+
     pub fn index_for_lib(&mut self, lib: Lib) -> GlobalLibIndex {
         let libs = &mut self.libs;
         *self.lib_map.entry(lib.clone()).or_insert_with(|| {
@@ -29,6 +46,19 @@ impl GlobalLibTable {
     pub fn get_lib(&self, index: GlobalLibIndex) -> Option<&Lib> {
         self.libs.get(index.0)
     }
+
+    pub fn set_symbol_table(
+        &mut self,
+        index: GlobalLibIndex,
+        symbol_table: Option<Arc<SymbolTable>>,
+    ) {
+        let lib = &mut self.libs[index.0];
+        if self.lib_map.get(lib) == Some(&index) {
+            self.lib_map.remove(lib);
+        }
+        lib.symbol_table = symbol_table;
+        self.lib_map.entry(lib.clone()).or_insert(index);
+    }
 }
 
 impl Serialize for GlobalLibTable {
@@ -38,7 +68,7 @@ impl Serialize for GlobalLibTable {
 }
 
 #[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash)]
-pub struct GlobalLibIndex(usize);
+pub struct GlobalLibIndex(pub(crate) usize);
 
 impl Serialize for GlobalLibIndex {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
@@ -7,6 +7,10 @@
 //! information into it. To convert it to JSON, use [`serde_json`], for
 //! example [`serde_json::to_writer`] or [`serde_json::to_string`].
 //!
+//! Existing profile JSON can be read back into a [`Profile`] with
+//! [`serde_json::from_reader`] or [`serde_json::from_str`], modified, and
+//! written out again.
+//!
 //! ## Example
 //!
 //! ```
@@ -34,7 +38,9 @@ pub use debugid;
 
 mod category;
 mod category_color;
+mod counters;
 mod cpu_delta;
+mod deserialization;
 mod fast_hash_map;
 mod frame;
 mod frame_table;
@@ -45,6 +51,7 @@ mod library_info;
 mod libs_with_ranges;
 mod marker_table;
 mod markers;
+mod native_allocations;
 mod native_symbols;
 mod process;
 mod profile;
@@ -60,12 +67,14 @@ mod timestamp;
 
 pub use category::{CategoryHandle, CategoryPairHandle};
 pub use category_color::CategoryColor;
+pub use counters::CounterHandle;
 pub use cpu_delta::CpuDelta;
 pub use frame::Frame;
 pub use library_info::{LibraryInfo, Symbol, SymbolTable};
 pub use markers::*;
 pub use process::ThreadHandle;
-pub use profile::{Profile, SamplingInterval, StringHandle};
+pub use profile::{PausedRangeReason, Profile, SamplingInterval, StringHandle};
 pub use reference_timestamp::ReferenceTimestamp;
+pub use sample_table::WeightType;
 pub use thread::ProcessHandle;
 pub use timestamp::*;
@@ -87,7 +87,7 @@ impl SymbolTable {
     }
 
     /// Look up the symbol for an address. This address is relative to the library's base address.
-    pub fn lookup(&self, address: u32) -> Option<&Symbol> {
+    pub fn lookup(&self, address: u64) -> Option<&Symbol> {
         let index = match self
             .symbols
             .binary_search_by_key(&address, |symbol| symbol.address)
@@ -109,9 +109,9 @@ impl SymbolTable {
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Symbol {
     /// The symbol's address, as a "relative address", i.e. relative to the library's base address.
-    pub address: u32,
+    pub address: u64,
     /// The symbol's size, if known. This is often just set based on the address of the next symbol.
-    pub size: Option<u32>,
+    pub size: Option<u64>,
     /// The symbol name.
     pub name: String,
 }
@@ -1,9 +1,11 @@
 use std::hash::Hash;
+use std::ops::Range;
+use std::sync::Arc;
 
 use crate::fast_hash_map::FastHashMap;
 use crate::global_lib_table::{GlobalLibIndex, GlobalLibTable};
 use crate::lib_info::Lib;
-use crate::LibraryInfo;
+use crate::{LibraryInfo, SymbolTable};
 
 #[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash)]
 struct InternalLibIndex(usize);
@@ -49,16 +51,39 @@ impl LibsWithRanges {
         self.lib_ranges.remove(base_address);
     }
 
+    pub fn update_lib(
+        &mut self,
+        global_libs: &mut GlobalLibTable,
+        base_address: u64,
+        avma_range: Range<u64>,
+        symbol_table: Option<Arc<SymbolTable>>,
+    ) {
+        let lib_index = match self.lib_ranges.remove(base_address) {
+            Some(lib_index) => lib_index,
+            None => return,
+        };
+        self.lib_ranges.insert(LibRange {
+            lib_index,
+            base: base_address,
+            start: avma_range.start,
+            end: avma_range.end,
+        });
+        self.libs[lib_index.0].symbol_table = symbol_table.clone();
+        if let Some(global_lib_index) = self.used_libs.get(&lib_index) {
+            global_libs.set_symbol_table(*global_lib_index, symbol_table);
+        }
+    }
+
     pub fn convert_address(
         &mut self,
         global_libs: &mut GlobalLibTable,
         address: u64,
-    ) -> Option<(u32, GlobalLibIndex)> {
+    ) -> Option<(u64, GlobalLibIndex)> {
         let range = match self.lib_ranges.lookup(address) {
             Some(range) => range,
             None => return None,
         };
-        let relative_address = (address - range.base) as u32;
+        let relative_address = address - range.base;
         let lib = &self.libs[range.lib_index.0];
         let global_lib_index = *self
             .used_libs
@@ -103,25 +128,38 @@ impl<I> LibRanges<I> {
         self.sorted_lib_ranges.insert(insertion_index, range);
     }
 
-    pub fn remove(&mut self, base_address: u64) {
-        self.sorted_lib_ranges.retain(|r| r.base != base_address);
+    /// Removes the ranges with the given base address, and returns the library
+    /// index of the last one.
+    pub fn remove(&mut self, base_address: u64) -> Option<I>
+    where
+        I: Copy,
+    {
+        let mut lib_index = None;
+        self.sorted_lib_ranges.retain(|r| {
+            if r.base == base_address {
+                lib_index = Some(r.lib_index);
+                false
+            } else {
+                true
+            }
+        });
+        lib_index
     }
 
     pub fn lookup(&self, address: u64) -> Option<&LibRange<I>> {
         let ranges = &self.sorted_lib_ranges[..];
-        let index = match ranges.binary_search_by_key(&address, |r| r.start) {
-            Err(0) => return None,
-            Ok(exact_match) => exact_match,
+        match ranges.binary_search_by_key(&address, |r| r.start) {
+            Ok(exact_match) => Some(&ranges[exact_match]),
             Err(insertion_index) => {
-                let range_index = insertion_index - 1;
-                if address < ranges[range_index].end {
-                    range_index
-                } else {
-                    return None;
-                }
+                // Ranges can be nested, e.g. when a perf map range covers JIT code
+                // around other libraries, so we look for the innermost range which
+                // contains the address.
+                ranges[..insertion_index]
+                    .iter()
+                    .rev()
+                    .find(|r| address < r.end)
             }
-        };
-        Some(&ranges[index])
+        }
     }
 }
 
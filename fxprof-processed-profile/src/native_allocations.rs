@@ -0,0 +1,82 @@
+use serde::ser::{Serialize, SerializeMap, Serializer};
+
+use crate::Timestamp;
+
+/// The native allocations of a thread, serialized as the thread's `nativeAllocations`
+/// table.
+///
+/// Allocations have a positive weight, deallocations have a negative weight. The
+/// memory address of each allocation allows the Firefox Profiler to match up
+/// allocations with their deallocations, so that it can display retained memory.
+#[derive(Debug, Clone, Default)]
+pub struct NativeAllocationTable {
+    timestamps: Vec<Timestamp>,
+    stack_indexes: Vec<Option<usize>>,
+    weights: Vec<i64>,
+    memory_addresses: Vec<u64>,
+}
+
+impl NativeAllocationTable {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn from_columns(
+        timestamps: Vec<Timestamp>,
+        stack_indexes: Vec<Option<usize>>,
+        weights: Vec<i64>,
+        memory_addresses: Vec<u64>,
+    ) -> Self {
+        Self {
+            timestamps,
+            stack_indexes,
+            weights,
+            memory_addresses,
+        }
+    }
+
+    pub fn add_allocation(
+        &mut self,
+        timestamp: Timestamp,
+        stack_index: Option<usize>,
+        allocation_address: u64,
+        allocation_size: i64,
+    ) {
+        self.timestamps.push(timestamp);
+        self.stack_indexes.push(stack_index);
+        self.weights.push(allocation_size);
+        self.memory_addresses.push(allocation_address);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timestamps.is_empty()
+    }
+
+    pub fn as_serializable(&self, thread_id: u64) -> impl Serialize + '_ {
+        SerializableNativeAllocationTable {
+            table: self,
+            thread_id,
+        }
+    }
+}
+
+struct SerializableNativeAllocationTable<'a> {
+    table: &'a NativeAllocationTable,
+    thread_id: u64,
+}
+
+impl<'a> Serialize for SerializableNativeAllocationTable<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let table = self.table;
+        let len = table.timestamps.len();
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("length", &len)?;
+        map.serialize_entry("memoryAddress", &table.memory_addresses)?;
+        map.serialize_entry("stack", &table.stack_indexes)?;
+        map.serialize_entry("threadId", &vec![self.thread_id; len])?;
+        map.serialize_entry("time", &table.timestamps)?;
+        map.serialize_entry("weight", &table.weights)?;
+        map.serialize_entry("weightType", &"bytes")?;
+        map.end()
+    }
+}
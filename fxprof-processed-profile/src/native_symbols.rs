@@ -11,12 +11,12 @@ use crate::{
 /// They can be from different libraries. Only used symbols are included.
 #[derive(Debug, Clone, Default)]
 pub struct NativeSymbols {
-    addresses: Vec<u32>,
-    function_sizes: Vec<Option<u32>>,
+    addresses: Vec<u64>,
+    function_sizes: Vec<Option<u64>>,
     lib_indexes: Vec<GlobalLibIndex>,
     names: Vec<ThreadInternalStringIndex>,
 
-    lib_and_symbol_address_to_symbol_index: FastHashMap<(GlobalLibIndex, u32), usize>,
+    lib_and_symbol_address_to_symbol_index: FastHashMap<(GlobalLibIndex, u64), usize>,
 }
 
 impl NativeSymbols {
@@ -24,6 +24,32 @@ impl NativeSymbols {
         Default::default()
     }
 
+    pub fn from_columns(
+        addresses: Vec<u64>,
+        function_sizes: Vec<Option<u64>>,
+        lib_indexes: Vec<GlobalLibIndex>,
+        names: Vec<ThreadInternalStringIndex>,
+    ) -> Self {
+        let mut lib_and_symbol_address_to_symbol_index = FastHashMap::default();
+        for (i, key) in lib_indexes
+            .iter()
+            .cloned()
+            .zip(addresses.iter().cloned())
+            .enumerate()
+        {
+            lib_and_symbol_address_to_symbol_index
+                .entry(key)
+                .or_insert(i);
+        }
+        Self {
+            addresses,
+            function_sizes,
+            lib_indexes,
+            names,
+            lib_and_symbol_address_to_symbol_index,
+        }
+    }
+
     pub fn symbol_index_and_string_index_for_symbol(
         &mut self,
         lib_index: GlobalLibIndex,
@@ -51,7 +77,7 @@ impl NativeSymbols {
 }
 
 #[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash)]
-pub struct NativeSymbolIndex(u32);
+pub struct NativeSymbolIndex(pub(crate) u32);
 
 impl Serialize for NativeSymbolIndex {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
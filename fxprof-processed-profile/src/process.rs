@@ -1,9 +1,11 @@
 use std::cmp::Ordering;
 use std::hash::Hash;
+use std::ops::Range;
+use std::sync::Arc;
 
 use crate::frame_table::InternalFrameLocation;
 use crate::global_lib_table::GlobalLibTable;
-use crate::library_info::LibraryInfo;
+use crate::library_info::{LibraryInfo, SymbolTable};
 use crate::libs_with_ranges::LibsWithRanges;
 use crate::Timestamp;
 
@@ -103,4 +105,15 @@ impl Process {
     pub fn unload_lib(&mut self, base_address: u64) {
         self.libs.unload_lib(base_address);
     }
+
+    pub fn update_lib(
+        &mut self,
+        global_libs: &mut GlobalLibTable,
+        base_address: u64,
+        avma_range: Range<u64>,
+        symbol_table: Option<Arc<SymbolTable>>,
+    ) {
+        self.libs
+            .update_lib(global_libs, base_address, avma_range, symbol_table);
+    }
 }
@@ -1,23 +1,32 @@
+use std::ops::Range;
+use std::sync::Arc;
 use std::time::Duration;
 
-use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
-use serde_json::json;
+use serde::ser::{SerializeMap, SerializeSeq, Serializer};
+use serde::Serialize;
+use serde_json::{json, Value};
 
 use crate::category::{Category, CategoryHandle, CategoryPairHandle};
 use crate::category_color::CategoryColor;
+use crate::counters::{Counter, CounterHandle};
 use crate::cpu_delta::CpuDelta;
 use crate::fast_hash_map::FastHashMap;
 use crate::frame::Frame;
 use crate::frame_table::{InternalFrame, InternalFrameLocation};
 use crate::global_lib_table::GlobalLibTable;
-use crate::library_info::LibraryInfo;
+use crate::library_info::{LibraryInfo, SymbolTable};
 use crate::libs_with_ranges::LibsWithRanges;
 use crate::process::{Process, ThreadHandle};
 use crate::reference_timestamp::ReferenceTimestamp;
+use crate::sample_table::WeightType;
 use crate::string_table::{GlobalStringIndex, GlobalStringTable};
 use crate::thread::{ProcessHandle, Thread};
 use crate::{MarkerSchema, MarkerTiming, ProfilerMarker, Timestamp};
 
+/// The version of the processed profile format which is written, and which
+/// can be read back in.
+pub(crate) const PREPROCESSED_PROFILE_VERSION: u32 = 46;
+
 /// The sampling interval used during profile recording.
 ///
 /// This doesn't have to match the actual delta between sample timestamps.
@@ -100,16 +109,22 @@ pub struct StringHandle(GlobalStringIndex);
 pub struct Profile {
     pub(crate) product: String,
     pub(crate) interval: SamplingInterval,
+    pub(crate) sampled_event_name: Option<String>,
     pub(crate) global_libs: GlobalLibTable,
     pub(crate) kernel_libs: LibsWithRanges,
     pub(crate) categories: Vec<Category>, // append-only for stable CategoryHandles
     pub(crate) processes: Vec<Process>,   // append-only for stable ProcessHandles
     pub(crate) threads: Vec<Thread>,      // append-only for stable ThreadHandles
+    pub(crate) counters: Vec<Counter>,    // append-only for stable CounterHandles
+    pub(crate) paused_ranges: Vec<PausedRange>,
     pub(crate) reference_timestamp: ReferenceTimestamp,
     pub(crate) string_table: GlobalStringTable,
     pub(crate) marker_schemas: FastHashMap<&'static str, MarkerSchema>,
-    used_pids: FastHashMap<u32, u32>,
-    used_tids: FastHashMap<u32, u32>,
+    /// Marker schemas from a deserialized profile, keyed by marker type name.
+    /// They are serialized unless a schema with the same name is in `marker_schemas`.
+    pub(crate) deserialized_marker_schemas: FastHashMap<String, Value>,
+    pub(crate) used_pids: FastHashMap<u32, u32>,
+    pub(crate) used_tids: FastHashMap<u32, u32>,
 }
 
 impl Profile {
@@ -126,14 +141,18 @@ impl Profile {
     ) -> Self {
         Profile {
             interval,
+            sampled_event_name: None,
             product: product.to_string(),
             threads: Vec::new(),
+            counters: Vec::new(),
+            paused_ranges: Vec::new(),
             global_libs: GlobalLibTable::new(),
             kernel_libs: LibsWithRanges::new(),
             reference_timestamp,
             processes: Vec::new(),
             string_table: GlobalStringTable::new(),
             marker_schemas: FastHashMap::default(),
+            deserialized_marker_schemas: FastHashMap::default(),
             categories: vec![Category {
                 name: "Other".to_string(),
                 color: CategoryColor::Grey,
@@ -154,6 +173,12 @@ impl Profile {
         self.reference_timestamp = reference_timestamp;
     }
 
+    /// Set the name of the event which triggered the samples, e.g. `cycles` or
+    /// `cache-misses`. It is displayed in the profile's metadata.
+    pub fn set_sampled_event_name(&mut self, name: &str) {
+        self.sampled_event_name = Some(name.to_string());
+    }
+
     /// Change the product name.
     pub fn set_product(&mut self, product: &str) {
         self.product = product.to_string();
@@ -210,6 +235,18 @@ impl Profile {
         }
     }
 
+    /// The handles of all processes in this profile, in the order in which
+    /// they were added. This is useful for modifying a deserialized profile.
+    pub fn processes(&self) -> Vec<ProcessHandle> {
+        (0..self.processes.len()).map(ProcessHandle).collect()
+    }
+
+    /// The handles of all threads of the given process, in the order in which
+    /// they were added.
+    pub fn threads(&self, process: ProcessHandle) -> Vec<ThreadHandle> {
+        self.processes[process.0].threads()
+    }
+
     /// Change the start time of a process.
     pub fn set_process_start_time(&mut self, process: ProcessHandle, start_time: Timestamp) {
         self.processes[process.0].set_start_time(start_time);
@@ -241,6 +278,27 @@ impl Profile {
         self.processes[process.0].unload_lib(base_address);
     }
 
+    /// Replace the address range and the symbol table of the library at the specified
+    /// base address in the specified process.
+    ///
+    /// This is meant for libraries whose contents grow during the recording, for example
+    /// the JIT code listed in a perf map. Unlike unloading the library and adding a new
+    /// one, this keeps a single entry for the library in the profile.
+    pub fn update_lib(
+        &mut self,
+        process: ProcessHandle,
+        base_address: u64,
+        avma_range: Range<u64>,
+        symbol_table: Option<Arc<SymbolTable>>,
+    ) {
+        self.processes[process.0].update_lib(
+            &mut self.global_libs,
+            base_address,
+            avma_range,
+            symbol_table,
+        );
+    }
+
     /// Add a kernel library. This allows symbolication of kernel stacks once the profile is
     /// opened in the Firefox Profiler. Kernel libraries are global and not tied to a process.
     ///
@@ -279,6 +337,20 @@ impl Profile {
         self.threads[thread.0].set_name(name);
     }
 
+    /// Set the unit of the sample weights of a thread. The default is
+    /// [`WeightType::Samples`].
+    ///
+    /// This is useful for threads whose samples don't come from sampling, for
+    /// example if each sample's weight is the duration of a traced function call
+    /// ([`WeightType::TracingMs`]), or the size of an allocation ([`WeightType::Bytes`]).
+    pub fn set_thread_samples_weight_type(
+        &mut self,
+        thread: ThreadHandle,
+        weight_type: WeightType,
+    ) {
+        self.threads[thread.0].set_samples_weight_type(weight_type);
+    }
+
     /// Change the start time of a thread.
     pub fn set_thread_start_time(&mut self, thread: ThreadHandle, start_time: Timestamp) {
         self.threads[thread.0].set_start_time(start_time);
@@ -289,6 +361,40 @@ impl Profile {
         self.threads[thread.0].set_end_time(end_time);
     }
 
+    /// Add a counter to the given process, and return its handle.
+    ///
+    /// Counters are displayed as a graph track in the Firefox Profiler, for example
+    /// for memory usage. The `category` is used to pick the kind of graph; the
+    /// Firefox Profiler treats counters with the category "Memory" as memory tracks.
+    pub fn add_counter(
+        &mut self,
+        process: ProcessHandle,
+        name: &str,
+        category: &str,
+        description: &str,
+    ) -> CounterHandle {
+        let handle = CounterHandle(self.counters.len());
+        self.counters
+            .push(Counter::new(name, category, description, process));
+        handle
+    }
+
+    /// Add a sample to a counter.
+    ///
+    /// The `value_delta` is the change of the counter value since the previous
+    /// sample, for example the number of bytes that were allocated minus the number
+    /// of bytes that were freed. The `number_of_operations_delta` is the number of
+    /// operations since the previous sample, for example the number of allocations.
+    pub fn add_counter_sample(
+        &mut self,
+        counter: CounterHandle,
+        timestamp: Timestamp,
+        value_delta: f64,
+        number_of_operations_delta: u32,
+    ) {
+        self.counters[counter.0].add_sample(timestamp, value_delta, number_of_operations_delta)
+    }
+
     /// Turn the string into in a [`StringHandle`], for use in [`Frame::Label`].
     pub fn intern_string(&mut self, s: &str) -> StringHandle {
         StringHandle(self.string_table.index_for_string(s))
@@ -317,13 +423,16 @@ impl Profile {
     /// and "after" groups, you can use -1 for all "before" samples and 1 for all "after"
     /// samples, and the call tree will show you which stacks occur more frequently in
     /// the "after" part of the profile, by sorting those stacks to the top.
+    ///
+    /// The unit of the weight is determined by the thread's weight type, see
+    /// [`Profile::set_thread_samples_weight_type`].
     pub fn add_sample(
         &mut self,
         thread: ThreadHandle,
         timestamp: Timestamp,
         frames: impl Iterator<Item = (Frame, CategoryPairHandle)>,
         cpu_delta: CpuDelta,
-        weight: i32,
+        weight: i64,
     ) {
         let stack_index = self.stack_index_for_frames(thread, frames);
         self.threads[thread.0].add_sample(timestamp, stack_index, cpu_delta, weight);
@@ -336,11 +445,48 @@ impl Profile {
         &mut self,
         thread: ThreadHandle,
         timestamp: Timestamp,
-        weight: i32,
+        weight: i64,
     ) {
         self.threads[thread.0].add_sample_same_stack_zero_cpu(timestamp, weight);
     }
 
+    /// Add an allocation or deallocation sample to the given thread. This is used
+    /// for native allocations, which are displayed in a separate call tree in the
+    /// Firefox Profiler, with the allocated bytes as the weight.
+    ///
+    /// The `allocation_size` is positive for allocations and negative for
+    /// deallocations. A deallocation should have the same `allocation_address` as
+    /// the allocation it frees, so that the Firefox Profiler can compute retained
+    /// memory.
+    pub fn add_allocation_sample(
+        &mut self,
+        thread: ThreadHandle,
+        timestamp: Timestamp,
+        frames: impl Iterator<Item = (Frame, CategoryPairHandle)>,
+        allocation_address: u64,
+        allocation_size: i64,
+    ) {
+        let stack_index = self.stack_index_for_frames(thread, frames);
+        self.threads[thread.0].add_allocation(
+            timestamp,
+            stack_index,
+            allocation_address,
+            allocation_size,
+        );
+    }
+
+    /// Mark a time range in which the profiler didn't record all data, for
+    /// example because events were lost. Details about the range can be
+    /// recorded in a marker.
+    pub fn add_paused_range(
+        &mut self,
+        start: Timestamp,
+        end: Timestamp,
+        reason: PausedRangeReason,
+    ) {
+        self.paused_ranges.push(PausedRange { start, end, reason });
+    }
+
     /// Add a marker to the given thread.
     pub fn add_marker<T: ProfilerMarker>(
         &mut self,
@@ -352,7 +498,27 @@ impl Profile {
         self.marker_schemas
             .entry(T::MARKER_TYPE_NAME)
             .or_insert_with(T::schema);
-        self.threads[thread.0].add_marker(name, marker, timing);
+        self.threads[thread.0].add_marker(name, marker, timing, None);
+    }
+
+    /// Add a marker with a stack to the given thread. The Firefox Profiler shows
+    /// the stack in the marker's tooltip and sidebar, as the marker's "cause".
+    ///
+    /// The stack frames are ordered from the root function to the leaf, like for
+    /// [`Profile::add_sample`].
+    pub fn add_marker_with_stack<T: ProfilerMarker>(
+        &mut self,
+        thread: ThreadHandle,
+        name: &str,
+        marker: T,
+        timing: MarkerTiming,
+        frames: impl Iterator<Item = (Frame, CategoryPairHandle)>,
+    ) {
+        self.marker_schemas
+            .entry(T::MARKER_TYPE_NAME)
+            .or_insert_with(T::schema);
+        let stack_index = self.stack_index_for_frames(thread, frames);
+        self.threads[thread.0].add_marker(name, marker, timing, stack_index);
     }
 
     // frames is ordered from caller to callee, i.e. root function first, pc last
@@ -391,19 +557,97 @@ impl Profile {
     }
 }
 
+impl Profile {
+    /// The order of the threads in the serialized threads list.
+    ///
+    /// The processed profile format has all threads from all processes in a flattened threads list.
+    /// Each thread duplicates some information about its process, which allows the Firefox Profiler
+    /// UI to group threads from the same process.
+    fn sorted_threads(&self) -> Vec<ThreadHandle> {
+        let mut sorted_processes: Vec<_> = (0..self.processes.len()).map(ProcessHandle).collect();
+        sorted_processes.sort_by(|a_handle, b_handle| {
+            let a = &self.processes[a_handle.0];
+            let b = &self.processes[b_handle.0];
+            a.cmp_for_json_order(b)
+        });
+
+        let mut sorted_threads = Vec::with_capacity(self.threads.len());
+        for process in sorted_processes {
+            let mut process_threads = self.processes[process.0].threads();
+            process_threads.sort_by(|a_handle, b_handle| {
+                let a = &self.threads[a_handle.0];
+                let b = &self.threads[b_handle.0];
+                a.cmp_for_json_order(b)
+            });
+            sorted_threads.extend(process_threads);
+        }
+        sorted_threads
+    }
+}
+
 impl Serialize for Profile {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let sorted_threads = self.sorted_threads();
         let mut map = serializer.serialize_map(None)?;
         map.serialize_entry("meta", &SerializableProfileMeta(self))?;
         map.serialize_entry("libs", &self.global_libs)?;
-        map.serialize_entry("threads", &SerializableProfileThreadsProperty(self))?;
+        map.serialize_entry(
+            "threads",
+            &SerializableProfileThreadsProperty(self, &sorted_threads),
+        )?;
         map.serialize_entry("pages", &[] as &[()])?;
         map.serialize_entry("profilerOverhead", &[] as &[()])?;
-        map.serialize_entry("counters", &[] as &[()])?;
+        map.serialize_entry(
+            "counters",
+            &SerializableProfileCountersProperty(self, &sorted_threads),
+        )?;
         map.end()
     }
 }
 
+/// A time range in which the profiler didn't record all data.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct PausedRange {
+    #[serde(rename = "startTime")]
+    pub start: Timestamp,
+    #[serde(rename = "endTime")]
+    pub end: Timestamp,
+    pub reason: PausedRangeReason,
+}
+
+/// Why the profiler didn't record all data during a paused range. These are
+/// the reasons the Firefox Profiler understands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PausedRangeReason {
+    /// Recording was paused, for example by the user.
+    ProfilerPaused,
+    /// The data could not be collected, for example because events were lost.
+    CollectorPaused,
+}
+
+impl PausedRangeReason {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            PausedRangeReason::ProfilerPaused => "profiler-paused",
+            PausedRangeReason::CollectorPaused => "collector-paused",
+        }
+    }
+
+    pub(crate) fn parse(s: &str) -> Option<Self> {
+        match s {
+            "profiler-paused" => Some(PausedRangeReason::ProfilerPaused),
+            "collector-paused" => Some(PausedRangeReason::CollectorPaused),
+            _ => None,
+        }
+    }
+}
+
+impl Serialize for PausedRangeReason {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
 struct SerializableProfileMeta<'a>(&'a Profile);
 
 impl<'a> Serialize for SerializableProfileMeta<'a> {
@@ -411,6 +655,19 @@ impl<'a> Serialize for SerializableProfileMeta<'a> {
         let mut map = serializer.serialize_map(None)?;
         map.serialize_entry("categories", &self.0.categories)?;
         map.serialize_entry("debug", &false)?;
+        if let Some(sampled_event_name) = &self.0.sampled_event_name {
+            map.serialize_entry(
+                "extra",
+                &json!([{
+                    "label": "Sampling",
+                    "entries": [{
+                        "label": "Event",
+                        "format": "string",
+                        "value": sampled_event_name,
+                    }],
+                }]),
+            )?;
+        }
         map.serialize_entry(
             "extensions",
             &json!({
@@ -421,7 +678,7 @@ impl<'a> Serialize for SerializableProfileMeta<'a> {
             }),
         )?;
         map.serialize_entry("interval", &(self.0.interval.as_secs_f64() * 1000.0))?;
-        map.serialize_entry("preprocessedProfileVersion", &46)?;
+        map.serialize_entry("preprocessedProfileVersion", &PREPROCESSED_PROFILE_VERSION)?;
         map.serialize_entry("processType", &0)?;
         map.serialize_entry("product", &self.0.product)?;
         map.serialize_entry(
@@ -434,54 +691,84 @@ impl<'a> Serialize for SerializableProfileMeta<'a> {
         )?;
         map.serialize_entry("startTime", &self.0.reference_timestamp)?;
         map.serialize_entry("symbolicated", &false)?;
-        map.serialize_entry("pausedRanges", &[] as &[()])?;
+        map.serialize_entry("pausedRanges", &self.0.paused_ranges)?;
         map.serialize_entry("version", &24)?;
         map.serialize_entry("usesOnlyOneStackType", &true)?;
         map.serialize_entry("doesNotUseFrameImplementation", &true)?;
         map.serialize_entry("sourceCodeIsNotOnSearchfox", &true)?;
 
-        let mut marker_schemas: Vec<MarkerSchema> =
-            self.0.marker_schemas.values().cloned().collect();
-        marker_schemas.sort_by_key(|schema| schema.type_name);
+        let mut marker_schemas: Vec<(&str, SerializableMarkerSchema)> = self
+            .0
+            .marker_schemas
+            .values()
+            .map(|schema| (schema.type_name, SerializableMarkerSchema::Typed(schema)))
+            .collect();
+        marker_schemas.extend(
+            self.0
+                .deserialized_marker_schemas
+                .iter()
+                .filter(|(name, _)| !self.0.marker_schemas.contains_key(name.as_str()))
+                .map(|(name, schema)| {
+                    (
+                        name.as_str(),
+                        SerializableMarkerSchema::Deserialized(schema),
+                    )
+                }),
+        );
+        marker_schemas.sort_by_key(|(name, _)| *name);
+        let marker_schemas: Vec<_> = marker_schemas
+            .into_iter()
+            .map(|(_, schema)| schema)
+            .collect();
         map.serialize_entry("markerSchema", &marker_schemas)?;
 
         map.end()
     }
 }
 
-struct SerializableProfileThreadsProperty<'a>(&'a Profile);
+#[derive(Serialize)]
+#[serde(untagged)]
+enum SerializableMarkerSchema<'a> {
+    Typed(&'a MarkerSchema),
+    Deserialized(&'a Value),
+}
+
+struct SerializableProfileThreadsProperty<'a>(&'a Profile, &'a [ThreadHandle]);
 
 impl<'a> Serialize for SerializableProfileThreadsProperty<'a> {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        // The processed profile format has all threads from all processes in a flattened threads list.
-        // Each thread duplicates some information about its process, which allows the Firefox Profiler
-        // UI to group threads from the same process.
-
-        let mut seq = serializer.serialize_seq(Some(self.0.threads.len()))?;
-
-        let mut sorted_processes: Vec<_> = (0..self.0.processes.len()).map(ProcessHandle).collect();
-        sorted_processes.sort_by(|a_handle, b_handle| {
-            let a = &self.0.processes[a_handle.0];
-            let b = &self.0.processes[b_handle.0];
-            a.cmp_for_json_order(b)
-        });
+        let SerializableProfileThreadsProperty(profile, sorted_threads) = self;
+        let mut seq = serializer.serialize_seq(Some(sorted_threads.len()))?;
+        for thread in sorted_threads.iter() {
+            let categories = &profile.categories;
+            let thread = &profile.threads[thread.0];
+            let process = &profile.processes[thread.process().0];
+            seq.serialize_element(&SerializableProfileThread(process, thread, categories))?;
+        }
+        seq.end()
+    }
+}
 
-        for process in sorted_processes {
-            let mut sorted_threads = self.0.processes[process.0].threads();
-            sorted_threads.sort_by(|a_handle, b_handle| {
-                let a = &self.0.threads[a_handle.0];
-                let b = &self.0.threads[b_handle.0];
-                a.cmp_for_json_order(b)
-            });
+struct SerializableProfileCountersProperty<'a>(&'a Profile, &'a [ThreadHandle]);
 
-            for thread in sorted_threads {
-                let categories = &self.0.categories;
-                let thread = &self.0.threads[thread.0];
-                let process = &self.0.processes[thread.process().0];
-                seq.serialize_element(&SerializableProfileThread(process, thread, categories))?;
-            }
+impl<'a> Serialize for SerializableProfileCountersProperty<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let SerializableProfileCountersProperty(profile, sorted_threads) = self;
+        let mut seq = serializer.serialize_seq(Some(profile.counters.len()))?;
+        for counter in &profile.counters {
+            let process = counter.process();
+            // Counters are displayed with the main thread of their process. Fall
+            // back to the first thread of the process if it has no main thread.
+            let is_in_process =
+                |thread: &ThreadHandle| profile.threads[thread.0].process() == process;
+            let main_thread_index = sorted_threads
+                .iter()
+                .position(|thread| is_in_process(thread) && profile.threads[thread.0].is_main())
+                .or_else(|| sorted_threads.iter().position(is_in_process))
+                .unwrap_or(0);
+            let pid = profile.processes[process.0].pid();
+            seq.serialize_element(&counter.as_serializable(main_thread_index, pid))?;
         }
-
         seq.end()
     }
 }
@@ -18,6 +18,23 @@ impl ResourceTable {
         Default::default()
     }
 
+    pub fn from_columns(
+        resource_libs: Vec<GlobalLibIndex>,
+        resource_names: Vec<ThreadInternalStringIndex>,
+    ) -> Self {
+        let mut lib_to_resource = FastHashMap::default();
+        for (i, lib_index) in resource_libs.iter().enumerate() {
+            lib_to_resource
+                .entry(*lib_index)
+                .or_insert(ResourceIndex(i as u32));
+        }
+        Self {
+            resource_libs,
+            resource_names,
+            lib_to_resource,
+        }
+    }
+
     pub fn resource_for_lib(
         &mut self,
         lib_index: GlobalLibIndex,
@@ -54,7 +71,7 @@ impl Serialize for ResourceTable {
 }
 
 #[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash)]
-pub struct ResourceIndex(u32);
+pub struct ResourceIndex(pub(crate) u32);
 
 impl Serialize for ResourceIndex {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
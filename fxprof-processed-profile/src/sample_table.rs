@@ -3,9 +3,47 @@ use serde::ser::{Serialize, SerializeMap, Serializer};
 use crate::cpu_delta::CpuDelta;
 use crate::Timestamp;
 
+/// The unit of the sample weights of a thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WeightType {
+    /// Each weight is a number of samples. This is the default.
+    Samples,
+    /// Each weight is a duration in milliseconds, for example for samples which
+    /// were created from instrumentation, such as function entry and exit events.
+    TracingMs,
+    /// Each weight is a number of bytes, for example for memory allocations.
+    Bytes,
+}
+
+impl Default for WeightType {
+    fn default() -> Self {
+        WeightType::Samples
+    }
+}
+
+impl WeightType {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            WeightType::Samples => "samples",
+            WeightType::TracingMs => "tracing-ms",
+            WeightType::Bytes => "bytes",
+        }
+    }
+
+    pub(crate) fn parse(s: &str) -> Option<Self> {
+        match s {
+            "samples" => Some(WeightType::Samples),
+            "tracing-ms" => Some(WeightType::TracingMs),
+            "bytes" => Some(WeightType::Bytes),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct SampleTable {
-    sample_weights: Vec<i32>,
+    weight_type: WeightType,
+    sample_weights: Vec<i64>,
     sample_timestamps: Vec<Timestamp>,
     sample_stack_indexes: Vec<Option<usize>>,
     sample_cpu_deltas: Vec<CpuDelta>,
@@ -21,7 +59,7 @@ impl SampleTable {
         timestamp: Timestamp,
         stack_index: Option<usize>,
         cpu_delta: CpuDelta,
-        weight: i32,
+        weight: i64,
     ) {
         self.sample_weights.push(weight);
         self.sample_timestamps.push(timestamp);
@@ -29,7 +67,34 @@ impl SampleTable {
         self.sample_cpu_deltas.push(cpu_delta);
     }
 
-    pub fn modify_last_sample(&mut self, timestamp: Timestamp, weight: i32) {
+    pub fn from_columns(
+        weight_type: WeightType,
+        sample_weights: Vec<i64>,
+        sample_timestamps: Vec<Timestamp>,
+        sample_stack_indexes: Vec<Option<usize>>,
+        sample_cpu_deltas: Vec<CpuDelta>,
+    ) -> Self {
+        Self {
+            weight_type,
+            sample_weights,
+            sample_timestamps,
+            sample_stack_indexes,
+            sample_cpu_deltas,
+        }
+    }
+
+    /// The stack and CPU delta of the last sample, if there is one.
+    pub fn last_sample(&self) -> Option<(Option<usize>, CpuDelta)> {
+        let stack_index = *self.sample_stack_indexes.last()?;
+        let cpu_delta = *self.sample_cpu_deltas.last()?;
+        Some((stack_index, cpu_delta))
+    }
+
+    pub fn set_weight_type(&mut self, weight_type: WeightType) {
+        self.weight_type = weight_type;
+    }
+
+    pub fn modify_last_sample(&mut self, timestamp: Timestamp, weight: i64) {
         *self.sample_weights.last_mut().unwrap() += weight;
         *self.sample_timestamps.last_mut().unwrap() = timestamp;
     }
@@ -43,7 +108,7 @@ impl Serialize for SampleTable {
         map.serialize_entry("stack", &self.sample_stack_indexes)?;
         map.serialize_entry("time", &self.sample_timestamps)?;
         map.serialize_entry("weight", &self.sample_weights)?;
-        map.serialize_entry("weightType", &"samples")?;
+        map.serialize_entry("weightType", self.weight_type.as_str())?;
         map.serialize_entry("threadCPUDelta", &self.sample_cpu_deltas)?;
         map.end()
     }
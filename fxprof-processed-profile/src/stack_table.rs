@@ -21,6 +21,34 @@ impl StackTable {
         Default::default()
     }
 
+    pub fn from_columns(
+        stack_prefixes: Vec<Option<usize>>,
+        stack_frames: Vec<usize>,
+        stack_categories: Vec<CategoryHandle>,
+        stack_subcategories: Vec<Subcategory>,
+    ) -> Self {
+        let mut index = FastHashMap::default();
+        for (i, key) in stack_prefixes
+            .iter()
+            .cloned()
+            .zip(stack_frames.iter().cloned())
+            .enumerate()
+        {
+            index.entry(key).or_insert(i);
+        }
+        Self {
+            stack_prefixes,
+            stack_frames,
+            stack_categories,
+            stack_subcategories,
+            index,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.stack_prefixes.len()
+    }
+
     pub fn index_for_stack(
         &mut self,
         prefix: Option<usize>,
@@ -5,7 +5,7 @@ use serde::{Serialize, Serializer};
 use crate::fast_hash_map::FastHashMap;
 
 #[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash)]
-pub struct StringIndex(u32);
+pub struct StringIndex(pub(crate) u32);
 
 #[derive(Debug, Clone, Default)]
 pub struct StringTable {
@@ -29,6 +29,16 @@ impl StringTable {
     pub fn get_string(&self, index: StringIndex) -> Option<&str> {
         self.strings.get(index.0 as usize).map(Deref::deref)
     }
+
+    /// Create a string table from an existing list of strings, keeping the
+    /// string indexes intact. The list may contain duplicates.
+    pub fn from_strings(strings: Vec<String>) -> Self {
+        let mut index = FastHashMap::default();
+        for (i, s) in strings.iter().enumerate() {
+            index.entry(s.clone()).or_insert(StringIndex(i as u32));
+        }
+        Self { strings, index }
+    }
 }
 
 impl Serialize for StringTable {
@@ -2,6 +2,7 @@ use std::borrow::Cow;
 use std::cmp::Ordering;
 
 use serde::ser::{SerializeMap, Serializer};
+use serde_json::json;
 
 use crate::category::{Category, CategoryPairHandle};
 use crate::cpu_delta::CpuDelta;
@@ -9,9 +10,10 @@ use crate::frame_table::{FrameTable, InternalFrame};
 use crate::func_table::FuncTable;
 use crate::global_lib_table::GlobalLibTable;
 use crate::marker_table::MarkerTable;
+use crate::native_allocations::NativeAllocationTable;
 use crate::native_symbols::NativeSymbols;
 use crate::resource_table::ResourceTable;
-use crate::sample_table::SampleTable;
+use crate::sample_table::{SampleTable, WeightType};
 use crate::stack_table::StackTable;
 use crate::string_table::{GlobalStringIndex, GlobalStringTable};
 use crate::thread_string_table::{ThreadInternalStringIndex, ThreadStringTable};
@@ -21,6 +23,20 @@ use crate::{MarkerTiming, ProfilerMarker, Timestamp};
 #[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash)]
 pub struct ProcessHandle(pub(crate) usize);
 
+/// The tables of a thread, for creating a thread from existing profile data.
+#[derive(Debug)]
+pub struct ThreadTables {
+    pub stack_table: StackTable,
+    pub frame_table: FrameTable,
+    pub func_table: FuncTable,
+    pub samples: SampleTable,
+    pub native_allocations: NativeAllocationTable,
+    pub markers: MarkerTable,
+    pub resources: ResourceTable,
+    pub native_symbols: NativeSymbols,
+    pub string_table: ThreadStringTable,
+}
+
 #[derive(Debug)]
 pub struct Thread {
     process: ProcessHandle,
@@ -33,6 +49,7 @@ pub struct Thread {
     frame_table: FrameTable,
     func_table: FuncTable,
     samples: SampleTable,
+    native_allocations: NativeAllocationTable,
     markers: MarkerTable,
     resources: ResourceTable,
     native_symbols: NativeSymbols,
@@ -54,6 +71,7 @@ impl Thread {
             frame_table: FrameTable::new(),
             func_table: FuncTable::new(),
             samples: SampleTable::new(),
+            native_allocations: NativeAllocationTable::new(),
             markers: MarkerTable::new(),
             resources: ResourceTable::new(),
             native_symbols: NativeSymbols::new(),
@@ -62,6 +80,40 @@ impl Thread {
             last_sample_was_zero_cpu: false,
         }
     }
+    pub fn from_tables(
+        process: ProcessHandle,
+        tid: String,
+        name: Option<String>,
+        start_time: Timestamp,
+        end_time: Option<Timestamp>,
+        is_main: bool,
+        tables: ThreadTables,
+    ) -> Self {
+        let (last_sample_stack, last_sample_was_zero_cpu) = match tables.samples.last_sample() {
+            Some((stack_index, cpu_delta)) => (stack_index, cpu_delta == CpuDelta::ZERO),
+            None => (None, false),
+        };
+        Self {
+            process,
+            tid,
+            name,
+            start_time,
+            end_time,
+            is_main,
+            stack_table: tables.stack_table,
+            frame_table: tables.frame_table,
+            func_table: tables.func_table,
+            samples: tables.samples,
+            native_allocations: tables.native_allocations,
+            markers: tables.markers,
+            resources: tables.resources,
+            native_symbols: tables.native_symbols,
+            string_table: tables.string_table,
+            last_sample_stack,
+            last_sample_was_zero_cpu,
+        }
+    }
+
     pub fn set_name(&mut self, name: &str) {
         self.name = Some(name.to_string());
     }
@@ -78,6 +130,10 @@ impl Thread {
         self.process
     }
 
+    pub fn is_main(&self) -> bool {
+        self.is_main
+    }
+
     pub fn convert_string_index(
         &mut self,
         global_table: &GlobalStringTable,
@@ -117,7 +173,7 @@ impl Thread {
         timestamp: Timestamp,
         stack_index: Option<usize>,
         cpu_delta: CpuDelta,
-        weight: i32,
+        weight: i64,
     ) {
         self.samples
             .add_sample(timestamp, stack_index, cpu_delta, weight);
@@ -125,7 +181,11 @@ impl Thread {
         self.last_sample_was_zero_cpu = cpu_delta == CpuDelta::ZERO;
     }
 
-    pub fn add_sample_same_stack_zero_cpu(&mut self, timestamp: Timestamp, weight: i32) {
+    pub fn set_samples_weight_type(&mut self, weight_type: WeightType) {
+        self.samples.set_weight_type(weight_type);
+    }
+
+    pub fn add_sample_same_stack_zero_cpu(&mut self, timestamp: Timestamp, weight: i64) {
         if self.last_sample_was_zero_cpu {
             self.samples.modify_last_sample(timestamp, weight);
         } else {
@@ -136,10 +196,34 @@ impl Thread {
         }
     }
 
-    pub fn add_marker<T: ProfilerMarker>(&mut self, name: &str, marker: T, timing: MarkerTiming) {
+    pub fn add_allocation(
+        &mut self,
+        timestamp: Timestamp,
+        stack_index: Option<usize>,
+        allocation_address: u64,
+        allocation_size: i64,
+    ) {
+        self.native_allocations.add_allocation(
+            timestamp,
+            stack_index,
+            allocation_address,
+            allocation_size,
+        );
+    }
+
+    pub fn add_marker<T: ProfilerMarker>(
+        &mut self,
+        name: &str,
+        marker: T,
+        timing: MarkerTiming,
+        stack_index: Option<usize>,
+    ) {
         let name_string_index = self.string_table.index_for_string(name);
-        self.markers
-            .add_marker(name_string_index, timing, marker.json_marker_data());
+        let mut data = marker.json_marker_data();
+        if let (Some(stack_index), Some(data)) = (stack_index, data.as_object_mut()) {
+            data.insert("cause".to_string(), json!({ "stack": stack_index }));
+        }
+        self.markers.add_marker(name_string_index, timing, data);
     }
 
     pub fn cmp_for_json_order(&self, other: &Thread) -> Ordering {
@@ -180,6 +264,19 @@ impl Thread {
         map.serialize_entry("name", &thread_name)?;
         map.serialize_entry("isMainThread", &self.is_main)?;
         map.serialize_entry("nativeSymbols", &self.native_symbols)?;
+        if !self.native_allocations.is_empty() {
+            // The tid can have a ".N" suffix if it was reused, see Profile::make_unique_tid.
+            let thread_id = self
+                .tid
+                .split('.')
+                .next()
+                .and_then(|tid| tid.parse().ok())
+                .unwrap_or(0);
+            map.serialize_entry(
+                "nativeAllocations",
+                &self.native_allocations.as_serializable(thread_id),
+            )?;
+        }
         map.serialize_entry("pausedRanges", &[] as &[()])?;
         map.serialize_entry("pid", &pid)?;
         map.serialize_entry("processName", process_name)?;
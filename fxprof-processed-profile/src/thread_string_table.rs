@@ -23,6 +23,17 @@ impl ThreadStringTable {
         Default::default()
     }
 
+    pub fn from_strings(strings: Vec<String>) -> Self {
+        Self {
+            table: StringTable::from_strings(strings),
+            global_to_local_string: FastHashMap::default(),
+        }
+    }
+
+    pub fn get_string(&self, index: ThreadInternalStringIndex) -> Option<&str> {
+        self.table.get_string(index.0)
+    }
+
     pub fn index_for_string(&mut self, s: &str) -> ThreadInternalStringIndex {
         ThreadInternalStringIndex(self.table.index_for_string(s))
     }
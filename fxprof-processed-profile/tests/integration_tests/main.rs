@@ -3,9 +3,10 @@ use debugid::DebugId;
 use serde_json::json;
 
 use fxprof_processed_profile::{
-    CategoryColor, CpuDelta, Frame, LibraryInfo, MarkerDynamicField, MarkerFieldFormat,
-    MarkerLocation, MarkerSchema, MarkerSchemaField, MarkerStaticField, MarkerTiming, Profile,
-    ProfilerMarker, ReferenceTimestamp, SamplingInterval, Symbol, SymbolTable, Timestamp,
+    CategoryColor, CategoryHandle, CpuDelta, Frame, LibraryInfo, MarkerDynamicField,
+    MarkerFieldFormat, MarkerLocation, MarkerSchema, MarkerSchemaField, MarkerStaticField,
+    MarkerTiming, PausedRangeReason, Profile, ProfilerMarker, ReferenceTimestamp, SamplingInterval,
+    Symbol, SymbolTable, Timestamp, WeightType,
 };
 
 use std::sync::Arc;
@@ -929,3 +930,549 @@ fn it_works() {
         )
     )
 }
+
+#[test]
+fn deserialize_round_trip() {
+    let mut profile = Profile::new(
+        "test",
+        ReferenceTimestamp::from_millis_since_unix_epoch(1636162232627.0),
+        SamplingInterval::from_millis(1),
+    );
+    let process = profile.add_process("test", 123, Timestamp::from_millis_since_reference(0.0));
+    let thread = profile.add_thread(
+        process,
+        12345,
+        Timestamp::from_millis_since_reference(0.0),
+        true,
+    );
+    let worker = profile.add_thread(
+        process,
+        12346,
+        Timestamp::from_millis_since_reference(0.5),
+        false,
+    );
+    profile.set_thread_name(worker, "Worker");
+    profile.add_lib(
+        process,
+        LibraryInfo {
+            name: "libc.so.6".to_string(),
+            debug_name: "libc.so.6".to_string(),
+            path: "/usr/lib/x86_64-linux-gnu/libc.so.6".to_string(),
+            code_id: Some("f0fc29165cbe6088c0e1adf03b0048fbecbc003a".to_string()),
+            debug_path: "/usr/lib/x86_64-linux-gnu/libc.so.6".to_string(),
+            debug_id: DebugId::from_breakpad("1629FCF0BE5C8860C0E1ADF03B0048FB0").unwrap(),
+            arch: None,
+            base_avma: 0x00007f76b7e5d000,
+            avma_range: 0x00007f76b7e85000..0x00007f76b8019000,
+            symbol_table: Some(Arc::new(SymbolTable::new(vec![Symbol {
+                address: 0x19f000,
+                size: Some(0x1000),
+                name: "libc_symbol".to_string(),
+            }]))),
+        },
+    );
+    let category = profile.add_category("Regular", CategoryColor::Blue);
+    let subcategory = profile.add_subcategory(category, "Sub");
+    let root_label = profile.intern_string("Root");
+    let stack = vec![
+        (Frame::Label(root_label), category.into()),
+        (Frame::ReturnAddress(0x7f76b7ffc0e7), subcategory),
+        (Frame::InstructionPointer(0x1234), category.into()),
+    ];
+    profile.add_sample(
+        thread,
+        Timestamp::from_millis_since_reference(1.1),
+        stack.clone().into_iter(),
+        CpuDelta::from_micros(300),
+        1,
+    );
+    profile.add_sample_same_stack_zero_cpu(thread, Timestamp::from_millis_since_reference(2.3), 2);
+    profile.add_sample(
+        worker,
+        Timestamp::from_millis_since_reference(1.7),
+        stack[..1].iter().cloned(),
+        CpuDelta::ZERO,
+        1,
+    );
+    profile.add_marker(
+        thread,
+        "Experimental",
+        TextMarker("Hello world!".to_string()),
+        MarkerTiming::Interval(
+            Timestamp::from_millis_since_reference(0.25),
+            Timestamp::from_millis_since_reference(2.0),
+        ),
+    );
+    let counter = profile.add_counter(process, "malloc", "Memory", "Amount of allocated memory");
+    profile.add_counter_sample(
+        counter,
+        Timestamp::from_millis_since_reference(1.5),
+        64.0,
+        2,
+    );
+    profile.add_allocation_sample(
+        thread,
+        Timestamp::from_millis_since_reference(1.6),
+        stack.clone().into_iter(),
+        0x5555_0000,
+        64,
+    );
+
+    let json = serde_json::to_value(&profile).unwrap();
+    let mut deserialized: Profile = serde_json::from_value(json.clone()).unwrap();
+    assert_json_eq!(deserialized, json);
+
+    // Adding a sample with an existing stack needs to reuse the existing frames
+    // and stacks. Library address ranges aren't part of the JSON, so only
+    // the label frame can be matched without adding the library again.
+    let process = deserialized.processes()[0];
+    let worker = deserialized.threads(process)[1];
+    let root_label = deserialized.intern_string("Root");
+    deserialized.add_sample(
+        worker,
+        Timestamp::from_millis_since_reference(3.0),
+        vec![(Frame::Label(root_label), category.into())].into_iter(),
+        CpuDelta::ZERO,
+        1,
+    );
+    let json = serde_json::to_value(&deserialized).unwrap();
+    let worker_json = &json["threads"][1];
+    assert_eq!(worker_json["name"], "Worker");
+    assert_eq!(worker_json["frameTable"]["length"], 1);
+    assert_eq!(worker_json["stackTable"]["length"], 1);
+    assert_eq!(worker_json["samples"]["stack"], json!([0, 0]));
+
+    // New processes get a unique pid.
+    let new_process =
+        deserialized.add_process("test", 123, Timestamp::from_millis_since_reference(4.0));
+    deserialized.add_thread(
+        new_process,
+        12345,
+        Timestamp::from_millis_since_reference(4.0),
+        true,
+    );
+    let json = serde_json::to_value(&deserialized).unwrap();
+    assert_eq!(json["threads"][2]["pid"], "123.1");
+    assert_eq!(json["threads"][2]["tid"], "12345.1");
+}
+
+#[test]
+fn deserialize_rejects_bad_profiles() {
+    let profile = Profile::new(
+        "test",
+        ReferenceTimestamp::from_millis_since_unix_epoch(1636162232627.0),
+        SamplingInterval::from_millis(1),
+    );
+    let json = serde_json::to_value(&profile).unwrap();
+
+    let mut wrong_version = json.clone();
+    wrong_version["meta"]["preprocessedProfileVersion"] = json!(45);
+    assert!(serde_json::from_value::<Profile>(wrong_version).is_err());
+
+    let mut no_categories = json;
+    no_categories["meta"]["categories"] = json!([]);
+    assert!(serde_json::from_value::<Profile>(no_categories).is_err());
+}
+
+#[test]
+fn deserialize_rejects_negative_times() {
+    let mut profile = Profile::new(
+        "test",
+        ReferenceTimestamp::from_millis_since_unix_epoch(1636162232627.0),
+        SamplingInterval::from_millis(1),
+    );
+    let process = profile.add_process("test", 123, Timestamp::from_millis_since_reference(0.0));
+    let thread = profile.add_thread(
+        process,
+        12345,
+        Timestamp::from_millis_since_reference(0.0),
+        true,
+    );
+    let main_label = profile.intern_string("main");
+    profile.add_sample(
+        thread,
+        Timestamp::from_millis_since_reference(1.0),
+        vec![(Frame::Label(main_label), CategoryHandle::OTHER.into())].into_iter(),
+        CpuDelta::ZERO,
+        1,
+    );
+    profile.add_marker(
+        thread,
+        "Marker",
+        TextMarker("text".to_string()),
+        MarkerTiming::Instant(Timestamp::from_millis_since_reference(2.0)),
+    );
+    profile.add_paused_range(
+        Timestamp::from_millis_since_reference(3.0),
+        Timestamp::from_millis_since_reference(4.0),
+        PausedRangeReason::ProfilerPaused,
+    );
+    let json = serde_json::to_value(&profile).unwrap();
+    assert!(serde_json::from_value::<Profile>(json.clone()).is_ok());
+
+    let mut negative_sample_time = json.clone();
+    negative_sample_time["threads"][0]["samples"]["time"][0] = json!(-1.0);
+    assert!(serde_json::from_value::<Profile>(negative_sample_time).is_err());
+
+    let mut negative_marker_time = json.clone();
+    negative_marker_time["threads"][0]["markers"]["startTime"][0] = json!(-0.5);
+    assert!(serde_json::from_value::<Profile>(negative_marker_time).is_err());
+
+    let mut negative_paused_range = json;
+    negative_paused_range["meta"]["pausedRanges"][0]["startTime"] = json!(-3.0);
+    assert!(serde_json::from_value::<Profile>(negative_paused_range).is_err());
+}
+
+#[test]
+fn counters() {
+    let mut profile = Profile::new(
+        "test",
+        ReferenceTimestamp::from_millis_since_unix_epoch(1636162232627.0),
+        SamplingInterval::from_millis(1),
+    );
+    let process = profile.add_process("test", 123, Timestamp::from_millis_since_reference(0.0));
+    profile.add_thread(
+        process,
+        12346,
+        Timestamp::from_millis_since_reference(0.0),
+        false,
+    );
+    profile.add_thread(
+        process,
+        12345,
+        Timestamp::from_millis_since_reference(0.0),
+        true,
+    );
+    let counter = profile.add_counter(process, "malloc", "Memory", "Amount of allocated memory");
+    profile.add_counter_sample(
+        counter,
+        Timestamp::from_millis_since_reference(1.0),
+        1024.0,
+        3,
+    );
+    profile.add_counter_sample(
+        counter,
+        Timestamp::from_millis_since_reference(2.0),
+        -512.0,
+        1,
+    );
+
+    let json = serde_json::to_value(&profile).unwrap();
+    assert_json_eq!(
+        json["counters"],
+        json!([
+            {
+                "category": "Memory",
+                "name": "malloc",
+                "description": "Amount of allocated memory",
+                "mainThreadIndex": 0,
+                "pid": "123",
+                "sampleGroups": [
+                    {
+                        "id": 0,
+                        "samples": {
+                            "length": 2,
+                            "count": [1024.0, -512.0],
+                            "number": [3, 1],
+                            "time": [1.0, 2.0]
+                        }
+                    }
+                ]
+            }
+        ])
+    );
+    assert_eq!(json["threads"][0]["tid"], "12345");
+}
+
+#[test]
+fn native_allocations() {
+    let mut profile = Profile::new(
+        "test",
+        ReferenceTimestamp::from_millis_since_unix_epoch(1636162232627.0),
+        SamplingInterval::from_millis(1),
+    );
+    let process = profile.add_process("test", 123, Timestamp::from_millis_since_reference(0.0));
+    let thread = profile.add_thread(
+        process,
+        12345,
+        Timestamp::from_millis_since_reference(0.0),
+        true,
+    );
+    let malloc_label = profile.intern_string("malloc");
+    let free_label = profile.intern_string("free");
+    profile.add_allocation_sample(
+        thread,
+        Timestamp::from_millis_since_reference(1.0),
+        vec![(Frame::Label(malloc_label), CategoryHandle::OTHER.into())].into_iter(),
+        0x7f00_0000_1000,
+        4096,
+    );
+    profile.add_allocation_sample(
+        thread,
+        Timestamp::from_millis_since_reference(2.0),
+        vec![(Frame::Label(free_label), CategoryHandle::OTHER.into())].into_iter(),
+        0x7f00_0000_1000,
+        -4096,
+    );
+
+    let json = serde_json::to_value(&profile).unwrap();
+    assert_json_eq!(
+        json["threads"][0]["nativeAllocations"],
+        json!({
+            "length": 2,
+            "memoryAddress": [0x7f00_0000_1000u64, 0x7f00_0000_1000u64],
+            "stack": [0, 1],
+            "threadId": [12345, 12345],
+            "time": [1.0, 2.0],
+            "weight": [4096, -4096],
+            "weightType": "bytes"
+        })
+    );
+}
+
+#[test]
+fn marker_stacks() {
+    let mut profile = Profile::new(
+        "test",
+        ReferenceTimestamp::from_millis_since_unix_epoch(1636162232627.0),
+        SamplingInterval::from_millis(1),
+    );
+    let process = profile.add_process("test", 123, Timestamp::from_millis_since_reference(0.0));
+    let thread = profile.add_thread(
+        process,
+        12345,
+        Timestamp::from_millis_since_reference(0.0),
+        true,
+    );
+    let main_label = profile.intern_string("main");
+    let read_label = profile.intern_string("read");
+    profile.add_marker(
+        thread,
+        "Without stack",
+        TextMarker("first".to_string()),
+        MarkerTiming::Instant(Timestamp::from_millis_since_reference(1.0)),
+    );
+    profile.add_marker_with_stack(
+        thread,
+        "With stack",
+        TextMarker("second".to_string()),
+        MarkerTiming::Interval(
+            Timestamp::from_millis_since_reference(2.0),
+            Timestamp::from_millis_since_reference(3.0),
+        ),
+        vec![
+            (Frame::Label(main_label), CategoryHandle::OTHER.into()),
+            (Frame::Label(read_label), CategoryHandle::OTHER.into()),
+        ]
+        .into_iter(),
+    );
+
+    let json = serde_json::to_value(&profile).unwrap();
+    let thread_json = &json["threads"][0];
+    assert_json_eq!(
+        thread_json["markers"]["data"],
+        json!([
+            { "type": "Text", "name": "first" },
+            { "type": "Text", "name": "second", "cause": { "stack": 1 } }
+        ])
+    );
+    assert_eq!(thread_json["stackTable"]["prefix"], json!([null, 0]));
+
+    let deserialized: Profile = serde_json::from_value(json.clone()).unwrap();
+    assert_json_eq!(deserialized, json);
+}
+
+#[test]
+fn sample_weight_types() {
+    let mut profile = Profile::new(
+        "test",
+        ReferenceTimestamp::from_millis_since_unix_epoch(1636162232627.0),
+        SamplingInterval::from_millis(1),
+    );
+    let process = profile.add_process("test", 123, Timestamp::from_millis_since_reference(0.0));
+    let thread = profile.add_thread(
+        process,
+        12345,
+        Timestamp::from_millis_since_reference(0.0),
+        true,
+    );
+    profile.set_thread_samples_weight_type(thread, WeightType::TracingMs);
+    let label = profile.intern_string("traced function");
+    profile.add_sample(
+        thread,
+        Timestamp::from_millis_since_reference(1.0),
+        vec![(Frame::Label(label), CategoryHandle::OTHER.into())].into_iter(),
+        CpuDelta::ZERO,
+        5_000_000_000,
+    );
+
+    let json = serde_json::to_value(&profile).unwrap();
+    let samples = &json["threads"][0]["samples"];
+    assert_eq!(samples["weightType"], "tracing-ms");
+    assert_eq!(samples["weight"], json!([5_000_000_000i64]));
+
+    let deserialized: Profile = serde_json::from_value(json.clone()).unwrap();
+    assert_json_eq!(deserialized, json);
+
+    let mut unknown_weight_type = json;
+    unknown_weight_type["threads"][0]["samples"]["weightType"] = json!("furlongs");
+    assert!(serde_json::from_value::<Profile>(unknown_weight_type).is_err());
+}
+
+#[test]
+fn sampled_event_name() {
+    let mut profile = Profile::new(
+        "test",
+        ReferenceTimestamp::from_millis_since_unix_epoch(1636162232627.0),
+        SamplingInterval::from_millis(1),
+    );
+    let json = serde_json::to_value(&profile).unwrap();
+    assert!(json["meta"].get("extra").is_none());
+
+    profile.set_sampled_event_name("cache-misses");
+    let json = serde_json::to_value(&profile).unwrap();
+    assert_eq!(
+        json["meta"]["extra"],
+        json!([{
+            "label": "Sampling",
+            "entries": [{
+                "label": "Event",
+                "format": "string",
+                "value": "cache-misses",
+            }],
+        }])
+    );
+
+    let deserialized: Profile = serde_json::from_value(json.clone()).unwrap();
+    assert_json_eq!(deserialized, json);
+}
+
+#[test]
+fn paused_ranges() {
+    let mut profile = Profile::new(
+        "test",
+        ReferenceTimestamp::from_millis_since_unix_epoch(1636162232627.0),
+        SamplingInterval::from_millis(1),
+    );
+    profile.add_paused_range(
+        Timestamp::from_millis_since_reference(1.5),
+        Timestamp::from_millis_since_reference(3.0),
+        PausedRangeReason::CollectorPaused,
+    );
+
+    let json = serde_json::to_value(&profile).unwrap();
+    assert_eq!(
+        json["meta"]["pausedRanges"],
+        json!([{ "startTime": 1.5, "endTime": 3.0, "reason": "collector-paused" }])
+    );
+
+    let deserialized: Profile = serde_json::from_value(json.clone()).unwrap();
+    assert_json_eq!(deserialized, json);
+
+    let mut json = json;
+    json["meta"]["pausedRanges"][0]["reason"] = json!("Samples lost");
+    assert!(serde_json::from_value::<Profile>(json).is_err());
+}
+
+#[test]
+fn update_lib() {
+    let mut profile = Profile::new(
+        "test",
+        ReferenceTimestamp::from_millis_since_unix_epoch(1636162232627.0),
+        SamplingInterval::from_millis(1),
+    );
+    let process = profile.add_process("test", 123, Timestamp::from_millis_since_reference(0.0));
+    let thread = profile.add_thread(
+        process,
+        12345,
+        Timestamp::from_millis_since_reference(0.0),
+        true,
+    );
+    let jit_lib = |avma_range, symbols| LibraryInfo {
+        base_avma: 0,
+        avma_range,
+        debug_id: DebugId::nil(),
+        code_id: None,
+        path: "/tmp/perf-123.map".to_string(),
+        debug_path: "/tmp/perf-123.map".to_string(),
+        debug_name: "perf-123.map".to_string(),
+        name: "perf-123.map".to_string(),
+        arch: None,
+        symbol_table: Some(Arc::new(SymbolTable::new(symbols))),
+    };
+    let symbol = |address, name: &str| Symbol {
+        address,
+        size: Some(0x10),
+        name: name.to_string(),
+    };
+    let add_sample = |profile: &mut Profile, time, address| {
+        profile.add_sample(
+            thread,
+            Timestamp::from_millis_since_reference(time),
+            vec![(
+                Frame::InstructionPointer(address),
+                CategoryHandle::OTHER.into(),
+            )]
+            .into_iter(),
+            CpuDelta::ZERO,
+            1,
+        )
+    };
+
+    profile.add_lib(
+        process,
+        jit_lib(0x1000..0x1010, vec![symbol(0x1000, "first")]),
+    );
+    add_sample(&mut profile, 1.0, 0x1000);
+
+    // A library inside the range of the JIT library.
+    profile.add_lib(
+        process,
+        LibraryInfo {
+            base_avma: 0x2000,
+            avma_range: 0x2000..0x2100,
+            debug_id: DebugId::nil(),
+            code_id: None,
+            path: "/usr/lib/libc.so.6".to_string(),
+            debug_path: "/usr/lib/libc.so.6".to_string(),
+            debug_name: "libc.so.6".to_string(),
+            name: "libc.so.6".to_string(),
+            arch: None,
+            symbol_table: None,
+        },
+    );
+    let updated = jit_lib(
+        0x1000..0x3010,
+        vec![symbol(0x1000, "first"), symbol(0x3000, "second")],
+    );
+    profile.update_lib(process, 0, updated.avma_range, updated.symbol_table);
+    add_sample(&mut profile, 2.0, 0x1004);
+    add_sample(&mut profile, 3.0, 0x2004);
+    add_sample(&mut profile, 4.0, 0x3004);
+
+    let json = serde_json::to_value(&profile).unwrap();
+    let names: Vec<&str> = json["libs"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|lib| lib["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["perf-123.map", "libc.so.6"]);
+    let thread_json = &json["threads"][0];
+    assert_eq!(
+        thread_json["frameTable"]["address"],
+        json!([0x1000, 0x1004, 0x4, 0x3004])
+    );
+    let string_array = thread_json["stringArray"].as_array().unwrap();
+    let symbol_names: Vec<&str> = thread_json["nativeSymbols"]["name"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|index| {
+            string_array[index.as_u64().unwrap() as usize]
+                .as_str()
+                .unwrap()
+        })
+        .collect();
+    assert_eq!(symbol_names, ["first", "second"]);
+}
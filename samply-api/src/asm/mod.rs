@@ -1,3 +1,4 @@
+use std::convert::TryFrom;
 use std::str::FromStr;
 
 use samply_symbols::{
@@ -102,10 +103,11 @@ impl<'a, 'h: 'a, H: FileAndPathHelper<'h>> AsmApi<'a, 'h, H> {
                 .get_function_end_address(&library_info, *start_address)
                 .await
             {
-                if function_end_address >= *start_address
-                    && function_end_address - *start_address > *size
-                {
-                    disassembly_len = function_end_address - *start_address;
+                let function_len = function_end_address
+                    .checked_sub(*start_address)
+                    .and_then(|len| u32::try_from(len).ok());
+                if let Some(function_len) = function_len {
+                    disassembly_len = disassembly_len.max(function_len);
                 }
             }
         }
@@ -116,8 +118,8 @@ impl<'a, 'h: 'a, H: FileAndPathHelper<'h>> AsmApi<'a, 'h, H> {
     async fn get_function_end_address(
         &self,
         library_info: &LibraryInfo,
-        address_within_function: u32,
-    ) -> Option<u32> {
+        address_within_function: u64,
+    ) -> Option<u64> {
         let symbol_map_res = self.symbol_manager.load_symbol_map(library_info).await;
         let symbol = symbol_map_res
             .ok()?
@@ -129,7 +131,7 @@ impl<'a, 'h: 'a, H: FileAndPathHelper<'h>> AsmApi<'a, 'h, H> {
 
 fn compute_response<'data: 'file, 'file>(
     object: &'file impl Object<'data, 'file>,
-    start_address: u32,
+    start_address: u64,
     disassembly_len: u32,
 ) -> Result<response_json::Response, AsmError> {
     // Align the start address, for architectures with instruction alignment.
@@ -149,7 +151,7 @@ fn compute_response<'data: 'file, 'file>(
     // Translate start_address from a "relative address" into an
     // SVMA ("stated virtual memory address").
     let image_base = relative_address_base(object);
-    let start_svma = image_base + u64::from(relative_start_address);
+    let start_svma = image_base + relative_start_address;
 
     // Find the section and segment which contains our start_svma.
     use object::ObjectSection;
@@ -370,7 +372,7 @@ where
     )) as u32;
 
     Response {
-        start_address: (start_svma - image_base) as u32,
+        start_address: start_svma - image_base,
         size: final_offset,
         arch: A::ARCH_NAME.to_string(),
         syntax: A::SYNTAX.iter().map(ToString::to_string).collect(),
@@ -12,7 +12,7 @@ pub struct Request {
     /// as a "0x"-prefixed hex string, interpreted as a
     /// library-relative offset in bytes.
     #[serde(deserialize_with = "crate::hex::from_prefixed_hex_str")]
-    pub start_address: u32,
+    pub start_address: u64,
 
     /// The length, in bytes, of the machine code that should be disassembled,
     /// as a "0x"-prefixed hex string.
@@ -52,4 +52,20 @@ mod test {
         assert!(!r.continue_until_function_end);
         Ok(())
     }
+
+    #[test]
+    fn parse_64_bit_start_address() -> Result<()> {
+        let data = r#"
+        {
+          "debugName": "libxul.so",
+          "debugId": "A14CAFD390A3E1884C4C44205044422E1",
+          "startAddress": "0x100001000",
+          "size": "0x84"
+        }"#;
+
+        let r: Request = serde_json::from_str(data)?;
+        assert_eq!(r.start_address, 0x1_0000_1000);
+        assert_eq!(r.size, 0x84);
+        Ok(())
+    }
 }
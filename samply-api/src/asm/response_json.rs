@@ -7,7 +7,7 @@ pub struct Response {
     /// as a "0x"-prefixed hex string, interpreted as a
     /// library-relative offset in bytes.
     #[serde(serialize_with = "crate::hex::as_hex_string")]
-    pub start_address: u32,
+    pub start_address: u64,
 
     /// The length, in bytes, of the disassembled machine code.
     #[serde(serialize_with = "crate::hex::as_hex_string")]
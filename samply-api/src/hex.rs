@@ -1,3 +1,5 @@
+use std::convert::TryFrom;
+
 pub fn as_hex_string<S, T>(field: &T, serializer: S) -> std::result::Result<S::Ok, S::Error>
 where
     S: serde::Serializer,
@@ -20,9 +22,10 @@ where
     }
 }
 
-pub fn from_prefixed_hex_str<'de, D>(deserializer: D) -> Result<u32, D::Error>
+pub fn from_prefixed_hex_str<'de, D, T>(deserializer: D) -> Result<T, D::Error>
 where
     D: serde::Deserializer<'de>,
+    T: TryFrom<u64>,
 {
     use serde::Deserialize;
     let s = String::deserialize(deserializer)?;
@@ -33,5 +36,7 @@ where
             "Unexpected hex string {s} without 0x prefix."
         )));
     };
-    u32::from_str_radix(s, 16).map_err(serde::de::Error::custom)
+    let value = u64::from_str_radix(s, 16).map_err(serde::de::Error::custom)?;
+    T::try_from(value)
+        .map_err(|_| serde::de::Error::custom(format!("Hex value 0x{s} is out of range.")))
 }
@@ -16,7 +16,7 @@ pub struct Request {
     /// This address is symbolicated, and any of the files referenced in
     /// the symbolication results is eligible to be requested.
     #[serde(deserialize_with = "crate::hex::from_prefixed_hex_str")]
-    pub module_offset: u32,
+    pub module_offset: u64,
 
     /// The full path of the requested file, must match exactly what
     /// /symbolicate/v5 returned in its response json for the given
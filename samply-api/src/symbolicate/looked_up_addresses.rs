@@ -2,13 +2,13 @@ use samply_symbols::FrameDebugInfo;
 use std::collections::BTreeMap;
 
 pub struct AddressResult {
-    pub symbol_address: u32,
+    pub symbol_address: u64,
     pub symbol_name: String,
-    pub function_size: Option<u32>,
+    pub function_size: Option<u64>,
     pub inline_frames: Option<Vec<FrameDebugInfo>>,
 }
 
-pub type AddressResults = BTreeMap<u32, Option<AddressResult>>;
+pub type AddressResults = BTreeMap<u64, Option<AddressResult>>;
 
 pub struct LookedUpAddresses {
     pub address_results: AddressResults,
@@ -16,7 +16,7 @@ pub struct LookedUpAddresses {
 }
 
 impl LookedUpAddresses {
-    pub fn for_addresses(addresses: &[u32]) -> Self {
+    pub fn for_addresses(addresses: &[u64]) -> Self {
         LookedUpAddresses {
             address_results: addresses.iter().map(|&addr| (addr, None)).collect(),
             symbol_count: 0,
@@ -25,10 +25,10 @@ impl LookedUpAddresses {
 
     pub fn add_address_symbol(
         &mut self,
-        address: u32,
-        symbol_address: u32,
+        address: u64,
+        symbol_address: u64,
         symbol_name: String,
-        function_size: Option<u32>,
+        function_size: Option<u64>,
     ) {
         *self.address_results.get_mut(&address).unwrap() = Some(AddressResult {
             symbol_address,
@@ -38,7 +38,7 @@ impl LookedUpAddresses {
         });
     }
 
-    pub fn add_address_debug_info(&mut self, address: u32, frames: Vec<FrameDebugInfo>) {
+    pub fn add_address_debug_info(&mut self, address: u64, frames: Vec<FrameDebugInfo>) {
         let outer_function_name = frames.last().and_then(|f| f.function.as_deref());
         let entry = self.address_results.get_mut(&address).unwrap();
 
@@ -47,7 +47,7 @@ impl<'a, 'h: 'a, H: FileAndPathHelper<'h>> SymbolicateApi<'a, 'h, H> {
 
     async fn symbolicate_requested_addresses(
         &self,
-        requested_addresses: HashMap<Lib, Vec<u32>>,
+        requested_addresses: HashMap<Lib, Vec<u64>>,
     ) -> HashMap<Lib, Result<LookedUpAddresses, samply_symbols::Error>> {
         let mut symbolicated_addresses = HashMap::new();
         for (lib, addresses) in requested_addresses.into_iter() {
@@ -62,7 +62,7 @@ impl<'a, 'h: 'a, H: FileAndPathHelper<'h>> SymbolicateApi<'a, 'h, H> {
     async fn symbolicate_requested_addresses_for_lib(
         &self,
         lib: &Lib,
-        mut addresses: Vec<u32>,
+        mut addresses: Vec<u64>,
     ) -> Result<LookedUpAddresses, samply_symbols::Error> {
         // Sort the addresses before the lookup, to have a higher chance of hitting
         // the same external file for subsequent addresses.
@@ -134,10 +134,10 @@ impl<'a, 'h: 'a, H: FileAndPathHelper<'h>> SymbolicateApi<'a, 'h, H> {
 
 fn gather_requested_addresses(
     request: &request_json::Request,
-) -> Result<HashMap<Lib, Vec<u32>>, Error> {
-    let mut requested_addresses: HashMap<Lib, Vec<u32>> = HashMap::new();
+) -> Result<HashMap<Lib, Vec<u64>>, Error> {
+    let mut requested_addresses: HashMap<Lib, Vec<u64>> = HashMap::new();
     for job in request.jobs() {
-        let mut requested_addresses_by_module_index: HashMap<u32, Vec<u32>> = HashMap::new();
+        let mut requested_addresses_by_module_index: HashMap<u32, Vec<u64>> = HashMap::new();
         for stack in &job.stacks {
             for frame in &stack.0 {
                 requested_addresses_by_module_index
@@ -37,7 +37,7 @@ pub struct StackFrame {
     /// index into memory_map
     pub module_index: u32,
     /// lib-relative memory offset
-    pub address: u32,
+    pub address: u64,
 }
 
 pub enum JobIterator<'a> {
@@ -120,4 +120,28 @@ mod test {
         assert_eq!(r.jobs().count(), 1);
         Ok(())
     }
+
+    #[test]
+    fn parse_64_bit_address() -> Result<()> {
+        let data = r#"
+        {
+            "memoryMap": [
+              [
+                "libxul.so",
+                "44E4EC8C2F41492B9369D6B9A059577C2"
+              ]
+            ],
+            "stacks": [
+              [
+                [0, 4294971392]
+              ]
+            ]
+          }
+          "#;
+
+        let r: Request = serde_json::from_str(data)?;
+        let job = r.jobs().next().unwrap();
+        assert_eq!(job.stacks[0].0[0].address, 0x1_0000_1000);
+        Ok(())
+    }
 }
@@ -25,7 +25,7 @@ pub struct StackFrame {
     pub frame: u32,
 
     #[serde(serialize_with = "crate::hex::as_hex_string")]
-    pub module_offset: u32,
+    pub module_offset: u64,
 
     pub module: String,
 
@@ -38,13 +38,13 @@ pub struct Symbol {
     pub function: String,
 
     #[serde(serialize_with = "crate::hex::as_hex_string")]
-    pub function_offset: u32,
+    pub function_offset: u64,
 
     #[serde(
         skip_serializing_if = "Option::is_none",
         serialize_with = "crate::hex::as_optional_hex_string"
     )]
-    pub function_size: Option<u32>,
+    pub function_size: Option<u64>,
 
     #[serde(flatten)]
     pub debug_info: Option<DebugInfo>,
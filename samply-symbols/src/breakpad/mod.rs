@@ -1,10 +1,13 @@
 mod index;
 mod symbol_map;
+mod writer;
 
 pub use index::{
     BreakpadIndex, BreakpadIndexParser, BreakpadParseError, BreakpadSymindexParseError,
 };
 pub use symbol_map::get_symbol_map_for_breakpad_sym;
+pub use writer::write_breakpad_sym_file;
+pub(crate) use writer::{write_breakpad_sym_file_with_external_files, ExternalFiles};
 
 use crate::{FileContents, FileContentsWrapper};
 
@@ -1,6 +1,7 @@
 use std::{
     borrow::Cow,
     collections::{hash_map::Entry, HashMap},
+    convert::TryFrom,
     sync::Mutex,
 };
 
@@ -47,11 +48,11 @@ impl<T: FileContents> SymbolMapTrait for BreakpadSymbolMap<T> {
         self.0.get().0.symbol_count()
     }
 
-    fn iter_symbols(&self) -> Box<dyn Iterator<Item = (u32, Cow<'_, str>)> + '_> {
+    fn iter_symbols(&self) -> Box<dyn Iterator<Item = (u64, Cow<'_, str>)> + '_> {
         self.0.get().0.iter_symbols()
     }
 
-    fn lookup_relative_address(&self, address: u32) -> Option<AddressInfo> {
+    fn lookup_relative_address(&self, address: u64) -> Option<AddressInfo> {
         self.0.get().0.lookup_relative_address(address)
     }
 
@@ -62,6 +63,10 @@ impl<T: FileContents> SymbolMapTrait for BreakpadSymbolMap<T> {
     fn lookup_offset(&self, offset: u64) -> Option<AddressInfo> {
         self.0.get().0.lookup_offset(offset)
     }
+
+    fn debug_info_boundaries(&self, start: u64, end: u64) -> Option<Vec<u64>> {
+        self.0.get().0.debug_info_boundaries(start, end)
+    }
 }
 
 pub struct BreakpadSymbolMapOuter<T: FileContents> {
@@ -234,7 +239,7 @@ impl<'a, T: FileContents> SymbolMapTrait for BreakpadSymbolMapInner<'a, T> {
         self.index.symbol_addresses.len()
     }
 
-    fn iter_symbols(&self) -> Box<dyn Iterator<Item = (u32, Cow<'_, str>)> + '_> {
+    fn iter_symbols(&self) -> Box<dyn Iterator<Item = (u64, Cow<'_, str>)> + '_> {
         let iter = (0..self.symbol_count()).filter_map(move |i| {
             let address = self.index.symbol_addresses[i];
             let mut cache = self.cache.lock().unwrap();
@@ -248,12 +253,14 @@ impl<'a, T: FileContents> SymbolMapTrait for BreakpadSymbolMapInner<'a, T> {
                     func_info.name
                 }
             };
-            Some((address, Cow::Borrowed(name)))
+            Some((u64::from(address), Cow::Borrowed(name)))
         });
         Box::new(iter)
     }
 
-    fn lookup_relative_address(&self, address: u32) -> Option<AddressInfo> {
+    fn lookup_relative_address(&self, address: u64) -> Option<AddressInfo> {
+        // The symindex format stores 32-bit addresses.
+        let address = u32::try_from(address).ok()?;
         let index = match self.index.symbol_addresses.binary_search(&address) {
             Ok(i) => i,
             Err(0) => return None,
@@ -272,9 +279,11 @@ impl<'a, T: FileContents> SymbolMapTrait for BreakpadSymbolMapInner<'a, T> {
                 let info = symbols.get_public_info(public, self.data).ok()?;
                 Some(AddressInfo {
                     symbol: SymbolInfo {
-                        address: symbol_address,
+                        address: u64::from(symbol_address),
                         size: next_symbol_address.and_then(|next_symbol_address| {
-                            next_symbol_address.checked_sub(symbol_address)
+                            next_symbol_address
+                                .checked_sub(symbol_address)
+                                .map(u64::from)
                         }),
                         name: info.name.to_string(),
                     },
@@ -324,8 +333,8 @@ impl<'a, T: FileContents> SymbolMapTrait for BreakpadSymbolMapInner<'a, T> {
 
                 Some(AddressInfo {
                     symbol: SymbolInfo {
-                        address: symbol_address,
-                        size: Some(info.size),
+                        address: u64::from(symbol_address),
+                        size: Some(u64::from(info.size)),
                         name: info.name.to_string(),
                     },
                     frames: FramesLookupResult::Available(frames),
@@ -343,6 +352,38 @@ impl<'a, T: FileContents> SymbolMapTrait for BreakpadSymbolMapInner<'a, T> {
         // Breakpad symbol files have no information about file offsets.
         None
     }
+
+    fn debug_info_boundaries(&self, start: u64, end: u64) -> Option<Vec<u64>> {
+        let start = match u32::try_from(start) {
+            Ok(start) => start,
+            Err(_) => return Some(Vec::new()),
+        };
+        let index = match self.index.symbol_addresses.binary_search(&start) {
+            Ok(i) => i,
+            Err(0) => return Some(Vec::new()),
+            Err(i) => i - 1,
+        };
+        let func = match &self.index.symbol_offsets[index] {
+            BreakpadSymbolType::Func(func) => func,
+            BreakpadSymbolType::Public(_) => return Some(Vec::new()),
+        };
+        let mut cache = self.cache.lock().unwrap();
+        let info = cache.symbols.get_func_info(func, self.data).ok()?;
+        let line_ranges = info.lines.iter().map(|line| (line.address, line.size));
+        let inlinee_ranges = info
+            .inlinees
+            .iter()
+            .map(|inlinee| (inlinee.address, inlinee.size));
+        let mut boundaries: Vec<u64> = line_ranges
+            .chain(inlinee_ranges)
+            .flat_map(|(address, size)| [address, address.saturating_add(size)])
+            .map(u64::from)
+            .filter(|address| u64::from(start) <= *address && *address < end)
+            .collect();
+        boundaries.sort_unstable();
+        boundaries.dedup();
+        Some(boundaries)
+    }
 }
 
 #[cfg(test)]
@@ -480,4 +521,36 @@ mod test {
             }
         );
     }
+
+    #[test]
+    fn write_sym_file_round_trip() {
+        let sym = "MODULE Linux x86_64 BE4E976C325246EE9D6B7847A670B2A90 example-linux
+INFO CODE_ID 6c974ebe5232ee469d6b7847a670b2a9db1ffa03 example-linux
+FILE 0 /src/main.c
+FILE 1 /src/inline.h
+INLINE_ORIGIN 0 outer_inline
+INLINE_ORIGIN 1 inner_inline
+FUNC 1000 40 0 main
+INLINE 0 12 0 0 1010 18 1030 10
+INLINE 1 30 1 1 1018 8
+1000 10 10 0
+1010 8 29 1
+1018 8 3 1
+1020 10 13 0
+1030 10 31 1
+PUBLIC 1040 0 _start
+";
+        let fc = FileContentsWrapper::new(sym.as_bytes());
+        let symbol_map = get_symbol_map_for_breakpad_sym(fc, DummyLocation, None).unwrap();
+        let info = crate::LibraryInfo {
+            debug_name: Some("example-linux".into()),
+            name: Some("example-linux".into()),
+            arch: Some("x86_64".into()),
+            code_id: "6c974ebe5232ee469d6b7847a670b2a9db1ffa03".parse().ok(),
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        super::super::write_breakpad_sym_file(&mut output, &symbol_map, &info, "Linux").unwrap();
+        assert_eq!(std::str::from_utf8(&output).unwrap(), sym);
+    }
 }
@@ -0,0 +1,474 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use crate::{
+    ExternalFileAddressRef, ExternalFileRef, ExternalFileSymbolMap, FileLocation, FrameDebugInfo,
+    FramesLookupResult, LibraryInfo, SymbolMap,
+};
+
+/// The loaded external files which contain debug info for a symbol map, see
+/// [`FramesLookupResult::External`].
+pub type ExternalFiles = HashMap<ExternalFileRef, ExternalFileSymbolMap>;
+
+/// Write the contents of `symbol_map` as a Breakpad .sym file.
+///
+/// `os` is the operating system in the `MODULE` record, e.g. `Linux`, `mac` or
+/// `windows`. The architecture, the debug name and the code ID are taken from
+/// `info`. The debug ID is always taken from the symbol map.
+///
+/// Functions with debug info become `FUNC` records with line and `INLINE`
+/// records, all other symbols become `PUBLIC` records.
+///
+/// This function doesn't load any files, so functions whose debug info is in
+/// external object files (Mach-O binaries without a dSYM) become `PUBLIC`
+/// records. Use `SymbolManager::write_breakpad_sym_file` to include them.
+pub fn write_breakpad_sym_file<FL: FileLocation>(
+    w: &mut impl Write,
+    symbol_map: &SymbolMap<FL>,
+    info: &LibraryInfo,
+    os: &str,
+) -> std::io::Result<()> {
+    write_breakpad_sym_file_with_external_files(w, symbol_map, &ExternalFiles::new(), info, os)
+}
+
+/// Like [`write_breakpad_sym_file`], but the debug info of functions which is
+/// in one of `external_files` is written as well.
+pub fn write_breakpad_sym_file_with_external_files<FL: FileLocation>(
+    w: &mut impl Write,
+    symbol_map: &SymbolMap<FL>,
+    external_files: &ExternalFiles,
+    info: &LibraryInfo,
+    os: &str,
+) -> std::io::Result<()> {
+    let mut symbol_addresses: Vec<u64> = symbol_map.iter_symbols().map(|(a, _)| a).collect();
+    symbol_addresses.sort_unstable();
+    symbol_addresses.dedup();
+
+    let mut files = StringTable::default();
+    let mut inline_origins = StringTable::default();
+    let mut records = Vec::new();
+    for (i, &address) in symbol_addresses.iter().enumerate() {
+        let address_info = match symbol_map.lookup_relative_address(address) {
+            Some(address_info) if address_info.symbol.address == address => address_info,
+            _ => continue,
+        };
+        let name = address_info.symbol.name;
+        let size = address_info.symbol.size.or_else(|| {
+            let next_address = symbol_addresses.get(i + 1)?;
+            next_address.checked_sub(address)
+        });
+        let func = size.and_then(|size| {
+            make_func_record(
+                symbol_map,
+                external_files,
+                address,
+                size,
+                &name,
+                &mut files,
+                &mut inline_origins,
+            )
+        });
+        records.push(match func {
+            Some(func) => SymbolRecord::Func(func),
+            None => SymbolRecord::Public { address, name },
+        });
+    }
+
+    let debug_name = info.debug_name.as_deref().unwrap_or("<unknown>");
+    let arch = info.arch.as_deref().unwrap_or("unknown");
+    let debug_id = symbol_map.debug_id();
+    let debug_id = debug_id.breakpad();
+    writeln!(w, "MODULE {os} {arch} {debug_id} {debug_name}")?;
+    if let Some(code_id) = &info.code_id {
+        match &info.name {
+            Some(name) => writeln!(w, "INFO CODE_ID {code_id} {name}")?,
+            None => writeln!(w, "INFO CODE_ID {code_id}")?,
+        }
+    }
+    for (index, file) in files.strings.iter().enumerate() {
+        writeln!(w, "FILE {index} {file}")?;
+    }
+    for (index, origin) in inline_origins.strings.iter().enumerate() {
+        writeln!(w, "INLINE_ORIGIN {index} {origin}")?;
+    }
+    for record in records {
+        match record {
+            SymbolRecord::Public { address, name } => {
+                writeln!(w, "PUBLIC {address:x} 0 {name}")?;
+            }
+            SymbolRecord::Func(func) => {
+                writeln!(w, "FUNC {:x} {:x} 0 {}", func.address, func.size, func.name)?;
+                for inline in func.inlines {
+                    write!(
+                        w,
+                        "INLINE {} {} {} {}",
+                        inline.depth, inline.call_line, inline.call_file, inline.origin
+                    )?;
+                    for (address, size) in inline.ranges {
+                        write!(w, " {address:x} {size:x}")?;
+                    }
+                    writeln!(w)?;
+                }
+                for line in func.lines {
+                    writeln!(
+                        w,
+                        "{:x} {:x} {} {}",
+                        line.address, line.size, line.line, line.file
+                    )?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+enum SymbolRecord {
+    Public { address: u64, name: String },
+    Func(FuncRecord),
+}
+
+struct FuncRecord {
+    address: u64,
+    size: u64,
+    name: String,
+    inlines: Vec<InlineRecord>,
+    lines: Vec<LineRecord>,
+}
+
+struct InlineRecord {
+    depth: usize,
+    call_line: u32,
+    call_file: u32,
+    origin: u32,
+    ranges: Vec<(u64, u64)>,
+}
+
+/// The (origin, call file, call line) of an inlined call.
+type InlineKey = (u32, u32, u32);
+
+struct LineRecord {
+    address: u64,
+    size: u64,
+    line: u32,
+    file: u32,
+}
+
+/// Assigns the indexes for `FILE` and `INLINE_ORIGIN` records.
+#[derive(Default)]
+struct StringTable {
+    strings: Vec<String>,
+    indexes: HashMap<String, u32>,
+}
+
+impl StringTable {
+    fn index_for(&mut self, s: &str) -> u32 {
+        if let Some(index) = self.indexes.get(s) {
+            return *index;
+        }
+        let index = self.strings.len() as u32;
+        self.strings.push(s.to_string());
+        self.indexes.insert(s.to_string(), index);
+        index
+    }
+}
+
+/// Build the `FUNC` record for the function at `address`, or return `None` if
+/// there is no debug info for this function.
+fn make_func_record<FL: FileLocation>(
+    symbol_map: &SymbolMap<FL>,
+    external_files: &ExternalFiles,
+    address: u64,
+    size: u64,
+    name: &str,
+    files: &mut StringTable,
+    inline_origins: &mut StringTable,
+) -> Option<FuncRecord> {
+    let end = address.checked_add(size)?;
+    let boundaries = match symbol_map.lookup_relative_address(address)?.frames {
+        FramesLookupResult::External(external_address) => {
+            external_debug_info_boundaries(external_files, &external_address, address, size)
+        }
+        _ => symbol_map.inner.debug_info_boundaries(address, end),
+    };
+    let has_address_ranges = boundaries.is_some();
+    let mut starts = boundaries.unwrap_or_default();
+    starts.push(address);
+    starts.sort_unstable();
+    starts.dedup();
+
+    let mut has_debug_info = false;
+    let mut lines: Vec<LineRecord> = Vec::new();
+    let mut inlines: Vec<InlineRecord> = Vec::new();
+    // Maps the record index of the caller (`None` for the outer function) and the
+    // key of an inlined call to its record in `inlines`,
+    // so that all address ranges of the same inlined call end up in one record.
+    let mut inline_record_indexes: HashMap<(Option<usize>, InlineKey), usize> = HashMap::new();
+    for (i, &start) in starts.iter().enumerate() {
+        let frames = match symbol_map.lookup_relative_address(start) {
+            Some(address_info) => match address_info.frames {
+                FramesLookupResult::Available(frames) => frames,
+                FramesLookupResult::External(external_address) => {
+                    match external_files
+                        .get(&external_address.file_ref)
+                        .and_then(|file| file.lookup(&external_address.address_in_file))
+                    {
+                        Some(frames) => frames,
+                        None => continue,
+                    }
+                }
+                _ => continue,
+            },
+            None => continue,
+        };
+        if frames.is_empty() {
+            continue;
+        }
+        has_debug_info = true;
+        if !has_address_ranges {
+            break;
+        }
+        let range_end = starts.get(i + 1).copied().unwrap_or(end);
+        let range_size = range_end - start;
+
+        // `frames` starts with the innermost frame, and the last frame is the
+        // function itself. The call location of each inlined function is the
+        // location in the frame that follows it.
+        let inline_keys: Vec<InlineKey> = frames
+            .windows(2)
+            .rev()
+            .map(|pair| {
+                let (callee, caller) = (&pair[0], &pair[1]);
+                let origin =
+                    inline_origins.index_for(callee.function.as_deref().unwrap_or("unknown"));
+                (
+                    origin,
+                    file_index(caller, files),
+                    caller.line_number.unwrap_or(0),
+                )
+            })
+            .collect();
+        let mut parent_record_index = None;
+        for (depth, &key) in inline_keys.iter().enumerate() {
+            let record_index = *inline_record_indexes
+                .entry((parent_record_index, key))
+                .or_insert_with(|| {
+                    let (origin, call_file, call_line) = key;
+                    inlines.push(InlineRecord {
+                        depth,
+                        call_line,
+                        call_file,
+                        origin,
+                        ranges: Vec::new(),
+                    });
+                    inlines.len() - 1
+                });
+            let ranges = &mut inlines[record_index].ranges;
+            match ranges.last_mut() {
+                Some((address, size)) if *address + *size == start => *size += range_size,
+                _ => ranges.push((start, range_size)),
+            }
+            parent_record_index = Some(record_index);
+        }
+
+        let innermost_frame = &frames[0];
+        if innermost_frame.file_path.is_none() {
+            continue;
+        }
+        let file = file_index(innermost_frame, files);
+        let line = innermost_frame.line_number.unwrap_or(0);
+        match lines.last_mut() {
+            Some(last)
+                if last.address + last.size == start && last.file == file && last.line == line =>
+            {
+                last.size += range_size;
+            }
+            _ => lines.push(LineRecord {
+                address: start,
+                size: range_size,
+                line,
+                file,
+            }),
+        }
+    }
+
+    if !has_debug_info {
+        return None;
+    }
+    Some(FuncRecord {
+        address,
+        size,
+        name: name.to_string(),
+        inlines,
+        lines,
+    })
+}
+
+/// Returns the debug info boundaries of the function at `address`, whose debug
+/// info is in an external file, as relative addresses in the symbol map.
+fn external_debug_info_boundaries(
+    external_files: &ExternalFiles,
+    external_address: &ExternalFileAddressRef,
+    address: u64,
+    size: u64,
+) -> Option<Vec<u64>> {
+    let file = external_files.get(&external_address.file_ref)?;
+    let address_in_file = &external_address.address_in_file;
+    let offset_from_symbol = u64::from(address_in_file.offset_from_symbol);
+    let boundaries = file.debug_info_boundaries(address_in_file, size)?;
+    Some(
+        boundaries
+            .into_iter()
+            .map(|offset| address + (offset - offset_from_symbol))
+            .collect(),
+    )
+}
+
+fn file_index(frame: &FrameDebugInfo, files: &mut StringTable) -> u32 {
+    let path = frame.file_path.as_ref().map(|path| path.raw_path());
+    files.index_for(path.unwrap_or("<unknown>"))
+}
+
+#[cfg(test)]
+mod test {
+    use std::borrow::Cow;
+
+    use debugid::DebugId;
+
+    use super::*;
+    use crate::symbol_map::SymbolMapTrait;
+    use crate::{AddressInfo, ExternalFileAddressInFileRef, SymbolInfo};
+
+    #[derive(Clone)]
+    struct DummyLocation;
+
+    impl FileLocation for DummyLocation {
+        fn location_for_dyld_subcache(&self, _suffix: &str) -> Option<Self> {
+            None
+        }
+
+        fn location_for_external_object_file(&self, _object_file: &str) -> Option<Self> {
+            None
+        }
+
+        fn location_for_pdb_from_binary(&self, _pdb_path: &str) -> Option<Self> {
+            None
+        }
+
+        fn location_for_source_file(&self, _source_file_path: &str) -> Option<Self> {
+            None
+        }
+
+        fn location_for_breakpad_symindex(&self) -> Option<Self> {
+            None
+        }
+    }
+    impl std::fmt::Display for DummyLocation {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            "DummyLocation".fmt(f)
+        }
+    }
+
+    fn archive_file_ref() -> ExternalFileRef {
+        ExternalFileRef {
+            file_name: "example-linux-archive.a".into(),
+            arch: None,
+        }
+    }
+
+    /// A symbol map like the one of a Mach-O binary without a dSYM: It has a single
+    /// function at 0x2000 whose debug info is in the archive member `example.o`.
+    struct ExternalDebugInfoSymbolMap;
+
+    impl SymbolMapTrait for ExternalDebugInfoSymbolMap {
+        fn debug_id(&self) -> DebugId {
+            DebugId::nil()
+        }
+
+        fn symbol_count(&self) -> usize {
+            1
+        }
+
+        fn iter_symbols(&self) -> Box<dyn Iterator<Item = (u64, Cow<'_, str>)> + '_> {
+            Box::new(std::iter::once((0x2000, Cow::Borrowed("sum_of_squares"))))
+        }
+
+        fn lookup_relative_address(&self, address: u64) -> Option<AddressInfo> {
+            if !(0x2000..0x200a).contains(&address) {
+                return None;
+            }
+            Some(AddressInfo {
+                symbol: SymbolInfo {
+                    address: 0x2000,
+                    size: Some(0xa),
+                    name: "sum_of_squares".into(),
+                },
+                frames: FramesLookupResult::External(ExternalFileAddressRef {
+                    file_ref: archive_file_ref(),
+                    address_in_file: ExternalFileAddressInFileRef {
+                        name_in_archive: Some("example.o".into()),
+                        symbol_name: b"sum_of_squares".to_vec(),
+                        offset_from_symbol: (address - 0x2000) as u32,
+                    },
+                }),
+            })
+        }
+
+        fn lookup_svma(&self, _svma: u64) -> Option<AddressInfo> {
+            None
+        }
+
+        fn lookup_offset(&self, _offset: u64) -> Option<AddressInfo> {
+            None
+        }
+    }
+
+    #[test]
+    fn write_sym_file_with_external_files() {
+        let symbol_map = SymbolMap::new(DummyLocation, Box::new(ExternalDebugInfoSymbolMap));
+        let info = LibraryInfo {
+            debug_name: Some("example".into()),
+            arch: Some("x86_64".into()),
+            ..Default::default()
+        };
+
+        let mut output = Vec::new();
+        write_breakpad_sym_file(&mut output, &symbol_map, &info, "mac").unwrap();
+        assert_eq!(
+            std::str::from_utf8(&output).unwrap(),
+            "MODULE mac x86_64 000000000000000000000000000000000 example
+PUBLIC 2000 0 sum_of_squares
+"
+        );
+
+        let archive_path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../fixtures/other/example-linux-archive.a");
+        let archive = std::fs::read(archive_path).unwrap();
+        let mut external_files = ExternalFiles::new();
+        external_files.insert(
+            archive_file_ref(),
+            ExternalFileSymbolMap::new("example-linux-archive.a", archive, None).unwrap(),
+        );
+        let mut output = Vec::new();
+        write_breakpad_sym_file_with_external_files(
+            &mut output,
+            &symbol_map,
+            &external_files,
+            &info,
+            "mac",
+        )
+        .unwrap();
+        assert_eq!(
+            std::str::from_utf8(&output).unwrap(),
+            "MODULE mac x86_64 000000000000000000000000000000000 example
+FILE 0 ./example.c
+INLINE_ORIGIN 0 square
+FUNC 2000 a 0 sum_of_squares
+INLINE 0 6 0 0 2000 3
+INLINE 0 7 0 0 2003 3
+2000 6 2 0
+2006 3 7 0
+2009 1 9 0
+"
+        );
+    }
+}
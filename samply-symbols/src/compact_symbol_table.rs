@@ -9,7 +9,7 @@ use crate::{FileLocation, SymbolMap};
 pub struct CompactSymbolTable {
     /// A sorted array of symbol addresses, as library-relative offsets in
     /// bytes, in ascending order.
-    pub addr: Vec<u32>,
+    pub addr: Vec<u64>,
     /// Contains positions into `buffer`. For every address `addr[i]`,
     /// `index[i]` is the position where the string for that address starts in
     /// the buffer. Also contains one extra index at the end which is `buffer.len()`.
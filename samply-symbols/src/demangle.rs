@@ -1,4 +1,4 @@
-use super::demangle_ocaml;
+use super::{demangle_ocaml, demangle_swift};
 use msvc_demangler::DemangleFlags;
 
 pub fn demangle_any(name: &str) -> String {
@@ -18,6 +18,10 @@ pub fn demangle_any(name: &str) -> String {
         return format!("{demangled_symbol:#}");
     }
 
+    if let Some(symbol) = demangle_swift::demangle(name) {
+        return symbol;
+    }
+
     if name.starts_with('_') {
         let options = cpp_demangle::DemangleOptions::default().no_return_type();
         if let Ok(symbol) = cpp_demangle::Symbol::new(name) {
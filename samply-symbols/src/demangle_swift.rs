@@ -0,0 +1,2567 @@
+//! A demangler for Swift symbols (`$s...`, `_$s...`, `$S...`).
+//!
+//! This follows the structure of the demangler in the Swift compiler
+//! (`lib/Demangling/Demangler.cpp`): the mangled name is parsed by a stack
+//! machine into a node tree, which is then printed the same way that
+//! `swift-demangle -simplified` prints it. Only the parts of the mangling
+//! grammar that show up in the symbol tables of compiled code are supported;
+//! for anything else, `demangle` returns `None`.
+
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+pub fn demangle(name: &str) -> Option<String> {
+    let mangled = ["_$s", "$s", "_$S", "$S"]
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix))?;
+    let global = Demangler::new(mangled.as_bytes()).demangle_symbol()?;
+    let mut printer = Printer::default();
+    printer.print(&global, false);
+    if printer.valid {
+        Some(printer.out)
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Global,
+    Suffix,
+    Type,
+    TypeMangling,
+    Module,
+    Identifier,
+    LocalDeclName,
+    PrivateDeclName,
+    InfixOperator,
+    PrefixOperator,
+    PostfixOperator,
+    Number,
+    Index,
+    Class,
+    Structure,
+    Enum,
+    Protocol,
+    TypeAlias,
+    Extension,
+    Function,
+    Allocator,
+    Constructor,
+    Deallocator,
+    Destructor,
+    IVarInitializer,
+    IVarDestroyer,
+    ExplicitClosure,
+    ImplicitClosure,
+    DefaultArgumentInitializer,
+    Initializer,
+    Variable,
+    Subscript,
+    Static,
+    Getter,
+    Setter,
+    ModifyAccessor,
+    ReadAccessor,
+    WillSet,
+    DidSet,
+    GlobalGetter,
+    UnsafeAddressor,
+    UnsafeMutableAddressor,
+    MaterializeForSet,
+    LabelList,
+    EmptyList,
+    FirstElementMarker,
+    VariadicMarker,
+    TypeList,
+    Tuple,
+    TupleElement,
+    TupleElementName,
+    FunctionType,
+    NoEscapeFunctionType,
+    AutoClosureType,
+    EscapingAutoClosureType,
+    ThinFunctionType,
+    CFunctionPointer,
+    ObjCBlock,
+    EscapingObjCBlock,
+    UncurriedFunctionType,
+    ArgumentTuple,
+    ReturnType,
+    ThrowsAnnotation,
+    AsyncAnnotation,
+    ConcurrentFunctionType,
+    GlobalActorFunctionType,
+    InOut,
+    Shared,
+    Owned,
+    Weak,
+    Unowned,
+    Unmanaged,
+    Isolated,
+    Sending,
+    CompileTimeConst,
+    DynamicSelf,
+    Metatype,
+    ExistentialMetatype,
+    ProtocolList,
+    ProtocolListWithClass,
+    ProtocolListWithAnyObject,
+    BuiltinTypeName,
+    BoundGenericClass,
+    BoundGenericStructure,
+    BoundGenericEnum,
+    BoundGenericProtocol,
+    BoundGenericTypeAlias,
+    DependentGenericParamType,
+    DependentGenericParamCount,
+    DependentGenericSignature,
+    DependentGenericType,
+    DependentMemberType,
+    DependentAssociatedTypeRef,
+    Requirement,
+    OpaqueReturnType,
+    TypeMetadata,
+    TypeMetadataAccessFunction,
+    TypeMetadataPattern,
+    TypeMetadataLazyCache,
+    TypeMetadataInstantiationCache,
+    FullTypeMetadata,
+    NominalTypeDescriptor,
+    ProtocolDescriptor,
+    ClassMetadataBaseOffset,
+    MethodLookupFunction,
+    ObjCMetadataUpdateFunction,
+    ValueWitnessTable,
+    ProtocolWitnessTable,
+    ProtocolWitnessTableAccessor,
+    LazyProtocolWitnessTableAccessor,
+    LazyProtocolWitnessTableCacheVariable,
+    ProtocolConformance,
+    ProtocolWitness,
+    FieldOffset,
+    Directness,
+    PartialApplyForwarder,
+    PartialApplyObjCForwarder,
+    ObjCAttribute,
+    NonObjCAttribute,
+    DynamicAttribute,
+    DirectMethodReferenceAttribute,
+    MergedFunction,
+    DispatchThunk,
+    MethodDescriptor,
+    CurryThunk,
+    AsyncFunctionPointer,
+    AsyncAwaitResumePartialFunction,
+    AsyncSuspendResumePartialFunction,
+    GenericSpecialization,
+    FunctionSignatureSpecialization,
+    SpecializationPassId,
+    FunctionSignatureSpecializationParam,
+    FunctionSignatureSpecializationReturn,
+}
+
+impl Kind {
+    fn is_context(self) -> bool {
+        matches!(
+            self,
+            Kind::Module
+                | Kind::Class
+                | Kind::Structure
+                | Kind::Enum
+                | Kind::Protocol
+                | Kind::TypeAlias
+                | Kind::Extension
+                | Kind::Function
+                | Kind::Allocator
+                | Kind::Constructor
+                | Kind::Deallocator
+                | Kind::Destructor
+                | Kind::IVarInitializer
+                | Kind::IVarDestroyer
+                | Kind::ExplicitClosure
+                | Kind::ImplicitClosure
+                | Kind::DefaultArgumentInitializer
+                | Kind::Initializer
+                | Kind::Variable
+                | Kind::Subscript
+                | Kind::Static
+                | Kind::Getter
+                | Kind::Setter
+                | Kind::ModifyAccessor
+                | Kind::ReadAccessor
+                | Kind::WillSet
+                | Kind::DidSet
+                | Kind::GlobalGetter
+                | Kind::UnsafeAddressor
+                | Kind::UnsafeMutableAddressor
+                | Kind::MaterializeForSet
+        )
+    }
+
+    fn is_entity(self) -> bool {
+        self == Kind::Type || self.is_context()
+    }
+
+    fn is_decl_name(self) -> bool {
+        matches!(
+            self,
+            Kind::Identifier
+                | Kind::LocalDeclName
+                | Kind::PrivateDeclName
+                | Kind::InfixOperator
+                | Kind::PrefixOperator
+                | Kind::PostfixOperator
+        )
+    }
+
+    fn is_any_generic(self) -> bool {
+        matches!(
+            self,
+            Kind::Class | Kind::Structure | Kind::Enum | Kind::Protocol | Kind::TypeAlias
+        )
+    }
+
+    fn is_function_attr(self) -> bool {
+        matches!(
+            self,
+            Kind::PartialApplyForwarder
+                | Kind::PartialApplyObjCForwarder
+                | Kind::ObjCAttribute
+                | Kind::NonObjCAttribute
+                | Kind::DynamicAttribute
+                | Kind::DirectMethodReferenceAttribute
+                | Kind::MergedFunction
+                | Kind::AsyncFunctionPointer
+                | Kind::AsyncAwaitResumePartialFunction
+                | Kind::AsyncSuspendResumePartialFunction
+                | Kind::GenericSpecialization
+                | Kind::FunctionSignatureSpecialization
+        )
+    }
+
+    fn is_function_type(self) -> bool {
+        matches!(
+            self,
+            Kind::FunctionType
+                | Kind::NoEscapeFunctionType
+                | Kind::AutoClosureType
+                | Kind::EscapingAutoClosureType
+                | Kind::ThinFunctionType
+                | Kind::CFunctionPointer
+                | Kind::ObjCBlock
+                | Kind::EscapingObjCBlock
+                | Kind::UncurriedFunctionType
+        )
+    }
+}
+
+type NodeRef = Rc<Node>;
+
+#[derive(Debug)]
+struct Node {
+    kind: Kind,
+    text: String,
+    index: u64,
+    children: Vec<NodeRef>,
+    /// The length of the longest path from this node down to a leaf.
+    depth: usize,
+}
+
+impl Node {
+    fn leaf(kind: Kind) -> NodeRef {
+        Self::with_children(kind, Vec::new())
+    }
+
+    fn with_text(kind: Kind, text: impl Into<String>) -> NodeRef {
+        Rc::new(Node {
+            kind,
+            text: text.into(),
+            index: 0,
+            children: Vec::new(),
+            depth: 0,
+        })
+    }
+
+    fn with_index(kind: Kind, index: u64) -> NodeRef {
+        Rc::new(Node {
+            kind,
+            text: String::new(),
+            index,
+            children: Vec::new(),
+            depth: 0,
+        })
+    }
+
+    fn with_children(kind: Kind, children: Vec<NodeRef>) -> NodeRef {
+        let depth = children.iter().map(|child| child.depth + 1).max();
+        Rc::new(Node {
+            kind,
+            text: String::new(),
+            index: 0,
+            children,
+            depth: depth.unwrap_or(0),
+        })
+    }
+
+    fn with_child(kind: Kind, child: NodeRef) -> NodeRef {
+        Self::with_children(kind, vec![child])
+    }
+
+    fn type_of(child: NodeRef) -> NodeRef {
+        Self::with_child(Kind::Type, child)
+    }
+
+    fn child(&self, index: usize) -> Option<&NodeRef> {
+        self.children.get(index)
+    }
+
+    fn find_child(&self, kind: Kind) -> Option<&NodeRef> {
+        self.children.iter().find(|child| child.kind == kind)
+    }
+}
+
+/// The maximum number of words that can be referenced by word substitutions.
+const MAX_NUM_WORDS: usize = 26;
+
+/// The maximum depth of the node tree. Deeper trees are rejected so that
+/// printing and dropping them can't overflow the stack, even on threads with
+/// small stacks and in debug builds. Real symbols are nowhere near this deep.
+const MAX_NODE_DEPTH: usize = 256;
+
+struct Demangler<'a> {
+    text: &'a [u8],
+    pos: usize,
+    stack: Vec<NodeRef>,
+    substitutions: Vec<NodeRef>,
+    words: Vec<&'a [u8]>,
+}
+
+impl<'a> Demangler<'a> {
+    fn new(text: &'a [u8]) -> Self {
+        Self {
+            text,
+            pos: 0,
+            stack: Vec::new(),
+            substitutions: Vec::new(),
+            words: Vec::new(),
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn next_if(&mut self, c: u8) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn push_back(&mut self) {
+        self.pos -= 1;
+    }
+
+    fn pop_if(&mut self, pred: impl FnOnce(Kind) -> bool) -> Option<NodeRef> {
+        match self.stack.last() {
+            Some(node) if pred(node.kind) => self.stack.pop(),
+            _ => None,
+        }
+    }
+
+    fn pop_kind(&mut self, kind: Kind) -> Option<NodeRef> {
+        self.pop_if(|k| k == kind)
+    }
+
+    fn pop_type_and_get_child(&mut self) -> Option<NodeRef> {
+        self.pop_kind(Kind::Type)?.child(0).cloned()
+    }
+
+    fn pop_type_and_get_any_generic(&mut self) -> Option<NodeRef> {
+        let child = self.pop_type_and_get_child()?;
+        if child.kind.is_any_generic() {
+            Some(child)
+        } else {
+            None
+        }
+    }
+
+    fn demangle_natural(&mut self) -> Option<u64> {
+        if !self.peek()?.is_ascii_digit() {
+            return None;
+        }
+        let mut num: u64 = 0;
+        while let Some(c) = self.peek().filter(u8::is_ascii_digit) {
+            num = num.checked_mul(10)?.checked_add(u64::from(c - b'0'))?;
+            self.pos += 1;
+        }
+        Some(num)
+    }
+
+    fn demangle_index(&mut self) -> Option<u64> {
+        if self.next_if(b'_') {
+            return Some(0);
+        }
+        let num = self.demangle_natural()?;
+        if self.next_if(b'_') {
+            num.checked_add(1)
+        } else {
+            None
+        }
+    }
+
+    fn demangle_index_as_node(&mut self) -> Option<NodeRef> {
+        Some(Node::with_index(Kind::Number, self.demangle_index()?))
+    }
+
+    fn demangle_symbol(mut self) -> Option<NodeRef> {
+        while self.pos < self.text.len() {
+            let node = self.demangle_operator()?;
+            if node.depth > MAX_NODE_DEPTH {
+                return None;
+            }
+            self.stack.push(node);
+        }
+
+        let mut attrs = Vec::new();
+        while let Some(attr) = self.pop_if(Kind::is_function_attr) {
+            attrs.push(attr);
+        }
+
+        // Everything but the suffix has to have been folded into one node.
+        let mut rest: Vec<NodeRef> = Vec::new();
+        for node in std::mem::take(&mut self.stack) {
+            match node.kind {
+                Kind::Suffix => {}
+                Kind::Type => rest.push(node.child(0)?.clone()),
+                _ => rest.push(node),
+            }
+        }
+        if rest.len() != 1 {
+            return None;
+        }
+
+        let mut children = Vec::new();
+        let mut partial_apply = None;
+        for attr in attrs {
+            match attr.kind {
+                Kind::PartialApplyForwarder | Kind::PartialApplyObjCForwarder
+                    if partial_apply.is_none() =>
+                {
+                    partial_apply = Some(attr.kind)
+                }
+                _ => children.push(attr),
+            }
+        }
+        children.extend(rest);
+        let global = match partial_apply {
+            Some(kind) => vec![Node::with_children(kind, children)],
+            None => children,
+        };
+        Some(Node::with_children(Kind::Global, global))
+    }
+
+    fn demangle_identifier(&mut self) -> Option<NodeRef> {
+        let mut has_word_substs = false;
+        let mut is_punycoded = false;
+        if self.peek()? == b'0' {
+            self.pos += 1;
+            if self.peek()? == b'0' {
+                self.pos += 1;
+                is_punycoded = true;
+            } else {
+                has_word_substs = true;
+            }
+        }
+        if is_punycoded {
+            // Identifiers with non-ASCII characters are not supported.
+            return None;
+        }
+
+        let mut identifier = Vec::new();
+        loop {
+            while has_word_substs && self.peek()?.is_ascii_alphabetic() {
+                let c = self.next()?;
+                let word_index = if c.is_ascii_lowercase() {
+                    c - b'a'
+                } else {
+                    has_word_substs = false;
+                    c - b'A'
+                };
+                identifier.extend_from_slice(self.words.get(usize::from(word_index))?);
+            }
+            if self.next_if(b'0') {
+                break;
+            }
+            let num_chars = usize::try_from(self.demangle_natural()?).ok()?;
+            let end = self.pos.checked_add(num_chars)?;
+            if num_chars == 0 || end > self.text.len() {
+                return None;
+            }
+            let slice = &self.text[self.pos..end];
+            self.pos = end;
+            identifier.extend_from_slice(slice);
+            self.add_words(slice);
+            if !has_word_substs {
+                break;
+            }
+        }
+        if identifier.is_empty() {
+            return None;
+        }
+
+        let text = String::from_utf8(identifier).ok()?;
+        let node = Node::with_text(Kind::Identifier, text);
+        self.substitutions.push(node.clone());
+        Some(node)
+    }
+
+    /// Collects the words of a literal identifier part, which can be referenced
+    /// by word substitutions in later identifiers.
+    fn add_words(&mut self, slice: &'a [u8]) {
+        fn is_word_start(c: u8) -> bool {
+            !c.is_ascii_digit() && c != b'_' && c != 0
+        }
+        fn is_word_end(c: u8, prev: u8) -> bool {
+            c == b'_' || c == 0 || (!prev.is_ascii_uppercase() && c.is_ascii_uppercase())
+        }
+
+        let mut word_start = None;
+        for idx in 0..=slice.len() {
+            let c = slice.get(idx).copied().unwrap_or(0);
+            if let Some(start) = word_start {
+                if is_word_end(c, slice[idx - 1]) {
+                    if idx - start >= 2 && self.words.len() < MAX_NUM_WORDS {
+                        self.words.push(&slice[start..idx]);
+                    }
+                    word_start = None;
+                }
+            }
+            if word_start.is_none() && is_word_start(c) {
+                word_start = Some(idx);
+            }
+        }
+    }
+
+    fn demangle_operator_identifier(&mut self) -> Option<NodeRef> {
+        const OP_CHARS: &[u8] = b"& @/= >    <*!|+?%-~   ^ .";
+
+        let ident = self.pop_kind(Kind::Identifier)?;
+        let mut op = String::new();
+        for c in ident.text.chars() {
+            if !c.is_ascii() {
+                op.push(c);
+                continue;
+            }
+            if !c.is_ascii_lowercase() {
+                return None;
+            }
+            let op_char = OP_CHARS[usize::from(c as u8 - b'a')];
+            if op_char == b' ' {
+                return None;
+            }
+            op.push(char::from(op_char));
+        }
+        let kind = match self.next()? {
+            b'i' => Kind::InfixOperator,
+            b'p' => Kind::PrefixOperator,
+            b'P' => Kind::PostfixOperator,
+            _ => return None,
+        };
+        Some(Node::with_text(kind, op))
+    }
+
+    fn demangle_local_identifier(&mut self) -> Option<NodeRef> {
+        if self.next_if(b'L') {
+            let discriminator = self.pop_kind(Kind::Identifier)?;
+            let name = self.pop_if(Kind::is_decl_name)?;
+            return Some(Node::with_children(
+                Kind::PrivateDeclName,
+                vec![discriminator, name],
+            ));
+        }
+        if self.next_if(b'l') {
+            let discriminator = self.pop_kind(Kind::Identifier)?;
+            return Some(Node::with_child(Kind::PrivateDeclName, discriminator));
+        }
+        if matches!(self.peek()?, b'a'..=b'j' | b'A'..=b'J') {
+            // Related entity names (e.g. imported Clang declarations).
+            return None;
+        }
+        let discriminator = self.demangle_index_as_node()?;
+        let name = self.pop_if(Kind::is_decl_name)?;
+        Some(Node::with_children(
+            Kind::LocalDeclName,
+            vec![discriminator, name],
+        ))
+    }
+
+    fn demangle_multi_substitutions(&mut self) -> Option<NodeRef> {
+        let mut repeat_count = None;
+        loop {
+            let c = self.next()?;
+            if c.is_ascii_lowercase() {
+                let node = self.push_multi_substitutions(repeat_count, usize::from(c - b'a'))?;
+                self.stack.push(node);
+                repeat_count = None;
+            } else if c.is_ascii_uppercase() {
+                return self.push_multi_substitutions(repeat_count, usize::from(c - b'A'));
+            } else if c == b'_' {
+                let index = usize::try_from(repeat_count?.checked_add(27)?).ok()?;
+                return self.substitutions.get(index).cloned();
+            } else {
+                self.push_back();
+                repeat_count = Some(self.demangle_natural()?);
+            }
+        }
+    }
+
+    fn push_multi_substitutions(
+        &mut self,
+        repeat_count: Option<u64>,
+        index: usize,
+    ) -> Option<NodeRef> {
+        let node = self.substitutions.get(index)?.clone();
+        let repeat_count = repeat_count.unwrap_or(1);
+        if repeat_count > 2048 {
+            return None;
+        }
+        for _ in 1..repeat_count {
+            self.stack.push(node.clone());
+        }
+        Some(node)
+    }
+}
+
+impl<'a> Demangler<'a> {
+    fn demangle_operator(&mut self) -> Option<NodeRef> {
+        match self.next()? {
+            b'A' => self.demangle_multi_substitutions(),
+            b'B' => self.demangle_builtin_type(),
+            b'C' => self.demangle_any_generic_type(Kind::Class),
+            b'D' => Some(Node::with_child(
+                Kind::TypeMangling,
+                self.pop_kind(Kind::Type)?,
+            )),
+            b'E' => self.demangle_extension_context(),
+            b'F' => self.demangle_plain_function(),
+            b'G' => self.demangle_bound_generic_type(),
+            b'K' => Some(Node::leaf(Kind::ThrowsAnnotation)),
+            b'L' => self.demangle_local_identifier(),
+            b'M' => self.demangle_metadata(),
+            b'N' => Some(Node::with_child(
+                Kind::TypeMetadata,
+                self.pop_kind(Kind::Type)?,
+            )),
+            b'O' => self.demangle_any_generic_type(Kind::Enum),
+            b'P' => self.demangle_any_generic_type(Kind::Protocol),
+            b'Q' => self.demangle_archetype(),
+            b'R' => self.demangle_generic_requirement(),
+            b'S' => self.demangle_standard_substitution(),
+            b'T' => self.demangle_thunk_or_specialization(),
+            b'V' => self.demangle_any_generic_type(Kind::Structure),
+            b'W' => self.demangle_witness(),
+            b'X' => self.demangle_special_type(),
+            b'Y' => self.demangle_type_annotation(),
+            b'Z' => Some(Node::with_child(
+                Kind::Static,
+                self.pop_if(Kind::is_entity)?,
+            )),
+            b'a' => self.demangle_any_generic_type(Kind::TypeAlias),
+            b'c' => self.pop_function_type(Kind::FunctionType),
+            b'd' => Some(Node::leaf(Kind::VariadicMarker)),
+            b'f' => self.demangle_function_entity(),
+            b'h' => self.wrap_type(Kind::Shared),
+            b'i' => self.demangle_subscript(),
+            b'l' => self.demangle_generic_signature(false),
+            b'm' => {
+                let ty = self.pop_kind(Kind::Type)?;
+                Some(Node::type_of(Node::with_child(Kind::Metatype, ty)))
+            }
+            b'n' => self.wrap_type(Kind::Owned),
+            b'o' => self.demangle_operator_identifier(),
+            b'p' => Some(Node::type_of(self.demangle_protocol_list()?)),
+            b'q' => Some(Node::type_of(self.demangle_generic_param_index()?)),
+            b'r' => self.demangle_generic_signature(true),
+            b's' => Some(Node::with_text(Kind::Module, "Swift")),
+            b't' => self.pop_tuple(),
+            b'u' => self.demangle_generic_type(),
+            b'v' => self.demangle_variable(),
+            b'x' => Some(Node::type_of(dependent_generic_param_type(0, 0))),
+            b'y' => Some(Node::leaf(Kind::EmptyList)),
+            b'z' => self.wrap_type(Kind::InOut),
+            b'_' => Some(Node::leaf(Kind::FirstElementMarker)),
+            b'.' => {
+                let suffix = std::str::from_utf8(&self.text[self.pos - 1..]).ok()?;
+                self.pos = self.text.len();
+                Some(Node::with_text(Kind::Suffix, suffix))
+            }
+            _ => {
+                self.push_back();
+                self.demangle_identifier()
+            }
+        }
+    }
+
+    /// Wraps the type on top of the stack, e.g. for `inout` parameters.
+    fn wrap_type(&mut self, kind: Kind) -> Option<NodeRef> {
+        let ty = self.pop_type_and_get_child()?;
+        Some(Node::type_of(Node::with_child(kind, ty)))
+    }
+
+    fn pop_module(&mut self) -> Option<NodeRef> {
+        if let Some(ident) = self.pop_kind(Kind::Identifier) {
+            return Some(Node::with_text(Kind::Module, ident.text.clone()));
+        }
+        self.pop_kind(Kind::Module)
+    }
+
+    fn pop_context(&mut self) -> Option<NodeRef> {
+        if let Some(module) = self.pop_module() {
+            return Some(module);
+        }
+        if let Some(ty) = self.pop_kind(Kind::Type) {
+            return ty.child(0).cloned();
+        }
+        self.pop_if(Kind::is_context)
+    }
+
+    fn demangle_any_generic_type(&mut self, kind: Kind) -> Option<NodeRef> {
+        let name = self.pop_if(Kind::is_decl_name)?;
+        let context = self.pop_context()?;
+        let ty = Node::type_of(Node::with_children(kind, vec![context, name]));
+        self.substitutions.push(ty.clone());
+        Some(ty)
+    }
+
+    fn demangle_extension_context(&mut self) -> Option<NodeRef> {
+        let generic_sig = self.pop_kind(Kind::DependentGenericSignature);
+        let module = self.pop_module()?;
+        let ty = self.pop_type_and_get_any_generic()?;
+        let mut children = vec![module, ty];
+        children.extend(generic_sig);
+        Some(Node::with_children(Kind::Extension, children))
+    }
+
+    fn demangle_builtin_type(&mut self) -> Option<NodeRef> {
+        let name = match self.next()? {
+            b'b' => "Builtin.BridgeObject".to_string(),
+            b'B' => "Builtin.UnsafeValueBuffer".to_string(),
+            b'e' => "Builtin.Executor".to_string(),
+            b'f' => format!("Builtin.FPIEEE{}", self.demangle_index()?.checked_sub(1)?),
+            b'i' => format!("Builtin.Int{}", self.demangle_index()?.checked_sub(1)?),
+            b'I' => "Builtin.IntLiteral".to_string(),
+            b'O' => "Builtin.UnknownObject".to_string(),
+            b'o' => "Builtin.NativeObject".to_string(),
+            b'p' => "Builtin.RawPointer".to_string(),
+            b't' => "Builtin.SILToken".to_string(),
+            b'w' => "Builtin.Word".to_string(),
+            b'c' => "Builtin.RawUnsafeContinuation".to_string(),
+            b'D' => "Builtin.DefaultActorStorage".to_string(),
+            b'd' => "Builtin.NonDefaultDistributedActorStorage".to_string(),
+            b'j' => "Builtin.Job".to_string(),
+            _ => return None,
+        };
+        Some(Node::type_of(Node::with_text(Kind::BuiltinTypeName, name)))
+    }
+
+    fn demangle_standard_substitution(&mut self) -> Option<NodeRef> {
+        match self.peek()? {
+            b'o' => {
+                self.pos += 1;
+                return Some(Node::with_text(Kind::Module, "__C"));
+            }
+            b'C' => {
+                self.pos += 1;
+                return Some(Node::with_text(Kind::Module, "__C_Synthesized"));
+            }
+            b'g' => {
+                self.pos += 1;
+                let ty = self.pop_kind(Kind::Type)?;
+                let optional = Node::type_of(Node::with_children(
+                    Kind::BoundGenericEnum,
+                    vec![
+                        swift_type(Kind::Enum, "Optional"),
+                        Node::with_child(Kind::TypeList, ty),
+                    ],
+                ));
+                self.substitutions.push(optional.clone());
+                return Some(optional);
+            }
+            _ => {}
+        }
+        let repeat_count = self.demangle_natural().unwrap_or(1);
+        if repeat_count > 2048 {
+            return None;
+        }
+        let second_level = self.next_if(b'c');
+        let node = standard_substitution(self.next()?, second_level)?;
+        for _ in 1..repeat_count {
+            self.stack.push(node.clone());
+        }
+        Some(node)
+    }
+
+    fn demangle_bound_generic_type(&mut self) -> Option<NodeRef> {
+        let mut type_lists = Vec::new();
+        loop {
+            let mut types = Vec::new();
+            while let Some(ty) = self.pop_kind(Kind::Type) {
+                types.push(ty);
+            }
+            types.reverse();
+            type_lists.push(Node::with_children(Kind::TypeList, types));
+            if self.pop_kind(Kind::EmptyList).is_some() {
+                break;
+            }
+            self.pop_kind(Kind::FirstElementMarker)?;
+        }
+        let nominal = self.pop_type_and_get_any_generic()?;
+        let bound = Node::type_of(bound_generic_args(&nominal, &type_lists, 0)?);
+        self.substitutions.push(bound.clone());
+        Some(bound)
+    }
+
+    /// Pops the elements of a type list which ends in a `_` marker, or which
+    /// is empty (`y`).
+    fn pop_type_list(&mut self) -> Option<NodeRef> {
+        let mut types = Vec::new();
+        if self.pop_kind(Kind::EmptyList).is_none() {
+            loop {
+                let first = self.pop_kind(Kind::FirstElementMarker).is_some();
+                types.push(self.pop_kind(Kind::Type)?);
+                if first {
+                    break;
+                }
+            }
+            types.reverse();
+        }
+        Some(Node::with_children(Kind::TypeList, types))
+    }
+
+    fn pop_tuple(&mut self) -> Option<NodeRef> {
+        let mut elements = Vec::new();
+        if self.pop_kind(Kind::EmptyList).is_none() {
+            loop {
+                let first = self.pop_kind(Kind::FirstElementMarker).is_some();
+                let mut children = Vec::new();
+                children.extend(self.pop_kind(Kind::VariadicMarker));
+                if let Some(ident) = self.pop_kind(Kind::Identifier) {
+                    children.push(Node::with_text(Kind::TupleElementName, ident.text.clone()));
+                }
+                children.push(self.pop_kind(Kind::Type)?);
+                elements.push(Node::with_children(Kind::TupleElement, children));
+                if first {
+                    break;
+                }
+            }
+            elements.reverse();
+        }
+        Some(Node::type_of(Node::with_children(Kind::Tuple, elements)))
+    }
+
+    fn demangle_generic_type(&mut self) -> Option<NodeRef> {
+        let generic_sig = self.pop_kind(Kind::DependentGenericSignature)?;
+        let ty = self.pop_kind(Kind::Type)?;
+        Some(Node::type_of(Node::with_children(
+            Kind::DependentGenericType,
+            vec![generic_sig, ty],
+        )))
+    }
+
+    fn demangle_protocol_list(&mut self) -> Option<NodeRef> {
+        let mut protocols = Vec::new();
+        if self.pop_kind(Kind::EmptyList).is_none() {
+            loop {
+                let first = self.pop_kind(Kind::FirstElementMarker).is_some();
+                protocols.push(self.pop_protocol()?);
+                if first {
+                    break;
+                }
+            }
+            protocols.reverse();
+        }
+        Some(Node::with_child(
+            Kind::ProtocolList,
+            Node::with_children(Kind::TypeList, protocols),
+        ))
+    }
+
+    fn pop_protocol(&mut self) -> Option<NodeRef> {
+        if let Some(ty) = self.pop_kind(Kind::Type) {
+            return if ty.child(0)?.kind == Kind::Protocol {
+                Some(ty)
+            } else {
+                None
+            };
+        }
+        let name = self.pop_if(Kind::is_decl_name)?;
+        let context = self.pop_context()?;
+        Some(Node::type_of(Node::with_children(
+            Kind::Protocol,
+            vec![context, name],
+        )))
+    }
+
+    fn demangle_special_type(&mut self) -> Option<NodeRef> {
+        match self.next()? {
+            b'E' => self.pop_function_type(Kind::NoEscapeFunctionType),
+            b'A' => self.pop_function_type(Kind::EscapingAutoClosureType),
+            b'f' => self.pop_function_type(Kind::ThinFunctionType),
+            b'K' => self.pop_function_type(Kind::AutoClosureType),
+            b'U' => self.pop_function_type(Kind::UncurriedFunctionType),
+            b'L' => self.pop_function_type(Kind::EscapingObjCBlock),
+            b'B' => self.pop_function_type(Kind::ObjCBlock),
+            b'C' => self.pop_function_type(Kind::CFunctionPointer),
+            b'o' => self.wrap_type(Kind::Unowned),
+            b'u' => self.wrap_type(Kind::Unmanaged),
+            b'w' => self.wrap_type(Kind::Weak),
+            b'D' => self.wrap_type(Kind::DynamicSelf),
+            b'p' => {
+                let ty = self.pop_kind(Kind::Type)?;
+                Some(Node::type_of(Node::with_child(
+                    Kind::ExistentialMetatype,
+                    ty,
+                )))
+            }
+            b'c' => {
+                let superclass = self.pop_kind(Kind::Type)?;
+                let protocols = self.demangle_protocol_list()?;
+                Some(Node::type_of(Node::with_children(
+                    Kind::ProtocolListWithClass,
+                    vec![protocols, superclass],
+                )))
+            }
+            b'l' => {
+                let protocols = self.demangle_protocol_list()?;
+                Some(Node::type_of(Node::with_child(
+                    Kind::ProtocolListWithAnyObject,
+                    protocols,
+                )))
+            }
+            _ => None,
+        }
+    }
+
+    fn demangle_type_annotation(&mut self) -> Option<NodeRef> {
+        match self.next()? {
+            b'a' => Some(Node::leaf(Kind::AsyncAnnotation)),
+            b'b' => Some(Node::leaf(Kind::ConcurrentFunctionType)),
+            b'c' => Some(Node::with_child(
+                Kind::GlobalActorFunctionType,
+                self.pop_type_and_get_child()?,
+            )),
+            b'K' => Some(Node::with_child(
+                Kind::ThrowsAnnotation,
+                self.pop_type_and_get_child()?,
+            )),
+            b'i' => self.wrap_type(Kind::Isolated),
+            b't' => self.wrap_type(Kind::CompileTimeConst),
+            b'u' => self.wrap_type(Kind::Sending),
+            _ => None,
+        }
+    }
+
+    fn pop_function_type(&mut self, kind: Kind) -> Option<NodeRef> {
+        let mut children = Vec::new();
+        children.extend(self.pop_kind(Kind::GlobalActorFunctionType));
+        children.extend(self.pop_kind(Kind::ThrowsAnnotation));
+        children.extend(self.pop_kind(Kind::ConcurrentFunctionType));
+        children.extend(self.pop_kind(Kind::AsyncAnnotation));
+        children.push(self.pop_function_params(Kind::ArgumentTuple)?);
+        children.push(self.pop_function_params(Kind::ReturnType)?);
+        Some(Node::type_of(Node::with_children(kind, children)))
+    }
+
+    fn pop_function_params(&mut self, kind: Kind) -> Option<NodeRef> {
+        let params = match self.pop_kind(Kind::EmptyList) {
+            Some(_) => Node::type_of(Node::leaf(Kind::Tuple)),
+            None => self.pop_kind(Kind::Type)?,
+        };
+        Some(Node::with_child(kind, params))
+    }
+
+    /// Pops the argument labels of a function whose type is `ty`. Returns
+    /// `None` if the function has no label list.
+    fn pop_function_param_labels(&mut self, ty: &NodeRef) -> Option<NodeRef> {
+        if self.pop_kind(Kind::EmptyList).is_some() {
+            return Some(Node::leaf(Kind::LabelList));
+        }
+        let mut func_type = ty.child(0)?;
+        if func_type.kind == Kind::DependentGenericType {
+            func_type = func_type.child(1)?.child(0)?;
+        }
+        if !matches!(
+            func_type.kind,
+            Kind::FunctionType | Kind::NoEscapeFunctionType
+        ) {
+            return None;
+        }
+        let params = func_type
+            .find_child(Kind::ArgumentTuple)?
+            .child(0)?
+            .child(0)?;
+        let num_params = if params.kind == Kind::Tuple {
+            params.children.len()
+        } else {
+            1
+        };
+        if num_params == 0 {
+            return None;
+        }
+        let mut labels = Vec::new();
+        for _ in 0..num_params {
+            labels.push(self.pop_if(|k| k == Kind::Identifier || k == Kind::FirstElementMarker)?);
+        }
+        if labels.iter().all(|l| l.kind == Kind::FirstElementMarker) {
+            return Some(Node::leaf(Kind::LabelList));
+        }
+        labels.reverse();
+        Some(Node::with_children(Kind::LabelList, labels))
+    }
+}
+
+fn swift_type(kind: Kind, name: &str) -> NodeRef {
+    Node::type_of(Node::with_children(
+        kind,
+        vec![
+            Node::with_text(Kind::Module, "Swift"),
+            Node::with_text(Kind::Identifier, name),
+        ],
+    ))
+}
+
+fn standard_substitution(c: u8, second_level: bool) -> Option<NodeRef> {
+    use Kind::{Enum, Protocol, Structure};
+    let (kind, name) = if second_level {
+        match c {
+            b'A' => (Protocol, "Actor"),
+            b'C' => (Structure, "CheckedContinuation"),
+            b'c' => (Structure, "UnsafeContinuation"),
+            b'E' => (Structure, "CancellationError"),
+            b'e' => (Structure, "UnownedSerialExecutor"),
+            b'F' => (Protocol, "Executor"),
+            b'f' => (Protocol, "SerialExecutor"),
+            b'G' => (Structure, "TaskGroup"),
+            b'g' => (Structure, "ThrowingTaskGroup"),
+            b'I' => (Protocol, "AsyncIteratorProtocol"),
+            b'i' => (Protocol, "AsyncSequence"),
+            b'J' => (Structure, "UnownedJob"),
+            b'M' => (Kind::Class, "MainActor"),
+            b'P' => (Structure, "TaskPriority"),
+            b'S' => (Structure, "AsyncStream"),
+            b's' => (Structure, "AsyncThrowingStream"),
+            b'T' => (Structure, "Task"),
+            b't' => (Structure, "UnsafeCurrentTask"),
+            _ => return None,
+        }
+    } else {
+        match c {
+            b'A' => (Structure, "AutoreleasingUnsafeMutablePointer"),
+            b'a' => (Structure, "Array"),
+            b'b' => (Structure, "Bool"),
+            b'D' => (Structure, "Dictionary"),
+            b'd' => (Structure, "Double"),
+            b'f' => (Structure, "Float"),
+            b'h' => (Structure, "Set"),
+            b'I' => (Structure, "DefaultIndices"),
+            b'i' => (Structure, "Int"),
+            b'J' => (Structure, "Character"),
+            b'N' => (Structure, "ClosedRange"),
+            b'n' => (Structure, "Range"),
+            b'O' => (Structure, "ObjectIdentifier"),
+            b'P' => (Structure, "UnsafePointer"),
+            b'p' => (Structure, "UnsafeMutablePointer"),
+            b'R' => (Structure, "UnsafeBufferPointer"),
+            b'r' => (Structure, "UnsafeMutableBufferPointer"),
+            b'S' => (Structure, "String"),
+            b's' => (Structure, "Substring"),
+            b'u' => (Structure, "UInt"),
+            b'V' => (Structure, "UnsafeRawPointer"),
+            b'v' => (Structure, "UnsafeMutableRawPointer"),
+            b'W' => (Structure, "UnsafeRawBufferPointer"),
+            b'w' => (Structure, "UnsafeMutableRawBufferPointer"),
+            b'q' => (Enum, "Optional"),
+            b'B' => (Protocol, "BinaryFloatingPoint"),
+            b'E' => (Protocol, "Encodable"),
+            b'e' => (Protocol, "Decodable"),
+            b'F' => (Protocol, "FloatingPoint"),
+            b'G' => (Protocol, "RandomNumberGenerator"),
+            b'H' => (Protocol, "Hashable"),
+            b'j' => (Protocol, "Numeric"),
+            b'K' => (Protocol, "BidirectionalCollection"),
+            b'k' => (Protocol, "RandomAccessCollection"),
+            b'L' => (Protocol, "Comparable"),
+            b'l' => (Protocol, "Collection"),
+            b'M' => (Protocol, "MutableCollection"),
+            b'm' => (Protocol, "RangeReplaceableCollection"),
+            b'Q' => (Protocol, "Equatable"),
+            b'T' => (Protocol, "Sequence"),
+            b't' => (Protocol, "IteratorProtocol"),
+            b'U' => (Protocol, "UnsignedInteger"),
+            b'X' => (Protocol, "RangeExpression"),
+            b'x' => (Protocol, "Strideable"),
+            b'Y' => (Protocol, "RawRepresentable"),
+            b'y' => (Protocol, "StringProtocol"),
+            b'Z' => (Protocol, "SignedInteger"),
+            b'z' => (Protocol, "BinaryInteger"),
+            _ => return None,
+        }
+    };
+    Some(swift_type(kind, name))
+}
+
+fn dependent_generic_param_type(depth: u64, index: u64) -> NodeRef {
+    Node::with_children(
+        Kind::DependentGenericParamType,
+        vec![
+            Node::with_index(Kind::Index, depth),
+            Node::with_index(Kind::Index, index),
+        ],
+    )
+}
+
+/// Applies the generic arguments in `type_lists` to `nominal` and its parent
+/// contexts. The innermost type comes first.
+fn bound_generic_args(nominal: &NodeRef, type_lists: &[NodeRef], index: usize) -> Option<NodeRef> {
+    let args = type_lists.get(index)?;
+    let context = nominal.child(0)?;
+    let mut nominal = nominal.clone();
+    if index + 1 < type_lists.len() {
+        let bound_parent = if context.kind == Kind::Extension {
+            let bound = bound_generic_args(context.child(1)?, type_lists, index + 1)?;
+            let mut children = vec![context.child(0)?.clone(), bound];
+            children.extend(context.child(2).cloned());
+            Node::with_children(Kind::Extension, children)
+        } else {
+            bound_generic_args(context, type_lists, index + 1)?
+        };
+        let mut children = vec![bound_parent];
+        children.extend(nominal.children[1..].iter().cloned());
+        nominal = Node::with_children(nominal.kind, children);
+    }
+    if args.children.is_empty() {
+        return Some(nominal);
+    }
+    let kind = match nominal.kind {
+        Kind::Class => Kind::BoundGenericClass,
+        Kind::Structure => Kind::BoundGenericStructure,
+        Kind::Enum => Kind::BoundGenericEnum,
+        Kind::Protocol => Kind::BoundGenericProtocol,
+        Kind::TypeAlias => Kind::BoundGenericTypeAlias,
+        _ => return None,
+    };
+    Some(Node::with_children(
+        kind,
+        vec![Node::type_of(nominal), args.clone()],
+    ))
+}
+
+/// The parameter kinds of a function signature specialization which take
+/// extra operands from the node stack.
+const FUNC_SPEC_PARAM_PLAIN: u64 = 0;
+const FUNC_SPEC_PARAM_CLOSURE_PROP: u64 = 1;
+const FUNC_SPEC_PARAM_CONSTANT_PROP_NAME: u64 = 2;
+
+impl<'a> Demangler<'a> {
+    fn demangle_plain_function(&mut self) -> Option<NodeRef> {
+        let generic_sig = self.pop_kind(Kind::DependentGenericSignature);
+        let mut ty = self.pop_function_type(Kind::FunctionType)?;
+        let label_list = self.pop_function_param_labels(&ty);
+        if let Some(generic_sig) = generic_sig {
+            ty = Node::type_of(Node::with_children(
+                Kind::DependentGenericType,
+                vec![generic_sig, ty],
+            ));
+        }
+        let name = self.pop_if(Kind::is_decl_name)?;
+        let context = self.pop_context()?;
+        let mut children = vec![context, name];
+        children.extend(label_list);
+        children.push(ty);
+        Some(Node::with_children(Kind::Function, children))
+    }
+
+    fn demangle_entity(&mut self, kind: Kind) -> Option<NodeRef> {
+        let ty = self.pop_kind(Kind::Type)?;
+        let label_list = self.pop_function_param_labels(&ty);
+        let name = self.pop_if(Kind::is_decl_name)?;
+        let context = self.pop_context()?;
+        let mut children = vec![context, name];
+        children.extend(label_list);
+        children.push(ty);
+        Some(Node::with_children(kind, children))
+    }
+
+    fn demangle_variable(&mut self) -> Option<NodeRef> {
+        let variable = self.demangle_entity(Kind::Variable)?;
+        self.demangle_accessor(variable)
+    }
+
+    fn demangle_subscript(&mut self) -> Option<NodeRef> {
+        let private_name = self.pop_kind(Kind::PrivateDeclName);
+        let ty = self.pop_kind(Kind::Type)?;
+        let label_list = self.pop_function_param_labels(&ty);
+        let context = self.pop_context()?;
+        let mut children = vec![context];
+        children.extend(label_list);
+        children.push(ty);
+        children.extend(private_name);
+        self.demangle_accessor(Node::with_children(Kind::Subscript, children))
+    }
+
+    fn demangle_accessor(&mut self, storage: NodeRef) -> Option<NodeRef> {
+        let kind = match self.next()? {
+            b'm' => Kind::MaterializeForSet,
+            b's' => Kind::Setter,
+            b'g' => Kind::Getter,
+            b'G' => Kind::GlobalGetter,
+            b'w' => Kind::WillSet,
+            b'W' => Kind::DidSet,
+            b'r' => Kind::ReadAccessor,
+            b'M' => Kind::ModifyAccessor,
+            b'a' if self.next_if(b'u') => Kind::UnsafeMutableAddressor,
+            b'l' if self.next_if(b'u') => Kind::UnsafeAddressor,
+            // A pseudo-accessor which refers to the storage itself.
+            b'p' => return Some(storage),
+            _ => return None,
+        };
+        Some(Node::with_child(kind, storage))
+    }
+
+    fn demangle_function_entity(&mut self) -> Option<NodeRef> {
+        enum Args {
+            None,
+            TypeAndMaybePrivateName,
+            TypeAndIndex,
+            Index,
+        }
+
+        let (kind, args) = match self.next()? {
+            b'D' => (Kind::Deallocator, Args::None),
+            b'd' => (Kind::Destructor, Args::None),
+            b'E' => (Kind::IVarDestroyer, Args::None),
+            b'e' => (Kind::IVarInitializer, Args::None),
+            b'i' => (Kind::Initializer, Args::None),
+            b'C' => (Kind::Allocator, Args::TypeAndMaybePrivateName),
+            b'c' => (Kind::Constructor, Args::TypeAndMaybePrivateName),
+            b'U' => (Kind::ExplicitClosure, Args::TypeAndIndex),
+            b'u' => (Kind::ImplicitClosure, Args::TypeAndIndex),
+            b'A' => (Kind::DefaultArgumentInitializer, Args::Index),
+            _ => return None,
+        };
+
+        let mut children = Vec::new();
+        match args {
+            Args::None => {
+                children.push(self.pop_context()?);
+            }
+            Args::TypeAndMaybePrivateName => {
+                let private_name = self.pop_kind(Kind::PrivateDeclName);
+                let ty = self.pop_kind(Kind::Type)?;
+                let label_list = self.pop_function_param_labels(&ty);
+                children.push(self.pop_context()?);
+                children.extend(label_list);
+                children.push(ty);
+                children.extend(private_name);
+            }
+            Args::TypeAndIndex => {
+                let index = self.demangle_index_as_node()?;
+                let ty = self.pop_kind(Kind::Type)?;
+                children.push(self.pop_context()?);
+                children.push(index);
+                children.push(ty);
+            }
+            Args::Index => {
+                let index = self.demangle_index_as_node()?;
+                children.push(self.pop_context()?);
+                children.push(index);
+            }
+        }
+        Some(Node::with_children(kind, children))
+    }
+
+    fn demangle_generic_param_index(&mut self) -> Option<NodeRef> {
+        if self.next_if(b'd') {
+            let depth = self.demangle_index()?.checked_add(1)?;
+            let index = self.demangle_index()?;
+            return Some(dependent_generic_param_type(depth, index));
+        }
+        if self.next_if(b'z') {
+            return Some(dependent_generic_param_type(0, 0));
+        }
+        Some(dependent_generic_param_type(
+            0,
+            self.demangle_index()?.checked_add(1)?,
+        ))
+    }
+
+    fn demangle_generic_signature(&mut self, has_param_counts: bool) -> Option<NodeRef> {
+        let mut children = Vec::new();
+        if has_param_counts {
+            while !self.next_if(b'l') {
+                let count = if self.next_if(b'z') {
+                    0
+                } else {
+                    self.demangle_index()?.checked_add(1)?
+                };
+                children.push(Node::with_index(Kind::DependentGenericParamCount, count));
+            }
+        } else {
+            children.push(Node::with_index(Kind::DependentGenericParamCount, 1));
+        }
+        let num_counts = children.len();
+        while let Some(requirement) = self.pop_kind(Kind::Requirement) {
+            children.push(requirement);
+        }
+        children[num_counts..].reverse();
+        Some(Node::with_children(
+            Kind::DependentGenericSignature,
+            children,
+        ))
+    }
+
+    fn demangle_generic_requirement(&mut self) -> Option<NodeRef> {
+        enum TypeKind {
+            Generic,
+            Assoc,
+            CompoundAssoc,
+            Substitution,
+        }
+        enum ConstraintKind {
+            Protocol,
+            BaseClass,
+            SameType,
+            Layout,
+        }
+
+        let (constraint, type_kind) = match self.next()? {
+            b'c' => (ConstraintKind::BaseClass, TypeKind::Assoc),
+            b'C' => (ConstraintKind::BaseClass, TypeKind::CompoundAssoc),
+            b'b' => (ConstraintKind::BaseClass, TypeKind::Generic),
+            b'B' => (ConstraintKind::BaseClass, TypeKind::Substitution),
+            b't' => (ConstraintKind::SameType, TypeKind::Assoc),
+            b'T' => (ConstraintKind::SameType, TypeKind::CompoundAssoc),
+            b's' => (ConstraintKind::SameType, TypeKind::Generic),
+            b'S' => (ConstraintKind::SameType, TypeKind::Substitution),
+            b'm' => (ConstraintKind::Layout, TypeKind::Assoc),
+            b'M' => (ConstraintKind::Layout, TypeKind::CompoundAssoc),
+            b'l' => (ConstraintKind::Layout, TypeKind::Generic),
+            b'L' => (ConstraintKind::Layout, TypeKind::Substitution),
+            b'p' => (ConstraintKind::Protocol, TypeKind::Assoc),
+            b'P' => (ConstraintKind::Protocol, TypeKind::CompoundAssoc),
+            b'Q' => (ConstraintKind::Protocol, TypeKind::Substitution),
+            b'v' | b'h' | b'i' | b'I' => return None,
+            _ => {
+                self.push_back();
+                (ConstraintKind::Protocol, TypeKind::Generic)
+            }
+        };
+
+        let constrained_type = match type_kind {
+            TypeKind::Generic => Node::type_of(self.demangle_generic_param_index()?),
+            TypeKind::Assoc => {
+                let base = self.demangle_generic_param_index()?;
+                let ty = self.demangle_associated_type_simple(Some(base))?;
+                self.substitutions.push(ty.clone());
+                ty
+            }
+            TypeKind::CompoundAssoc => {
+                let base = self.demangle_generic_param_index()?;
+                let ty = self.demangle_associated_type_compound(Some(base))?;
+                self.substitutions.push(ty.clone());
+                ty
+            }
+            TypeKind::Substitution => self.pop_kind(Kind::Type)?,
+        };
+
+        let constraint = match constraint {
+            ConstraintKind::Protocol => self.pop_protocol()?,
+            ConstraintKind::BaseClass | ConstraintKind::SameType => self.pop_kind(Kind::Type)?,
+            ConstraintKind::Layout => {
+                let layout = self.next()?;
+                match layout {
+                    b'U' | b'R' | b'N' | b'C' | b'D' | b'T' => {}
+                    b'E' | b'e' => {
+                        self.demangle_index()?;
+                        self.demangle_index()?;
+                    }
+                    b'M' | b'm' | b'S' => {
+                        self.demangle_index()?;
+                    }
+                    _ => return None,
+                }
+                Node::with_text(Kind::Identifier, char::from(layout).to_string())
+            }
+        };
+        Some(Node::with_children(
+            Kind::Requirement,
+            vec![constrained_type, constraint],
+        ))
+    }
+
+    fn pop_assoc_type_name(&mut self) -> Option<NodeRef> {
+        let protocol = match self.pop_kind(Kind::Type) {
+            Some(ty) if ty.child(0)?.kind == Kind::Protocol => Some(ty),
+            Some(_) => return None,
+            None => None,
+        };
+        let ident = self.pop_kind(Kind::Identifier)?;
+        let mut node = Node {
+            kind: Kind::DependentAssociatedTypeRef,
+            text: ident.text.clone(),
+            index: 0,
+            children: Vec::new(),
+            depth: 0,
+        };
+        if let Some(protocol) = protocol {
+            node.depth = protocol.depth + 1;
+            node.children.push(protocol);
+        }
+        Some(Rc::new(node))
+    }
+
+    fn demangle_associated_type_simple(&mut self, base: Option<NodeRef>) -> Option<NodeRef> {
+        let name = self.pop_assoc_type_name()?;
+        let base = match base {
+            Some(base) => Node::type_of(base),
+            None => self.pop_kind(Kind::Type)?,
+        };
+        Some(Node::type_of(Node::with_children(
+            Kind::DependentMemberType,
+            vec![base, name],
+        )))
+    }
+
+    fn demangle_associated_type_compound(&mut self, base: Option<NodeRef>) -> Option<NodeRef> {
+        let mut names = Vec::new();
+        loop {
+            let first = self.pop_kind(Kind::FirstElementMarker).is_some();
+            names.push(self.pop_assoc_type_name()?);
+            if first {
+                break;
+            }
+        }
+        let mut base = match base {
+            Some(base) => base,
+            None => self.pop_type_and_get_child()?,
+        };
+        while let Some(name) = names.pop() {
+            base = Node::with_children(Kind::DependentMemberType, vec![Node::type_of(base), name]);
+        }
+        Some(Node::type_of(base))
+    }
+
+    fn demangle_archetype(&mut self) -> Option<NodeRef> {
+        let ty = match self.next()? {
+            b'r' => return Some(Node::type_of(Node::leaf(Kind::OpaqueReturnType))),
+            b'x' => self.demangle_associated_type_simple(None)?,
+            b'X' => self.demangle_associated_type_compound(None)?,
+            b'y' => {
+                let base = self.demangle_generic_param_index()?;
+                self.demangle_associated_type_simple(Some(base))?
+            }
+            b'Y' => {
+                let base = self.demangle_generic_param_index()?;
+                self.demangle_associated_type_compound(Some(base))?
+            }
+            b'z' => {
+                self.demangle_associated_type_simple(Some(dependent_generic_param_type(0, 0)))?
+            }
+            b'Z' => {
+                self.demangle_associated_type_compound(Some(dependent_generic_param_type(0, 0)))?
+            }
+            _ => return None,
+        };
+        self.substitutions.push(ty.clone());
+        Some(ty)
+    }
+
+    fn demangle_metadata(&mut self) -> Option<NodeRef> {
+        let kind = match self.next()? {
+            b'a' => Kind::TypeMetadataAccessFunction,
+            b'f' => Kind::FullTypeMetadata,
+            b'i' => Kind::TypeMetadataInstantiationCache,
+            b'L' => Kind::TypeMetadataLazyCache,
+            b'n' => Kind::NominalTypeDescriptor,
+            b'o' => Kind::ClassMetadataBaseOffset,
+            b'P' => Kind::TypeMetadataPattern,
+            b'u' => Kind::MethodLookupFunction,
+            b'U' => Kind::ObjCMetadataUpdateFunction,
+            b'p' => {
+                let protocol = self.pop_protocol()?;
+                return Some(Node::with_child(Kind::ProtocolDescriptor, protocol));
+            }
+            _ => return None,
+        };
+        Some(Node::with_child(kind, self.pop_kind(Kind::Type)?))
+    }
+
+    fn pop_protocol_conformance(&mut self) -> Option<NodeRef> {
+        let generic_sig = self.pop_kind(Kind::DependentGenericSignature);
+        let module = self.pop_module()?;
+        let protocol = self.pop_protocol()?;
+        let mut ty = self.pop_kind(Kind::Type)?;
+        if let Some(generic_sig) = generic_sig {
+            ty = Node::type_of(Node::with_children(
+                Kind::DependentGenericType,
+                vec![generic_sig, ty],
+            ));
+        }
+        Some(Node::with_children(
+            Kind::ProtocolConformance,
+            vec![ty, protocol, module],
+        ))
+    }
+
+    fn demangle_witness(&mut self) -> Option<NodeRef> {
+        match self.next()? {
+            b'V' => Some(Node::with_child(
+                Kind::ValueWitnessTable,
+                self.pop_kind(Kind::Type)?,
+            )),
+            b'v' => {
+                let directness = match self.next()? {
+                    b'd' => "direct",
+                    b'i' => "indirect",
+                    _ => return None,
+                };
+                let entity = self.pop_if(Kind::is_entity)?;
+                Some(Node::with_children(
+                    Kind::FieldOffset,
+                    vec![Node::with_text(Kind::Directness, directness), entity],
+                ))
+            }
+            b'P' => Some(Node::with_child(
+                Kind::ProtocolWitnessTable,
+                self.pop_protocol_conformance()?,
+            )),
+            b'a' => Some(Node::with_child(
+                Kind::ProtocolWitnessTableAccessor,
+                self.pop_protocol_conformance()?,
+            )),
+            c @ (b'l' | b'L') => {
+                let conformance = self.pop_protocol_conformance()?;
+                let ty = self.pop_kind(Kind::Type)?;
+                let kind = if c == b'l' {
+                    Kind::LazyProtocolWitnessTableAccessor
+                } else {
+                    Kind::LazyProtocolWitnessTableCacheVariable
+                };
+                Some(Node::with_children(kind, vec![ty, conformance]))
+            }
+            _ => None,
+        }
+    }
+
+    fn demangle_thunk_or_specialization(&mut self) -> Option<NodeRef> {
+        let node = match self.next()? {
+            b'A' => Node::leaf(Kind::PartialApplyForwarder),
+            b'a' => Node::leaf(Kind::PartialApplyObjCForwarder),
+            b'D' => Node::leaf(Kind::DynamicAttribute),
+            b'd' => Node::leaf(Kind::DirectMethodReferenceAttribute),
+            b'm' => Node::leaf(Kind::MergedFunction),
+            b'O' => Node::leaf(Kind::NonObjCAttribute),
+            b'o' => Node::leaf(Kind::ObjCAttribute),
+            b'u' => Node::leaf(Kind::AsyncFunctionPointer),
+            b'c' => Node::with_child(Kind::CurryThunk, self.pop_if(Kind::is_entity)?),
+            b'j' => Node::with_child(Kind::DispatchThunk, self.pop_if(Kind::is_entity)?),
+            b'q' => Node::with_child(Kind::MethodDescriptor, self.pop_if(Kind::is_entity)?),
+            b'Q' => Node::with_child(
+                Kind::AsyncAwaitResumePartialFunction,
+                self.demangle_index_as_node()?,
+            ),
+            b'Y' => Node::with_child(
+                Kind::AsyncSuspendResumePartialFunction,
+                self.demangle_index_as_node()?,
+            ),
+            b'W' => {
+                let entity = self.pop_if(Kind::is_entity)?;
+                let conformance = self.pop_protocol_conformance()?;
+                Node::with_children(Kind::ProtocolWitness, vec![conformance, entity])
+            }
+            b'g' | b'G' | b'B' | b's' | b'i' | b'P' | b'p' => {
+                let mut children = vec![self.demangle_spec_attributes()?];
+                children.extend(self.pop_type_list()?.children.iter().cloned());
+                Node::with_children(Kind::GenericSpecialization, children)
+            }
+            b'f' => self.demangle_function_specialization()?,
+            _ => return None,
+        };
+        Some(node)
+    }
+
+    fn demangle_spec_attributes(&mut self) -> Option<NodeRef> {
+        self.next_if(b'q');
+        self.next_if(b'a');
+        let pass_id = self.next()?;
+        if !pass_id.is_ascii_digit() {
+            return None;
+        }
+        Some(Node::with_index(
+            Kind::SpecializationPassId,
+            u64::from(pass_id - b'0'),
+        ))
+    }
+
+    fn demangle_function_specialization(&mut self) -> Option<NodeRef> {
+        let mut params = Vec::new();
+        let pass_id = self.demangle_spec_attributes()?;
+        while !self.next_if(b'_') {
+            params.push(self.demangle_func_spec_param(Kind::FunctionSignatureSpecializationParam)?);
+        }
+        if !self.next_if(b'n') {
+            params
+                .push(self.demangle_func_spec_param(Kind::FunctionSignatureSpecializationReturn)?);
+        }
+
+        // The operands of the parameters are on the stack in reverse order.
+        let mut children = vec![pass_id];
+        for param in params.into_iter().rev() {
+            match param.index {
+                FUNC_SPEC_PARAM_CLOSURE_PROP => {
+                    while self.pop_kind(Kind::Type).is_some() {}
+                    self.pop_kind(Kind::Identifier)?;
+                }
+                FUNC_SPEC_PARAM_CONSTANT_PROP_NAME => {
+                    self.pop_kind(Kind::Identifier)?;
+                }
+                _ => {}
+            }
+            children.push(param);
+        }
+        children[1..].reverse();
+        Some(Node::with_children(
+            Kind::FunctionSignatureSpecialization,
+            children,
+        ))
+    }
+
+    fn demangle_func_spec_param(&mut self, kind: Kind) -> Option<NodeRef> {
+        let param_kind = match self.next()? {
+            b'n' | b'x' | b'i' | b's' | b'r' => FUNC_SPEC_PARAM_PLAIN,
+            b'c' => FUNC_SPEC_PARAM_CLOSURE_PROP,
+            b'p' => match self.next()? {
+                b'f' | b'g' => FUNC_SPEC_PARAM_CONSTANT_PROP_NAME,
+                b's' => match self.next()? {
+                    b'b' | b'w' | b'c' => FUNC_SPEC_PARAM_CONSTANT_PROP_NAME,
+                    _ => return None,
+                },
+                b'i' | b'd' => {
+                    self.demangle_natural()?;
+                    FUNC_SPEC_PARAM_PLAIN
+                }
+                _ => return None,
+            },
+            b'e' => {
+                self.next_if(b'D');
+                self.next_if(b'G');
+                self.next_if(b'O');
+                self.next_if(b'X');
+                FUNC_SPEC_PARAM_PLAIN
+            }
+            b'd' => {
+                self.next_if(b'G');
+                self.next_if(b'O');
+                self.next_if(b'X');
+                FUNC_SPEC_PARAM_PLAIN
+            }
+            b'g' | b'o' => {
+                self.next_if(b'X');
+                FUNC_SPEC_PARAM_PLAIN
+            }
+            _ => return None,
+        };
+        Some(Node::with_index(kind, param_kind))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TypePrinting {
+    NoType,
+    WithColon,
+    FunctionStyle,
+}
+
+/// Prints a node tree with the options of `swift-demangle -simplified`:
+/// no module names, no generic specialization or conformance details, and
+/// function parameters shown as argument labels only.
+struct Printer {
+    out: String,
+    valid: bool,
+    specialization_prefix_printed: bool,
+}
+
+impl Default for Printer {
+    fn default() -> Self {
+        Self {
+            out: String::new(),
+            valid: true,
+            specialization_prefix_printed: false,
+        }
+    }
+}
+
+impl Printer {
+    fn print_children(&mut self, node: &Node, separator: &str) {
+        for (i, child) in node.children.iter().enumerate() {
+            if i != 0 {
+                self.out.push_str(separator);
+            }
+            self.print(child, false);
+        }
+    }
+
+    fn print_child(&mut self, node: &Node, index: usize) {
+        match node.child(index) {
+            Some(child) => {
+                self.print(child, false);
+            }
+            None => self.valid = false,
+        }
+    }
+
+    fn print_with_prefix(&mut self, prefix: &str, node: &Node) {
+        self.out.push_str(prefix);
+        self.print_child(node, 0);
+    }
+
+    /// Prints `node`. If `as_prefix_context` is true and the node is printed
+    /// as a postfix (`... in foo()`), the node is returned instead and the
+    /// caller has to print it after the entity.
+    fn print(&mut self, node: &NodeRef, as_prefix_context: bool) -> Option<NodeRef> {
+        use TypePrinting::{FunctionStyle, NoType};
+
+        match node.kind {
+            Kind::Global => self.print_children(node, ""),
+            Kind::Type | Kind::TypeMangling => self.print_child(node, 0),
+            Kind::Suffix
+            | Kind::Module
+            | Kind::LabelList
+            | Kind::MergedFunction
+            | Kind::AsyncAwaitResumePartialFunction
+            | Kind::AsyncSuspendResumePartialFunction => {}
+            Kind::Identifier | Kind::BuiltinTypeName => self.out.push_str(&node.text),
+            Kind::Number | Kind::Index => self.out.push_str(&node.index.to_string()),
+            Kind::LocalDeclName => {
+                self.print_child(node, 1);
+                let index = node.child(0).map_or(0, |n| n.index);
+                self.out.push_str(&format!(" #{}", index.saturating_add(1)));
+            }
+            Kind::PrivateDeclName => {
+                if node.children.len() >= 2 {
+                    self.print_child(node, 1);
+                }
+            }
+            Kind::InfixOperator => self.out.push_str(&format!("{} infix", node.text)),
+            Kind::PrefixOperator => self.out.push_str(&format!("{} prefix", node.text)),
+            Kind::PostfixOperator => self.out.push_str(&format!("{} postfix", node.text)),
+            Kind::Class | Kind::Structure | Kind::Enum | Kind::Protocol | Kind::TypeAlias => {
+                return self.print_entity(node, as_prefix_context, NoType, true, "", None, "")
+            }
+            Kind::Function => {
+                return self.print_entity(
+                    node,
+                    as_prefix_context,
+                    FunctionStyle,
+                    true,
+                    "",
+                    None,
+                    "",
+                )
+            }
+            Kind::Allocator => {
+                let is_class = node
+                    .child(0)
+                    .map_or(false, |context| context.kind == Kind::Class);
+                let name = if is_class {
+                    "__allocating_init"
+                } else {
+                    "init"
+                };
+                return self.print_entity(
+                    node,
+                    as_prefix_context,
+                    FunctionStyle,
+                    false,
+                    name,
+                    None,
+                    "",
+                );
+            }
+            Kind::Constructor => {
+                return self.print_entity(
+                    node,
+                    as_prefix_context,
+                    FunctionStyle,
+                    false,
+                    "init",
+                    None,
+                    "",
+                )
+            }
+            Kind::Destructor => {
+                return self.print_entity(
+                    node,
+                    as_prefix_context,
+                    NoType,
+                    false,
+                    "deinit",
+                    None,
+                    "",
+                )
+            }
+            Kind::Deallocator => {
+                let is_class = node
+                    .child(0)
+                    .map_or(false, |context| context.kind == Kind::Class);
+                let name = if is_class {
+                    "__deallocating_deinit"
+                } else {
+                    "deinit"
+                };
+                return self.print_entity(node, as_prefix_context, NoType, false, name, None, "");
+            }
+            Kind::IVarInitializer => {
+                return self.print_entity(
+                    node,
+                    as_prefix_context,
+                    NoType,
+                    false,
+                    "__ivar_initializer",
+                    None,
+                    "",
+                )
+            }
+            Kind::IVarDestroyer => {
+                return self.print_entity(
+                    node,
+                    as_prefix_context,
+                    NoType,
+                    false,
+                    "__ivar_destroyer",
+                    None,
+                    "",
+                )
+            }
+            Kind::ExplicitClosure | Kind::ImplicitClosure => {
+                let name = if node.kind == Kind::ExplicitClosure {
+                    "closure #"
+                } else {
+                    "implicit closure #"
+                };
+                let index = node.child(1).map_or(0, |n| n.index).saturating_add(1);
+                return self.print_entity(
+                    node,
+                    as_prefix_context,
+                    NoType,
+                    false,
+                    name,
+                    Some(index),
+                    "",
+                );
+            }
+            Kind::DefaultArgumentInitializer => {
+                let index = node.child(1).map_or(0, |n| n.index);
+                return self.print_entity(
+                    node,
+                    as_prefix_context,
+                    NoType,
+                    false,
+                    "default argument ",
+                    Some(index),
+                    "",
+                );
+            }
+            Kind::Initializer => {
+                return self.print_entity(
+                    node,
+                    as_prefix_context,
+                    NoType,
+                    false,
+                    "variable initialization expression",
+                    None,
+                    "",
+                )
+            }
+            Kind::Variable | Kind::Subscript => {
+                return self.print_abstract_storage(node, as_prefix_context, "")
+            }
+            Kind::Getter | Kind::GlobalGetter => {
+                return self.print_accessor(node, as_prefix_context, "getter")
+            }
+            Kind::Setter => return self.print_accessor(node, as_prefix_context, "setter"),
+            Kind::ModifyAccessor => return self.print_accessor(node, as_prefix_context, "modify"),
+            Kind::ReadAccessor => return self.print_accessor(node, as_prefix_context, "read"),
+            Kind::WillSet => return self.print_accessor(node, as_prefix_context, "willset"),
+            Kind::DidSet => return self.print_accessor(node, as_prefix_context, "didset"),
+            Kind::MaterializeForSet => {
+                return self.print_accessor(node, as_prefix_context, "materializeForSet")
+            }
+            Kind::UnsafeAddressor => {
+                return self.print_accessor(node, as_prefix_context, "unsafeAddressor")
+            }
+            Kind::UnsafeMutableAddressor => {
+                return self.print_accessor(node, as_prefix_context, "unsafeMutableAddressor")
+            }
+            Kind::Static => {
+                self.out.push_str("static ");
+                match node.child(0) {
+                    Some(child) => return self.print(child, as_prefix_context),
+                    None => self.valid = false,
+                }
+            }
+            Kind::Extension => self.print_child(node, 1),
+            Kind::TypeList => self.print_children(node, ", "),
+            Kind::Tuple => {
+                self.out.push('(');
+                self.print_children(node, ", ");
+                self.out.push(')');
+            }
+            Kind::TupleElement => {
+                if let Some(label) = node.find_child(Kind::TupleElementName) {
+                    self.out.push_str(&label.text);
+                    self.out.push_str(": ");
+                }
+                match node.find_child(Kind::Type) {
+                    Some(ty) => {
+                        self.print(ty, false);
+                    }
+                    None => self.valid = false,
+                }
+                if node.find_child(Kind::VariadicMarker).is_some() {
+                    self.out.push_str("...");
+                }
+            }
+            Kind::FunctionType | Kind::NoEscapeFunctionType | Kind::UncurriedFunctionType => {
+                self.print_function_type(None, node)
+            }
+            Kind::AutoClosureType | Kind::EscapingAutoClosureType => {
+                self.out.push_str("@autoclosure ");
+                self.print_function_type(None, node);
+            }
+            Kind::ThinFunctionType => {
+                self.out.push_str("@convention(thin) ");
+                self.print_function_type(None, node);
+            }
+            Kind::CFunctionPointer => {
+                self.out.push_str("@convention(c) ");
+                self.print_function_type(None, node);
+            }
+            Kind::ObjCBlock | Kind::EscapingObjCBlock => {
+                self.out.push_str("@convention(block) ");
+                self.print_function_type(None, node);
+            }
+            Kind::InOut => self.print_with_prefix("inout ", node),
+            Kind::Shared => self.print_with_prefix("__shared ", node),
+            Kind::Owned => self.print_with_prefix("__owned ", node),
+            Kind::Weak => self.print_with_prefix("weak ", node),
+            Kind::Unowned => self.print_with_prefix("unowned ", node),
+            Kind::Unmanaged => self.print_with_prefix("unowned(unsafe) ", node),
+            Kind::Isolated => self.print_with_prefix("isolated ", node),
+            Kind::Sending => self.print_with_prefix("sending ", node),
+            Kind::CompileTimeConst => self.print_with_prefix("_const ", node),
+            Kind::DynamicSelf => self.out.push_str("Self"),
+            Kind::OpaqueReturnType => self.out.push_str("some"),
+            Kind::Metatype | Kind::ExistentialMetatype => {
+                let ty = node.child(0).and_then(|ty| ty.child(0)).cloned();
+                match ty {
+                    Some(ty) => {
+                        self.print_with_parens(&ty);
+                        let is_protocol_metatype = node.kind == Kind::Metatype
+                            && matches!(
+                                ty.kind,
+                                Kind::ProtocolList
+                                    | Kind::ProtocolListWithClass
+                                    | Kind::ProtocolListWithAnyObject
+                            );
+                        self.out.push_str(if is_protocol_metatype {
+                            ".Protocol"
+                        } else {
+                            ".Type"
+                        });
+                    }
+                    None => self.valid = false,
+                }
+            }
+            Kind::ProtocolList => match node.child(0) {
+                Some(list) if list.children.is_empty() => self.out.push_str("Any"),
+                Some(list) => self.print_children(list, " & "),
+                None => self.valid = false,
+            },
+            Kind::ProtocolListWithClass => {
+                self.print_child(node, 1);
+                self.out.push_str(" & ");
+                if let Some(list) = node.child(0).and_then(|protocols| protocols.child(0)) {
+                    self.print_children(list, " & ");
+                }
+            }
+            Kind::ProtocolListWithAnyObject => {
+                if let Some(list) = node.child(0).and_then(|protocols| protocols.child(0)) {
+                    if !list.children.is_empty() {
+                        self.print_children(list, " & ");
+                        self.out.push_str(" & ");
+                    }
+                }
+                self.out.push_str("Swift.AnyObject");
+            }
+            Kind::BoundGenericClass
+            | Kind::BoundGenericStructure
+            | Kind::BoundGenericEnum
+            | Kind::BoundGenericProtocol
+            | Kind::BoundGenericTypeAlias => self.print_bound_generic(node),
+            Kind::DependentGenericParamType => {
+                let depth = node.child(0).map_or(0, |n| n.index);
+                let index = node.child(1).map_or(0, |n| n.index);
+                self.out.push_str(&generic_parameter_name(depth, index));
+            }
+            Kind::DependentGenericSignature => self.print_generic_signature(node),
+            Kind::DependentGenericType => {
+                self.print_child(node, 0);
+                if let Some(ty) = node.child(1) {
+                    if need_space_before_type(ty) {
+                        self.out.push(' ');
+                    }
+                    self.print(ty, false);
+                }
+            }
+            Kind::DependentMemberType => {
+                self.print_child(node, 0);
+                self.out.push('.');
+                self.print_child(node, 1);
+            }
+            Kind::DependentAssociatedTypeRef => {
+                if let Some(protocol) = node.child(0) {
+                    self.print(protocol, false);
+                    self.out.push('.');
+                }
+                self.out.push_str(&node.text);
+            }
+            Kind::TypeMetadata => self.print_with_prefix("type metadata for ", node),
+            Kind::TypeMetadataAccessFunction => {
+                self.print_with_prefix("type metadata accessor for ", node)
+            }
+            Kind::TypeMetadataPattern => {
+                self.print_with_prefix("generic type metadata pattern for ", node)
+            }
+            Kind::TypeMetadataLazyCache => {
+                self.print_with_prefix("lazy cache variable for type metadata for ", node)
+            }
+            Kind::TypeMetadataInstantiationCache => {
+                self.print_with_prefix("type metadata instantiation cache for ", node)
+            }
+            Kind::FullTypeMetadata => self.print_with_prefix("full type metadata for ", node),
+            Kind::NominalTypeDescriptor => {
+                self.print_with_prefix("nominal type descriptor for ", node)
+            }
+            Kind::ProtocolDescriptor => self.print_with_prefix("protocol descriptor for ", node),
+            Kind::ClassMetadataBaseOffset => {
+                self.print_with_prefix("class metadata base offset for ", node)
+            }
+            Kind::MethodLookupFunction => {
+                self.print_with_prefix("method lookup function for ", node)
+            }
+            Kind::ObjCMetadataUpdateFunction => {
+                self.print_with_prefix("ObjC metadata update function for ", node)
+            }
+            Kind::ValueWitnessTable => self.print_with_prefix("value witness table for ", node),
+            Kind::ProtocolWitnessTable => {
+                self.print_with_prefix("protocol witness table for ", node)
+            }
+            Kind::ProtocolWitnessTableAccessor => {
+                self.print_with_prefix("protocol witness table accessor for ", node)
+            }
+            Kind::LazyProtocolWitnessTableAccessor
+            | Kind::LazyProtocolWitnessTableCacheVariable => {
+                self.out
+                    .push_str(if node.kind == Kind::LazyProtocolWitnessTableAccessor {
+                        "lazy protocol witness table accessor for type "
+                    } else {
+                        "lazy protocol witness table cache variable for type "
+                    });
+                self.print_child(node, 0);
+                self.out.push_str(" and conformance ");
+                self.print_child(node, 1);
+            }
+            // The protocol and the module of conformances are not shown.
+            Kind::ProtocolConformance => self.print_child(node, 0),
+            Kind::ProtocolWitness => {
+                self.out.push_str("protocol witness for ");
+                self.print_child(node, 1);
+                self.out.push_str(" in conformance ");
+                self.print_child(node, 0);
+            }
+            Kind::FieldOffset => {
+                self.print_child(node, 0);
+                self.out.push_str("field offset for ");
+                self.print_child(node, 1);
+            }
+            Kind::Directness => {
+                self.out.push_str(&node.text);
+                self.out.push(' ');
+            }
+            Kind::PartialApplyForwarder | Kind::PartialApplyObjCForwarder => {
+                self.out
+                    .push_str(if node.kind == Kind::PartialApplyForwarder {
+                        "partial apply"
+                    } else {
+                        "partial apply ObjC"
+                    });
+                if !node.children.is_empty() {
+                    self.out.push_str(" for ");
+                    self.print_children(node, "");
+                }
+            }
+            Kind::ObjCAttribute => self.out.push_str("@objc "),
+            Kind::NonObjCAttribute => self.out.push_str("@nonobjc "),
+            Kind::DynamicAttribute => self.out.push_str("dynamic "),
+            Kind::DirectMethodReferenceAttribute => self.out.push_str("super "),
+            Kind::AsyncFunctionPointer => self.out.push_str("async function pointer to "),
+            Kind::DispatchThunk => self.print_with_prefix("dispatch thunk of ", node),
+            Kind::MethodDescriptor => self.print_with_prefix("method descriptor for ", node),
+            Kind::CurryThunk => self.print_with_prefix("curry thunk of ", node),
+            Kind::GenericSpecialization | Kind::FunctionSignatureSpecialization => {
+                if !self.specialization_prefix_printed {
+                    self.out.push_str("specialized ");
+                    self.specialization_prefix_printed = true;
+                }
+            }
+            _ => self.valid = false,
+        }
+        None
+    }
+}
+
+impl Printer {
+    fn print_accessor(
+        &mut self,
+        node: &Node,
+        as_prefix_context: bool,
+        name: &str,
+    ) -> Option<NodeRef> {
+        match node.child(0) {
+            Some(storage) => self.print_abstract_storage(storage, as_prefix_context, name),
+            None => {
+                self.valid = false;
+                None
+            }
+        }
+    }
+
+    fn print_abstract_storage(
+        &mut self,
+        node: &NodeRef,
+        as_prefix_context: bool,
+        extra_name: &str,
+    ) -> Option<NodeRef> {
+        use TypePrinting::WithColon;
+
+        match node.kind {
+            Kind::Variable => self.print_entity(
+                node,
+                as_prefix_context,
+                WithColon,
+                true,
+                extra_name,
+                None,
+                "",
+            ),
+            Kind::Subscript => self.print_entity(
+                node,
+                as_prefix_context,
+                WithColon,
+                false,
+                extra_name,
+                None,
+                "subscript",
+            ),
+            _ => {
+                self.valid = false;
+                None
+            }
+        }
+    }
+
+    /// Prints an entity with its context, e.g. `Foo.bar(x:)`, or
+    /// `closure #1 in foo()` if the entity name consists of multiple words.
+    #[allow(clippy::too_many_arguments)]
+    fn print_entity(
+        &mut self,
+        entity: &NodeRef,
+        as_prefix_context: bool,
+        mut type_printing: TypePrinting,
+        has_name: bool,
+        extra_name: &str,
+        extra_index: Option<u64>,
+        overwrite_name: &str,
+    ) -> Option<NodeRef> {
+        let mut multi_word_name = extra_name.contains(' ');
+        if has_name
+            && entity
+                .child(1)
+                .map_or(false, |name| name.kind == Kind::LocalDeclName)
+        {
+            multi_word_name = true;
+        }
+        if as_prefix_context && (type_printing != TypePrinting::NoType || multi_word_name) {
+            return Some(entity.clone());
+        }
+
+        let mut postfix_context = None;
+        match entity.child(0) {
+            Some(context) if multi_word_name => postfix_context = Some(context.clone()),
+            Some(context) => {
+                let len = self.out.len();
+                postfix_context = self.print(context, true);
+                if self.out.len() != len {
+                    self.out.push('.');
+                }
+            }
+            None => self.valid = false,
+        }
+
+        let mut extra_name = extra_name;
+        let mut extra_index = extra_index;
+        if has_name || !overwrite_name.is_empty() {
+            if !extra_name.is_empty() && multi_word_name {
+                self.out.push_str(extra_name);
+                if let Some(index) = extra_index {
+                    self.out.push_str(&index.to_string());
+                }
+                self.out.push_str(" of ");
+                extra_name = "";
+                extra_index = None;
+            }
+            let len = self.out.len();
+            if !overwrite_name.is_empty() {
+                self.out.push_str(overwrite_name);
+            } else {
+                match entity.child(1) {
+                    Some(name) if name.kind != Kind::PrivateDeclName => {
+                        self.print(name, false);
+                    }
+                    Some(_) => {}
+                    None => self.valid = false,
+                }
+                if let Some(private_name) = entity.find_child(Kind::PrivateDeclName) {
+                    self.print(private_name, false);
+                }
+            }
+            if self.out.len() != len && !extra_name.is_empty() {
+                self.out.push('.');
+            }
+        }
+        if !extra_name.is_empty() {
+            self.out.push_str(extra_name);
+            if let Some(index) = extra_index {
+                self.out.push_str(&index.to_string());
+            }
+        }
+
+        if type_printing != TypePrinting::NoType {
+            if let Some(ty) = entity.find_child(Kind::Type).and_then(|ty| ty.child(0)) {
+                if type_printing == TypePrinting::FunctionStyle {
+                    let mut t = ty;
+                    while t.kind == Kind::DependentGenericType {
+                        match t.child(1).and_then(|t| t.child(0)) {
+                            Some(inner) => t = inner,
+                            None => break,
+                        }
+                    }
+                    if !t.kind.is_function_type() {
+                        type_printing = TypePrinting::WithColon;
+                    }
+                }
+                // Entity types after a colon are not shown.
+                if type_printing == TypePrinting::FunctionStyle {
+                    if multi_word_name || need_space_before_type(ty) {
+                        self.out.push(' ');
+                    }
+                    self.print_entity_type(entity, ty);
+                }
+            }
+        }
+
+        if !as_prefix_context {
+            if let Some(context) = postfix_context.take() {
+                self.out.push_str(match entity.kind {
+                    Kind::DefaultArgumentInitializer | Kind::Initializer => " of ",
+                    _ => " in ",
+                });
+                self.print(&context, false);
+            }
+        }
+        postfix_context
+    }
+
+    fn print_entity_type(&mut self, entity: &Node, ty: &NodeRef) {
+        let label_list = match entity.find_child(Kind::LabelList) {
+            Some(label_list) => label_list,
+            None => {
+                self.print(ty, false);
+                return;
+            }
+        };
+        let mut ty = ty.clone();
+        if ty.kind == Kind::DependentGenericType {
+            self.print_child(&ty, 0);
+            let dependent_type = match ty.child(1) {
+                Some(dependent_type) => dependent_type.clone(),
+                None => {
+                    self.valid = false;
+                    return;
+                }
+            };
+            if need_space_before_type(&dependent_type) {
+                self.out.push(' ');
+            }
+            ty = match dependent_type.child(0) {
+                Some(ty) => ty.clone(),
+                None => {
+                    self.valid = false;
+                    return;
+                }
+            };
+        }
+        self.print_function_type(Some(label_list), &ty);
+    }
+
+    /// Prints the parameter list of a function type. Parameter types are not
+    /// shown, only the argument labels, e.g. `(_:x:)`.
+    fn print_function_type(&mut self, label_list: Option<&NodeRef>, node: &Node) {
+        let params = match node
+            .find_child(Kind::ArgumentTuple)
+            .and_then(|arg_tuple| arg_tuple.child(0))
+            .and_then(|ty| ty.child(0))
+        {
+            Some(params) => params,
+            None => {
+                self.valid = false;
+                return;
+            }
+        };
+        if params.kind != Kind::Tuple {
+            self.out.push_str("(_:)");
+            return;
+        }
+        self.out.push('(');
+        for index in 0..params.children.len() {
+            let label = label_list
+                .and_then(|labels| labels.child(index))
+                .filter(|label| label.kind == Kind::Identifier);
+            match label {
+                Some(label) => self.out.push_str(&label.text),
+                None => self.out.push('_'),
+            }
+            self.out.push(':');
+        }
+        self.out.push(')');
+    }
+
+    fn print_bound_generic(&mut self, node: &Node) {
+        let (ty, args) = match (node.child(0), node.child(1)) {
+            (Some(ty), Some(args)) => (ty, args),
+            _ => {
+                self.valid = false;
+                return;
+            }
+        };
+        match find_sugar(node, ty, args) {
+            Sugar::Optional => {
+                let wrapped = &args.children[0];
+                self.print_with_parens(wrapped);
+                self.out.push('?');
+            }
+            Sugar::Array => {
+                self.out.push('[');
+                self.print(&args.children[0], false);
+                self.out.push(']');
+            }
+            Sugar::Dictionary => {
+                self.out.push('[');
+                self.print(&args.children[0], false);
+                self.out.push_str(" : ");
+                self.print(&args.children[1], false);
+                self.out.push(']');
+            }
+            Sugar::None => {
+                self.print(ty, false);
+                self.out.push('<');
+                self.print_children(args, ", ");
+                self.out.push('>');
+            }
+        }
+    }
+
+    fn print_with_parens(&mut self, ty: &NodeRef) {
+        let needs_parens = !is_simple_type(ty);
+        if needs_parens {
+            self.out.push('(');
+        }
+        self.print(ty, false);
+        if needs_parens {
+            self.out.push(')');
+        }
+    }
+
+    fn print_generic_signature(&mut self, node: &Node) {
+        self.out.push('<');
+        let param_counts = node
+            .children
+            .iter()
+            .take_while(|child| child.kind == Kind::DependentGenericParamCount);
+        for (depth, count) in param_counts.enumerate() {
+            if depth != 0 {
+                self.out.push_str("><");
+            }
+            for index in 0..count.index {
+                if index != 0 {
+                    self.out.push_str(", ");
+                }
+                // Malformed symbols can have huge counts.
+                if index >= 128 {
+                    self.out.push_str("...");
+                    break;
+                }
+                self.out
+                    .push_str(&generic_parameter_name(depth as u64, index));
+            }
+        }
+        self.out.push('>');
+    }
+}
+
+enum Sugar {
+    None,
+    Optional,
+    Array,
+    Dictionary,
+}
+
+fn find_sugar(node: &Node, ty: &Node, args: &Node) -> Sugar {
+    let nominal = match ty.child(0) {
+        Some(nominal) if ty.kind == Kind::Type && args.kind == Kind::TypeList => nominal,
+        _ => return Sugar::None,
+    };
+    let is_swift_type = |name: &str| {
+        matches!(nominal.child(0), Some(module) if module.kind == Kind::Module && module.text == "Swift")
+            && matches!(nominal.child(1), Some(ident) if ident.kind == Kind::Identifier && ident.text == name)
+    };
+    match (node.kind, args.children.len()) {
+        (Kind::BoundGenericEnum, 1) if is_swift_type("Optional") => Sugar::Optional,
+        (Kind::BoundGenericStructure, 1) if is_swift_type("Array") => Sugar::Array,
+        (Kind::BoundGenericStructure, 2) if is_swift_type("Dictionary") => Sugar::Dictionary,
+        _ => Sugar::None,
+    }
+}
+
+fn is_simple_type(node: &Node) -> bool {
+    match node.kind {
+        Kind::Type => node.child(0).map_or(true, |child| is_simple_type(child)),
+        Kind::ProtocolList => node.child(0).map_or(true, |list| list.children.len() <= 1),
+        Kind::ProtocolListWithClass | Kind::ProtocolListWithAnyObject => false,
+        kind => !kind.is_function_type(),
+    }
+}
+
+fn need_space_before_type(node: &Node) -> bool {
+    match node.kind {
+        Kind::Type => node
+            .child(0)
+            .map_or(true, |child| need_space_before_type(child)),
+        Kind::FunctionType
+        | Kind::NoEscapeFunctionType
+        | Kind::UncurriedFunctionType
+        | Kind::DependentGenericType => false,
+        _ => true,
+    }
+}
+
+/// Returns the name that is used for a generic parameter: `A`, `B`, ...,
+/// `Z`, `BA`, ..., with the depth appended for nested generic contexts.
+fn generic_parameter_name(depth: u64, index: u64) -> String {
+    let mut name = String::new();
+    let mut index = index;
+    loop {
+        name.push(char::from(b'A' + (index % 26) as u8));
+        index /= 26;
+        if index == 0 {
+            break;
+        }
+    }
+    if depth != 0 {
+        name.push_str(&depth.to_string());
+    }
+    name
+}
+
+#[cfg(test)]
+mod test {
+    use super::demangle;
+
+    #[test]
+    fn demangle_swift() {
+        assert!(demangle("main").is_none());
+        assert!(demangle("_ZN3foo3barEv").is_none());
+        assert!(demangle("$s4main3foo").is_none());
+        assert!(demangle("$s4main3fooyyFXYZ").is_none());
+    }
+
+    #[test]
+    fn demangle_swift_symbols_file() {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../fixtures/other/swift-symbols.txt");
+        let symbols = std::fs::read_to_string(path).unwrap();
+        let mut count = 0;
+        for line in symbols.lines() {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (mangled, expected) = line.split_once(" ---> ").unwrap();
+            assert_eq!(demangle(mangled).as_deref(), Some(expected), "{mangled}");
+            count += 1;
+        }
+        assert_eq!(count, 67);
+    }
+
+    #[test]
+    fn demangle_swift_malformed() {
+        assert!(demangle("$s18446744073709551615abc").is_none());
+        assert!(demangle("$s18446744073709551616abc").is_none());
+        assert!(demangle("$s4main9223372036854775807abc").is_none());
+        assert!(demangle("$s4main1000000fooyyF").is_none());
+    }
+
+    #[test]
+    fn demangle_swift_deeply_nested() {
+        let deep = format!("$s{}Si{}N", "Say".repeat(100_000), "G".repeat(100_000));
+        assert!(demangle(&deep).is_none());
+        let deep = format!("$s{}Si{}N", "Say".repeat(100), "G".repeat(100));
+        assert!(demangle(&deep).is_none());
+        let shallow = format!("$s{}Si{}N", "Say".repeat(50), "G".repeat(50));
+        assert!(demangle(&shallow).is_some());
+
+        // Nested types and closures, which would overflow the stack when
+        // printed if the depth wasn't limited.
+        let deep = format!("$s4main{}3fooyyF", "3BarV".repeat(1000));
+        assert!(demangle(&deep).is_none());
+        let shallow = format!("$s4main{}3fooyyF", "3BarV".repeat(200));
+        assert!(demangle(&shallow).is_some());
+        let deep = format!("$s4main3fooyyF{}", "yycfU_".repeat(1000));
+        assert!(demangle(&deep).is_none());
+        let shallow = format!("$s4main3fooyyF{}", "yycfU_".repeat(200));
+        assert_eq!(
+            demangle(&shallow).map(|name| name.matches("closure #1 in ").count()),
+            Some(200)
+        );
+    }
+}
@@ -1,22 +1,39 @@
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::io::Read;
 use std::marker::PhantomData;
 
 use crate::path_mapper::PathMapper;
 use crate::shared::FrameDebugInfo;
+use crate::split_dwarf::SplitDwarfSections;
 use crate::{demangle, Error, SourceFilePath};
 use addr2line::fallible_iterator;
 use addr2line::gimli;
+use addr2line::{LookupContinuation, LookupResult};
 use elsa::sync::FrozenVec;
 use fallible_iterator::FallibleIterator;
-use gimli::{EndianSlice, Reader, RunTimeEndian, SectionId};
+use gimli::{EndianSlice, Reader, ReaderOffset, RunTimeEndian};
 use object::read::ReadRef;
 use object::{CompressedFileRange, CompressionFormat};
 
 pub fn get_frames<R: Reader>(
     address: u64,
     context: Option<&addr2line::Context<R>>,
+    split_dwarf: Option<&SplitDwarfSections<R>>,
     path_mapper: &mut PathMapper<()>,
 ) -> Option<Vec<FrameDebugInfo>> {
-    let frame_iter = context?.find_frames(address).ok()?;
+    let mut lookup_result = context?.find_frames(address);
+    let frame_iter = loop {
+        match lookup_result {
+            LookupResult::Output(frame_iter) => break frame_iter.ok()?,
+            LookupResult::Load { load, continuation } => {
+                // The address is in a skeleton unit, and the rest of the debug
+                // info for that unit is in a .dwo file or in a .dwp package.
+                let dwo_dwarf = split_dwarf.and_then(|split_dwarf| split_dwarf.load(load));
+                lookup_result = continuation.resume(dwo_dwarf);
+            }
+        }
+    };
     let frames: Vec<_> = frame_iter
         .map(|f| Ok(convert_stack_frame(f, &mut *path_mapper)))
         .collect()
@@ -29,6 +46,85 @@ pub fn get_frames<R: Reader>(
     }
 }
 
+/// The start and end addresses of the address ranges of the inlined calls in
+/// each unit, keyed by the unit's DWO id and its offset in `.debug_info`.
+#[derive(Default)]
+pub struct InlineRangeBoundaries(HashMap<(Option<u64>, u64), Vec<u64>>);
+
+/// Returns the addresses in `start..end` at which the debug info, i.e. the line
+/// number or the inline stack, can change. These are the boundaries of the
+/// line table rows and of the address ranges of inlined calls. The latter
+/// don't always start a new row.
+pub fn get_debug_info_boundaries<R: Reader>(
+    context: &addr2line::Context<R>,
+    split_dwarf: Option<&SplitDwarfSections<R>>,
+    start: u64,
+    end: u64,
+    inline_range_boundaries: &mut InlineRangeBoundaries,
+) -> Option<Vec<u64>> {
+    let mut boundaries = Vec::new();
+    for (address, size, _location) in context.find_location_range(start, end).ok()? {
+        boundaries.push(address);
+        boundaries.push(address.saturating_add(size));
+    }
+
+    let mut lookup_result = context.find_dwarf_and_unit(start);
+    let dwarf_and_unit = loop {
+        match lookup_result {
+            LookupResult::Output(dwarf_and_unit) => break dwarf_and_unit,
+            LookupResult::Load { load, continuation } => {
+                let dwo_dwarf = split_dwarf.and_then(|split_dwarf| split_dwarf.load(load));
+                lookup_result = continuation.resume(dwo_dwarf);
+            }
+        }
+    };
+    if let Some((dwarf, unit)) = dwarf_and_unit {
+        let unit_offset = match unit.header.offset().as_debug_info_offset() {
+            Some(offset) => offset.0.into_u64(),
+            None => u64::MAX,
+        };
+        let unit_key = (unit.dwo_id.map(|dwo_id| dwo_id.0), unit_offset);
+        let unit_boundaries = inline_range_boundaries
+            .0
+            .entry(unit_key)
+            .or_insert_with(|| get_inline_range_boundaries(dwarf, unit));
+        let first = unit_boundaries.partition_point(|&address| address < start);
+        boundaries.extend(
+            unit_boundaries[first..]
+                .iter()
+                .take_while(|&&address| address < end),
+        );
+    }
+
+    boundaries.retain(|&address| start <= address && address < end);
+    boundaries.sort_unstable();
+    boundaries.dedup();
+    Some(boundaries)
+}
+
+/// Returns the sorted start and end addresses of the inlined calls in `unit`.
+fn get_inline_range_boundaries<R: Reader>(
+    dwarf: &gimli::Dwarf<R>,
+    unit: &gimli::Unit<R>,
+) -> Vec<u64> {
+    let mut boundaries = Vec::new();
+    let mut entries = unit.entries();
+    while let Ok(Some((_, entry))) = entries.next_dfs() {
+        if entry.tag() != gimli::DW_TAG_inlined_subroutine {
+            continue;
+        }
+        if let Ok(mut ranges) = dwarf.die_ranges(unit, entry) {
+            while let Ok(Some(range)) = ranges.next() {
+                boundaries.push(range.begin);
+                boundaries.push(range.end);
+            }
+        }
+    }
+    boundaries.sort_unstable();
+    boundaries.dedup();
+    boundaries
+}
+
 pub fn convert_stack_frame<R: gimli::Reader>(
     frame: addr2line::Frame<R>,
     path_mapper: &mut PathMapper<()>,
@@ -68,7 +164,7 @@ pub enum SingleSectionData<'data, T: ReadRef<'data>> {
 pub fn try_get_section_data<'data, 'file, O, T>(
     data: T,
     file: &'file O,
-    section_id: SectionId,
+    section_name: &str,
 ) -> Option<SingleSectionData<'data, T>>
 where
     'data: 'file,
@@ -76,7 +172,6 @@ where
     T: ReadRef<'data>,
 {
     use object::ObjectSection;
-    let section_name = section_id.name();
     let (section, used_manual_zdebug_path) =
         if let Some(section) = file.section_by_name(section_name) {
             (section, false)
@@ -92,7 +187,30 @@ where
         };
 
     // Handle sections which are not compressed.
-    let mut file_range = section.compressed_file_range().ok()?;
+    let mut file_range = match section.compressed_file_range() {
+        Ok(file_range) => file_range,
+        Err(_) => {
+            // object doesn't support zstd-compressed ELF sections, so we handle
+            // them ourselves.
+            let (offset, compressed_size, uncompressed_size) =
+                elf_zstd_compressed_range(data, file, &section)?;
+            let compressed_bytes = data.read_bytes_at(offset, compressed_size).ok()?;
+            // Don't trust the uncompressed size from the header for the
+            // preallocation; a bogus value could make us allocate huge amounts.
+            let capacity = uncompressed_size.min(compressed_size.saturating_mul(8));
+            let mut decompressed = Vec::with_capacity(capacity as usize);
+            let decoder = ruzstd::StreamingDecoder::new(compressed_bytes).ok()?;
+            // Stop decoding as soon as the output is longer than announced.
+            decoder
+                .take(uncompressed_size.saturating_add(1))
+                .read_to_end(&mut decompressed)
+                .ok()?;
+            if decompressed.len() as u64 != uncompressed_size {
+                return None;
+            }
+            return Some(SingleSectionData::Owned(decompressed));
+        }
+    };
     if file_range.format == CompressionFormat::None
         && used_manual_zdebug_path
         && file_range.uncompressed_size > 12
@@ -144,6 +262,62 @@ where
     }
 }
 
+/// If this is an ELF section which is compressed with `ELFCOMPRESS_ZSTD`, return
+/// the file offset and size of the compressed data, and the uncompressed size.
+fn elf_zstd_compressed_range<'data, 'file, O, T>(
+    data: T,
+    file: &'file O,
+    section: &O::Section,
+) -> Option<(u64, u64, u64)>
+where
+    'data: 'file,
+    O: object::Object<'data, 'file>,
+    T: ReadRef<'data>,
+{
+    use object::{ObjectSection, SectionFlags};
+    const SHF_COMPRESSED: u64 = 1 << 11;
+    const ELFCOMPRESS_ZSTD: u32 = 2;
+
+    match section.flags() {
+        SectionFlags::Elf { sh_flags } if sh_flags & SHF_COMPRESSED != 0 => {}
+        _ => return None,
+    }
+    let (section_offset, section_size) = section.file_range()?;
+    // The compression header is Elf32_Chdr or Elf64_Chdr. Elf64_Chdr has
+    // a 4 byte padding field after ch_type.
+    let header_size = if file.is_64() { 24 } else { 12 };
+    let header = data.read_bytes_at(section_offset, header_size).ok()?;
+    let read_u32 = |bytes: &[u8]| {
+        let bytes = bytes.try_into().ok()?;
+        Some(if file.is_little_endian() {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    };
+    let read_u64 = |bytes: &[u8]| {
+        let bytes = bytes.try_into().ok()?;
+        Some(if file.is_little_endian() {
+            u64::from_le_bytes(bytes)
+        } else {
+            u64::from_be_bytes(bytes)
+        })
+    };
+    if read_u32(&header[0..4])? != ELFCOMPRESS_ZSTD {
+        return None;
+    }
+    let uncompressed_size = if file.is_64() {
+        read_u64(&header[8..16])?
+    } else {
+        u64::from(read_u32(&header[4..8])?)
+    };
+    Some((
+        section_offset + header_size,
+        section_size.checked_sub(header_size)?,
+        uncompressed_size,
+    ))
+}
+
 /// Holds on to section data so that we can create an addr2line::Context for that
 /// that data. This avoids one copy compared to what addr2line::Context::new does
 /// by default, saving 1.5 seconds on libxul. (For comparison, dumping all symbols
@@ -160,11 +334,13 @@ impl Addr2lineContextData {
         }
     }
 
-    fn sect<'data, 'ctxdata, 'file, O, R>(
+    /// Get the data of the section with the given name, e.g. `.debug_info` or
+    /// `.debug_info.dwo`. Missing sections are returned as empty slices.
+    pub fn sect<'data, 'ctxdata, 'file, O, R>(
         &'ctxdata self,
         data: R,
         obj: &'file O,
-        section_id: SectionId,
+        section_name: &str,
         endian: RunTimeEndian,
     ) -> EndianSlice<'ctxdata, RunTimeEndian>
     where
@@ -174,7 +350,7 @@ impl Addr2lineContextData {
         O: object::Object<'data, 'file>,
         R: ReadRef<'data>,
     {
-        let slice: &[u8] = match try_get_section_data(data, obj, section_id) {
+        let slice: &[u8] = match try_get_section_data(data, obj, section_name) {
             Some(SingleSectionData::Owned(section_data)) => {
                 self.uncompressed_section_data.push_get(section_data)
             }
@@ -205,11 +381,11 @@ impl Addr2lineContextData {
         } else {
             gimli::RunTimeEndian::Big
         };
-        let mut dwarf = gimli::Dwarf::load(|s| Ok(self.sect(data, obj, s, e)))
+        let mut dwarf = gimli::Dwarf::load(|s| Ok(self.sect(data, obj, s.name(), e)))
             .map_err(Error::Addr2lineContextCreationError)?;
         if let (Some(sup_obj), Some(sup_data)) = (sup_obj, sup_data) {
             dwarf
-                .load_sup(|s| Ok(self.sect(sup_data, sup_obj, s, e)))
+                .load_sup(|s| Ok(self.sect(sup_data, sup_obj, s.name(), e)))
                 .map_err(Error::Addr2lineContextCreationError)?;
         }
         let context =
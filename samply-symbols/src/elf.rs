@@ -1,5 +1,7 @@
 use crate::error::Error;
+use crate::gopclntab::go_pclntab_symbol_map_data;
 use crate::shared::{FileContents, FileContentsWrapper};
+use crate::split_dwarf::{load_split_dwarf_files, SplitDwarfFileContents};
 use crate::symbol_map::{
     GenericSymbolMap, SymbolMap, SymbolMapDataMidTrait, SymbolMapDataOuterTrait,
 };
@@ -33,7 +35,13 @@ where
     if let Some(supplementary_file) =
         try_to_load_supplementary_file(&file_location, &elf_file, helper).await
     {
-        let owner = ElfSymbolMapData::new(file_contents, Some(supplementary_file), file_kind, None);
+        let owner = ElfSymbolMapData::new(
+            file_contents,
+            Some(supplementary_file),
+            SplitDwarfFileContents::default(),
+            file_kind,
+            None,
+        );
         let symbol_map = GenericSymbolMap::new(owner)?;
         return Ok(SymbolMap::new(file_location, Box::new(symbol_map)));
     }
@@ -45,7 +53,9 @@ where
         return Ok(symbol_map);
     }
 
-    let owner = ElfSymbolMapData::new(file_contents, None, file_kind, None);
+    let split_dwarf_files =
+        load_split_dwarf_files(&file_location, &file_contents, &elf_file, helper).await;
+    let owner = ElfSymbolMapData::new(file_contents, None, split_dwarf_files, file_kind, None);
     let symbol_map = GenericSymbolMap::new(owner)?;
     Ok(SymbolMap::new(file_location, Box::new(symbol_map)))
 }
@@ -109,7 +119,19 @@ where
         return Err(Error::DebugLinkCrcMismatch(actual_crc, expected_crc));
     }
 
-    let owner = ElfSymbolMapData::new(file_contents, None, file_kind, Some(debug_id));
+    let split_dwarf_files = match File::parse(&file_contents) {
+        Ok(elf_file) => {
+            load_split_dwarf_files(original_file_location, &file_contents, &elf_file, helper).await
+        }
+        Err(_) => SplitDwarfFileContents::default(),
+    };
+    let owner = ElfSymbolMapData::new(
+        file_contents,
+        None,
+        split_dwarf_files,
+        file_kind,
+        Some(debug_id),
+    );
     let symbol_map = GenericSymbolMap::new(owner)?;
     Ok(SymbolMap::new(
         original_file_location.clone(),
@@ -265,7 +287,13 @@ fn try_get_symbol_map_from_mini_debug_info<'data, R: ReadRef<'data>, FL: FileLoc
     let mut objdata = Vec::new();
     lzma_rs::xz_decompress(&mut cursor, &mut objdata).ok()?;
     let file_contents = FileContentsWrapper::new(objdata);
-    let owner = ElfSymbolMapData::new(file_contents, None, file_kind, None);
+    let owner = ElfSymbolMapData::new(
+        file_contents,
+        None,
+        SplitDwarfFileContents::default(),
+        file_kind,
+        None,
+    );
     let symbol_map = GenericSymbolMap::new(owner).ok()?;
     Some(SymbolMap::new(
         debug_file_location.clone(),
@@ -279,6 +307,7 @@ where
 {
     file_data: FileContentsWrapper<T>,
     supplementary_file_data: Option<FileContentsWrapper<T>>,
+    split_dwarf_files: SplitDwarfFileContents<T>,
     file_kind: FileKind,
     override_debug_id: Option<DebugId>,
 }
@@ -287,12 +316,14 @@ impl<T: FileContents> ElfSymbolMapData<T> {
     pub fn new(
         file_data: FileContentsWrapper<T>,
         supplementary_file_data: Option<FileContentsWrapper<T>>,
+        split_dwarf_files: SplitDwarfFileContents<T>,
         file_kind: FileKind,
         override_debug_id: Option<DebugId>,
     ) -> Self {
         Self {
             file_data,
             supplementary_file_data,
+            split_dwarf_files,
             file_kind,
             override_debug_id,
         }
@@ -316,12 +347,18 @@ impl<T: FileContents + 'static> SymbolMapDataOuterTrait for ElfSymbolMapData<T>
             debug_id_for_object(&object)
                 .ok_or(Error::InvalidInputError("debug ID cannot be read"))?
         };
+        if supplementary_object.is_none() {
+            if let Some(go_data) = go_pclntab_symbol_map_data(&object, debug_id) {
+                return Ok(Box::new(go_data));
+            }
+        }
         let object = ObjectSymbolMapDataMid::new(
             object,
             supplementary_object,
             ElfFunctionAddressesComputer,
             &self.file_data,
             self.supplementary_file_data.as_ref(),
+            self.split_dwarf_files.parse_objects(),
             None,
             debug_id,
         );
@@ -336,7 +373,7 @@ impl<'data> FunctionAddressesComputer<'data> for ElfFunctionAddressesComputer {
     fn compute_function_addresses<'file, O>(
         &'file self,
         object_file: &'file O,
-    ) -> (Option<Vec<u32>>, Option<Vec<u32>>)
+    ) -> (Option<Vec<u64>>, Option<Vec<u64>>)
     where
         'data: 'file,
         O: object::Object<'data, 'file>,
@@ -413,8 +450,8 @@ impl<'data> FunctionAddressesComputer<'data> for ElfFunctionAddressesComputer {
                         }
                         cie
                     }) {
-                        start_addresses.push(fde.initial_address() as u32);
-                        end_addresses.push((fde.initial_address() + fde.len()) as u32);
+                        start_addresses.push(fde.initial_address());
+                        end_addresses.push(fde.initial_address() + fde.len());
                     }
                 }
             }
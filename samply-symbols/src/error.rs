@@ -4,7 +4,10 @@ use pdb_addr2line::pdb::Error as PdbError;
 use std::path::PathBuf;
 use thiserror::Error;
 
-use crate::{breakpad::BreakpadParseError, CodeId, FatArchiveMember, LibraryInfo};
+use crate::{
+    breakpad::BreakpadParseError, jitdump::JitDumpParseError, wasm::WasmParseError, CodeId,
+    FatArchiveMember, LibraryInfo,
+};
 
 /// The error type used in this crate.
 #[derive(Error, Debug)]
@@ -22,6 +25,12 @@ pub enum Error {
     #[error("The Breakpad sym file was malformed, causing a parsing error: {0}")]
     BreakpadParsing(#[from] BreakpadParseError),
 
+    #[error("The jitdump file was malformed, causing a parsing error: {0}")]
+    JitDumpParsing(#[from] JitDumpParseError),
+
+    #[error("The wasm module was malformed, causing a parsing error: {0}")]
+    WasmParsing(#[from] WasmParseError),
+
     #[error("Invalid index {0} for file or inline_origin in breakpad sym file")]
     InvalidFileOrInlineOriginIndexInBreakpadFile(u32),
 
@@ -217,6 +226,8 @@ impl Error {
             Error::UnmatchedDebugId(_, _) => "UnmatchedDebugId",
             Error::NoDisambiguatorForFatArchive(_) => "NoDisambiguatorForFatArchive",
             Error::BreakpadParsing(_) => "BreakpadParsing",
+            Error::JitDumpParsing(_) => "JitDumpParsing",
+            Error::WasmParsing(_) => "WasmParsing",
             Error::NotEnoughInformationToIdentifyBinary => "NotEnoughInformationToIdentifyBinary",
             Error::NotEnoughInformationToIdentifySymbolMap => {
                 "NotEnoughInformationToIdentifySymbolMap"
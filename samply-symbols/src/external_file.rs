@@ -4,7 +4,7 @@ use object::{read::archive::ArchiveFile, File, FileKind, ReadRef};
 use yoke::{Yoke, Yokeable};
 
 use crate::{
-    dwarf::{get_frames, Addr2lineContextData},
+    dwarf::{get_debug_info_boundaries, get_frames, Addr2lineContextData, InlineRangeBoundaries},
     macho,
     path_mapper::PathMapper,
     shared::{ExternalFileAddressInFileRef, ExternalFileRef, FileContentsWrapper, RangeReadRef},
@@ -27,17 +27,17 @@ where
         )
         .await
         .map_err(|e| Error::HelperErrorDuringOpenFile(external_file_ref.file_name.clone(), e))?;
-    let symbol_map = ExternalFileSymbolMapImpl::new(
+    ExternalFileSymbolMap::new(
         &external_file_ref.file_name,
         file,
         external_file_ref.arch.as_deref(),
-    )?;
-    Ok(ExternalFileSymbolMap(Box::new(symbol_map)))
+    )
 }
 
 struct ExternalFileMemberContext<'a> {
     context: Option<addr2line::Context<gimli::EndianSlice<'a, gimli::RunTimeEndian>>>,
     symbol_addresses: HashMap<&'a [u8], u64>,
+    inline_range_boundaries: Mutex<InlineRangeBoundaries>,
 }
 
 impl<'a> ExternalFileMemberContext<'a> {
@@ -49,7 +49,30 @@ impl<'a> ExternalFileMemberContext<'a> {
     ) -> Option<Vec<FrameDebugInfo>> {
         let symbol_address = self.symbol_addresses.get(symbol_name)?;
         let address = symbol_address + offset_from_symbol as u64;
-        get_frames(address, self.context.as_ref(), path_mapper)
+        get_frames(address, self.context.as_ref(), None, path_mapper)
+    }
+
+    pub fn debug_info_boundaries(
+        &self,
+        symbol_name: &[u8],
+        start_offset: u64,
+        end_offset: u64,
+    ) -> Option<Vec<u64>> {
+        let symbol_address = *self.symbol_addresses.get(symbol_name)?;
+        let mut inline_range_boundaries = self.inline_range_boundaries.lock().unwrap();
+        let boundaries = get_debug_info_boundaries(
+            self.context.as_ref()?,
+            None,
+            symbol_address + start_offset,
+            symbol_address + end_offset,
+            &mut inline_range_boundaries,
+        )?;
+        Some(
+            boundaries
+                .into_iter()
+                .map(|address| address - symbol_address)
+                .collect(),
+        )
     }
 }
 
@@ -78,6 +101,30 @@ trait ExternalFileContextTrait {
         &self,
         external_file_address: &ExternalFileAddressInFileRef,
     ) -> Option<Vec<FrameDebugInfo>>;
+    fn debug_info_boundaries(
+        &self,
+        external_file_address: &ExternalFileAddressInFileRef,
+        size: u64,
+    ) -> Option<Vec<u64>>;
+}
+
+impl<'a, F: FileContents> ExternalFileContext<'a, F> {
+    fn with_member_context<T>(
+        &self,
+        name_in_archive: Option<&str>,
+        f: impl FnOnce(&ExternalFileMemberContext<'a>) -> T,
+    ) -> Option<T> {
+        let member_key = name_in_archive.unwrap_or("");
+        let mut member_contexts = self.member_contexts.lock().unwrap();
+        if !member_contexts.contains_key(member_key) {
+            let member_context = self
+                .external_file
+                .make_member_context(name_in_archive)
+                .ok()?;
+            member_contexts.insert(member_key.to_string(), member_context);
+        }
+        Some(f(&member_contexts[member_key]))
+    }
 }
 
 impl<'a, F: FileContents> ExternalFileContextTrait for ExternalFileContext<'a, F> {
@@ -85,32 +132,35 @@ impl<'a, F: FileContents> ExternalFileContextTrait for ExternalFileContext<'a, F
         &self,
         external_file_address: &ExternalFileAddressInFileRef,
     ) -> Option<Vec<FrameDebugInfo>> {
-        let member_key = external_file_address
-            .name_in_archive
-            .as_deref()
-            .unwrap_or("");
-        let mut member_contexts = self.member_contexts.lock().unwrap();
-        let mut path_mapper = self.path_mapper.lock().unwrap();
-        match member_contexts.get(member_key) {
-            Some(member_context) => member_context.lookup(
-                &external_file_address.symbol_name,
-                external_file_address.offset_from_symbol,
-                &mut path_mapper,
-            ),
-            None => {
-                let member_context = self
-                    .external_file
-                    .make_member_context(external_file_address.name_in_archive.as_deref())
-                    .ok()?;
-                let res = member_context.lookup(
+        self.with_member_context(
+            external_file_address.name_in_archive.as_deref(),
+            |member_context| {
+                let mut path_mapper = self.path_mapper.lock().unwrap();
+                member_context.lookup(
                     &external_file_address.symbol_name,
                     external_file_address.offset_from_symbol,
                     &mut path_mapper,
-                );
-                member_contexts.insert(member_key.to_string(), member_context);
-                res
-            }
-        }
+                )
+            },
+        )?
+    }
+
+    fn debug_info_boundaries(
+        &self,
+        external_file_address: &ExternalFileAddressInFileRef,
+        size: u64,
+    ) -> Option<Vec<u64>> {
+        let start_offset = u64::from(external_file_address.offset_from_symbol);
+        self.with_member_context(
+            external_file_address.name_in_archive.as_deref(),
+            |member_context| {
+                member_context.debug_info_boundaries(
+                    &external_file_address.symbol_name,
+                    start_offset,
+                    start_offset + size,
+                )
+            },
+        )?
     }
 }
 
@@ -125,6 +175,11 @@ trait ExternalFileSymbolMapTrait {
         &self,
         external_file_address: &ExternalFileAddressInFileRef,
     ) -> Option<Vec<FrameDebugInfo>>;
+    fn debug_info_boundaries(
+        &self,
+        external_file_address: &ExternalFileAddressInFileRef,
+        size: u64,
+    ) -> Option<Vec<u64>>;
 }
 
 impl<F: FileContents + 'static> ExternalFileSymbolMapImpl<F> {
@@ -157,6 +212,17 @@ impl<F: FileContents + 'static> ExternalFileSymbolMapTrait for ExternalFileSymbo
     ) -> Option<Vec<FrameDebugInfo>> {
         self.0.get().0.lookup(external_file_address)
     }
+
+    fn debug_info_boundaries(
+        &self,
+        external_file_address: &ExternalFileAddressInFileRef,
+        size: u64,
+    ) -> Option<Vec<u64>> {
+        self.0
+            .get()
+            .0
+            .debug_info_boundaries(external_file_address, size)
+    }
 }
 
 /// A symbol map for an external object file. You usually don't need this because
@@ -170,6 +236,15 @@ pub struct ExternalFileSymbolMap(Box<dyn ExternalFileSymbolMapTrait + Send + Syn
 pub struct ExternalFileSymbolMap(Box<dyn ExternalFileSymbolMapTrait>);
 
 impl ExternalFileSymbolMap {
+    pub(crate) fn new<F: FileContents + 'static>(
+        file_name: &str,
+        file: F,
+        arch: Option<&str>,
+    ) -> Result<Self, Error> {
+        let symbol_map = ExternalFileSymbolMapImpl::new(file_name, file, arch)?;
+        Ok(Self(Box::new(symbol_map)))
+    }
+
     /// The string which identifies this external file. This is usually an absolute
     /// path. (XXX does this contain the `archive.a(membername)` stuff or no?)
     pub fn name(&self) -> &str {
@@ -190,6 +265,17 @@ impl ExternalFileSymbolMap {
     ) -> Option<Vec<FrameDebugInfo>> {
         self.0.lookup(external_file_address)
     }
+
+    /// Returns the offsets from the symbol in `external_file_address`, in the
+    /// `size` bytes from its `offset_from_symbol` on, at which the debug info
+    /// can change.
+    pub(crate) fn debug_info_boundaries(
+        &self,
+        external_file_address: &ExternalFileAddressInFileRef,
+        size: u64,
+    ) -> Option<Vec<u64>> {
+        self.0.debug_info_boundaries(external_file_address, size)
+    }
 }
 
 #[cfg(feature = "send_futures")]
@@ -231,6 +317,7 @@ impl<F: FileContents> ExternalFileDataOuterTrait for ExternalFileData<F> {
         let uplooker = ExternalFileMemberContext {
             context: context.ok(),
             symbol_addresses,
+            inline_range_boundaries: Mutex::new(InlineRangeBoundaries::default()),
         };
         Ok(uplooker)
     }
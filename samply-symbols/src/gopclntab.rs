@@ -0,0 +1,837 @@
+//! Support for the `pclntab` of Go binaries.
+//!
+//! Go binaries are often stripped of their DWARF and of their symbol table,
+//! for example when they're built with `-ldflags=-s -w`. But they always keep
+//! the `pclntab`, because the Go runtime needs it for stack traces. It has the
+//! name of every function, along with tables which map each instruction
+//! address to a file and line number.
+//!
+//! The layout of the `pclntab` has changed a few times; we support the
+//! layouts of Go 1.2, Go 1.16, Go 1.18 and Go 1.20. The magic number at the
+//! start of the table tells us which one we have.
+
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::sync::Mutex;
+
+use debugid::DebugId;
+use object::{FileFlags, Object, ObjectSection, ObjectSymbol, SectionKind};
+
+use crate::path_mapper::PathMapper;
+use crate::shared::{
+    relative_address_base, AddressInfo, FrameDebugInfo, FramesLookupResult, SourceFilePath,
+    SymbolInfo,
+};
+use crate::symbol_map::{SymbolMapDataMidTrait, SymbolMapInnerWrapper, SymbolMapTrait};
+use crate::symbol_map_object::{file_offset_to_svma, svma_file_ranges, SvmaFileRange};
+use crate::Error;
+
+const GO12_MAGIC: u32 = 0xfffffffb;
+const GO116_MAGIC: u32 = 0xfffffffa;
+const GO118_MAGIC: u32 = 0xfffffff0;
+const GO120_MAGIC: u32 = 0xfffffff1;
+
+/// The names of the section which contains the `pclntab`. In ELF files which
+/// use RELRO, the section name gets a `.data.rel.ro` prefix.
+const PCLNTAB_SECTION_NAMES: &[&str] = &[".gopclntab", ".data.rel.ro.gopclntab", "__gopclntab"];
+
+/// The symbol which points at the start of the `pclntab`, if the binary still
+/// has a symbol table.
+const PCLNTAB_SYMBOL_NAME: &str = "runtime.pclntab";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum GoPclntabVersion {
+    Go12,
+    Go116,
+    Go118,
+    Go120,
+}
+
+/// Creates symbol map data from the Go `pclntab` of `object_file`, if it is a
+/// Go binary without DWARF debug info.
+///
+/// Returns `None` if the object has debug info, because the DWARF path gives
+/// better results, e.g. inline frames.
+pub fn go_pclntab_symbol_map_data<'data: 'file, 'file>(
+    object_file: &'file impl Object<'data, 'file>,
+    debug_id: DebugId,
+) -> Option<GoPclntabSymbolMapData<'data>> {
+    if object_file.has_debug_symbols() {
+        return None;
+    }
+    let text_start = symbol_by_name(object_file, "runtime.text")
+        .map(|symbol| symbol.address())
+        .or_else(|| {
+            object_file
+                .sections()
+                .find(|section| section.kind() == SectionKind::Text)
+                .map(|section| section.address())
+        })
+        .unwrap_or(0);
+    let pclntab = find_pclntab(object_file, text_start)?;
+    Some(GoPclntabSymbolMapData::new(
+        pclntab,
+        relative_address_base(object_file),
+        svma_file_ranges(object_file),
+        debug_id,
+    ))
+}
+
+/// Finds the `pclntab`: by section name, by symbol name, or, as a last resort
+/// for stripped PE binaries which have neither, by looking for the header in
+/// the data sections.
+fn find_pclntab<'data: 'file, 'file>(
+    object_file: &'file impl Object<'data, 'file>,
+    text_start: u64,
+) -> Option<GoPclntab<'data>> {
+    let parse = |data: &'data [u8]| {
+        GoPclntab::parse(data, text_start)
+            .filter(|pclntab| pclntab_matches_text(pclntab, object_file))
+    };
+
+    for name in PCLNTAB_SECTION_NAMES {
+        if let Some(section) = object_file.section_by_name(name) {
+            if let Ok(data) = section.data() {
+                if !data.is_empty() {
+                    return parse(data);
+                }
+            }
+        }
+    }
+
+    if let Some(symbol) = symbol_by_name(object_file, PCLNTAB_SYMBOL_NAME) {
+        let section = symbol
+            .section_index()
+            .and_then(|index| object_file.section_by_index(index).ok());
+        if let Some(section) = section {
+            let data = section.data().ok()?;
+            let offset = symbol.address().checked_sub(section.address())?;
+            return parse(data.get(usize::try_from(offset).ok()?..)?);
+        }
+    }
+
+    if !matches!(object_file.flags(), FileFlags::Coff { .. }) {
+        return None;
+    }
+    object_file
+        .sections()
+        .filter(|section| {
+            matches!(
+                section.kind(),
+                SectionKind::ReadOnlyData | SectionKind::Text
+            )
+        })
+        .filter_map(|section| section.data().ok())
+        .find_map(|data| {
+            (0..data.len().saturating_sub(16))
+                .step_by(4)
+                .map(|offset| &data[offset..])
+                .filter(|candidate| GoPclntab::header_looks_valid(candidate))
+                .find_map(parse)
+        })
+}
+
+fn symbol_by_name<'data: 'file, 'file, O: Object<'data, 'file>>(
+    object_file: &'file O,
+    name: &str,
+) -> Option<O::Symbol> {
+    object_file
+        .symbols()
+        .find(|symbol| symbol.name() == Ok(name))
+}
+
+/// Checks that the first and the last function in the `pclntab` are in the
+/// executable code of the object. This rejects false positives from the scan
+/// in `find_pclntab`.
+fn pclntab_matches_text<'data: 'file, 'file>(
+    pclntab: &GoPclntab<'data>,
+    object_file: &'file impl Object<'data, 'file>,
+) -> bool {
+    let (first_pc, _) = match pclntab.functab_entry(0) {
+        Some(entry) => entry,
+        None => return false,
+    };
+    let (last_pc, _) = match pclntab.functab_entry(pclntab.function_count) {
+        Some(entry) => entry,
+        None => return false,
+    };
+    object_file
+        .sections()
+        .filter(|section| section.kind() == SectionKind::Text)
+        .any(|section| {
+            let start = section.address();
+            let end = start.saturating_add(section.size());
+            start <= first_pc && first_pc <= last_pc && last_pc <= end
+        })
+}
+
+/// The parsed header of a `pclntab`, with the sub-tables it points to.
+struct GoPclntab<'data> {
+    data: &'data [u8],
+    version: GoPclntabVersion,
+    little_endian: bool,
+    quantum: u8,
+    ptr_size: u8,
+    function_count: usize,
+    /// The address which the function entries are relative to, for Go 1.18+.
+    text_start: u64,
+    funcname_tab: &'data [u8],
+    cu_tab: &'data [u8],
+    file_tab: &'data [u8],
+    pc_tab: &'data [u8],
+    /// The function table.
+    func_tab: &'data [u8],
+    /// The data which the `_func` offsets in the function table are relative to.
+    func_data: &'data [u8],
+}
+
+/// The fields we need from a `_func` struct.
+struct GoFunc {
+    name_offset: u32,
+    pcfile: u32,
+    pcln: u32,
+    cu_offset: u32,
+}
+
+impl<'data> GoPclntab<'data> {
+    fn magic(data: &[u8]) -> Option<(GoPclntabVersion, bool)> {
+        let magic = data.get(..4)?;
+        let magic_le = u32::from_le_bytes([magic[0], magic[1], magic[2], magic[3]]);
+        let magic_be = u32::from_be_bytes([magic[0], magic[1], magic[2], magic[3]]);
+        let version_for_magic = |magic| match magic {
+            GO12_MAGIC => Some(GoPclntabVersion::Go12),
+            GO116_MAGIC => Some(GoPclntabVersion::Go116),
+            GO118_MAGIC => Some(GoPclntabVersion::Go118),
+            GO120_MAGIC => Some(GoPclntabVersion::Go120),
+            _ => None,
+        };
+        if let Some(version) = version_for_magic(magic_le) {
+            return Some((version, true));
+        }
+        version_for_magic(magic_be).map(|version| (version, false))
+    }
+
+    fn header_looks_valid(data: &[u8]) -> bool {
+        Self::magic(data).is_some()
+            && data.len() >= 8
+            && data[4] == 0
+            && data[5] == 0
+            && matches!(data[6], 1 | 2 | 4)
+            && matches!(data[7], 4 | 8)
+    }
+
+    fn parse(data: &'data [u8], text_start: u64) -> Option<Self> {
+        if !Self::header_looks_valid(data) {
+            return None;
+        }
+        let (version, little_endian) = Self::magic(data)?;
+        let header = Self {
+            data,
+            version,
+            little_endian,
+            quantum: data[6],
+            ptr_size: data[7],
+            function_count: 0,
+            text_start,
+            funcname_tab: data,
+            cu_tab: &[],
+            file_tab: data,
+            pc_tab: data,
+            func_tab: data,
+            func_data: data,
+        };
+        let ptr_size = usize::from(header.ptr_size);
+        let header_word = |index: usize| header.read_uintptr(data, 8 + index * ptr_size);
+        let sub_table = |index: usize| data.get(usize::try_from(header_word(index)?).ok()?..);
+        let function_count = usize::try_from(header_word(0)?).ok()?;
+        let pclntab = match version {
+            GoPclntabVersion::Go12 => {
+                let func_tab = data.get(8 + ptr_size..)?;
+                let file_tab_offset_offset = (function_count * 2 + 1).checked_mul(ptr_size)?;
+                let file_tab_offset = header.read_u32(func_tab, file_tab_offset_offset)?;
+                Self {
+                    function_count,
+                    file_tab: data.get(file_tab_offset as usize..)?,
+                    func_tab,
+                    ..header
+                }
+            }
+            GoPclntabVersion::Go116 => Self {
+                function_count,
+                funcname_tab: sub_table(2)?,
+                cu_tab: sub_table(3)?,
+                file_tab: sub_table(4)?,
+                pc_tab: sub_table(5)?,
+                func_tab: sub_table(6)?,
+                func_data: sub_table(6)?,
+                ..header
+            },
+            GoPclntabVersion::Go118 | GoPclntabVersion::Go120 => Self {
+                function_count,
+                // The text start is zero in position-independent binaries, where it
+                // is filled in by a relocation. Use the caller's value in that case.
+                text_start: match header_word(2)? {
+                    0 => text_start,
+                    header_text_start => header_text_start,
+                },
+                funcname_tab: sub_table(3)?,
+                cu_tab: sub_table(4)?,
+                file_tab: sub_table(5)?,
+                pc_tab: sub_table(6)?,
+                func_tab: sub_table(7)?,
+                func_data: sub_table(7)?,
+                ..header
+            },
+        };
+        Some(pclntab)
+    }
+
+    fn read_u32(&self, data: &[u8], offset: usize) -> Option<u32> {
+        let bytes = data.get(offset..offset.checked_add(4)?)?;
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        Some(if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    fn read_uintptr(&self, data: &[u8], offset: usize) -> Option<u64> {
+        if self.ptr_size == 4 {
+            return self.read_u32(data, offset).map(u64::from);
+        }
+        let bytes = data.get(offset..offset.checked_add(8)?)?;
+        let bytes = <[u8; 8]>::try_from(bytes).ok()?;
+        Some(if self.little_endian {
+            u64::from_le_bytes(bytes)
+        } else {
+            u64::from_be_bytes(bytes)
+        })
+    }
+
+    /// Returns the entry address and the `_func` offset of the function at
+    /// `index`. The entry at `function_count` only has the end address of the
+    /// last function.
+    fn functab_entry(&self, index: usize) -> Option<(u64, usize)> {
+        if self.version >= GoPclntabVersion::Go118 {
+            let offset = index.checked_mul(8)?;
+            let entry_offset = self.read_u32(self.func_tab, offset)?;
+            let func_offset = self.read_u32(self.func_tab, offset + 4).unwrap_or(0);
+            let pc = self.text_start.checked_add(u64::from(entry_offset))?;
+            return Some((pc, func_offset as usize));
+        }
+        let ptr_size = usize::from(self.ptr_size);
+        let offset = index.checked_mul(2 * ptr_size)?;
+        let pc = self.read_uintptr(self.func_tab, offset)?;
+        let func_offset = self
+            .read_uintptr(self.func_tab, offset + ptr_size)
+            .unwrap_or(0);
+        Some((pc, usize::try_from(func_offset).ok()?))
+    }
+
+    fn func(&self, func_offset: usize) -> Option<GoFunc> {
+        // The entry field is a uintptr before Go 1.18 and a u32 offset after.
+        let entry_size = match self.version {
+            GoPclntabVersion::Go12 | GoPclntabVersion::Go116 => usize::from(self.ptr_size),
+            GoPclntabVersion::Go118 | GoPclntabVersion::Go120 => 4,
+        };
+        let field =
+            |index: usize| self.read_u32(self.func_data, func_offset + entry_size + index * 4);
+        Some(GoFunc {
+            name_offset: field(0)?,
+            pcfile: field(4)?,
+            pcln: field(5)?,
+            cu_offset: match self.version {
+                GoPclntabVersion::Go12 => 0,
+                _ => field(7)?,
+            },
+        })
+    }
+
+    fn function_name(&self, func: &GoFunc) -> Option<&'data str> {
+        read_cstr(self.funcname_tab, func.name_offset as usize)
+    }
+
+    fn file_name(&self, func: &GoFunc, file_index: i32) -> Option<&'data str> {
+        let file_index = u32::try_from(file_index).ok()?;
+        if self.version == GoPclntabVersion::Go12 {
+            // The file table starts with the number of entries, and entry 0 is unused.
+            let file_count = self.read_u32(self.file_tab, 0)?;
+            if file_index == 0 || file_index >= file_count {
+                return None;
+            }
+            let name_offset = self.read_u32(self.file_tab, file_index as usize * 4)?;
+            return read_cstr(self.data, name_offset as usize);
+        }
+        let cu_index = func.cu_offset.checked_add(file_index)?;
+        let name_offset = self.read_u32(self.cu_tab, cu_index as usize * 4)?;
+        if name_offset == u32::MAX {
+            return None;
+        }
+        read_cstr(self.file_tab, name_offset as usize)
+    }
+
+    fn pc_values(&self, table_offset: u32, entry: u64) -> PcValueIter<'data> {
+        let data = match table_offset {
+            0 => &[],
+            offset => self.pc_tab.get(offset as usize..).unwrap_or(&[]),
+        };
+        PcValueIter {
+            data,
+            quantum: u64::from(self.quantum),
+            pc: entry,
+            value: -1,
+            is_first: true,
+        }
+    }
+
+    fn pc_value(&self, table_offset: u32, entry: u64, pc: u64) -> Option<i32> {
+        self.pc_values(table_offset, entry)
+            .find(|&(start, end, _)| start <= pc && pc < end)
+            .map(|(_, _, value)| value)
+    }
+}
+
+/// Iterates over a pc-value table, which is a sequence of (value delta, pc delta)
+/// pairs. Yields `(start_pc, end_pc, value)` for each run.
+struct PcValueIter<'data> {
+    data: &'data [u8],
+    quantum: u64,
+    pc: u64,
+    value: i32,
+    is_first: bool,
+}
+
+impl<'data> PcValueIter<'data> {
+    fn read_uvarint(&mut self) -> Option<u32> {
+        let mut result: u32 = 0;
+        for shift in (0..35).step_by(7) {
+            let (&byte, rest) = self.data.split_first()?;
+            self.data = rest;
+            result |= u32::from(byte & 0x7f).checked_shl(shift)?;
+            if byte & 0x80 == 0 {
+                return Some(result);
+            }
+        }
+        None
+    }
+}
+
+impl<'data> Iterator for PcValueIter<'data> {
+    type Item = (u64, u64, i32);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let value_delta = self.read_uvarint()?;
+        if value_delta == 0 && !self.is_first {
+            return None;
+        }
+        self.is_first = false;
+        // The value delta is zig-zag encoded.
+        let value_delta = if value_delta & 1 != 0 {
+            !(value_delta >> 1) as i32
+        } else {
+            (value_delta >> 1) as i32
+        };
+        let pc_delta = u64::from(self.read_uvarint()?) * self.quantum;
+        self.value = self.value.wrapping_add(value_delta);
+        let start = self.pc;
+        self.pc = start.checked_add(pc_delta)?;
+        Some((start, self.pc, self.value))
+    }
+}
+
+fn read_cstr(data: &[u8], offset: usize) -> Option<&str> {
+    let bytes = data.get(offset..)?;
+    let len = bytes.iter().position(|&b| b == 0)?;
+    std::str::from_utf8(&bytes[..len]).ok()
+}
+
+pub struct GoPclntabSymbolMapData<'data> {
+    pclntab: GoPclntab<'data>,
+    /// The relative address range of each function, with its `_func` offset.
+    functions: Vec<(u64, u64, usize)>,
+    image_base_address: u64,
+    svma_file_ranges: Vec<SvmaFileRange>,
+    debug_id: DebugId,
+}
+
+impl<'data> GoPclntabSymbolMapData<'data> {
+    fn new(
+        pclntab: GoPclntab<'data>,
+        image_base_address: u64,
+        svma_file_ranges: Vec<SvmaFileRange>,
+        debug_id: DebugId,
+    ) -> Self {
+        let mut functions = Vec::with_capacity(pclntab.function_count);
+        let relative = |svma: u64| svma.checked_sub(image_base_address);
+        for index in 0..pclntab.function_count {
+            let (start, func_offset, end) = match (
+                pclntab.functab_entry(index),
+                pclntab.functab_entry(index + 1),
+            ) {
+                (Some((start, func_offset)), Some((end, _))) => (start, func_offset, end),
+                _ => break,
+            };
+            if let (Some(start), Some(end)) = (relative(start), relative(end)) {
+                if start < end {
+                    functions.push((start, end, func_offset));
+                }
+            }
+        }
+        functions.sort_by_key(|&(start, _, _)| start);
+        Self {
+            pclntab,
+            functions,
+            image_base_address,
+            svma_file_ranges,
+            debug_id,
+        }
+    }
+}
+
+impl<'data> SymbolMapDataMidTrait for GoPclntabSymbolMapData<'data> {
+    fn make_symbol_map_inner(&self) -> Result<SymbolMapInnerWrapper<'_>, Error> {
+        let symbol_map = GoPclntabSymbolMapInner {
+            data: self,
+            path_mapper: Mutex::new(PathMapper::new()),
+        };
+        Ok(SymbolMapInnerWrapper(Box::new(symbol_map)))
+    }
+}
+
+struct GoPclntabSymbolMapInner<'a, 'data> {
+    data: &'a GoPclntabSymbolMapData<'data>,
+    path_mapper: Mutex<PathMapper<()>>,
+}
+
+impl<'a, 'data> GoPclntabSymbolMapInner<'a, 'data> {
+    fn function_index(&self, address: u64) -> Option<usize> {
+        let functions = &self.data.functions;
+        let index = match functions.binary_search_by_key(&address, |&(start, _, _)| start) {
+            Ok(i) => i,
+            Err(0) => return None,
+            Err(i) => i - 1,
+        };
+        let (_start, end, _func_offset) = functions[index];
+        if address >= end {
+            return None;
+        }
+        Some(index)
+    }
+}
+
+impl<'a, 'data> SymbolMapTrait for GoPclntabSymbolMapInner<'a, 'data> {
+    fn debug_id(&self) -> DebugId {
+        self.data.debug_id
+    }
+
+    fn symbol_count(&self) -> usize {
+        self.data.functions.len()
+    }
+
+    fn iter_symbols(&self) -> Box<dyn Iterator<Item = (u64, Cow<'_, str>)> + '_> {
+        let pclntab = &self.data.pclntab;
+        Box::new(
+            self.data
+                .functions
+                .iter()
+                .filter_map(move |&(start, _end, func_offset)| {
+                    let func = pclntab.func(func_offset)?;
+                    Some((start, Cow::Borrowed(pclntab.function_name(&func)?)))
+                }),
+        )
+    }
+
+    fn lookup_relative_address(&self, address: u64) -> Option<AddressInfo> {
+        let (start, end, func_offset) = self.data.functions[self.function_index(address)?];
+        let pclntab = &self.data.pclntab;
+        let func = pclntab.func(func_offset)?;
+        let name = pclntab.function_name(&func)?;
+
+        let entry = self.data.image_base_address + start;
+        let pc = self.data.image_base_address + address;
+        let file_path = pclntab
+            .pc_value(func.pcfile, entry, pc)
+            .and_then(|file_index| pclntab.file_name(&func, file_index))
+            .map(|file| {
+                let mut path_mapper = self.path_mapper.lock().unwrap();
+                let mapped_path = path_mapper.map_path(file);
+                SourceFilePath::new(file.into(), mapped_path)
+            });
+        let line_number = pclntab
+            .pc_value(func.pcln, entry, pc)
+            .and_then(|line| u32::try_from(line).ok());
+        let frames = if file_path.is_some() || line_number.is_some() {
+            FramesLookupResult::Available(vec![FrameDebugInfo {
+                function: Some(name.to_string()),
+                file_path,
+                line_number,
+            }])
+        } else {
+            FramesLookupResult::Unavailable
+        };
+
+        Some(AddressInfo {
+            symbol: SymbolInfo {
+                address: start,
+                size: Some(end - start),
+                name: name.to_string(),
+            },
+            frames,
+        })
+    }
+
+    fn lookup_svma(&self, svma: u64) -> Option<AddressInfo> {
+        let relative_address = svma.checked_sub(self.data.image_base_address)?;
+        self.lookup_relative_address(relative_address)
+    }
+
+    fn lookup_offset(&self, offset: u64) -> Option<AddressInfo> {
+        let svma = file_offset_to_svma(&self.data.svma_file_ranges, offset)?;
+        self.lookup_svma(svma)
+    }
+
+    fn debug_info_boundaries(&self, start: u64, end: u64) -> Option<Vec<u64>> {
+        let pclntab = &self.data.pclntab;
+        let image_base_address = self.data.image_base_address;
+        let first_index = self.function_index(start).unwrap_or_else(|| {
+            self.data
+                .functions
+                .partition_point(|&(function_start, _, _)| function_start < start)
+        });
+        let mut boundaries = Vec::new();
+        for &(function_start, _function_end, func_offset) in &self.data.functions[first_index..] {
+            if function_start >= end {
+                break;
+            }
+            let func = match pclntab.func(func_offset) {
+                Some(func) => func,
+                None => continue,
+            };
+            let entry = image_base_address + function_start;
+            for table_offset in [func.pcfile, func.pcln] {
+                for (run_start, _run_end, _value) in pclntab.pc_values(table_offset, entry) {
+                    let address = run_start - image_base_address;
+                    if start <= address && address < end {
+                        boundaries.push(address);
+                    }
+                }
+            }
+        }
+        boundaries.sort_unstable();
+        boundaries.dedup();
+        Some(boundaries)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn push_u32(v: &mut Vec<u8>, x: u32) {
+        v.extend_from_slice(&x.to_le_bytes());
+    }
+
+    fn push_u64(v: &mut Vec<u8>, x: u64) {
+        v.extend_from_slice(&x.to_le_bytes());
+    }
+
+    fn push_cstr(v: &mut Vec<u8>, s: &str) {
+        v.extend_from_slice(s.as_bytes());
+        v.push(0);
+    }
+
+    /// Builds a Go 1.20 pclntab with two functions at 0x401000 and 0x401020,
+    /// with the text ending at 0x401030.
+    fn go120_pclntab() -> Vec<u8> {
+        let mut funcname_tab = Vec::new();
+        push_cstr(&mut funcname_tab, "main.main");
+        push_cstr(&mut funcname_tab, "main.helper");
+
+        let mut file_tab = Vec::new();
+        push_cstr(&mut file_tab, "/src/main.go");
+        push_cstr(&mut file_tab, "/src/helper.go");
+
+        let mut cu_tab = Vec::new();
+        push_u32(&mut cu_tab, 0); // file 0 of cu 0: /src/main.go
+        push_u32(&mut cu_tab, 13); // file 1 of cu 0: /src/helper.go
+
+        // The pc tables. Offset 0 means "no table", so start with a padding byte.
+        // Each entry is (zig-zag value delta, pc delta / quantum), ending with 0.
+        let pc_tab = vec![
+            0, // padding
+            // main.main pcfile: file 0 for 0x20 bytes
+            0x02, 0x20, 0x00, //
+            // main.main pcln: line 10 for 0x10 bytes, line 12 for 0x10 bytes
+            0x16, 0x10, 0x04, 0x10, 0x00, //
+            // main.helper pcfile: file 1 for 0x10 bytes
+            0x04, 0x10, 0x00, //
+            // main.helper pcln: line 5 for 0x10 bytes
+            0x0c, 0x10, 0x00,
+        ];
+
+        let func = |entry_offset: u32, name_offset: u32, pcfile: u32, pcln: u32| {
+            let mut v = Vec::new();
+            push_u32(&mut v, entry_offset);
+            push_u32(&mut v, name_offset);
+            push_u32(&mut v, 0); // args
+            push_u32(&mut v, 0); // deferreturn
+            push_u32(&mut v, 0); // pcsp
+            push_u32(&mut v, pcfile);
+            push_u32(&mut v, pcln);
+            push_u32(&mut v, 0); // npcdata
+            push_u32(&mut v, 0); // cuOffset
+            push_u32(&mut v, 0); // startLine
+            push_u32(&mut v, 0); // funcID, flag, pad, nfuncdata
+            v
+        };
+        let functab_size = 3 * 8;
+        let func0 = func(0x0, 0, 1, 4);
+        let func1 = func(0x20, 10, 9, 12);
+        let mut func_tab = Vec::new();
+        push_u32(&mut func_tab, 0x0);
+        push_u32(&mut func_tab, functab_size);
+        push_u32(&mut func_tab, 0x20);
+        push_u32(&mut func_tab, functab_size + func0.len() as u32);
+        push_u32(&mut func_tab, 0x30);
+        push_u32(&mut func_tab, 0);
+        func_tab.extend_from_slice(&func0);
+        func_tab.extend_from_slice(&func1);
+
+        let mut v = Vec::new();
+        push_u32(&mut v, GO120_MAGIC);
+        v.extend_from_slice(&[0, 0, 1, 8]);
+        let header_size = 8 + 8 * 8;
+        let mut offset = header_size as u64;
+        push_u64(&mut v, 2); // nfunc
+        push_u64(&mut v, 2); // nfiles
+        push_u64(&mut v, 0x401000); // textStart
+        for table in [&funcname_tab, &cu_tab, &file_tab, &pc_tab, &func_tab] {
+            push_u64(&mut v, offset);
+            offset += table.len() as u64;
+        }
+        for table in [&funcname_tab, &cu_tab, &file_tab, &pc_tab, &func_tab] {
+            v.extend_from_slice(table);
+        }
+        v
+    }
+
+    #[test]
+    fn lookup_go120() {
+        let data = go120_pclntab();
+        let pclntab = GoPclntab::parse(&data, 0).unwrap();
+        assert_eq!(pclntab.version, GoPclntabVersion::Go120);
+        assert_eq!(pclntab.function_count, 2);
+
+        let debug_id = DebugId::nil();
+        let symbol_map_data = GoPclntabSymbolMapData::new(pclntab, 0x400000, Vec::new(), debug_id);
+        let symbol_map = symbol_map_data.make_symbol_map_inner().unwrap().0;
+        assert_eq!(symbol_map.symbol_count(), 2);
+        let symbols: Vec<_> = symbol_map.iter_symbols().collect();
+        assert_eq!(
+            symbols,
+            vec![
+                (0x1000, Cow::Borrowed("main.main")),
+                (0x1020, Cow::Borrowed("main.helper"))
+            ]
+        );
+
+        let frame = |address: u64| match symbol_map.lookup_relative_address(address) {
+            Some(AddressInfo {
+                frames: FramesLookupResult::Available(mut frames),
+                symbol,
+            }) => {
+                let frame = frames.pop().unwrap();
+                Some((
+                    symbol.name,
+                    frame.file_path.unwrap().raw_path().to_string(),
+                    frame.line_number.unwrap(),
+                ))
+            }
+            _ => None,
+        };
+        assert_eq!(
+            frame(0x1004),
+            Some(("main.main".into(), "/src/main.go".into(), 10))
+        );
+        assert_eq!(
+            frame(0x1018),
+            Some(("main.main".into(), "/src/main.go".into(), 12))
+        );
+        assert_eq!(
+            frame(0x102f),
+            Some(("main.helper".into(), "/src/helper.go".into(), 5))
+        );
+        assert!(symbol_map.lookup_relative_address(0x1030).is_none());
+        assert!(symbol_map.lookup_svma(0x401004).is_some());
+
+        assert_eq!(
+            symbol_map.debug_info_boundaries(0x1000, 0x1030),
+            Some(vec![0x1000, 0x1010, 0x1020])
+        );
+    }
+
+    #[test]
+    fn lookup_go12() {
+        // A 32-bit Go 1.2 pclntab with a single function at 0x1000..0x1010.
+        // All offsets are relative to the start of the table.
+        let mut v = Vec::new();
+        push_u32(&mut v, GO12_MAGIC);
+        v.extend_from_slice(&[0, 0, 1, 4]);
+        push_u32(&mut v, 1); // nfunc
+        push_u32(&mut v, 0x1000); // functab[0].entry
+        push_u32(&mut v, 32); // functab[0].funcoff
+        push_u32(&mut v, 0x1010); // end pc
+        push_u32(&mut v, 60); // filetab offset
+        assert_eq!(v.len(), 28);
+        v.extend_from_slice(&[0; 4]);
+        push_u32(&mut v, 0x1000); // entry
+        push_u32(&mut v, 72); // nameoff
+        push_u32(&mut v, 0); // args
+        push_u32(&mut v, 0); // frame
+        push_u32(&mut v, 0); // pcsp
+        push_u32(&mut v, 80); // pcfile
+        push_u32(&mut v, 83); // pcln
+        assert_eq!(v.len(), 60);
+        push_u32(&mut v, 2); // file count
+        push_u32(&mut v, 86); // file 1
+        push_u32(&mut v, 0); // padding
+        push_cstr(&mut v, "main.f");
+        v.push(0); // padding
+        v.extend_from_slice(&[0x04, 0x10, 0x00]); // pcfile: file 1
+        v.extend_from_slice(&[0x2a, 0x10, 0x00]); // pcln: line 20
+        push_cstr(&mut v, "/src/f.go");
+
+        let pclntab = GoPclntab::parse(&v, 0).unwrap();
+        assert_eq!(pclntab.version, GoPclntabVersion::Go12);
+        let symbol_map_data = GoPclntabSymbolMapData::new(pclntab, 0, Vec::new(), DebugId::nil());
+        let symbol_map = symbol_map_data.make_symbol_map_inner().unwrap().0;
+        let info = symbol_map.lookup_relative_address(0x1008).unwrap();
+        assert_eq!(info.symbol.name, "main.f");
+        assert_eq!(info.symbol.size, Some(0x10));
+        match info.frames {
+            FramesLookupResult::Available(frames) => {
+                assert_eq!(
+                    frames[0].file_path.as_ref().unwrap().raw_path(),
+                    "/src/f.go"
+                );
+                assert_eq!(frames[0].line_number, Some(20));
+            }
+            _ => panic!("no frames"),
+        }
+    }
+
+    #[test]
+    fn pc_value_table() {
+        let mut iter = PcValueIter {
+            data: &[0x16, 0x10, 0x03, 0x08, 0x00],
+            quantum: 4,
+            pc: 0x1000,
+            value: -1,
+            is_first: true,
+        };
+        assert_eq!(iter.next(), Some((0x1000, 0x1040, 10)));
+        assert_eq!(iter.next(), Some((0x1040, 0x1060, 8)));
+        assert_eq!(iter.next(), None);
+    }
+}
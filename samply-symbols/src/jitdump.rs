@@ -0,0 +1,673 @@
+//! Support for the `jit-<pid>.dump` files written by JIT compilers for `perf`.
+//!
+//! The format is described in `tools/perf/Documentation/jitdump-specification.txt`
+//! in the Linux kernel tree. A jitdump file consists of a header, followed by a
+//! list of records. Each record has a type, a size and a timestamp.
+//!
+//! JIT runtimes usually `mmap` the jitdump file so that the mapping shows up in the
+//! `perf` event stream, which lets the profiler find the file. The records are
+//! appended to the file while the process runs.
+//!
+//! For symbolication, the jitdump file itself acts as the "binary": the code bytes
+//! of each `JIT_CODE_LOAD` record are stored inside the file, and the "relative
+//! address" of an instruction is the file offset of that instruction's byte in
+//! the jitdump file. This gives every instruction of every JIT function a unique
+//! relative address within one library, even if code addresses are reused.
+
+use std::borrow::Cow;
+use std::convert::{TryFrom, TryInto};
+
+use debugid::DebugId;
+
+use crate::{
+    symbol_map::SymbolMapTrait, AddressInfo, Error, FileContents, FileContentsWrapper,
+    FileLocation, FrameDebugInfo, FramesLookupResult, SourceFilePath, SymbolInfo, SymbolMap,
+};
+
+const JITDUMP_MAGIC: u32 = 0x4A69_5444; // "JiTD"
+const JITDUMP_MAGIC_SWAPPED: u32 = 0x4454_694A;
+
+const JIT_CODE_LOAD: u32 = 0;
+const JIT_CODE_MOVE: u32 = 1;
+const JIT_CODE_DEBUG_INFO: u32 = 2;
+const JIT_CODE_CLOSE: u32 = 3;
+const JIT_CODE_UNWINDING_INFO: u32 = 4;
+
+const RECORD_HEADER_SIZE: usize = 16;
+
+/// Set in the header flags if the record timestamps come from an
+/// architecture-specific clock, e.g. the x86 TSC, instead of CLOCK_MONOTONIC.
+const JITDUMP_FLAGS_ARCH_TIMESTAMP: u64 = 1;
+
+/// An error which occurred while parsing a jitdump file.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum JitDumpParseError {
+    #[error("The file does not start with the jitdump magic bytes")]
+    BadMagic,
+
+    #[error("The jitdump header is truncated")]
+    TruncatedHeader,
+
+    #[error("The jitdump record at offset {0} has an invalid size")]
+    InvalidRecordSize(u64),
+
+    #[error("The jitdump record at offset {0} is malformed")]
+    MalformedRecord(u64),
+}
+
+/// Returns whether the file starts with the jitdump magic bytes, in either byte order.
+pub fn is_jitdump_file<T: FileContents>(file_contents: &FileContentsWrapper<T>) -> bool {
+    match file_contents.read_bytes_at(0, 4) {
+        Ok(bytes) => JitDumpHeader::endianness_from_magic(bytes).is_some(),
+        Err(_) => false,
+    }
+}
+
+/// The header at the start of a jitdump file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JitDumpHeader {
+    /// Whether the file was written in big-endian byte order.
+    pub big_endian: bool,
+    /// The format version.
+    pub version: u32,
+    /// The size of the header in bytes. Records start at this offset.
+    pub total_size: u32,
+    /// The ELF `e_machine` value of the architecture the JIT code was generated for.
+    pub elf_machine_arch: u32,
+    /// The pid of the process which wrote the file.
+    pub pid: u32,
+    /// The time at which the file was created.
+    pub timestamp: u64,
+    /// Format flags, e.g. `JITDUMP_FLAGS_ARCH_TIMESTAMP`.
+    pub flags: u64,
+}
+
+impl JitDumpHeader {
+    /// The size of the fixed part of the header, in bytes.
+    pub const SIZE: usize = 40;
+
+    fn endianness_from_magic(bytes: &[u8]) -> Option<bool> {
+        let magic = u32::from_le_bytes(bytes.get(..4)?.try_into().ok()?);
+        match magic {
+            JITDUMP_MAGIC => Some(false),
+            JITDUMP_MAGIC_SWAPPED => Some(true),
+            _ => None,
+        }
+    }
+
+    /// Parse the header from the start of the file.
+    pub fn parse(data: &[u8]) -> Result<Self, JitDumpParseError> {
+        let big_endian = Self::endianness_from_magic(data).ok_or(JitDumpParseError::BadMagic)?;
+        if data.len() < Self::SIZE {
+            return Err(JitDumpParseError::TruncatedHeader);
+        }
+        let r = Reader::new(data, big_endian);
+        let header = Self {
+            big_endian,
+            version: r.u32(4).ok_or(JitDumpParseError::TruncatedHeader)?,
+            total_size: r.u32(8).ok_or(JitDumpParseError::TruncatedHeader)?,
+            elf_machine_arch: r.u32(12).ok_or(JitDumpParseError::TruncatedHeader)?,
+            pid: r.u32(20).ok_or(JitDumpParseError::TruncatedHeader)?,
+            timestamp: r.u64(24).ok_or(JitDumpParseError::TruncatedHeader)?,
+            flags: r.u64(32).ok_or(JitDumpParseError::TruncatedHeader)?,
+        };
+        if (header.total_size as usize) < Self::SIZE {
+            return Err(JitDumpParseError::TruncatedHeader);
+        }
+        Ok(header)
+    }
+
+    /// Whether the record timestamps come from an architecture-specific clock,
+    /// e.g. the x86 TSC, rather than from CLOCK_MONOTONIC.
+    pub fn uses_arch_timestamps(&self) -> bool {
+        self.flags & JITDUMP_FLAGS_ARCH_TIMESTAMP != 0
+    }
+
+    /// A debug ID which identifies this jitdump file.
+    ///
+    /// jitdump files don't have an ID of their own, so we derive one from
+    /// the creation timestamp, the pid and the architecture.
+    pub fn debug_id(&self) -> DebugId {
+        let mut bytes = [0; 16];
+        bytes[0..8].copy_from_slice(&self.timestamp.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.pid.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.elf_machine_arch.to_le_bytes());
+        DebugId::from_guid_age(&bytes, 0).unwrap()
+    }
+
+    /// Iterate over the records in `data`, which needs to contain the file
+    /// contents starting from the beginning of the file. Iteration starts at
+    /// `offset`, which needs to be the start of a record, or zero to start
+    /// with the first record.
+    ///
+    /// The iterator stops at the first incomplete record, so that the rest
+    /// can be read once more data has been appended to the file.
+    pub fn records<'a>(&self, data: &'a [u8], offset: u64) -> JitDumpRecordIter<'a> {
+        JitDumpRecordIter {
+            data,
+            offset: offset.max(u64::from(self.total_size)),
+            big_endian: self.big_endian,
+        }
+    }
+}
+
+/// One record in a jitdump file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JitDumpRecord<'a> {
+    CodeLoad(JitCodeLoadRecord<'a>),
+    CodeMove(JitCodeMoveRecord),
+    CodeDebugInfo(JitCodeDebugInfoRecord<'a>),
+    CodeClose,
+    CodeUnwindingInfo(JitCodeUnwindingInfoRecord<'a>),
+    Other(u32),
+}
+
+/// A `JIT_CODE_LOAD` record, describing a newly-compiled function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JitCodeLoadRecord<'a> {
+    pub pid: u32,
+    pub tid: u32,
+    pub vma: u64,
+    pub code_addr: u64,
+    pub code_index: u64,
+    pub function_name: &'a [u8],
+    pub code_bytes: &'a [u8],
+    /// The offset of the code bytes in the jitdump file.
+    pub code_bytes_offset: u64,
+}
+
+/// A `JIT_CODE_MOVE` record, describing a function whose code was moved to a
+/// different address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JitCodeMoveRecord {
+    pub pid: u32,
+    pub tid: u32,
+    pub vma: u64,
+    pub old_code_addr: u64,
+    pub new_code_addr: u64,
+    pub code_size: u64,
+    pub code_index: u64,
+}
+
+/// A `JIT_CODE_DEBUG_INFO` record. It precedes the `JIT_CODE_LOAD` record for the
+/// function with the same `code_addr`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JitCodeDebugInfoRecord<'a> {
+    pub code_addr: u64,
+    pub entries: Vec<JitCodeDebugInfoEntry<'a>>,
+}
+
+/// A single line table entry of a [`JitCodeDebugInfoRecord`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JitCodeDebugInfoEntry<'a> {
+    /// The code address at which this line entry starts.
+    pub code_addr: u64,
+    pub line: u32,
+    pub column: u32,
+    pub file_path: &'a [u8],
+}
+
+/// A `JIT_CODE_UNWINDING_INFO` record. It applies to the next `JIT_CODE_LOAD` record.
+///
+/// `unwind_data` contains the `.eh_frame` data, followed by the `.eh_frame_hdr` data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JitCodeUnwindingInfoRecord<'a> {
+    pub eh_frame_hdr_size: u64,
+    pub mapped_size: u64,
+    pub unwind_data: &'a [u8],
+}
+
+impl<'a> JitCodeUnwindingInfoRecord<'a> {
+    pub fn eh_frame(&self) -> &'a [u8] {
+        let split = self.unwind_data.len() - self.eh_frame_hdr_len();
+        &self.unwind_data[..split]
+    }
+
+    pub fn eh_frame_hdr(&self) -> &'a [u8] {
+        let split = self.unwind_data.len() - self.eh_frame_hdr_len();
+        &self.unwind_data[split..]
+    }
+
+    fn eh_frame_hdr_len(&self) -> usize {
+        usize::try_from(self.eh_frame_hdr_size)
+            .unwrap_or(usize::MAX)
+            .min(self.unwind_data.len())
+    }
+}
+
+/// Iterates over the records of a jitdump file. Created by [`JitDumpHeader::records`].
+pub struct JitDumpRecordIter<'a> {
+    data: &'a [u8],
+    offset: u64,
+    big_endian: bool,
+}
+
+impl<'a> JitDumpRecordIter<'a> {
+    /// The file offset of the next record, i.e. the offset after the last
+    /// complete record that was returned.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    fn parse_record(
+        &self,
+        record_offset: u64,
+        record: &'a [u8],
+    ) -> Result<JitDumpRecord<'a>, JitDumpParseError> {
+        let r = Reader::new(record, self.big_endian);
+        let malformed = JitDumpParseError::MalformedRecord(record_offset);
+        let id = r.u32(0).ok_or_else(|| malformed.clone())?;
+        let payload_offset = RECORD_HEADER_SIZE;
+        let record = match id {
+            JIT_CODE_LOAD => {
+                let p = payload_offset;
+                let code_size = r.u64(p + 24).ok_or_else(|| malformed.clone())?;
+                let name_start = p + 40;
+                let name_len = memchr::memchr(0, record.get(name_start..).unwrap_or(&[]))
+                    .ok_or_else(|| malformed.clone())?;
+                let code_start = name_start + name_len + 1;
+                let code_end = usize::try_from(code_size)
+                    .ok()
+                    .and_then(|size| code_start.checked_add(size))
+                    .filter(|end| *end <= record.len())
+                    .ok_or_else(|| malformed.clone())?;
+                JitDumpRecord::CodeLoad(JitCodeLoadRecord {
+                    pid: r.u32(p).ok_or_else(|| malformed.clone())?,
+                    tid: r.u32(p + 4).ok_or_else(|| malformed.clone())?,
+                    vma: r.u64(p + 8).ok_or_else(|| malformed.clone())?,
+                    code_addr: r.u64(p + 16).ok_or_else(|| malformed.clone())?,
+                    code_index: r.u64(p + 32).ok_or_else(|| malformed.clone())?,
+                    function_name: &record[name_start..name_start + name_len],
+                    code_bytes: &record[code_start..code_end],
+                    code_bytes_offset: record_offset + code_start as u64,
+                })
+            }
+            JIT_CODE_MOVE => {
+                let p = payload_offset;
+                JitDumpRecord::CodeMove(JitCodeMoveRecord {
+                    pid: r.u32(p).ok_or_else(|| malformed.clone())?,
+                    tid: r.u32(p + 4).ok_or_else(|| malformed.clone())?,
+                    vma: r.u64(p + 8).ok_or_else(|| malformed.clone())?,
+                    old_code_addr: r.u64(p + 16).ok_or_else(|| malformed.clone())?,
+                    new_code_addr: r.u64(p + 24).ok_or_else(|| malformed.clone())?,
+                    code_size: r.u64(p + 32).ok_or_else(|| malformed.clone())?,
+                    code_index: r.u64(p + 40).ok_or_else(|| malformed.clone())?,
+                })
+            }
+            JIT_CODE_DEBUG_INFO => {
+                let p = payload_offset;
+                let code_addr = r.u64(p).ok_or_else(|| malformed.clone())?;
+                let entry_count = r.u64(p + 8).ok_or_else(|| malformed.clone())?;
+                let mut entries = Vec::new();
+                let mut pos = p + 16;
+                let mut prev_file_path: &[u8] = &[];
+                for _ in 0..entry_count {
+                    let entry_code_addr = r.u64(pos).ok_or_else(|| malformed.clone())?;
+                    let line = r.u32(pos + 8).ok_or_else(|| malformed.clone())?;
+                    let column = r.u32(pos + 12).ok_or_else(|| malformed.clone())?;
+                    let name_start = pos + 16;
+                    let name_len = memchr::memchr(0, record.get(name_start..).unwrap_or(&[]))
+                        .ok_or_else(|| malformed.clone())?;
+                    let mut file_path = &record[name_start..name_start + name_len];
+                    // A file name of "\xff" means "same file as the previous entry".
+                    if file_path == b"\xff" {
+                        file_path = prev_file_path;
+                    }
+                    prev_file_path = file_path;
+                    entries.push(JitCodeDebugInfoEntry {
+                        code_addr: entry_code_addr,
+                        line,
+                        column,
+                        file_path,
+                    });
+                    pos = name_start + name_len + 1;
+                }
+                JitDumpRecord::CodeDebugInfo(JitCodeDebugInfoRecord { code_addr, entries })
+            }
+            JIT_CODE_CLOSE => JitDumpRecord::CodeClose,
+            JIT_CODE_UNWINDING_INFO => {
+                let p = payload_offset;
+                let unwind_data_size = r.u64(p).ok_or_else(|| malformed.clone())?;
+                let eh_frame_hdr_size = r.u64(p + 8).ok_or_else(|| malformed.clone())?;
+                let mapped_size = r.u64(p + 16).ok_or_else(|| malformed.clone())?;
+                let data_start = p + 24;
+                let data_end = usize::try_from(unwind_data_size)
+                    .ok()
+                    .and_then(|size| data_start.checked_add(size))
+                    .filter(|end| *end <= record.len())
+                    .ok_or_else(|| malformed.clone())?;
+                JitDumpRecord::CodeUnwindingInfo(JitCodeUnwindingInfoRecord {
+                    eh_frame_hdr_size,
+                    mapped_size,
+                    unwind_data: &record[data_start..data_end],
+                })
+            }
+            other => JitDumpRecord::Other(other),
+        };
+        Ok(record)
+    }
+}
+
+impl<'a> Iterator for JitDumpRecordIter<'a> {
+    /// The record, together with its timestamp.
+    type Item = Result<(u64, JitDumpRecord<'a>), JitDumpParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let record_offset = self.offset;
+        let start = usize::try_from(record_offset).ok()?;
+        let remaining = self.data.get(start..)?;
+        let r = Reader::new(remaining, self.big_endian);
+        let total_size = r.u32(4)? as usize;
+        let timestamp = r.u64(8)?;
+        if total_size < RECORD_HEADER_SIZE {
+            // Make sure we don't return the same error over and over.
+            self.offset = self.data.len() as u64;
+            return Some(Err(JitDumpParseError::InvalidRecordSize(record_offset)));
+        }
+        let record = remaining.get(..total_size)?;
+        self.offset += total_size as u64;
+        Some(
+            self.parse_record(record_offset, record)
+                .map(|record| (timestamp, record)),
+        )
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8], big_endian: bool) -> Self {
+        Self { data, big_endian }
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        let bytes = self
+            .data
+            .get(offset..offset.checked_add(4)?)?
+            .try_into()
+            .ok()?;
+        Some(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    fn u64(&self, offset: usize) -> Option<u64> {
+        let bytes = self
+            .data
+            .get(offset..offset.checked_add(8)?)?
+            .try_into()
+            .ok()?;
+        Some(if self.big_endian {
+            u64::from_be_bytes(bytes)
+        } else {
+            u64::from_le_bytes(bytes)
+        })
+    }
+}
+
+pub fn get_symbol_map_for_jitdump<F, FL>(
+    file_contents: FileContentsWrapper<F>,
+    file_location: FL,
+) -> Result<SymbolMap<FL>, Error>
+where
+    F: FileContents + 'static,
+    FL: FileLocation,
+{
+    let data = file_contents
+        .read_entire_data()
+        .map_err(|e| Error::HelperErrorDuringFileReading(file_location.to_string(), e))?;
+    let symbol_map = JitDumpSymbolMap::new(data)?;
+    Ok(SymbolMap::new(file_location, Box::new(symbol_map)))
+}
+
+#[derive(Debug, Clone)]
+struct JitDumpFunction {
+    /// The file offset of the code bytes, which is the relative address of the function.
+    relative_address: u64,
+    size: u64,
+    code_addr: u64,
+    name: String,
+    /// Line entries, sorted by code address.
+    lines: Vec<(u64, u32, String)>,
+}
+
+struct JitDumpSymbolMap {
+    debug_id: DebugId,
+    /// Sorted by relative address.
+    functions: Vec<JitDumpFunction>,
+}
+
+impl JitDumpSymbolMap {
+    fn new(data: &[u8]) -> Result<Self, Error> {
+        let header = JitDumpHeader::parse(data)?;
+        let mut functions = Vec::new();
+        let mut pending_debug_info: Option<JitCodeDebugInfoRecord> = None;
+        for record in header.records(data, 0) {
+            match record?.1 {
+                JitDumpRecord::CodeDebugInfo(debug_info) => {
+                    pending_debug_info = Some(debug_info);
+                }
+                JitDumpRecord::CodeLoad(load) => {
+                    let relative_address = load.code_bytes_offset;
+                    let size = load.code_bytes.len() as u64;
+                    let mut lines = match pending_debug_info.take() {
+                        Some(debug_info) if debug_info.code_addr == load.code_addr => debug_info
+                            .entries
+                            .into_iter()
+                            .map(|entry| {
+                                let file_path = String::from_utf8_lossy(entry.file_path);
+                                (entry.code_addr, entry.line, file_path.into_owned())
+                            })
+                            .collect(),
+                        _ => Vec::new(),
+                    };
+                    lines.sort_by_key(|(code_addr, _, _)| *code_addr);
+                    functions.push(JitDumpFunction {
+                        relative_address,
+                        size,
+                        code_addr: load.code_addr,
+                        name: String::from_utf8_lossy(load.function_name).into_owned(),
+                        lines,
+                    });
+                }
+                _ => {}
+            }
+        }
+        functions.sort_by_key(|f| f.relative_address);
+        Ok(Self {
+            debug_id: header.debug_id(),
+            functions,
+        })
+    }
+}
+
+impl SymbolMapTrait for JitDumpSymbolMap {
+    fn debug_id(&self) -> DebugId {
+        self.debug_id
+    }
+
+    fn symbol_count(&self) -> usize {
+        self.functions.len()
+    }
+
+    fn iter_symbols(&self) -> Box<dyn Iterator<Item = (u64, Cow<'_, str>)> + '_> {
+        Box::new(
+            self.functions
+                .iter()
+                .map(|f| (f.relative_address, Cow::Borrowed(f.name.as_str()))),
+        )
+    }
+
+    fn lookup_relative_address(&self, address: u64) -> Option<AddressInfo> {
+        let index = match self
+            .functions
+            .binary_search_by_key(&address, |f| f.relative_address)
+        {
+            Ok(i) => i,
+            Err(0) => return None,
+            Err(i) => i - 1,
+        };
+        let function = &self.functions[index];
+        let offset_in_function = address - function.relative_address;
+        if offset_in_function >= function.size {
+            return None;
+        }
+        let code_addr = function.code_addr + offset_in_function;
+        let line_index = match function
+            .lines
+            .binary_search_by_key(&code_addr, |(addr, _, _)| *addr)
+        {
+            Ok(i) => Some(i),
+            Err(0) => None,
+            Err(i) => Some(i - 1),
+        };
+        let frames = match line_index {
+            Some(i) => {
+                let (_, line, file_path) = &function.lines[i];
+                FramesLookupResult::Available(vec![FrameDebugInfo {
+                    function: Some(function.name.clone()),
+                    file_path: Some(SourceFilePath::new(file_path.clone(), None)),
+                    line_number: Some(*line),
+                }])
+            }
+            None => FramesLookupResult::Unavailable,
+        };
+        Some(AddressInfo {
+            symbol: SymbolInfo {
+                address: function.relative_address,
+                size: Some(function.size),
+                name: function.name.clone(),
+            },
+            frames,
+        })
+    }
+
+    fn lookup_svma(&self, _svma: u64) -> Option<AddressInfo> {
+        // jitdump files don't have stated virtual memory addresses.
+        None
+    }
+
+    fn lookup_offset(&self, offset: u64) -> Option<AddressInfo> {
+        self.lookup_relative_address(offset)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn push_u32(v: &mut Vec<u8>, x: u32) {
+        v.extend_from_slice(&x.to_le_bytes());
+    }
+
+    fn push_u64(v: &mut Vec<u8>, x: u64) {
+        v.extend_from_slice(&x.to_le_bytes());
+    }
+
+    fn header() -> Vec<u8> {
+        let mut v = Vec::new();
+        push_u32(&mut v, JITDUMP_MAGIC);
+        push_u32(&mut v, 1); // version
+        push_u32(&mut v, JitDumpHeader::SIZE as u32);
+        push_u32(&mut v, 62); // EM_X86_64
+        push_u32(&mut v, 0); // pad
+        push_u32(&mut v, 1234); // pid
+        push_u64(&mut v, 5000); // timestamp
+        push_u64(&mut v, 0); // flags
+        v
+    }
+
+    fn record(v: &mut Vec<u8>, id: u32, timestamp: u64, payload: &[u8]) {
+        push_u32(v, id);
+        push_u32(v, (RECORD_HEADER_SIZE + payload.len()) as u32);
+        push_u64(v, timestamp);
+        v.extend_from_slice(payload);
+    }
+
+    fn test_file() -> Vec<u8> {
+        let mut v = header();
+
+        let mut debug_info = Vec::new();
+        push_u64(&mut debug_info, 0x1000);
+        push_u64(&mut debug_info, 2);
+        push_u64(&mut debug_info, 0x1000);
+        push_u32(&mut debug_info, 10);
+        push_u32(&mut debug_info, 0);
+        debug_info.extend_from_slice(b"file.js\0");
+        push_u64(&mut debug_info, 0x1004);
+        push_u32(&mut debug_info, 12);
+        push_u32(&mut debug_info, 0);
+        debug_info.extend_from_slice(b"\xff\0");
+        record(&mut v, JIT_CODE_DEBUG_INFO, 6000, &debug_info);
+
+        let mut load = Vec::new();
+        push_u32(&mut load, 1234);
+        push_u32(&mut load, 1235);
+        push_u64(&mut load, 0x1000);
+        push_u64(&mut load, 0x1000);
+        push_u64(&mut load, 8);
+        push_u64(&mut load, 1);
+        load.extend_from_slice(b"JS:*foo\0");
+        load.extend_from_slice(&[0x90; 8]);
+        record(&mut v, JIT_CODE_LOAD, 6001, &load);
+        v
+    }
+
+    #[test]
+    fn parse_records() {
+        let data = test_file();
+        let header = JitDumpHeader::parse(&data).unwrap();
+        assert_eq!(header.pid, 1234);
+        assert!(!header.uses_arch_timestamps());
+        let records: Vec<_> = header.records(&data, 0).collect::<Result<_, _>>().unwrap();
+        assert_eq!(records.len(), 2);
+        match &records[0] {
+            (6000, JitDumpRecord::CodeDebugInfo(info)) => {
+                assert_eq!(info.entries.len(), 2);
+                assert_eq!(info.entries[1].file_path, b"file.js");
+            }
+            other => panic!("unexpected record {:?}", other),
+        }
+        match &records[1] {
+            (6001, JitDumpRecord::CodeLoad(load)) => {
+                assert_eq!(load.function_name, b"JS:*foo");
+                assert_eq!(load.code_bytes, &[0x90; 8]);
+                assert_eq!(load.code_bytes_offset, data.len() as u64 - 8);
+            }
+            other => panic!("unexpected record {:?}", other),
+        }
+
+        // A truncated file stops before the incomplete record.
+        let mut iter = header.records(&data[..data.len() - 1], 0);
+        assert!(matches!(iter.next(), Some(Ok(_))));
+        let offset = iter.offset();
+        assert!(iter.next().is_none());
+        assert_eq!(iter.offset(), offset);
+    }
+
+    #[test]
+    fn symbol_map_lookup() {
+        let data = test_file();
+        let symbol_map = JitDumpSymbolMap::new(&data).unwrap();
+        let code_offset = data.len() as u64 - 8;
+        let info = symbol_map.lookup_relative_address(code_offset + 5).unwrap();
+        assert_eq!(info.symbol.name, "JS:*foo");
+        assert_eq!(info.symbol.address, code_offset);
+        match info.frames {
+            FramesLookupResult::Available(frames) => {
+                assert_eq!(frames[0].line_number, Some(12));
+                assert_eq!(frames[0].file_path.as_ref().unwrap().raw_path(), "file.js");
+            }
+            other => panic!("unexpected frames {:?}", other),
+        }
+        assert!(symbol_map
+            .lookup_relative_address(code_offset + 8)
+            .is_none());
+    }
+}
@@ -55,6 +55,10 @@
 //! based on debug data, i.e. inline callstacks where each frame has a function name, a file name,
 //! and a line number.
 //! For debug data we support both DWARF debug data (inside mach-o and ELF binaries) and PDB debug data.
+//! WebAssembly modules are supported too, with function names from the `name` section and
+//! DWARF debug data from custom sections.
+//! Go binaries without DWARF are symbolicated from their `pclntab`, which has function names,
+//! file names and line numbers.
 //!
 //! # Example
 //!
@@ -220,6 +224,7 @@
 //! }
 //! ```
 
+use std::collections::BTreeSet;
 use std::sync::Mutex;
 
 use binary_image::BinaryImageInner;
@@ -237,27 +242,37 @@ mod compact_symbol_table;
 mod debugid_util;
 mod demangle;
 mod demangle_ocaml;
+mod demangle_swift;
 mod dwarf;
 mod elf;
 mod error;
 mod external_file;
+mod gopclntab;
+mod jitdump;
 mod macho;
 mod mapped_path;
 mod path_mapper;
 mod shared;
+mod split_dwarf;
 mod symbol_map;
 mod symbol_map_object;
+mod wasm;
 mod windows;
 
 pub use crate::binary_image::BinaryImage;
 pub use crate::breakpad::{
-    BreakpadIndex, BreakpadIndexParser, BreakpadParseError, BreakpadSymindexParseError,
+    write_breakpad_sym_file, BreakpadIndex, BreakpadIndexParser, BreakpadParseError,
+    BreakpadSymindexParseError,
 };
 pub use crate::cache::{FileByteSource, FileContentsWithChunkedCaching};
 pub use crate::compact_symbol_table::CompactSymbolTable;
 pub use crate::debugid_util::{debug_id_for_object, DebugIdExt};
 pub use crate::error::Error;
 pub use crate::external_file::{load_external_file, ExternalFileSymbolMap};
+pub use crate::jitdump::{
+    JitCodeDebugInfoEntry, JitCodeDebugInfoRecord, JitCodeLoadRecord, JitCodeMoveRecord,
+    JitCodeUnwindingInfoRecord, JitDumpHeader, JitDumpParseError, JitDumpRecord, JitDumpRecordIter,
+};
 pub use crate::macho::FatArchiveMember;
 pub use crate::mapped_path::MappedPath;
 pub use crate::shared::{
@@ -268,6 +283,7 @@ pub use crate::shared::{
     OptionallySendFuture, PeCodeId, SourceFilePath, SymbolInfo,
 };
 pub use crate::symbol_map::SymbolMap;
+pub use crate::wasm::WasmParseError;
 
 pub struct SymbolManager<'h, H: FileAndPathHelper<'h>> {
     helper: &'h H,
@@ -421,6 +437,48 @@ where
         lookup_result
     }
 
+    /// Write the contents of `symbol_map` as a Breakpad .sym file, see
+    /// [`write_breakpad_sym_file`].
+    ///
+    /// Unlike that function, this also writes the debug info of functions which
+    /// is in external object files, see [`SymbolManager::lookup_external`]. These
+    /// files are loaded upfront.
+    pub async fn write_breakpad_sym_file(
+        &self,
+        w: &mut impl std::io::Write,
+        symbol_map: &SymbolMap<FL>,
+        info: &LibraryInfo,
+        os: &str,
+    ) -> std::io::Result<()> {
+        let external_file_refs: BTreeSet<ExternalFileRef> = symbol_map
+            .iter_symbols()
+            .filter_map(
+                |(address, _)| match symbol_map.lookup_relative_address(address)?.frames {
+                    FramesLookupResult::External(external_address) => {
+                        Some(external_address.file_ref)
+                    }
+                    _ => None,
+                },
+            )
+            .collect();
+        let mut external_files = breakpad::ExternalFiles::new();
+        for file_ref in external_file_refs {
+            if let Ok(external_file) = self
+                .load_external_file(symbol_map.debug_file_location(), &file_ref)
+                .await
+            {
+                external_files.insert(file_ref, external_file);
+            }
+        }
+        breakpad::write_breakpad_sym_file_with_external_files(
+            w,
+            symbol_map,
+            &external_files,
+            info,
+            os,
+        )
+    }
+
     async fn load_binary_from_dyld_cache(
         &self,
         dyld_cache_path: FL,
@@ -587,7 +645,11 @@ where
 
         let file_contents = FileContentsWrapper::new(file_contents);
 
-        if let Ok(file_kind) = FileKind::parse(&file_contents) {
+        // Check for wasm before calling FileKind::parse, so that wasm modules
+        // don't end up in the match below if object's "wasm" feature is enabled.
+        if wasm::is_wasm_file(&file_contents) {
+            wasm::get_symbol_map_for_wasm(file_contents, file_location)
+        } else if let Ok(file_kind) = FileKind::parse(&file_contents) {
             match file_kind {
                 FileKind::Elf32 | FileKind::Elf64 => {
                     elf::load_symbol_map_for_elf(
@@ -629,11 +691,13 @@ where
                     }
                 }
                 _ => Err(Error::InvalidInputError(
-                    "Input was Archive, Coff or Wasm format, which are unsupported for now",
+                    "Input was Archive or Coff format, which are unsupported for now",
                 )),
             }
         } else if windows::is_pdb_file(&file_contents) {
             windows::get_symbol_map_for_pdb(file_contents, file_location)
+        } else if jitdump::is_jitdump_file(&file_contents) {
+            jitdump::get_symbol_map_for_jitdump(file_contents, file_location)
         } else if breakpad::is_breakpad_file(&file_contents) {
             let index_file_contents =
                 if let Some(index_file_location) = file_location.location_for_breakpad_symindex() {
@@ -692,7 +756,7 @@ where
             }
             _ => {
                 return Err(Error::InvalidInputError(
-                    "Input was Archive, Coff or Wasm format, which are unsupported for now",
+                    "Input was Archive or Coff format, which are unsupported for now",
                 ))
             }
         };
@@ -1,6 +1,8 @@
 use crate::binary_image::BinaryImageInner;
 use crate::error::Error;
+use crate::gopclntab::go_pclntab_symbol_map_data;
 use crate::shared::{FileAndPathHelper, FileContents, FileContentsWrapper, RangeReadRef};
+use crate::split_dwarf::SplitDwarfObjects;
 use crate::symbol_map::{
     GenericSymbolMap, SymbolMap, SymbolMapDataMidTrait, SymbolMapDataOuterTrait,
 };
@@ -330,6 +332,7 @@ impl<T: FileContents + 'static> SymbolMapDataOuterTrait for DyldCacheFileData<T>
             function_addresses_computer,
             &self.root_file_data,
             None,
+            SplitDwarfObjects::default(),
             arch,
             debug_id,
         );
@@ -379,12 +382,16 @@ impl<T: FileContents + 'static> SymbolMapDataOuterTrait for MachSymbolMapData<T>
         let function_addresses_computer = MachOFunctionAddressesComputer { macho_data };
         let debug_id = debug_id_for_object(&macho_file)
             .ok_or(Error::InvalidInputError("debug ID cannot be read"))?;
+        if let Some(go_data) = go_pclntab_symbol_map_data(&macho_file, debug_id) {
+            return Ok(Box::new(go_data));
+        }
         let object = ObjectSymbolMapDataMid::new(
             macho_file,
             None,
             function_addresses_computer,
             &self.file_data,
             None,
+            SplitDwarfObjects::default(),
             arch,
             debug_id,
         );
@@ -425,12 +432,16 @@ impl<T: FileContents + 'static> SymbolMapDataOuterTrait for MachOFatArchiveMembe
         let function_addresses_computer = MachOFunctionAddressesComputer { macho_data };
         let debug_id = debug_id_for_object(&macho_file)
             .ok_or(Error::InvalidInputError("debug ID cannot be read"))?;
+        if let Some(go_data) = go_pclntab_symbol_map_data(&macho_file, debug_id) {
+            return Ok(Box::new(go_data));
+        }
         let object = ObjectSymbolMapDataMid::new(
             macho_file,
             None,
             function_addresses_computer,
             range_data,
             None,
+            SplitDwarfObjects::default(),
             arch,
             debug_id,
         );
@@ -468,7 +479,7 @@ impl<'data, R: ReadRef<'data>> FunctionAddressesComputer<'data>
     fn compute_function_addresses<'file, O>(
         &'file self,
         object_file: &'file O,
-    ) -> (Option<Vec<u32>>, Option<Vec<u32>>)
+    ) -> (Option<Vec<u64>>, Option<Vec<u64>>)
     where
         'data: 'file,
         O: object::Object<'data, 'file>,
@@ -485,7 +496,7 @@ impl<'data, R: ReadRef<'data>> FunctionAddressesComputer<'data>
             let function_starts = function_starts.get_or_insert_with(Vec::new);
             let mut iter = unwind_info.functions();
             while let Ok(Some(function)) = iter.next() {
-                function_starts.push(function.start_address);
+                function_starts.push(u64::from(function.start_address));
             }
         }
 
@@ -519,7 +530,7 @@ impl<'data, R: ReadRef<'data>> MachOData<'data, R> {
     /// functions with symbols end. This means that those symbols don't "overreach" to cover
     /// addresses after their function - instead, they get correctly terminated by a symbol-less
     /// function's start address.
-    pub fn get_function_starts(&self) -> Result<Option<Vec<u32>>, Error> {
+    pub fn get_function_starts(&self) -> Result<Option<Vec<u64>>, Error> {
         let data = self
             .function_start_data()
             .map_err(Error::MachOHeaderParseError)?;
@@ -537,7 +548,7 @@ impl<'data, R: ReadRef<'data>> MachOData<'data, R> {
             }
             bytes = rest;
             let address = prev_address + delta;
-            function_starts.push(address as u32);
+            function_starts.push(address);
             prev_address = address;
         }
 
@@ -335,6 +335,29 @@ pub trait FileAndPathHelper<'h> {
         Ok(Vec::new())
     }
 
+    /// Return a list of paths where the `.dwo` file of a skeleton unit in a binary
+    /// with split DWARF might be found. `dwo_path` is the `DW_AT_dwo_name` of the
+    /// skeleton unit, which is usually relative to `comp_dir`, the compilation
+    /// directory of the unit.
+    fn get_candidate_paths_for_dwo_file(
+        &self,
+        _original_file_path: &Self::FL,
+        _comp_dir: Option<&str>,
+        _dwo_path: &str,
+    ) -> FileAndPathHelperResult<Vec<Self::FL>> {
+        Ok(Vec::new())
+    }
+
+    /// Return a list of paths where the `.dwp` package of a binary with split
+    /// DWARF might be found. This is usually the path of the binary with a
+    /// `.dwp` extension appended.
+    fn get_candidate_paths_for_dwp_file(
+        &self,
+        _original_file_path: &Self::FL,
+    ) -> FileAndPathHelperResult<Vec<Self::FL>> {
+        Ok(Vec::new())
+    }
+
     /// This method is the entry point for file access during symbolication.
     /// The implementer needs to return an object which implements the `FileContents` trait.
     /// This method is asynchronous, but once it returns, the file data needs to be
@@ -633,9 +656,9 @@ pub fn relative_address_base<'data: 'file, 'file>(
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SymbolInfo {
     /// The function's address. This is a relative address.
-    pub address: u32,
+    pub address: u64,
     /// The function size, in bytes. May have been approximated from neighboring symbols.
-    pub size: Option<u32>,
+    pub size: Option<u64>,
     /// The function name, demangled.
     pub name: String,
 }
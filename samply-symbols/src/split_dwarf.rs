@@ -0,0 +1,342 @@
+//! Support for split DWARF, i.e. binaries compiled with `-gsplit-dwarf`.
+//!
+//! The compilation units in such a binary are "skeleton units" which only have
+//! the line tables and address ranges. The rest of the debug info, e.g. the
+//! function names and inlined calls, is in a `.dwo` file per compilation unit,
+//! or in a single `.dwp` package which combines all the `.dwo` files of a binary.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use addr2line::SplitDwarfLoad;
+use gimli::{DwoId, EndianSlice, Reader, RunTimeEndian};
+use object::{File, ReadRef};
+
+use crate::dwarf::{try_get_section_data, Addr2lineContextData, SingleSectionData};
+use crate::shared::{FileContents, FileContentsWrapper};
+use crate::FileAndPathHelper;
+
+/// The contents of the split DWARF files of a binary: either a `.dwp` package,
+/// or the `.dwo` files which were found for its skeleton units.
+pub struct SplitDwarfFileContents<T: FileContents> {
+    dwp: Option<FileContentsWrapper<T>>,
+    dwo_files: Vec<(DwoId, FileContentsWrapper<T>)>,
+}
+
+impl<T: FileContents> Default for SplitDwarfFileContents<T> {
+    fn default() -> Self {
+        Self {
+            dwp: None,
+            dwo_files: Vec::new(),
+        }
+    }
+}
+
+impl<T: FileContents> SplitDwarfFileContents<T> {
+    /// Parse the split DWARF files. Files which can't be parsed are skipped.
+    pub fn parse_objects(&self) -> SplitDwarfObjects<'_, &FileContentsWrapper<T>> {
+        SplitDwarfObjects {
+            dwp: self
+                .dwp
+                .as_ref()
+                .and_then(|data| Some((File::parse(data).ok()?, data))),
+            dwo_files: self
+                .dwo_files
+                .iter()
+                .filter_map(|(dwo_id, data)| Some((*dwo_id, File::parse(data).ok()?, data)))
+                .collect(),
+        }
+    }
+}
+
+/// The parsed split DWARF files of a binary.
+pub struct SplitDwarfObjects<'data, R: ReadRef<'data>> {
+    dwp: Option<(File<'data, R>, R)>,
+    dwo_files: Vec<(DwoId, File<'data, R>, R)>,
+}
+
+impl<'data, R: ReadRef<'data>> Default for SplitDwarfObjects<'data, R> {
+    fn default() -> Self {
+        Self {
+            dwp: None,
+            dwo_files: Vec::new(),
+        }
+    }
+}
+
+impl<'data, R: ReadRef<'data>> SplitDwarfObjects<'data, R> {
+    pub fn make_sections<'ctxdata, 'file>(
+        &'file self,
+        addr2line_context_data: &'ctxdata Addr2lineContextData,
+        endian: RunTimeEndian,
+    ) -> SplitDwarfSections<EndianSlice<'ctxdata, RunTimeEndian>>
+    where
+        'data: 'ctxdata,
+        'ctxdata: 'file,
+    {
+        let empty = EndianSlice::new(&[], endian);
+        let dwp = self.dwp.as_ref().and_then(|(obj, data)| {
+            gimli::DwarfPackage::load(
+                |s| match s.dwo_name() {
+                    Some(name) => Ok(addr2line_context_data.sect(*data, obj, name, endian)),
+                    None => Ok::<_, gimli::Error>(empty),
+                },
+                empty,
+            )
+            .ok()
+        });
+        let dwo_files = self
+            .dwo_files
+            .iter()
+            .filter_map(|(dwo_id, obj, data)| {
+                let dwarf = gimli::Dwarf::load(|s| match s.dwo_name() {
+                    Some(name) => Ok(addr2line_context_data.sect(*data, obj, name, endian)),
+                    None => Ok::<_, gimli::Error>(empty),
+                })
+                .ok()?;
+                Some((*dwo_id, dwarf))
+            })
+            .collect();
+        SplitDwarfSections {
+            dwp,
+            dwo_files: Mutex::new(dwo_files),
+        }
+    }
+}
+
+/// The DWARF sections of the split DWARF files, which are handed to addr2line
+/// when it needs the debug info of a skeleton unit.
+pub struct SplitDwarfSections<R: Reader> {
+    dwp: Option<gimli::DwarfPackage<R>>,
+    /// addr2line only requests each DWO once per context, so we can hand out
+    /// the sections of each file by value.
+    dwo_files: Mutex<HashMap<DwoId, gimli::Dwarf<R>>>,
+}
+
+impl<R: Reader> SplitDwarfSections<R> {
+    pub fn load(&self, load: SplitDwarfLoad<R>) -> Option<Arc<gimli::Dwarf<R>>> {
+        let dwo_file = self.dwo_files.lock().ok()?.remove(&load.dwo_id);
+        let mut dwarf = match dwo_file {
+            Some(dwarf) => dwarf,
+            None => self
+                .dwp
+                .as_ref()?
+                .find_cu(load.dwo_id, &load.parent)
+                .ok()??,
+        };
+        dwarf.make_dwo(&load.parent);
+        // addr2line resolves the file names of the skeleton unit's line table
+        // with the sections of the split unit. In DWARF 5 these names are in
+        // the parent's .debug_line_str, which split DWARF files don't have.
+        dwarf.debug_line_str = load.parent.debug_line_str.clone();
+        Some(Arc::new(dwarf))
+    }
+}
+
+/// A skeleton unit in a binary, which refers to a `.dwo` file.
+struct SkeletonUnit {
+    dwo_id: DwoId,
+    comp_dir: Option<String>,
+    dwo_name: String,
+}
+
+/// Find the skeleton units of the binary. Only the root entry of each unit is
+/// read. If the first unit is not a skeleton unit, the binary was not compiled
+/// with split DWARF and the remaining units are not looked at.
+fn find_skeleton_units<'data, R: ReadRef<'data>>(
+    data: R,
+    object: &File<'data, R>,
+) -> Vec<SkeletonUnit> {
+    use object::Object;
+
+    let endian = if object.is_little_endian() {
+        RunTimeEndian::Little
+    } else {
+        RunTimeEndian::Big
+    };
+    let debug_info = section_data(data, object, ".debug_info");
+    if debug_info.is_empty() {
+        return Vec::new();
+    }
+    let debug_abbrev = section_data(data, object, ".debug_abbrev");
+    let debug_str = section_data(data, object, ".debug_str");
+    let debug_str_offsets = section_data(data, object, ".debug_str_offsets");
+    let debug_line_str = section_data(data, object, ".debug_line_str");
+    let sections = SkeletonSections {
+        debug_abbrev: gimli::DebugAbbrev::new(&debug_abbrev, endian),
+        debug_str: gimli::DebugStr::new(&debug_str, endian),
+        debug_str_offsets: gimli::DebugStrOffsets::from(EndianSlice::new(
+            &debug_str_offsets,
+            endian,
+        )),
+        debug_line_str: gimli::DebugLineStr::new(&debug_line_str, endian),
+    };
+
+    let mut skeleton_units = Vec::new();
+    let mut headers = gimli::DebugInfo::new(&debug_info, endian).units();
+    let mut is_first_unit = true;
+    while let Ok(Some(header)) = headers.next() {
+        match sections.skeleton_unit(&header) {
+            Some(unit) => skeleton_units.push(unit),
+            None if is_first_unit => break,
+            None => {}
+        }
+        is_first_unit = false;
+    }
+    skeleton_units
+}
+
+/// Get the (decompressed) data of a section, borrowing from `data` if the
+/// section is not compressed. Missing sections are returned as empty slices.
+fn section_data<'data, R: ReadRef<'data>>(
+    data: R,
+    object: &File<'data, R>,
+    section_name: &str,
+) -> Cow<'data, [u8]> {
+    match try_get_section_data(data, object, section_name) {
+        Some(SingleSectionData::View {
+            data, offset, size, ..
+        }) => Cow::Borrowed(data.read_bytes_at(offset, size).unwrap_or(&[])),
+        Some(SingleSectionData::Owned(section_data)) => Cow::Owned(section_data),
+        None => Cow::Borrowed(&[]),
+    }
+}
+
+/// The sections which are needed to read the root entries of skeleton units.
+struct SkeletonSections<'a> {
+    debug_abbrev: gimli::DebugAbbrev<EndianSlice<'a, RunTimeEndian>>,
+    debug_str: gimli::DebugStr<EndianSlice<'a, RunTimeEndian>>,
+    debug_str_offsets: gimli::DebugStrOffsets<EndianSlice<'a, RunTimeEndian>>,
+    debug_line_str: gimli::DebugLineStr<EndianSlice<'a, RunTimeEndian>>,
+}
+
+impl<'a> SkeletonSections<'a> {
+    fn skeleton_unit(
+        &self,
+        header: &gimli::UnitHeader<EndianSlice<'a, RunTimeEndian>>,
+    ) -> Option<SkeletonUnit> {
+        // DWARF 5 skeleton units have their own unit type. Before DWARF 5,
+        // skeleton units were compilation units with a DW_AT_GNU_dwo_id.
+        let dwo_id = match header.type_() {
+            gimli::UnitType::Skeleton(dwo_id) => Some(dwo_id),
+            gimli::UnitType::Compilation if header.version() < 5 => None,
+            _ => return None,
+        };
+        let abbreviations = header.abbreviations(&self.debug_abbrev).ok()?;
+        let mut entries = header.entries(&abbreviations);
+        let (_, root) = entries.next_dfs().ok()??;
+        let dwo_id = match dwo_id {
+            Some(dwo_id) => dwo_id,
+            None => match root.attr_value(gimli::DW_AT_GNU_dwo_id).ok()?? {
+                gimli::AttributeValue::DwoId(dwo_id) => dwo_id,
+                _ => return None,
+            },
+        };
+        let dwo_name = root
+            .attr_value(gimli::DW_AT_dwo_name)
+            .ok()?
+            .or(root.attr_value(gimli::DW_AT_GNU_dwo_name).ok()?)?;
+        let dwo_name = self.attr_string(header, root, dwo_name)?;
+        let comp_dir = root
+            .attr_value(gimli::DW_AT_comp_dir)
+            .ok()?
+            .and_then(|comp_dir| self.attr_string(header, root, comp_dir));
+        Some(SkeletonUnit {
+            dwo_id,
+            comp_dir,
+            dwo_name,
+        })
+    }
+
+    fn attr_string(
+        &self,
+        header: &gimli::UnitHeader<EndianSlice<'a, RunTimeEndian>>,
+        root: &gimli::DebuggingInformationEntry<EndianSlice<'a, RunTimeEndian>>,
+        value: gimli::AttributeValue<EndianSlice<'a, RunTimeEndian>>,
+    ) -> Option<String> {
+        let string = match value {
+            gimli::AttributeValue::String(string) => string,
+            gimli::AttributeValue::DebugStrRef(offset) => self.debug_str.get_str(offset).ok()?,
+            gimli::AttributeValue::DebugLineStrRef(offset) => {
+                self.debug_line_str.get_str(offset).ok()?
+            }
+            gimli::AttributeValue::DebugStrOffsetsIndex(index) => {
+                let base = match root.attr_value(gimli::DW_AT_str_offsets_base) {
+                    Ok(Some(gimli::AttributeValue::DebugStrOffsetsBase(base))) => base,
+                    _ => gimli::DebugStrOffsetsBase::default_for_encoding_and_file(
+                        header.encoding(),
+                        gimli::DwarfFileType::Main,
+                    ),
+                };
+                let offset = self
+                    .debug_str_offsets
+                    .get_str_offset(header.format(), base, index)
+                    .ok()?;
+                self.debug_str.get_str(offset).ok()?
+            }
+            _ => return None,
+        };
+        Some(string.to_string_lossy().into_owned())
+    }
+}
+
+/// If the binary has skeleton units, load its `.dwp` package or, if there is
+/// none, the `.dwo` files of the skeleton units.
+pub async fn load_split_dwarf_files<'h, 'data, H, R>(
+    original_file_location: &H::FL,
+    data: R,
+    object: &File<'data, R>,
+    helper: &'h H,
+) -> SplitDwarfFileContents<H::F>
+where
+    H: FileAndPathHelper<'h>,
+    R: ReadRef<'data>,
+{
+    let skeleton_units = find_skeleton_units(data, object);
+    if skeleton_units.is_empty() {
+        return SplitDwarfFileContents::default();
+    }
+
+    let dwp_candidate_paths = helper
+        .get_candidate_paths_for_dwp_file(original_file_location)
+        .unwrap_or_default();
+    for candidate_path in dwp_candidate_paths {
+        if let Some(file_contents) = load_object_file(candidate_path, helper).await {
+            return SplitDwarfFileContents {
+                dwp: Some(file_contents),
+                dwo_files: Vec::new(),
+            };
+        }
+    }
+
+    let mut dwo_files = Vec::new();
+    for unit in skeleton_units {
+        let candidate_paths = helper
+            .get_candidate_paths_for_dwo_file(
+                original_file_location,
+                unit.comp_dir.as_deref(),
+                &unit.dwo_name,
+            )
+            .unwrap_or_default();
+        for candidate_path in candidate_paths {
+            if let Some(file_contents) = load_object_file(candidate_path, helper).await {
+                dwo_files.push((unit.dwo_id, file_contents));
+                break;
+            }
+        }
+    }
+    SplitDwarfFileContents {
+        dwp: None,
+        dwo_files,
+    }
+}
+
+async fn load_object_file<'h, H: FileAndPathHelper<'h>>(
+    location: H::FL,
+    helper: &'h H,
+) -> Option<FileContentsWrapper<H::F>> {
+    let file_contents = FileContentsWrapper::new(helper.load_file(location).await.ok()?);
+    File::parse(&file_contents).ok()?;
+    Some(file_contents)
+}
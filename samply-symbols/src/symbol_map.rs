@@ -30,11 +30,11 @@ impl<FL: FileLocation> SymbolMap<FL> {
         self.inner.symbol_count()
     }
 
-    pub fn iter_symbols(&self) -> Box<dyn Iterator<Item = (u32, Cow<'_, str>)> + '_> {
+    pub fn iter_symbols(&self) -> Box<dyn Iterator<Item = (u64, Cow<'_, str>)> + '_> {
         self.inner.iter_symbols()
     }
 
-    pub fn lookup_relative_address(&self, address: u32) -> Option<AddressInfo> {
+    pub fn lookup_relative_address(&self, address: u64) -> Option<AddressInfo> {
         self.inner.lookup_relative_address(address)
     }
 
@@ -52,11 +52,18 @@ pub trait SymbolMapTrait {
 
     fn symbol_count(&self) -> usize;
 
-    fn iter_symbols(&self) -> Box<dyn Iterator<Item = (u32, Cow<'_, str>)> + '_>;
+    fn iter_symbols(&self) -> Box<dyn Iterator<Item = (u64, Cow<'_, str>)> + '_>;
 
-    fn lookup_relative_address(&self, address: u32) -> Option<AddressInfo>;
+    fn lookup_relative_address(&self, address: u64) -> Option<AddressInfo>;
     fn lookup_svma(&self, svma: u64) -> Option<AddressInfo>;
     fn lookup_offset(&self, offset: u64) -> Option<AddressInfo>;
+
+    /// Returns the relative addresses in `start..end` at which the debug info,
+    /// i.e. the line number or the inline stack, can change. Returns `None` if
+    /// this symbol map can't enumerate its debug info.
+    fn debug_info_boundaries(&self, _start: u64, _end: u64) -> Option<Vec<u64>> {
+        None
+    }
 }
 
 pub trait SymbolMapDataOuterTrait {
@@ -113,11 +120,11 @@ impl<SMDO: SymbolMapDataOuterTrait> SymbolMapTrait for GenericSymbolMap<SMDO> {
         self.0.get().0.symbol_count()
     }
 
-    fn iter_symbols(&self) -> Box<dyn Iterator<Item = (u32, Cow<'_, str>)> + '_> {
+    fn iter_symbols(&self) -> Box<dyn Iterator<Item = (u64, Cow<'_, str>)> + '_> {
         self.0.get().0.iter_symbols()
     }
 
-    fn lookup_relative_address(&self, address: u32) -> Option<AddressInfo> {
+    fn lookup_relative_address(&self, address: u64) -> Option<AddressInfo> {
         self.0.get().0.lookup_relative_address(address)
     }
 
@@ -128,4 +135,8 @@ impl<SMDO: SymbolMapDataOuterTrait> SymbolMapTrait for GenericSymbolMap<SMDO> {
     fn lookup_offset(&self, offset: u64) -> Option<AddressInfo> {
         self.0.get().0.lookup_offset(offset)
     }
+
+    fn debug_info_boundaries(&self, start: u64, end: u64) -> Option<Vec<u64>> {
+        self.0.get().0.debug_info_boundaries(start, end)
+    }
 }
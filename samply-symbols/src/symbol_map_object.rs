@@ -1,4 +1,3 @@
-use std::convert::{TryFrom, TryInto};
 use std::{borrow::Cow, slice, sync::Mutex};
 
 use debugid::DebugId;
@@ -10,12 +9,13 @@ use object::{
 use crate::ExternalFileAddressRef;
 use crate::{
     demangle,
-    dwarf::{get_frames, Addr2lineContextData},
+    dwarf::{get_debug_info_boundaries, get_frames, Addr2lineContextData, InlineRangeBoundaries},
     path_mapper::PathMapper,
     shared::{
         relative_address_base, AddressInfo, ExternalFileAddressInFileRef, ExternalFileRef,
         SymbolInfo,
     },
+    split_dwarf::{SplitDwarfObjects, SplitDwarfSections},
     symbol_map::{SymbolMapDataMidTrait, SymbolMapInnerWrapper, SymbolMapTrait},
     Error, FramesLookupResult,
 };
@@ -24,7 +24,7 @@ pub trait FunctionAddressesComputer<'data> {
     fn compute_function_addresses<'file, O>(
         &'file self,
         object_file: &'file O,
-    ) -> (Option<Vec<u32>>, Option<Vec<u32>>)
+    ) -> (Option<Vec<u64>>, Option<Vec<u64>>)
     where
         'data: 'file,
         O: object::Object<'data, 'file>;
@@ -36,6 +36,7 @@ pub struct ObjectSymbolMapDataMid<'data, R: ReadRef<'data>, FAC: FunctionAddress
     function_addresses_computer: FAC,
     file_data: R,
     supplementary_file_data: Option<R>,
+    split_dwarf_objects: SplitDwarfObjects<'data, R>,
     addr2line_context_data: Addr2lineContextData,
     arch: Option<&'static str>,
     debug_id: DebugId,
@@ -44,12 +45,14 @@ pub struct ObjectSymbolMapDataMid<'data, R: ReadRef<'data>, FAC: FunctionAddress
 impl<'data, R: ReadRef<'data>, FAC: FunctionAddressesComputer<'data>>
     ObjectSymbolMapDataMid<'data, R, FAC>
 {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         object: File<'data, R>,
         supplementary_object: Option<File<'data, R>>,
         function_addresses_computer: FAC,
         file_data: R,
         supplementary_file_data: Option<R>,
+        split_dwarf_objects: SplitDwarfObjects<'data, R>,
         arch: Option<&'static str>,
         debug_id: DebugId,
     ) -> Self {
@@ -59,6 +62,7 @@ impl<'data, R: ReadRef<'data>, FAC: FunctionAddressesComputer<'data>>
             function_addresses_computer,
             file_data,
             supplementary_file_data,
+            split_dwarf_objects,
             addr2line_context_data: Addr2lineContextData::new(),
             arch,
             debug_id,
@@ -79,6 +83,7 @@ impl<'data, R: ReadRef<'data>, FAC: FunctionAddressesComputer<'data>> SymbolMapD
             self.supplementary_object.as_ref(),
             self.file_data,
             self.supplementary_file_data,
+            &self.split_dwarf_objects,
             self.debug_id,
             function_starts.as_deref(),
             function_ends.as_deref(),
@@ -115,7 +120,7 @@ impl<'a, Symbol: object::ObjectSymbol<'a>> std::fmt::Debug for FullSymbolListEnt
 }
 
 impl<'a, Symbol: object::ObjectSymbol<'a>> FullSymbolListEntry<'a, Symbol> {
-    fn name(&self, addr: u32) -> Result<Cow<'a, str>, ()> {
+    fn name(&self, addr: u64) -> Result<Cow<'a, str>, ()> {
         match self {
             FullSymbolListEntry::Synthesized => Ok(format!("fun_{addr:x}").into()),
             FullSymbolListEntry::Symbol(symbol) => match symbol.name_bytes() {
@@ -131,7 +136,7 @@ impl<'a, Symbol: object::ObjectSymbol<'a>> FullSymbolListEntry<'a, Symbol> {
 // A file range in an object file, such as a segment or a section,
 // for which we know the corresponding Stated Virtual Memory Address (SVMA).
 #[derive(Clone)]
-struct SvmaFileRange {
+pub(crate) struct SvmaFileRange {
     svma: u64,
     file_offset: u64,
     size: u64,
@@ -159,6 +164,40 @@ impl SvmaFileRange {
     }
 }
 
+/// Collects the file ranges of the segments of `object_file`, or of its sections
+/// if it has no segments.
+pub(crate) fn svma_file_ranges<'data: 'file, 'file>(
+    object_file: &'file impl object::Object<'data, 'file>,
+) -> Vec<SvmaFileRange> {
+    let svma_file_ranges: Vec<SvmaFileRange> = object_file
+        .segments()
+        .map(SvmaFileRange::from_segment)
+        .collect();
+
+    if !svma_file_ranges.is_empty() {
+        return svma_file_ranges;
+    }
+
+    // If no segment is found, fall back to using section information.
+    object_file
+        .sections()
+        .filter_map(SvmaFileRange::from_section)
+        .collect()
+}
+
+pub(crate) fn file_offset_to_svma(svma_file_ranges: &[SvmaFileRange], offset: u64) -> Option<u64> {
+    for svma_file_range in svma_file_ranges {
+        if svma_file_range.file_offset <= offset
+            && offset < svma_file_range.file_offset + svma_file_range.size
+        {
+            let offset_from_range_start = offset - svma_file_range.file_offset;
+            let svma = svma_file_range.svma.checked_add(offset_from_range_start)?;
+            return Some(svma);
+        }
+    }
+    None
+}
+
 impl std::fmt::Debug for SvmaFileRange {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("SvmaFileRange")
@@ -173,12 +212,14 @@ pub struct ObjectSymbolMapInner<'data, 'file, Symbol: object::ObjectSymbol<'data
 where
     'data: 'file,
 {
-    entries: Vec<(u32, FullSymbolListEntry<'data, Symbol>)>,
+    entries: Vec<(u64, FullSymbolListEntry<'data, Symbol>)>,
     debug_id: DebugId,
     arch: Option<&'static str>,
     path_mapper: Mutex<PathMapper<()>>,
     object_map: ObjectMap<'data>,
     context: Option<addr2line::Context<gimli::EndianSlice<'file, gimli::RunTimeEndian>>>,
+    split_dwarf: SplitDwarfSections<gimli::EndianSlice<'file, gimli::RunTimeEndian>>,
+    inline_range_boundaries: Mutex<InlineRangeBoundaries>,
     svma_file_ranges: Vec<SvmaFileRange>,
     image_base_address: u64,
 }
@@ -203,9 +244,10 @@ where
         sup_object_file: Option<&'file O>,
         data: R,
         sup_data: Option<R>,
+        split_dwarf_objects: &'file SplitDwarfObjects<'data, R>,
         debug_id: DebugId,
-        function_start_addresses: Option<&[u32]>,
-        function_end_addresses: Option<&[u32]>,
+        function_start_addresses: Option<&[u64]>,
+        function_end_addresses: Option<&[u64]>,
         arch: Option<&'static str>,
         addr2line_context_data: &'file Addr2lineContextData,
     ) -> Self
@@ -275,7 +317,7 @@ where
                 })
                 .filter_map(|symbol| {
                     Some((
-                        u32::try_from(symbol.address().checked_sub(base_address)?).ok()?,
+                        symbol.address().checked_sub(base_address)?,
                         FullSymbolListEntry::Symbol(symbol),
                     ))
                 }),
@@ -285,7 +327,7 @@ where
         if let Ok(exports) = object_file.exports() {
             for export in exports {
                 entries.push((
-                    (export.address() - base_address) as u32,
+                    export.address() - base_address,
                     FullSymbolListEntry::Export(export),
                 ));
             }
@@ -316,7 +358,6 @@ where
                 .filter_map(|section| {
                     let vma_end_address = section.address().checked_add(section.size())?;
                     let end_address = vma_end_address.checked_sub(base_address)?;
-                    let end_address = u32::try_from(end_address).ok()?;
                     Some((end_address, FullSymbolListEntry::EndAddress))
                 }),
         );
@@ -331,13 +372,10 @@ where
                 })
                 .filter_map(|symbol| {
                     Some((
-                        u32::try_from(
-                            symbol
-                                .address()
-                                .checked_add(symbol.size())?
-                                .checked_sub(base_address)?,
-                        )
-                        .ok()?,
+                        symbol
+                            .address()
+                            .checked_add(symbol.size())?
+                            .checked_sub(base_address)?,
                         FullSymbolListEntry::EndAddress,
                     ))
                 }),
@@ -368,21 +406,16 @@ where
         let context = addr2line_context_data
             .make_context(data, object_file, sup_data, sup_object_file)
             .ok();
+        let endian = if object_file.is_little_endian() {
+            gimli::RunTimeEndian::Little
+        } else {
+            gimli::RunTimeEndian::Big
+        };
+        let split_dwarf = split_dwarf_objects.make_sections(addr2line_context_data, endian);
 
         let path_mapper = Mutex::new(PathMapper::new());
 
-        let mut svma_file_ranges: Vec<SvmaFileRange> = object_file
-            .segments()
-            .map(SvmaFileRange::from_segment)
-            .collect();
-
-        if svma_file_ranges.is_empty() {
-            // If no segment is found, fall back to using section information.
-            svma_file_ranges = object_file
-                .sections()
-                .filter_map(SvmaFileRange::from_section)
-                .collect();
-        }
+        let svma_file_ranges = svma_file_ranges(object_file);
 
         Self {
             entries,
@@ -390,24 +423,13 @@ where
             path_mapper,
             object_map: object_file.object_map(),
             context,
+            split_dwarf,
+            inline_range_boundaries: Mutex::new(InlineRangeBoundaries::default()),
             arch,
             image_base_address: base_address,
             svma_file_ranges,
         }
     }
-
-    fn file_offset_to_svma(&self, offset: u64) -> Option<u64> {
-        for svma_file_range in &self.svma_file_ranges {
-            if svma_file_range.file_offset <= offset
-                && offset < svma_file_range.file_offset + svma_file_range.size
-            {
-                let offset_from_range_start = offset - svma_file_range.file_offset;
-                let svma = svma_file_range.svma.checked_add(offset_from_range_start)?;
-                return Some(svma);
-            }
-        }
-        None
-    }
 }
 
 impl<'data, 'file, Symbol: object::ObjectSymbol<'data>> SymbolMapTrait
@@ -431,13 +453,13 @@ where
             .count()
     }
 
-    fn iter_symbols(&self) -> Box<dyn Iterator<Item = (u32, Cow<'_, str>)> + '_> {
+    fn iter_symbols(&self) -> Box<dyn Iterator<Item = (u64, Cow<'_, str>)> + '_> {
         Box::new(SymbolMapIter {
             inner: self.entries.iter(),
         })
     }
 
-    fn lookup_relative_address(&self, address: u32) -> Option<AddressInfo> {
+    fn lookup_relative_address(&self, address: u64) -> Option<AddressInfo> {
         let index = match self
             .entries
             .binary_search_by_key(&address, |&(addr, _)| addr)
@@ -456,8 +478,13 @@ where
 
             let mut path_mapper = self.path_mapper.lock().unwrap();
 
-            let svma = self.image_base_address + u64::from(address);
-            let frames = match get_frames(svma, self.context.as_ref(), &mut path_mapper) {
+            let svma = self.image_base_address + address;
+            let frames = match get_frames(
+                svma,
+                self.context.as_ref(),
+                Some(&self.split_dwarf),
+                &mut path_mapper,
+            ) {
                 Some(frames) => FramesLookupResult::Available(frames),
                 None => {
                     if let Some(entry) = self.object_map.get(svma) {
@@ -511,25 +538,41 @@ where
     }
 
     fn lookup_svma(&self, svma: u64) -> Option<AddressInfo> {
-        let relative_address = svma.checked_sub(self.image_base_address)?.try_into().ok()?;
-        // 4200608 2103456 2097152
+        let relative_address = svma.checked_sub(self.image_base_address)?;
         self.lookup_relative_address(relative_address)
     }
 
     fn lookup_offset(&self, offset: u64) -> Option<AddressInfo> {
-        let svma = self.file_offset_to_svma(offset)?;
+        let svma = file_offset_to_svma(&self.svma_file_ranges, offset)?;
         self.lookup_svma(svma)
     }
+
+    fn debug_info_boundaries(&self, start: u64, end: u64) -> Option<Vec<u64>> {
+        let mut inline_range_boundaries = self.inline_range_boundaries.lock().unwrap();
+        let boundaries = get_debug_info_boundaries(
+            self.context.as_ref()?,
+            Some(&self.split_dwarf),
+            self.image_base_address + start,
+            self.image_base_address + end,
+            &mut inline_range_boundaries,
+        )?;
+        Some(
+            boundaries
+                .into_iter()
+                .map(|svma| svma - self.image_base_address)
+                .collect(),
+        )
+    }
 }
 
 pub struct SymbolMapIter<'data, 'map, Symbol: object::ObjectSymbol<'data>> {
-    inner: slice::Iter<'map, (u32, FullSymbolListEntry<'data, Symbol>)>,
+    inner: slice::Iter<'map, (u64, FullSymbolListEntry<'data, Symbol>)>,
 }
 
 impl<'data, 'map, Symbol: object::ObjectSymbol<'data>> Iterator
     for SymbolMapIter<'data, 'map, Symbol>
 {
-    type Item = (u32, Cow<'map, str>);
+    type Item = (u64, Cow<'map, str>);
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
@@ -0,0 +1,444 @@
+//! Support for WebAssembly modules.
+//!
+//! A wasm module consists of a header followed by a list of sections. The code
+//! of all functions is in the code section. Function names are in the `name`
+//! custom section, and DWARF debug info is stored in custom sections which are
+//! named after the DWARF sections, e.g. `.debug_info`.
+//!
+//! The "relative address" of an instruction in a wasm module is the offset of
+//! its byte in the module file. This matches the code offsets which browsers
+//! and wasm runtimes report in stack traces. DWARF addresses are offsets from
+//! the start of the code section's contents, so we convert between the two.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::sync::Mutex;
+
+use debugid::DebugId;
+use gimli::{EndianSlice, RunTimeEndian};
+
+use crate::debugid_util::DebugIdExt;
+use crate::dwarf::get_frames;
+use crate::path_mapper::PathMapper;
+use crate::symbol_map::{
+    GenericSymbolMap, SymbolMap, SymbolMapDataMidTrait, SymbolMapDataOuterTrait,
+    SymbolMapInnerWrapper, SymbolMapTrait,
+};
+use crate::{
+    demangle, AddressInfo, Error, FileContents, FileContentsWrapper, FileLocation,
+    FramesLookupResult, SymbolInfo,
+};
+
+const WASM_MAGIC: &[u8] = b"\0asm";
+
+const SECTION_CUSTOM: u8 = 0;
+const SECTION_IMPORT: u8 = 2;
+const SECTION_CODE: u8 = 10;
+
+const IMPORT_KIND_FUNCTION: u8 = 0;
+const IMPORT_KIND_TABLE: u8 = 1;
+const IMPORT_KIND_MEMORY: u8 = 2;
+const IMPORT_KIND_GLOBAL: u8 = 3;
+const IMPORT_KIND_TAG: u8 = 4;
+
+const NAME_SUBSECTION_FUNCTION_NAMES: u8 = 1;
+
+/// An error which occurred while parsing a wasm module.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum WasmParseError {
+    #[error("The file does not start with the wasm magic bytes")]
+    BadMagic,
+
+    #[error("The wasm module is truncated at offset {0}")]
+    UnexpectedEof(u64),
+
+    #[error("The wasm section at offset {0} is malformed")]
+    MalformedSection(u64),
+
+    #[error("The wasm module has no code section")]
+    NoCodeSection,
+}
+
+/// Returns whether the file starts with the wasm magic bytes.
+pub fn is_wasm_file<T: FileContents>(file_contents: &FileContentsWrapper<T>) -> bool {
+    matches!(
+        file_contents.read_bytes_at(0, WASM_MAGIC.len() as u64),
+        Ok(WASM_MAGIC)
+    )
+}
+
+pub fn get_symbol_map_for_wasm<F, FL>(
+    file_contents: FileContentsWrapper<F>,
+    file_location: FL,
+) -> Result<SymbolMap<FL>, Error>
+where
+    F: FileContents + 'static,
+    FL: FileLocation,
+{
+    let symbol_map = GenericSymbolMap::new(WasmSymbolData(file_contents))?;
+    Ok(SymbolMap::new(file_location, Box::new(symbol_map)))
+}
+
+struct WasmSymbolData<T: FileContents>(FileContentsWrapper<T>);
+
+impl<T: FileContents + 'static> SymbolMapDataOuterTrait for WasmSymbolData<T> {
+    fn make_symbol_map_data_mid(&self) -> Result<Box<dyn SymbolMapDataMidTrait + '_>, Error> {
+        let data = self
+            .0
+            .read_entire_data()
+            .map_err(|e| Error::HelperErrorDuringFileReading("wasm module".to_string(), e))?;
+        Ok(Box::new(WasmModule::parse(data)?))
+    }
+}
+
+/// The parts of a wasm module which are relevant for symbolication.
+struct WasmModule<'data> {
+    /// The file offset at which the contents of the code section start.
+    code_section_offset: u32,
+    code_section: &'data [u8],
+    /// The file offset ranges of the function bodies, with the function index.
+    functions: Vec<(u32, u32, u32)>,
+    function_names: HashMap<u32, &'data str>,
+    custom_sections: HashMap<&'data str, &'data [u8]>,
+}
+
+impl<'data> WasmModule<'data> {
+    fn parse(data: &'data [u8]) -> Result<Self, WasmParseError> {
+        if !data.starts_with(WASM_MAGIC) {
+            return Err(WasmParseError::BadMagic);
+        }
+        let mut r = WasmReader::new(data, 8);
+        let mut imported_function_count = 0;
+        let mut code_section = None;
+        let mut custom_sections = HashMap::new();
+        while !r.is_at_end() {
+            let header_offset = r.offset;
+            let eof = |_| WasmParseError::UnexpectedEof(header_offset as u64);
+            let section_id = r.u8().map_err(eof)?;
+            let section_size = r.u32().map_err(eof)?;
+            let section_offset = r.offset;
+            let section_data = r.bytes(section_size).map_err(eof)?;
+            let mut section = WasmReader::new(&data[..r.offset], section_offset);
+            let malformed = |_| WasmParseError::MalformedSection(section_offset as u64);
+            match section_id {
+                SECTION_CUSTOM => {
+                    let name = section.name().map_err(malformed)?;
+                    custom_sections.insert(name, &data[section.offset..r.offset]);
+                }
+                SECTION_IMPORT => {
+                    imported_function_count =
+                        count_imported_functions(&mut section).map_err(malformed)?;
+                }
+                SECTION_CODE => {
+                    code_section = Some((section_offset as u32, section_data));
+                }
+                _ => {}
+            }
+        }
+
+        let (code_section_offset, code_section) =
+            code_section.ok_or(WasmParseError::NoCodeSection)?;
+        let mut r = WasmReader::new(
+            &data[..code_section_offset as usize + code_section.len()],
+            code_section_offset as usize,
+        );
+        let malformed = |_| WasmParseError::MalformedSection(u64::from(code_section_offset));
+        let function_count = r.u32().map_err(malformed)?;
+        let mut functions = Vec::new();
+        for i in 0..function_count {
+            let body_size = r.u32().map_err(malformed)?;
+            let body_start = r.offset as u32;
+            r.bytes(body_size).map_err(malformed)?;
+            functions.push((body_start, r.offset as u32, imported_function_count + i));
+        }
+
+        let function_names = match custom_sections.get("name") {
+            Some(name_section) => parse_function_names(name_section).unwrap_or_default(),
+            None => HashMap::new(),
+        };
+
+        Ok(Self {
+            code_section_offset,
+            code_section,
+            functions,
+            function_names,
+            custom_sections,
+        })
+    }
+
+    /// The build ID from the `build_id` custom section, or a hash of the code.
+    fn debug_id(&self) -> DebugId {
+        let build_id = self
+            .custom_sections
+            .get("build_id")
+            .and_then(|section| WasmReader::new(section, 0).vec_bytes().ok());
+        match build_id {
+            Some(build_id) => DebugId::from_identifier(build_id, true),
+            None => DebugId::from_text_first_page(self.code_section, true),
+        }
+    }
+
+    fn function_name(&self, function_index: u32) -> Cow<'data, str> {
+        match self.function_names.get(&function_index) {
+            Some(name) => Cow::Borrowed(name),
+            None => Cow::Owned(format!("wasm-function[{function_index}]")),
+        }
+    }
+}
+
+impl<'data> SymbolMapDataMidTrait for WasmModule<'data> {
+    fn make_symbol_map_inner(&self) -> Result<SymbolMapInnerWrapper<'_>, Error> {
+        let endian = RunTimeEndian::Little;
+        let dwarf = gimli::Dwarf::load(|section_id| {
+            let data = self
+                .custom_sections
+                .get(section_id.name())
+                .copied()
+                .unwrap_or(&[]);
+            Ok::<_, gimli::Error>(EndianSlice::new(data, endian))
+        })
+        .map_err(Error::Addr2lineContextCreationError)?;
+        let context = addr2line::Context::from_dwarf(dwarf).ok();
+        let symbol_map = WasmSymbolMapInner {
+            module: self,
+            context,
+            path_mapper: Mutex::new(PathMapper::new()),
+        };
+        Ok(SymbolMapInnerWrapper(Box::new(symbol_map)))
+    }
+}
+
+struct WasmSymbolMapInner<'a, 'data> {
+    module: &'a WasmModule<'data>,
+    context: Option<addr2line::Context<EndianSlice<'data, RunTimeEndian>>>,
+    path_mapper: Mutex<PathMapper<()>>,
+}
+
+impl<'a, 'data> SymbolMapTrait for WasmSymbolMapInner<'a, 'data> {
+    fn debug_id(&self) -> DebugId {
+        self.module.debug_id()
+    }
+
+    fn symbol_count(&self) -> usize {
+        self.module.functions.len()
+    }
+
+    fn iter_symbols(&self) -> Box<dyn Iterator<Item = (u64, Cow<'_, str>)> + '_> {
+        let module = self.module;
+        Box::new(
+            module
+                .functions
+                .iter()
+                .map(move |&(start, _end, index)| (u64::from(start), module.function_name(index))),
+        )
+    }
+
+    fn lookup_relative_address(&self, address: u64) -> Option<AddressInfo> {
+        // Relative addresses are file offsets, and wasm modules use 32-bit offsets.
+        let address = u32::try_from(address).ok()?;
+        let functions = &self.module.functions;
+        let index = match functions.binary_search_by_key(&address, |&(start, _, _)| start) {
+            Ok(i) => i,
+            Err(0) => return None,
+            Err(i) => i - 1,
+        };
+        let (start, end, function_index) = functions[index];
+        if address >= end {
+            return None;
+        }
+        let name = demangle::demangle_any(&self.module.function_name(function_index));
+        let dwarf_address = address - self.module.code_section_offset;
+        let mut path_mapper = self.path_mapper.lock().unwrap();
+        let frames = match get_frames(
+            u64::from(dwarf_address),
+            self.context.as_ref(),
+            None,
+            &mut path_mapper,
+        ) {
+            Some(frames) => FramesLookupResult::Available(frames),
+            None => FramesLookupResult::Unavailable,
+        };
+        Some(AddressInfo {
+            symbol: SymbolInfo {
+                address: u64::from(start),
+                size: Some(u64::from(end - start)),
+                name,
+            },
+            frames,
+        })
+    }
+
+    fn lookup_svma(&self, svma: u64) -> Option<AddressInfo> {
+        // Wasm modules are not mapped into an address space, so we treat the
+        // stated address like a relative address.
+        self.lookup_relative_address(svma)
+    }
+
+    fn lookup_offset(&self, offset: u64) -> Option<AddressInfo> {
+        self.lookup_relative_address(offset)
+    }
+
+    fn debug_info_boundaries(&self, start: u64, end: u64) -> Option<Vec<u64>> {
+        let context = self.context.as_ref()?;
+        let code_section_offset = u64::from(self.module.code_section_offset);
+        let dwarf_start = start.checked_sub(code_section_offset)?;
+        let dwarf_end = end.checked_sub(code_section_offset)?;
+        let mut boundaries = Vec::new();
+        for (address, size, _location) in
+            context.find_location_range(dwarf_start, dwarf_end).ok()?
+        {
+            for dwarf_address in [address, address.saturating_add(size)] {
+                if dwarf_start <= dwarf_address && dwarf_address < dwarf_end {
+                    boundaries.push(dwarf_address + code_section_offset);
+                }
+            }
+        }
+        boundaries.sort_unstable();
+        boundaries.dedup();
+        Some(boundaries)
+    }
+}
+
+/// Count the imported functions in the import section. Imported functions come
+/// first in the function index space.
+fn count_imported_functions(r: &mut WasmReader) -> Result<u32, ()> {
+    let import_count = r.u32()?;
+    let mut function_count = 0;
+    for _ in 0..import_count {
+        let _module = r.name()?;
+        let _field = r.name()?;
+        match r.u8()? {
+            IMPORT_KIND_FUNCTION => {
+                let _type_index = r.u32()?;
+                function_count += 1;
+            }
+            IMPORT_KIND_TABLE => {
+                let _ref_type = r.u8()?;
+                r.limits()?;
+            }
+            IMPORT_KIND_MEMORY => r.limits()?,
+            IMPORT_KIND_GLOBAL => {
+                let _value_type = r.u8()?;
+                let _mutability = r.u8()?;
+            }
+            IMPORT_KIND_TAG => {
+                let _attribute = r.u8()?;
+                let _type_index = r.u32()?;
+            }
+            _ => return Err(()),
+        }
+    }
+    Ok(function_count)
+}
+
+/// Parse the function names subsection of the `name` custom section.
+fn parse_function_names(name_section: &[u8]) -> Result<HashMap<u32, &str>, ()> {
+    let mut r = WasmReader::new(name_section, 0);
+    while !r.is_at_end() {
+        let subsection_id = r.u8()?;
+        let subsection_size = r.u32()?;
+        let subsection = r.bytes(subsection_size)?;
+        if subsection_id != NAME_SUBSECTION_FUNCTION_NAMES {
+            continue;
+        }
+        let mut r = WasmReader::new(subsection, 0);
+        let name_count = r.u32()?;
+        let mut names = HashMap::new();
+        for _ in 0..name_count {
+            let function_index = r.u32()?;
+            let name = r.name()?;
+            names.insert(function_index, name);
+        }
+        return Ok(names);
+    }
+    Ok(HashMap::new())
+}
+
+/// Reads the primitive values of the wasm binary format from `data`, starting at `offset`.
+struct WasmReader<'data> {
+    data: &'data [u8],
+    offset: usize,
+}
+
+impl<'data> WasmReader<'data> {
+    fn new(data: &'data [u8], offset: usize) -> Self {
+        Self { data, offset }
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.offset >= self.data.len()
+    }
+
+    fn u8(&mut self) -> Result<u8, ()> {
+        let byte = *self.data.get(self.offset).ok_or(())?;
+        self.offset += 1;
+        Ok(byte)
+    }
+
+    /// Read an unsigned LEB128 number which fits into 32 bits.
+    fn u32(&mut self) -> Result<u32, ()> {
+        let mut result: u32 = 0;
+        for shift in (0..35).step_by(7) {
+            let byte = self.u8()?;
+            if shift == 28 && byte & 0x70 != 0 {
+                // The value doesn't fit into 32 bits.
+                return Err(());
+            }
+            result |= u32::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(result);
+            }
+        }
+        Err(())
+    }
+
+    fn bytes(&mut self, len: u32) -> Result<&'data [u8], ()> {
+        let end = self.offset.checked_add(len as usize).ok_or(())?;
+        let bytes = self.data.get(self.offset..end).ok_or(())?;
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn vec_bytes(&mut self) -> Result<&'data [u8], ()> {
+        let len = self.u32()?;
+        self.bytes(len)
+    }
+
+    fn name(&mut self) -> Result<&'data str, ()> {
+        std::str::from_utf8(self.vec_bytes()?).map_err(|_| ())
+    }
+
+    fn limits(&mut self) -> Result<(), ()> {
+        let flags = self.u8()?;
+        let _min = self.u32()?;
+        if flags & 1 != 0 {
+            let _max = self.u32()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn read_u32(data: &[u8]) -> Result<u32, ()> {
+        WasmReader::new(data, 0).u32()
+    }
+
+    #[test]
+    fn leb128_u32() {
+        assert_eq!(read_u32(&[0x00]), Ok(0));
+        assert_eq!(read_u32(&[0xe5, 0x8e, 0x26]), Ok(624485));
+        assert_eq!(read_u32(&[0x80, 0x80, 0x80, 0x80, 0x00]), Ok(0));
+        assert_eq!(read_u32(&[0xff, 0xff, 0xff, 0xff, 0x0f]), Ok(u32::MAX));
+        // The fifth byte must not have bits beyond the 32nd bit.
+        assert_eq!(read_u32(&[0xff, 0xff, 0xff, 0xff, 0x1f]), Err(()));
+        assert_eq!(read_u32(&[0x80, 0x80, 0x80, 0x80, 0x70]), Err(()));
+        // At most five bytes.
+        assert_eq!(read_u32(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x00]), Err(()));
+        assert_eq!(read_u32(&[0x80, 0x80]), Err(()));
+    }
+}
@@ -1,10 +1,12 @@
 use crate::debugid_util::debug_id_for_object;
 use crate::error::{Context, Error};
+use crate::gopclntab::go_pclntab_symbol_map_data;
 use crate::path_mapper::{ExtraPathMapper, PathMapper};
 use crate::shared::{
     AddressInfo, FileAndPathHelper, FileContents, FileContentsWrapper, FrameDebugInfo,
     FramesLookupResult, SymbolInfo,
 };
+use crate::split_dwarf::SplitDwarfObjects;
 use crate::symbol_map::{
     GenericSymbolMap, SymbolMap, SymbolMapDataMidTrait, SymbolMapDataOuterTrait,
     SymbolMapInnerWrapper, SymbolMapTrait,
@@ -16,10 +18,11 @@ use nom::bytes::complete::{tag, take_until1};
 use nom::combinator::eof;
 use nom::sequence::terminated;
 use object::{File, FileKind};
-use pdb::PDB;
+use pdb::{FallibleIterator, PDB};
 use pdb_addr2line::pdb;
 use std::borrow::Cow;
 use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::ops::Deref;
 use std::sync::Mutex;
 
@@ -99,12 +102,16 @@ impl<T: FileContents + 'static> SymbolMapDataOuterTrait for PeSymbolMapData<T> {
             File::parse(&self.file_data).map_err(|e| Error::ObjectParseError(self.file_kind, e))?;
         let debug_id = debug_id_for_object(&object)
             .ok_or(Error::InvalidInputError("debug ID cannot be read"))?;
+        if let Some(go_data) = go_pclntab_symbol_map_data(&object, debug_id) {
+            return Ok(Box::new(go_data));
+        }
         let object = ObjectSymbolMapDataMid::new(
             object,
             None,
             PeFunctionAddressesComputer,
             &self.file_data,
             None,
+            SplitDwarfObjects::default(),
             None,
             debug_id,
         );
@@ -119,7 +126,7 @@ impl<'data> FunctionAddressesComputer<'data> for PeFunctionAddressesComputer {
     fn compute_function_addresses<'file, O>(
         &'file self,
         object_file: &'file O,
-    ) -> (Option<Vec<u32>>, Option<Vec<u32>>)
+    ) -> (Option<Vec<u64>>, Option<Vec<u64>>)
     where
         'data: 'file,
         O: object::Object<'data, 'file>,
@@ -143,6 +150,7 @@ pub fn is_pdb_file<F: FileContents>(file: &FileContentsWrapper<F>) -> bool {
 }
 
 struct PdbObject<'data, FC: FileContents + 'static> {
+    file_contents: &'data FileContentsWrapper<FC>,
     context_data: pdb_addr2line::ContextPdbData<'data, 'data, &'data FileContentsWrapper<FC>>,
     debug_id: DebugId,
     srcsrv_stream: Option<Box<dyn Deref<Target = [u8]> + 'data>>,
@@ -164,6 +172,8 @@ impl<'data, FC: FileContents + 'static> SymbolMapDataMidTrait for PdbObject<'dat
             context,
             debug_id: self.debug_id,
             path_mapper: Mutex::new(path_mapper),
+            pdb_object: self,
+            debug_info_boundaries: Mutex::new(None),
         };
         Ok(SymbolMapInnerWrapper(Box::new(symbol_map)))
     }
@@ -178,6 +188,85 @@ impl<'data, FC: FileContents + 'static> PdbObject<'data, FC> {
     }
 }
 
+trait PdbDebugInfoBoundariesTrait {
+    /// Returns the sorted RVAs at which the line number or the inline stack
+    /// can change, in the whole PDB.
+    fn compute_debug_info_boundaries(&self) -> Result<Vec<u32>, pdb::Error>;
+}
+
+impl<'data, FC: FileContents + 'static> PdbDebugInfoBoundariesTrait for PdbObject<'data, FC> {
+    fn compute_debug_info_boundaries(&self) -> Result<Vec<u32>, pdb::Error> {
+        // pdb_addr2line only gives us the frames at a given address, so we read
+        // the line programs and the inline sites ourselves.
+        let mut pdb = PDB::open(self.file_contents)?;
+        let address_map = pdb.address_map()?;
+        let dbi = pdb.debug_information()?;
+        let mut modules = dbi.modules()?;
+        let mut boundaries = Vec::new();
+        let mut add_range = |offset: pdb::PdbInternalSectionOffset, length: Option<u32>| {
+            if let Some(rva) = offset.to_rva(&address_map) {
+                boundaries.push(rva.0);
+            }
+            let end_offset = length.and_then(|length| offset.offset.checked_add(length));
+            if let Some(end_offset) = end_offset {
+                let end = pdb::PdbInternalSectionOffset::new(offset.section, end_offset);
+                if let Some(rva) = end.to_rva(&address_map) {
+                    boundaries.push(rva.0);
+                }
+            }
+        };
+        while let Some(module) = modules.next()? {
+            let module_info = match pdb.module_info(&module)? {
+                Some(module_info) => module_info,
+                None => continue,
+            };
+            let line_program = module_info.line_program().ok();
+            let mut inlinees = HashMap::new();
+            if let Ok(mut inlinee_iter) = module_info.inlinees() {
+                while let Ok(Some(inlinee)) = inlinee_iter.next() {
+                    inlinees.insert(inlinee.index(), inlinee);
+                }
+            }
+            // The offsets of inline sites are relative to their procedure, and
+            // procedures can be nested.
+            let mut procedures: Vec<(pdb::SymbolIndex, pdb::PdbInternalSectionOffset)> = Vec::new();
+            let mut symbols = module_info.symbols()?;
+            while let Some(symbol) = symbols.next()? {
+                while matches!(procedures.last(), Some((end, _)) if symbol.index() >= *end) {
+                    procedures.pop();
+                }
+                match symbol.parse() {
+                    Ok(pdb::SymbolData::Procedure(procedure)) => {
+                        if let Some(line_program) = &line_program {
+                            let mut lines = line_program.lines_for_symbol(procedure.offset);
+                            while let Ok(Some(line)) = lines.next() {
+                                add_range(line.offset, line.length);
+                            }
+                        }
+                        procedures.push((procedure.end, procedure.offset));
+                    }
+                    Ok(pdb::SymbolData::InlineSite(site)) => {
+                        let procedure_offset = match procedures.last() {
+                            Some((_, procedure_offset)) => *procedure_offset,
+                            None => continue,
+                        };
+                        if let Some(inlinee) = inlinees.get(&site.inlinee) {
+                            let mut lines = inlinee.lines(procedure_offset, &site);
+                            while let Ok(Some(line)) = lines.next() {
+                                add_range(line.offset, line.length);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        boundaries.sort_unstable();
+        boundaries.dedup();
+        Ok(boundaries)
+    }
+}
+
 trait PdbAddr2lineContextTrait {
     fn find_frames(
         &self,
@@ -208,6 +297,9 @@ struct PdbSymbolMapInner<'object> {
     context: Box<dyn PdbAddr2lineContextTrait + 'object>,
     debug_id: DebugId,
     path_mapper: Mutex<PathMapper<SrcSrvPathMapper<'object>>>,
+    pdb_object: &'object dyn PdbDebugInfoBoundariesTrait,
+    /// Computed on first use. `Some(None)` if the PDB couldn't be read.
+    debug_info_boundaries: Mutex<Option<Option<Vec<u32>>>>,
 }
 
 impl<'object> SymbolMapTrait for PdbSymbolMapInner<'object> {
@@ -219,18 +311,20 @@ impl<'object> SymbolMapTrait for PdbSymbolMapInner<'object> {
         self.context.function_count()
     }
 
-    fn iter_symbols(&self) -> Box<dyn Iterator<Item = (u32, Cow<'_, str>)> + '_> {
+    fn iter_symbols(&self) -> Box<dyn Iterator<Item = (u64, Cow<'_, str>)> + '_> {
         let iter = self.context.functions().map(|f| {
             let start_rva = f.start_rva;
             (
-                start_rva,
+                u64::from(start_rva),
                 Cow::Owned(f.name.unwrap_or_else(|| format!("fun_{start_rva:x}"))),
             )
         });
         Box::new(iter)
     }
 
-    fn lookup_relative_address(&self, address: u32) -> Option<AddressInfo> {
+    fn lookup_relative_address(&self, address: u64) -> Option<AddressInfo> {
+        // RVAs in PDB files are 32 bits wide.
+        let address = u32::try_from(address).ok()?;
         let function_frames = self.context.find_frames(address).ok()??;
         let symbol_address = function_frames.start_rva;
         let symbol_name = match &function_frames.frames.last().unwrap().function {
@@ -239,10 +333,10 @@ impl<'object> SymbolMapTrait for PdbSymbolMapInner<'object> {
         };
         let function_size = function_frames
             .end_rva
-            .map(|end_rva| end_rva - function_frames.start_rva);
+            .map(|end_rva| u64::from(end_rva - function_frames.start_rva));
 
         let symbol = SymbolInfo {
-            address: symbol_address,
+            address: u64::from(symbol_address),
             size: function_size,
             name: symbol_name,
         };
@@ -279,6 +373,21 @@ impl<'object> SymbolMapTrait for PdbSymbolMapInner<'object> {
         // TODO
         None
     }
+
+    fn debug_info_boundaries(&self, start: u64, end: u64) -> Option<Vec<u64>> {
+        let mut debug_info_boundaries = self.debug_info_boundaries.lock().unwrap();
+        let boundaries = debug_info_boundaries
+            .get_or_insert_with(|| self.pdb_object.compute_debug_info_boundaries().ok())
+            .as_ref()?;
+        let first = boundaries.partition_point(|&rva| u64::from(rva) < start);
+        Some(
+            boundaries[first..]
+                .iter()
+                .map(|&rva| u64::from(rva))
+                .take_while(|&rva| rva < end)
+                .collect(),
+        )
+    }
 }
 
 fn box_stream<'data, T>(stream: T) -> Box<dyn Deref<Target = [u8]> + 'data>
@@ -308,6 +417,7 @@ impl<T: FileContents + 'static> SymbolMapDataOuterTrait for PdbSymbolData<T> {
             .context("ContextConstructionData::try_from_pdb")?;
 
         Ok(Box::new(PdbObject {
+            file_contents: &self.0,
             context_data,
             debug_id,
             srcsrv_stream,
@@ -482,14 +592,14 @@ impl<'s, F: FileContents> pdb::Source<'s> for &'s FileContentsWrapper<F> {
 /// This section has the addresses for functions with unwind info. That means
 /// it only covers a subset of functions; it does not include entries for
 /// leaf functions which don't allocate any stack space.
-fn function_start_and_end_addresses(pdata: &[u8]) -> (Vec<u32>, Vec<u32>) {
+fn function_start_and_end_addresses(pdata: &[u8]) -> (Vec<u64>, Vec<u64>) {
     let mut start_addresses = Vec::new();
     let mut end_addresses = Vec::new();
     for entry in pdata.chunks_exact(3 * std::mem::size_of::<u32>()) {
         let start_address = u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]);
         let end_address = u32::from_le_bytes([entry[4], entry[5], entry[6], entry[7]]);
-        start_addresses.push(start_address);
-        end_addresses.push(end_address);
+        start_addresses.push(u64::from(start_address));
+        end_addresses.push(u64::from(end_address));
     }
     (start_addresses, end_addresses)
 }
@@ -1,8 +1,8 @@
 use samply_symbols::debugid::DebugId;
 use samply_symbols::{
-    self, CandidatePathInfo, CompactSymbolTable, Error, FileAndPathHelper, FileAndPathHelperResult,
-    FileLocation, LibraryInfo, MultiArchDisambiguator, OptionallySendFuture, SymbolManager,
-    SymbolMap,
+    self, CandidatePathInfo, CompactSymbolTable, ElfBuildId, Error, FileAndPathHelper,
+    FileAndPathHelperResult, FileLocation, FramesLookupResult, LibraryInfo, MultiArchDisambiguator,
+    OptionallySendFuture, SymbolManager, SymbolMap,
 };
 use std::fs::File;
 use std::io::{BufWriter, Read, Write};
@@ -231,6 +231,36 @@ impl<'h> FileAndPathHelper<'h> for Helper {
             FileLocationType::new("/System/Library/dyld/dyld_shared_cache_x86_64"),
         ])
     }
+
+    fn get_candidate_paths_for_dwo_file(
+        &self,
+        _original_file_path: &Self::FL,
+        _comp_dir: Option<&str>,
+        dwo_path: &str,
+    ) -> FileAndPathHelperResult<Vec<Self::FL>> {
+        // The fixtures are not in their compilation directory.
+        Ok(vec![FileLocationType(self.symbol_directory.join(dwo_path))])
+    }
+
+    fn get_candidate_paths_for_dwp_file(
+        &self,
+        original_file_path: &Self::FL,
+    ) -> FileAndPathHelperResult<Vec<Self::FL>> {
+        let mut dwp_path = original_file_path.0.clone().into_os_string();
+        dwp_path.push(".dwp");
+        Ok(vec![FileLocationType(dwp_path.into())])
+    }
+
+    fn get_candidate_paths_for_supplementary_debug_file(
+        &self,
+        _original_file_path: &Self::FL,
+        supplementary_file_path: &str,
+        _supplementary_file_build_id: &ElfBuildId,
+    ) -> FileAndPathHelperResult<Vec<Self::FL>> {
+        Ok(vec![FileLocationType(
+            self.symbol_directory.join(supplementary_file_path),
+        )])
+    }
 }
 
 fn fixtures_dir() -> PathBuf {
@@ -533,6 +563,380 @@ fn example_linux_fallback() {
     assert_eq!(symbol_map.lookup_relative_address(0x6), None);
 }
 
+/// Checks that the debug info in `example-linux-zstd*` is found: These files have
+/// `.debug_*` sections which are compressed with `ELFCOMPRESS_ZSTD`.
+fn check_zstd_example_frames(symbol_map: &SymbolMap<FileLocationType>) {
+    let address_info = symbol_map.lookup_relative_address(0x114c).unwrap();
+    assert_eq!(address_info.symbol.name, "compute");
+    let frames = match address_info.frames {
+        FramesLookupResult::Available(frames) => frames,
+        _ => panic!("Expected debug info for 0x114c"),
+    };
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0].function.as_deref(), Some("compute"));
+    assert_eq!(frames[0].line_number, Some(6));
+    assert!(frames[0]
+        .file_path
+        .as_ref()
+        .unwrap()
+        .raw_path()
+        .ends_with("example.c"));
+}
+
+#[test]
+fn example_linux_zstd() {
+    let helper = Helper {
+        symbol_directory: fixtures_dir().join("other"),
+    };
+    let symbol_manager = SymbolManager::with_helper(&helper);
+    let symbol_map = futures::executor::block_on(symbol_manager.load_symbol_map_from_location(
+        FileLocationType(fixtures_dir().join("other").join("example-linux-zstd")),
+        None,
+    ))
+    .unwrap();
+    check_zstd_example_frames(&symbol_map);
+}
+
+#[test]
+fn example_linux_zstd_minidebuginfo() {
+    // The debug info is in an xz-compressed object in the .gnu_debugdata section.
+    let helper = Helper {
+        symbol_directory: fixtures_dir().join("other"),
+    };
+    let symbol_manager = SymbolManager::with_helper(&helper);
+    let symbol_map = futures::executor::block_on(
+        symbol_manager.load_symbol_map_from_location(
+            FileLocationType(
+                fixtures_dir()
+                    .join("other")
+                    .join("example-linux-zstd-minidebuginfo"),
+            ),
+            None,
+        ),
+    )
+    .unwrap();
+    check_zstd_example_frames(&symbol_map);
+}
+
+#[test]
+fn example_linux_zstd_supplementary_file() {
+    // The strings of the debug info are in the supplementary file which is
+    // referenced by the .gnu_debugaltlink section, and that file's .debug_str
+    // section is compressed with ELFCOMPRESS_ZSTD.
+    let helper = Helper {
+        symbol_directory: fixtures_dir().join("other"),
+    };
+    let symbol_manager = SymbolManager::with_helper(&helper);
+    let symbol_map = futures::executor::block_on(symbol_manager.load_symbol_map_from_location(
+        FileLocationType(fixtures_dir().join("other").join("example-linux-altlink")),
+        None,
+    ))
+    .unwrap();
+    check_zstd_example_frames(&symbol_map);
+}
+
+/// Checks that the inlined call to `square` in `sum_of_squares` is found. This
+/// information is only present in the split DWARF files of the binary.
+fn check_split_dwarf_example_frames(symbol_map: &SymbolMap<FileLocationType>) {
+    let address_info = symbol_map.lookup_relative_address(0x1182).unwrap();
+    assert_eq!(address_info.symbol.name, "sum_of_squares");
+    let frames = match address_info.frames {
+        FramesLookupResult::Available(frames) => frames,
+        _ => panic!("Expected debug info for 0x1182"),
+    };
+    let functions: Vec<_> = frames
+        .iter()
+        .map(|frame| (frame.function.as_deref(), frame.line_number))
+        .collect();
+    assert_eq!(
+        functions,
+        vec![
+            (Some("square"), Some(4)),
+            (Some("sum_of_squares"), Some(10))
+        ]
+    );
+}
+
+#[test]
+fn example_linux_split_dwarf_dwo() {
+    // example-linux-split-dwarf was compiled with -gsplit-dwarf, and its debug
+    // info is in example-linux-split-dwarf-split.dwo.
+    let helper = Helper {
+        symbol_directory: fixtures_dir().join("other"),
+    };
+    let symbol_manager = SymbolManager::with_helper(&helper);
+    let symbol_map = futures::executor::block_on(
+        symbol_manager.load_symbol_map_from_location(
+            FileLocationType(
+                fixtures_dir()
+                    .join("other")
+                    .join("example-linux-split-dwarf"),
+            ),
+            None,
+        ),
+    )
+    .unwrap();
+    check_split_dwarf_example_frames(&symbol_map);
+}
+
+#[test]
+fn example_linux_split_dwarf5_dwo() {
+    // example-linux-split-dwarf5 was compiled with -gdwarf-5 -gsplit-dwarf, so
+    // its compilation unit is a DW_UT_skeleton unit rather than a compilation
+    // unit with a DW_AT_GNU_dwo_id.
+    let helper = Helper {
+        symbol_directory: fixtures_dir().join("other"),
+    };
+    let symbol_manager = SymbolManager::with_helper(&helper);
+    let symbol_map = futures::executor::block_on(
+        symbol_manager.load_symbol_map_from_location(
+            FileLocationType(
+                fixtures_dir()
+                    .join("other")
+                    .join("example-linux-split-dwarf5"),
+            ),
+            None,
+        ),
+    )
+    .unwrap();
+    let address_info = symbol_map.lookup_relative_address(0x1139).unwrap();
+    assert_eq!(address_info.symbol.name, "sum_of_squares");
+    let frames = match address_info.frames {
+        FramesLookupResult::Available(frames) => frames,
+        _ => panic!("Expected debug info for 0x1139"),
+    };
+    let functions: Vec<_> = frames
+        .iter()
+        .map(|frame| (frame.function.as_deref(), frame.line_number))
+        .collect();
+    assert_eq!(
+        functions,
+        vec![(Some("square"), Some(1)), (Some("sum_of_squares"), Some(4))]
+    );
+}
+
+#[test]
+fn example_linux_split_dwarf_dwp() {
+    // The .dwo file of example-linux-dwp was packaged into example-linux-dwp.dwp.
+    let helper = Helper {
+        symbol_directory: fixtures_dir().join("other"),
+    };
+    let symbol_manager = SymbolManager::with_helper(&helper);
+    let symbol_map = futures::executor::block_on(symbol_manager.load_symbol_map_from_location(
+        FileLocationType(fixtures_dir().join("other").join("example-linux-dwp")),
+        None,
+    ))
+    .unwrap();
+    check_split_dwarf_example_frames(&symbol_map);
+}
+
+#[test]
+fn go_stripped_linux() {
+    // go-stripped-linux is gcloud-crc32c from the Google Cloud SDK, built with
+    // Go 1.24 and without a symbol table or DWARF. All symbols and line numbers
+    // come from .gopclntab.
+    let helper = Helper {
+        symbol_directory: fixtures_dir().join("other"),
+    };
+    let symbol_manager = SymbolManager::with_helper(&helper);
+    let symbol_map = futures::executor::block_on(symbol_manager.load_symbol_map_from_location(
+        FileLocationType(fixtures_dir().join("other").join("go-stripped-linux")),
+        None,
+    ))
+    .unwrap();
+    assert_eq!(
+        symbol_map.debug_id(),
+        DebugId::from_breakpad("A8460ACD119B584A4B8A339C6BD318EA0").unwrap()
+    );
+    assert_eq!(symbol_map.symbol_count(), 2063);
+
+    let frames_for = |address: u64| {
+        let address_info = symbol_map.lookup_relative_address(address).unwrap();
+        let frames = match address_info.frames {
+            FramesLookupResult::Available(frames) => frames,
+            _ => panic!("Expected line numbers from the pclntab"),
+        };
+        let frames: Vec<_> = frames
+            .iter()
+            .map(|frame| {
+                (
+                    frame.function.clone().unwrap(),
+                    frame.file_path.as_ref().unwrap().raw_path().to_string(),
+                    frame.line_number.unwrap(),
+                )
+            })
+            .collect();
+        (
+            address_info.symbol.name,
+            address_info.symbol.address,
+            frames,
+        )
+    };
+
+    // The ELF entry point, 0x470ca0.
+    assert_eq!(
+        frames_for(0x70ca0),
+        (
+            "_rt0_amd64_linux".to_string(),
+            0x70ca0,
+            vec![(
+                "_rt0_amd64_linux".to_string(),
+                "runtime/rt0_linux_amd64.s".to_string(),
+                8
+            )]
+        )
+    );
+    assert_eq!(
+        frames_for(0xac7e0),
+        (
+            "main.main".to_string(),
+            0xac7c0,
+            vec![("main.main".to_string(), "./crc32c.go".to_string(), 45)]
+        )
+    );
+}
+
+#[test]
+fn example_wasm() {
+    // example-wasm.wasm has function names in its name section, and DWARF debug
+    // info in custom sections. sum_of_squares calls square twice; both calls are
+    // inlined.
+    let helper = Helper {
+        symbol_directory: fixtures_dir().join("other"),
+    };
+    let symbol_manager = SymbolManager::with_helper(&helper);
+    let symbol_map = futures::executor::block_on(symbol_manager.load_symbol_map_from_location(
+        FileLocationType(fixtures_dir().join("other").join("example-wasm.wasm")),
+        None,
+    ))
+    .unwrap();
+    let symbols: Vec<_> = symbol_map
+        .iter_symbols()
+        .map(|(address, name)| (address, name.into_owned()))
+        .collect();
+    assert_eq!(
+        symbols,
+        vec![
+            (0x60, "square".to_string()),
+            (0x68, "sum_of_squares".to_string())
+        ]
+    );
+
+    // Relative addresses are offsets in the module file.
+    let address_info = symbol_map.lookup_relative_address(0x6e).unwrap();
+    assert_eq!(address_info.symbol.name, "sum_of_squares");
+    assert_eq!(address_info.symbol.address, 0x68);
+    assert_eq!(address_info.symbol.size, Some(0xd));
+    let frames = match address_info.frames {
+        FramesLookupResult::Available(frames) => frames,
+        _ => panic!("Expected debug info for 0x6e"),
+    };
+    let functions: Vec<_> = frames
+        .iter()
+        .map(|frame| (frame.function.as_deref(), frame.line_number))
+        .collect();
+    assert_eq!(
+        functions,
+        vec![
+            (Some("square"), Some(4)),
+            (Some("sum_of_squares"), Some(11))
+        ]
+    );
+    assert_eq!(
+        frames[0].file_path.as_ref().unwrap().raw_path(),
+        "/tmp/wasmfix/sum.c"
+    );
+    assert_eq!(symbol_map.lookup_offset(0x6e).unwrap().symbol.address, 0x68);
+    assert!(symbol_map.lookup_relative_address(0x5f).is_none());
+}
+
+#[test]
+fn breakpad_sym_from_dwarf() {
+    let helper = Helper {
+        symbol_directory: fixtures_dir().join("other"),
+    };
+    let symbol_manager = SymbolManager::with_helper(&helper);
+    let symbol_map = futures::executor::block_on(
+        symbol_manager.load_symbol_map_from_location(
+            FileLocationType(
+                fixtures_dir()
+                    .join("other")
+                    .join("example-linux-split-dwarf"),
+            ),
+            None,
+        ),
+    )
+    .unwrap();
+    let info = LibraryInfo {
+        debug_name: Some("example-linux-split-dwarf".to_string()),
+        arch: Some("x86_64".to_string()),
+        ..Default::default()
+    };
+    let mut output = Vec::new();
+    samply_symbols::write_breakpad_sym_file(&mut output, &symbol_map, &info, "Linux").unwrap();
+    let sym = String::from_utf8(output).unwrap();
+    let lines: Vec<&str> = sym.lines().collect();
+    assert_eq!(
+        lines[0],
+        "MODULE Linux x86_64 F54E222D1A60736C21A7DD1E9DCA0D160 example-linux-split-dwarf"
+    );
+    assert!(lines.contains(&"FILE 0 ./split.c"));
+    assert!(lines.contains(&"INLINE_ORIGIN 0 square"));
+    let func_index = lines
+        .iter()
+        .position(|line| *line == "FUNC 1170 2d 0 sum_of_squares")
+        .unwrap();
+    // The call to square on line 10 was inlined at 0x1180..0x1185.
+    assert_eq!(lines[func_index + 1], "INLINE 0 10 0 0 1180 5");
+    assert!(lines[func_index..].contains(&"1180 5 4 0"));
+}
+
+#[test]
+fn breakpad_sym_from_pdb() {
+    let helper = Helper {
+        symbol_directory: fixtures_dir().join("win64-local"),
+    };
+    let symbol_manager = SymbolManager::with_helper(&helper);
+    let symbol_map = futures::executor::block_on(symbol_manager.load_symbol_map_from_location(
+        FileLocationType(fixtures_dir().join("win64-local").join("mozglue.pdb")),
+        None,
+    ))
+    .unwrap();
+    let info = LibraryInfo {
+        debug_name: Some("mozglue.pdb".to_string()),
+        arch: Some("x86_64".to_string()),
+        ..Default::default()
+    };
+    let mut output = Vec::new();
+    samply_symbols::write_breakpad_sym_file(&mut output, &symbol_map, &info, "windows").unwrap();
+    let sym = String::from_utf8(output).unwrap();
+    let lines: Vec<&str> = sym.lines().collect();
+    assert_eq!(
+        lines[0],
+        "MODULE windows x86_64 B3CC644ECC086E044C4C44205044422E1 mozglue.pdb"
+    );
+    assert!(lines
+        .contains(&"FILE 1 c:\\mozilla-source\\mozilla-central\\memory\\build\\mozjemalloc.cpp"));
+    assert!(lines.contains(&"INLINE_ORIGIN 38 _malloc_message(char const*, char const*)"));
+    assert!(lines.contains(&"INLINE_ORIGIN 39 _malloc_message(char const*)"));
+    let func_index = lines
+        .iter()
+        .position(|line| *line == "FUNC 4cf0 55 0 pages_unmap(void*, unsigned long long)")
+        .unwrap();
+    assert_eq!(
+        &lines[func_index + 1..func_index + 8],
+        &[
+            "INLINE 0 1475 1 38 4d0d 38",
+            "INLINE 1 1235 1 39 4d0d 1b",
+            "INLINE 1 1236 1 39 4d28 1d",
+            "4cf0 5 1471 1",
+            "4cf5 12 1472 1",
+            "4d07 6 1475 1",
+            "4d0d 38 1228 1",
+        ]
+    );
+}
+
 #[test]
 fn compare_snapshot() {
     let table = futures::executor::block_on(crate::get_table(
@@ -1,14 +1,15 @@
 use framehop::{Module, Unwinder};
 use fxprof_processed_profile::Profile;
 use linux_perf_data::linux_perf_event_reader;
-use linux_perf_data::{PerfFileReader, PerfFileRecord};
-use linux_perf_event_reader::EventRecord;
+use linux_perf_data::{Feature, PerfFileReader, PerfFileRecord};
+use linux_perf_event_reader::{EventRecord, RecordType};
 
 use std::io::{Read, Seek};
 use std::path::Path;
 
 use crate::linux_shared::{
-    ConvertRegs, ConvertRegsAarch64, ConvertRegsX86_64, Converter, EventInterpretation,
+    lost_samples_count, ConvertRegs, ConvertRegsAarch64, ConvertRegsX86_64, Converter,
+    EventInterpretation,
 };
 
 #[derive(thiserror::Error, Debug)]
@@ -83,7 +84,15 @@ where
     for event_name in attributes.iter().filter_map(|attr| attr.name()) {
         println!("event {event_name}");
     }
-    let interpretation = EventInterpretation::divine_from_attrs(attributes);
+    let mut interpretation = EventInterpretation::divine_from_attrs(attributes);
+    if let Some(tracing_data) = perf_file.feature_section_data(Feature::TRACING_DATA) {
+        if let Some(formats) = interpretation.sched_state_formats.as_mut() {
+            formats.set_tracing_data(tracing_data);
+        }
+        interpretation
+            .syscall_formats
+            .set_tracing_data(tracing_data);
+    }
 
     let product = "Converted perf profile";
     let mut converter = Converter::<U>::new(
@@ -124,6 +133,12 @@ where
                     converter.handle_sample::<C>(e);
                 } else if interpretation.sched_switch_attr_index == Some(attr_index) {
                     converter.handle_sched_switch::<C>(e);
+                } else if interpretation.sched_wakeup_attr_index == Some(attr_index) {
+                    converter.handle_sched_wakeup(e);
+                } else if interpretation.syscall_enter_attr_index == Some(attr_index) {
+                    converter.handle_syscall_enter(e);
+                } else if interpretation.syscall_exit_attr_index == Some(attr_index) {
+                    converter.handle_syscall_exit::<C>(e);
                 }
             }
             EventRecord::Fork(e) => {
@@ -148,6 +163,25 @@ where
                 };
                 converter.handle_context_switch(e, common);
             }
+            EventRecord::Lost(e) => {
+                if let Ok(common) = record.common_data() {
+                    converter.handle_lost_events(e.count, common);
+                }
+            }
+            EventRecord::Throttle(e) => {
+                if let Ok(common) = record.common_data() {
+                    converter.handle_throttle(e, common);
+                }
+            }
+            EventRecord::Unthrottle(e) => {
+                converter.handle_unthrottle(e);
+            }
+            EventRecord::Raw(raw) if raw.record_type == RecordType::LOST_SAMPLES => {
+                if let (Some(count), Ok(common)) = (lost_samples_count(&raw), record.common_data())
+                {
+                    converter.handle_lost_events(count, common);
+                }
+            }
             _ => {
                 // println!("{:?}", record.record_type);
             }
@@ -0,0 +1,128 @@
+use memmap2::Mmap;
+use object::{Object, ObjectSegment, ObjectSymbol};
+
+use std::ffi::CString;
+use std::fs::File;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+use super::perf_event::Uprobe;
+use super::proc_maps;
+use super::profiler::read_string_lossy;
+use crate::linux_shared::{AllocationFunction, AllocationProbe};
+
+/// Create the uprobes for `samply record --allocations`: an entry probe on each
+/// of libc's allocation functions, and a return probe on those functions which
+/// return a new allocation.
+///
+/// The probes are placed in the libc which samply itself uses. This is the libc
+/// that the launched command will use, unless it's statically linked or brings
+/// its own allocator.
+pub fn allocation_uprobes() -> io::Result<Vec<(AllocationProbe, Uprobe)>> {
+    let pmu_type = read_string_lossy("/sys/bus/event_source/devices/uprobe/type")?;
+    let pmu_type: u32 = pmu_type
+        .trim()
+        .parse()
+        .map_err(|_| invalid_data("could not parse the uprobe PMU type"))?;
+    let retprobe_format =
+        read_string_lossy("/sys/bus/event_source/devices/uprobe/format/retprobe")?;
+    let retprobe_bit = parse_retprobe_format(&retprobe_format)
+        .ok_or_else(|| invalid_data("could not parse the uprobe retprobe format"))?;
+
+    let libc_path = find_libc_path()?;
+    let file = File::open(&libc_path)?;
+    let mmap = unsafe { Mmap::map(&file)? };
+    let obj = object::File::parse(&mmap[..])
+        .map_err(|err| invalid_data(&format!("could not parse {libc_path:?}: {err}")))?;
+    let path = CString::new(libc_path.as_os_str().as_bytes())
+        .map_err(|_| invalid_data("the libc path contains a nul byte"))?;
+
+    let mut probes = Vec::new();
+    for function in AllocationFunction::ALL {
+        let name = function.symbol_name();
+        let file_offset = symbol_file_offset(&obj, name)
+            .ok_or_else(|| invalid_data(&format!("could not find {name} in {libc_path:?}")))?;
+        let mut is_return_values = vec![false];
+        if function.needs_return_probe() {
+            is_return_values.push(true);
+        }
+        for is_return in is_return_values {
+            probes.push((
+                AllocationProbe {
+                    function,
+                    is_return,
+                },
+                Uprobe {
+                    pmu_type,
+                    retprobe_bit,
+                    path: path.clone(),
+                    file_offset,
+                    is_return,
+                },
+            ));
+        }
+    }
+    Ok(probes)
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Find the path of the libc which is mapped into our own process.
+fn find_libc_path() -> io::Result<PathBuf> {
+    let maps = read_string_lossy("/proc/self/maps")?;
+    proc_maps::parse(&maps)
+        .into_iter()
+        .map(|region| PathBuf::from(region.name))
+        .find(|path| is_libc_path(path))
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "could not find the libc shared library in /proc/self/maps",
+            )
+        })
+}
+
+fn is_libc_path(path: &Path) -> bool {
+    match path.file_name().and_then(|name| name.to_str()) {
+        Some(name) => name.starts_with("libc.so") || name.starts_with("libc-"),
+        None => false,
+    }
+}
+
+/// The format file contains something like "config:0", i.e. the bit in the
+/// `config` field which selects a return probe.
+fn parse_retprobe_format(format: &str) -> Option<u32> {
+    format.trim().strip_prefix("config:")?.parse().ok()
+}
+
+/// Uprobes are placed at file offsets, so translate the symbol's address into
+/// an offset in the file, using the segment which contains the symbol.
+fn symbol_file_offset<'data>(obj: &object::File<'data>, name: &str) -> Option<u64> {
+    let symbol = obj
+        .dynamic_symbols()
+        .find(|symbol| symbol.is_definition() && symbol.name() == Ok(name))?;
+    let address = symbol.address();
+    obj.segments().find_map(|segment| {
+        let (file_start, file_size) = segment.file_range();
+        let segment_address = segment.address();
+        if address >= segment_address && address < segment_address + file_size {
+            Some(address - segment_address + file_start)
+        } else {
+            None
+        }
+    })
+}
+
+#[test]
+fn test_parse_retprobe_format() {
+    assert_eq!(parse_retprobe_format("config:0\n"), Some(0));
+    assert_eq!(parse_retprobe_format("config1:0"), None);
+    assert!(is_libc_path(Path::new(
+        "/usr/lib/x86_64-linux-gnu/libc.so.6"
+    )));
+    assert!(is_libc_path(Path::new("/lib/libc-2.31.so")));
+    assert!(!is_libc_path(Path::new("/usr/lib/libcrypto.so.3")));
+}
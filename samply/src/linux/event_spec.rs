@@ -0,0 +1,306 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::perf_event::{EventSource, SampledEvent, Sampling};
+use super::sys::*;
+
+/// The event(s) selected with `samply record --event`.
+///
+/// The syntax follows `perf record -e`: a hardware or software event name such
+/// as `instructions` or `page-faults`, a raw PMU event such as `r01c2`, or a
+/// tracepoint such as `sched:sched_switch`. Tracepoint names can contain `*`
+/// wildcards, e.g. `syscalls:sys_enter_*`, which selects all matching
+/// tracepoints. The sampling rate can be set with a `/period=N/` or `/freq=N/`
+/// suffix, e.g. `cache-misses/period=1000/`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventSpec {
+    /// The event name as given by the user, without the sampling terms.
+    pub name: String,
+    pub events: Vec<SampledEvent>,
+}
+
+impl EventSpec {
+    /// The default event: CPU cycles, sampled at the given frequency.
+    pub fn cycles(frequency: u64) -> Self {
+        EventSpec {
+            name: "cycles".to_string(),
+            events: vec![SampledEvent {
+                source: EventSource::HwCpuCycles,
+                sampling: Sampling::Frequency(frequency),
+            }],
+        }
+    }
+
+    /// The fallback for machines without a cycles event, e.g. in VMs.
+    pub fn cpu_clock(frequency: u64) -> Self {
+        EventSpec {
+            name: "cpu-clock".to_string(),
+            events: vec![SampledEvent {
+                source: EventSource::SwCpuClock,
+                sampling: Sampling::Frequency(frequency),
+            }],
+        }
+    }
+
+    /// Parse an event spec. Events without a `period` or `freq` term are sampled
+    /// at `default_frequency`, except for tracepoints, which are sampled on every
+    /// hit.
+    pub fn parse(spec: &str, default_frequency: u64) -> Result<Self, String> {
+        let (name, terms) = match spec.split_once('/') {
+            Some((name, terms)) => {
+                let terms = terms.strip_suffix('/').ok_or_else(|| {
+                    format!("Missing closing '/' after the terms in event {spec:?}")
+                })?;
+                (name, Some(terms))
+            }
+            None => (spec, None),
+        };
+
+        let mut sampling = None;
+        for term in terms.into_iter().flat_map(|terms| terms.split(',')) {
+            let (key, value) = term
+                .split_once('=')
+                .ok_or_else(|| format!("Expected a term of the form key=value, got {term:?}"))?;
+            let value: u64 = parse_number(value)
+                .filter(|value| *value != 0)
+                .ok_or_else(|| format!("Expected a positive number for {key}, got {value:?}"))?;
+            sampling = Some(match key {
+                "period" => Sampling::Period(value),
+                "freq" => Sampling::Frequency(value),
+                _ => return Err(format!("Unsupported event term {key:?}")),
+            });
+        }
+
+        let sources = if let Some((category, event)) = name.split_once(':') {
+            let sources: Vec<EventSource> = find_tracepoint_ids(category, event)?
+                .into_iter()
+                .map(EventSource::Tracepoint)
+                .collect();
+            if sources.is_empty() {
+                return Err(format!("No tracepoint matches {name:?}"));
+            }
+            sources
+        } else {
+            vec![parse_event_name(name).ok_or_else(|| format!("Unknown event {name:?}"))?]
+        };
+
+        let events = sources
+            .into_iter()
+            .map(|source| SampledEvent {
+                source,
+                sampling: sampling.unwrap_or(match source {
+                    EventSource::Tracepoint(_) => Sampling::Period(1),
+                    _ => Sampling::Frequency(default_frequency),
+                }),
+            })
+            .collect();
+        Ok(EventSpec {
+            name: name.to_string(),
+            events,
+        })
+    }
+
+    /// If the samples of this event are spaced out by time, the sampling interval
+    /// in nanoseconds. This is the case for the clock events, and for CPU cycles
+    /// sampled at a frequency. Samples of other events are weighted by their period.
+    pub fn sampling_interval_nanos(&self) -> Option<u64> {
+        let event = match self.events.as_slice() {
+            [event] => event,
+            _ => return None,
+        };
+        let is_clock = matches!(
+            event.source,
+            EventSource::SwCpuClock
+                | EventSource::Software(PERF_COUNT_SW_CPU_CLOCK | PERF_COUNT_SW_TASK_CLOCK)
+        );
+        let is_cycles = matches!(
+            event.source,
+            EventSource::HwCpuCycles | EventSource::Hardware(PERF_COUNT_HW_CPU_CYCLES)
+        );
+        match event.sampling {
+            Sampling::Frequency(frequency) if is_clock || is_cycles => {
+                Some(1_000_000_000 / frequency)
+            }
+            Sampling::Period(period) if is_clock => Some(period),
+            _ => None,
+        }
+    }
+}
+
+fn parse_number(s: &str) -> Option<u64> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+fn parse_event_name(name: &str) -> Option<EventSource> {
+    let source = match name {
+        "cycles" | "cpu-cycles" => EventSource::Hardware(PERF_COUNT_HW_CPU_CYCLES),
+        "instructions" => EventSource::Hardware(PERF_COUNT_HW_INSTRUCTIONS),
+        "cache-references" => EventSource::Hardware(PERF_COUNT_HW_CACHE_REFERENCES),
+        "cache-misses" => EventSource::Hardware(PERF_COUNT_HW_CACHE_MISSES),
+        "branches" | "branch-instructions" => {
+            EventSource::Hardware(PERF_COUNT_HW_BRANCH_INSTRUCTIONS)
+        }
+        "branch-misses" => EventSource::Hardware(PERF_COUNT_HW_BRANCH_MISSES),
+        "bus-cycles" => EventSource::Hardware(PERF_COUNT_HW_BUS_CYCLES),
+        "stalled-cycles-frontend" | "idle-cycles-frontend" => {
+            EventSource::Hardware(PERF_COUNT_HW_STALLED_CYCLES_FRONTEND)
+        }
+        "stalled-cycles-backend" | "idle-cycles-backend" => {
+            EventSource::Hardware(PERF_COUNT_HW_STALLED_CYCLES_BACKEND)
+        }
+        "ref-cycles" => EventSource::Hardware(PERF_COUNT_HW_REF_CPU_CYCLES),
+        "cpu-clock" => EventSource::Software(PERF_COUNT_SW_CPU_CLOCK),
+        "task-clock" => EventSource::Software(PERF_COUNT_SW_TASK_CLOCK),
+        "page-faults" | "faults" => EventSource::Software(PERF_COUNT_SW_PAGE_FAULTS),
+        "context-switches" | "cs" => EventSource::Software(PERF_COUNT_SW_CONTEXT_SWITCHES),
+        "cpu-migrations" | "migrations" => EventSource::Software(PERF_COUNT_SW_CPU_MIGRATIONS),
+        "minor-faults" => EventSource::Software(PERF_COUNT_SW_PAGE_FAULTS_MIN),
+        "major-faults" => EventSource::Software(PERF_COUNT_SW_PAGE_FAULTS_MAJ),
+        "alignment-faults" => EventSource::Software(PERF_COUNT_SW_ALIGNMENT_FAULTS),
+        "emulation-faults" => EventSource::Software(PERF_COUNT_SW_EMULATION_FAULTS),
+        _ => {
+            // Raw events have the form rNNNN, with NNNN in hex.
+            let config = name.strip_prefix('r')?;
+            if config.is_empty() {
+                return None;
+            }
+            EventSource::Raw(u64::from_str_radix(config, 16).ok()?)
+        }
+    };
+    Some(source)
+}
+
+/// The tracefs events directory. Newer kernels mount tracefs at /sys/kernel/tracing,
+/// older ones only have it under debugfs.
+fn tracefs_events_dir() -> Result<PathBuf, String> {
+    [
+        "/sys/kernel/tracing/events",
+        "/sys/kernel/debug/tracing/events",
+    ]
+    .iter()
+    .map(Path::new)
+    .find(|dir| dir.is_dir())
+    .map(ToOwned::to_owned)
+    .ok_or_else(|| {
+        "Could not find the tracefs events directory. Tracepoints usually require root privileges."
+            .to_string()
+    })
+}
+
+/// Find the id of a single tracepoint, e.g. `sched:sched_switch`.
+pub fn tracepoint_id(category: &str, name: &str) -> Result<u64, String> {
+    find_tracepoint_ids(category, name)?
+        .first()
+        .copied()
+        .ok_or_else(|| format!("No tracepoint matches \"{category}:{name}\""))
+}
+
+/// Read the format description of a single tracepoint, which describes the
+/// layout of its raw data.
+pub fn tracepoint_format(category: &str, name: &str) -> Result<String, String> {
+    let format_path = tracefs_events_dir()?
+        .join(category)
+        .join(name)
+        .join("format");
+    fs::read_to_string(&format_path).map_err(|err| format!("Could not read {format_path:?}: {err}"))
+}
+
+/// Find the ids of all tracepoints whose category and name match the patterns.
+fn find_tracepoint_ids(category_pattern: &str, name_pattern: &str) -> Result<Vec<u64>, String> {
+    let events_dir = tracefs_events_dir()?;
+    let mut ids = Vec::new();
+    for category in matching_dir_entries(&events_dir, category_pattern)? {
+        for event in matching_dir_entries(&category, name_pattern)? {
+            let id_path = event.join("id");
+            let id = fs::read_to_string(&id_path)
+                .map_err(|err| format!("Could not read {id_path:?}: {err}"))?;
+            let id = id
+                .trim()
+                .parse()
+                .map_err(|_| format!("Could not parse the tracepoint id in {id_path:?}"))?;
+            ids.push(id);
+        }
+    }
+    Ok(ids)
+}
+
+fn matching_dir_entries(dir: &Path, pattern: &str) -> Result<Vec<PathBuf>, String> {
+    if !pattern.contains('*') {
+        let path = dir.join(pattern);
+        return Ok(if path.is_dir() { vec![path] } else { vec![] });
+    }
+    let entries = fs::read_dir(dir).map_err(|err| format!("Could not read {dir:?}: {err}"))?;
+    let mut paths: Vec<PathBuf> = entries
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .filter(|entry| glob_matches(pattern, &entry.file_name().to_string_lossy()))
+        .map(|entry| entry.path())
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+/// Match `s` against a pattern in which `*` matches any sequence of characters.
+fn glob_matches(pattern: &str, s: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == s,
+        Some((prefix, rest)) => {
+            let s = match s.strip_prefix(prefix) {
+                Some(s) => s,
+                None => return false,
+            };
+            (0..=s.len())
+                .filter(|i| s.is_char_boundary(*i))
+                .any(|i| glob_matches(rest, &s[i..]))
+        }
+    }
+}
+
+#[test]
+fn test_parse_event_spec() {
+    let spec = EventSpec::parse("instructions", 1000).unwrap();
+    assert_eq!(spec.name, "instructions");
+    assert_eq!(
+        spec.events,
+        vec![SampledEvent {
+            source: EventSource::Hardware(PERF_COUNT_HW_INSTRUCTIONS),
+            sampling: Sampling::Frequency(1000),
+        }]
+    );
+    assert_eq!(spec.sampling_interval_nanos(), None);
+
+    let spec = EventSpec::parse("cache-misses/period=10000/", 1000).unwrap();
+    assert_eq!(spec.name, "cache-misses");
+    assert_eq!(spec.events[0].sampling, Sampling::Period(10000));
+
+    let spec = EventSpec::parse("r01c2/freq=500/", 1000).unwrap();
+    assert_eq!(spec.events[0].source, EventSource::Raw(0x1c2));
+    assert_eq!(spec.events[0].sampling, Sampling::Frequency(500));
+
+    let spec = EventSpec::parse("task-clock/period=0x100000/", 1000).unwrap();
+    assert_eq!(spec.sampling_interval_nanos(), Some(0x100000));
+    assert_eq!(
+        EventSpec::parse("cycles", 1000)
+            .unwrap()
+            .sampling_interval_nanos(),
+        Some(1_000_000)
+    );
+
+    assert!(EventSpec::parse("not-an-event", 1000).is_err());
+    assert!(EventSpec::parse("r", 1000).is_err());
+    assert!(EventSpec::parse("cycles/period=0/", 1000).is_err());
+    assert!(EventSpec::parse("cycles/period=1", 1000).is_err());
+    assert!(EventSpec::parse("cycles/umask=1/", 1000).is_err());
+}
+
+#[test]
+fn test_glob_matches() {
+    assert!(glob_matches("sys_enter_*", "sys_enter_read"));
+    assert!(glob_matches("*", "sched_switch"));
+    assert!(glob_matches("sys_*_read", "sys_exit_read"));
+    assert!(!glob_matches("sys_enter_*", "sys_exit_read"));
+    assert!(!glob_matches("sched_switch", "sched_switch2"));
+}
@@ -1,3 +1,6 @@
+mod allocation_probes;
+mod event_spec;
+mod perf_data_writer;
 mod perf_event;
 mod perf_group;
 mod proc_maps;
@@ -0,0 +1,639 @@
+use byteorder::{NativeEndian, WriteBytesExt};
+use linux_perf_data::linux_perf_event_reader;
+use linux_perf_data::Feature;
+use linux_perf_event_reader::constants::{
+    PERF_RECORD_COMM, PERF_RECORD_MISC_BUILD_ID_SIZE, PERF_RECORD_MISC_KERNEL,
+    PERF_RECORD_MISC_MMAP_BUILD_ID, PERF_RECORD_MISC_USER, PERF_RECORD_MMAP, PERF_RECORD_MMAP2,
+};
+use linux_perf_event_reader::{
+    AttrFlags, CpuMode, EventRecord, Mmap2FileId, Mmap2Record, PerfEventAttr, RawData,
+    RawEventRecord, RecordType, SampleFormat,
+};
+use object::Object;
+
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+/// `PERF_RECORD_HEADER_BUILD_ID`, the record type of the entries in the build ID section.
+const PERF_RECORD_HEADER_BUILD_ID: u32 = 67;
+/// `PERF_RECORD_FINISHED_ROUND`, which tells readers that the records before it
+/// can be sorted by time.
+const PERF_RECORD_FINISHED_ROUND: u32 = 68;
+
+/// The size of `perf_file_header`.
+const FILE_HEADER_SIZE: u64 = 104;
+/// The size of `perf_file_section`.
+const FILE_SECTION_SIZE: u64 = 16;
+/// The size of `perf_event_header`.
+const EVENT_HEADER_SIZE: usize = 8;
+/// The alignment of the file names in the build ID section and of header strings.
+const NAME_ALIGN: usize = 64;
+
+/// The path under which `perf` records the kernel image.
+const KERNEL_MMAP_NAME: &[u8] = b"[kernel.kallsyms]_text";
+const KERNEL_BUILD_ID_NAME: &[u8] = b"[kernel.kallsyms]";
+
+/// An event in the perf.data file: the attr that the perf events were opened
+/// with, and the IDs of all perf events that were opened with this attr.
+#[derive(Debug, Clone)]
+pub struct PerfDataAttr {
+    /// The bytes of the `perf_event_attr` struct, in native endian.
+    pub raw_attr: Vec<u8>,
+    /// The event name, as shown by `perf report`.
+    pub name: String,
+    pub ids: Vec<u64>,
+}
+
+/// The information about the recording machine which goes into the feature
+/// sections of the perf.data file.
+#[derive(Debug, Clone, Default)]
+pub struct PerfDataHostInfo {
+    pub hostname: Option<String>,
+    pub os_release: Option<String>,
+    pub arch: Option<String>,
+    /// The command line of the recording process.
+    pub cmdline: Vec<String>,
+}
+
+impl PerfDataHostInfo {
+    pub fn for_this_machine() -> Self {
+        let info = uname::uname().ok();
+        PerfDataHostInfo {
+            hostname: info.as_ref().map(|info| info.nodename.clone()),
+            os_release: info.as_ref().map(|info| info.release.clone()),
+            arch: info.as_ref().map(|info| info.machine.clone()),
+            cmdline: std::env::args_os()
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect(),
+        }
+    }
+}
+
+/// Writes the raw records of a live recording into a perf.data file, which can
+/// be read by `samply load` and by `perf report`.
+///
+/// The records are written to the data section as they come in. The feature
+/// sections and the attrs are written by [`PerfDataWriter::finish`], because
+/// the build IDs of the mapped files and the IDs of all perf events are only
+/// known at the end of the recording.
+pub struct PerfDataWriter<W: Write + Seek> {
+    writer: W,
+    data_size: u64,
+    /// The sample_id fields of synthesized records are filled in as if they had
+    /// been emitted by the perf event with this ID and attr.
+    synthesized_record_id: u64,
+    synthesized_record_attr: PerfEventAttr,
+    /// Whether records were written since the last `FINISHED_ROUND` record.
+    round_has_records: bool,
+    /// The paths of the executable mappings in user space, for the build ID section.
+    mapped_files: BTreeSet<Vec<u8>>,
+    kernel_build_id: Option<Vec<u8>>,
+}
+
+impl PerfDataWriter<BufWriter<File>> {
+    pub fn create(path: &Path, main_attr: &PerfDataAttr) -> io::Result<Self> {
+        let file = File::create(path)?;
+        Self::new(BufWriter::new(file), main_attr)
+    }
+}
+
+impl<W: Write + Seek> PerfDataWriter<W> {
+    /// Start a new perf.data file. Records synthesized by this writer, for example
+    /// for the processes which were already running when the recording started,
+    /// are attributed to `main_attr`.
+    pub fn new(mut writer: W, main_attr: &PerfDataAttr) -> io::Result<Self> {
+        let synthesized_record_attr =
+            PerfEventAttr::parse::<_, NativeEndian>(&main_attr.raw_attr[..], None)?;
+        let synthesized_record_id = main_attr.ids.first().copied().unwrap_or(0);
+
+        // The header is written once the sizes of all sections are known.
+        writer.write_all(&[0; FILE_HEADER_SIZE as usize])?;
+
+        Ok(PerfDataWriter {
+            writer,
+            data_size: 0,
+            synthesized_record_id,
+            synthesized_record_attr,
+            round_has_records: false,
+            mapped_files: BTreeSet::new(),
+            kernel_build_id: None,
+        })
+    }
+
+    /// Write a record which was read from a perf event ring buffer.
+    pub fn write_record(&mut self, record: &RawEventRecord) -> io::Result<()> {
+        if record.record_type == RecordType::MMAP || record.record_type == RecordType::MMAP2 {
+            match record.parse() {
+                Ok(EventRecord::Mmap(e)) if e.is_executable => {
+                    self.note_mapped_file(e.cpu_mode, e.path);
+                }
+                Ok(EventRecord::Mmap2(e)) if e.protection & libc::PROT_EXEC as u32 != 0 => {
+                    self.note_mapped_file(e.cpu_mode, e.path);
+                }
+                _ => {}
+            }
+        }
+        match record.data {
+            RawData::Single(data) => self.write_event(record.record_type.0, record.misc, data, &[]),
+            RawData::Split(first, second) => {
+                self.write_event(record.record_type.0, record.misc, first, second)
+            }
+        }
+    }
+
+    /// Write a `COMM` record for a thread which was already running when the
+    /// recording started.
+    pub fn write_comm(&mut self, pid: i32, tid: i32, name: &[u8]) -> io::Result<()> {
+        let mut data = Vec::new();
+        data.write_i32::<NativeEndian>(pid)?;
+        data.write_i32::<NativeEndian>(tid)?;
+        write_padded_string(&mut data, name, 8);
+        self.write_sample_id(&mut data, pid, tid)?;
+        self.write_event(PERF_RECORD_COMM, PERF_RECORD_MISC_USER, &data, &[])
+    }
+
+    /// Write an `MMAP2` record for a mapping which existed before the recording
+    /// started.
+    pub fn write_mmap2(&mut self, e: &Mmap2Record) -> io::Result<()> {
+        let mut misc = cpu_mode_misc(e.cpu_mode);
+        let mut data = Vec::new();
+        data.write_i32::<NativeEndian>(e.pid)?;
+        data.write_i32::<NativeEndian>(e.tid)?;
+        data.write_u64::<NativeEndian>(e.address)?;
+        data.write_u64::<NativeEndian>(e.length)?;
+        data.write_u64::<NativeEndian>(e.page_offset)?;
+        match &e.file_id {
+            Mmap2FileId::InodeAndVersion(inode) => {
+                data.write_u32::<NativeEndian>(inode.major)?;
+                data.write_u32::<NativeEndian>(inode.minor)?;
+                data.write_u64::<NativeEndian>(inode.inode)?;
+                data.write_u64::<NativeEndian>(inode.inode_generation)?;
+            }
+            Mmap2FileId::BuildId(build_id) => {
+                misc |= PERF_RECORD_MISC_MMAP_BUILD_ID;
+                let mut bytes = [0; 24];
+                let len = build_id.len().min(20);
+                bytes[0] = len as u8;
+                bytes[4..4 + len].copy_from_slice(&build_id[..len]);
+                data.extend_from_slice(&bytes);
+            }
+        }
+        data.write_u32::<NativeEndian>(e.protection)?;
+        data.write_u32::<NativeEndian>(e.flags)?;
+        let path = e.path.as_slice();
+        write_padded_string(&mut data, &path, 8);
+        self.write_sample_id(&mut data, e.pid, e.tid)?;
+
+        if e.protection & libc::PROT_EXEC as u32 != 0 {
+            self.note_mapped_file(e.cpu_mode, e.path);
+        }
+        self.write_event(PERF_RECORD_MMAP2, misc, &data, &[])
+    }
+
+    /// Write the `MMAP` record for the kernel image, the way `perf record` does,
+    /// and remember the kernel's build ID for the build ID section.
+    pub fn write_kernel_mmap(&mut self, base_avma: u64, build_id: &[u8]) -> io::Result<()> {
+        self.kernel_build_id = Some(build_id.to_owned());
+        if base_avma == 0 {
+            // The kernel address is hidden from us by kptr_restrict.
+            return Ok(());
+        }
+        let mut data = Vec::new();
+        data.write_i32::<NativeEndian>(-1)?;
+        data.write_i32::<NativeEndian>(0)?;
+        data.write_u64::<NativeEndian>(base_avma)?;
+        data.write_u64::<NativeEndian>(u64::MAX - base_avma)?;
+        data.write_u64::<NativeEndian>(base_avma)?;
+        write_padded_string(&mut data, KERNEL_MMAP_NAME, 8);
+        self.write_sample_id(&mut data, -1, 0)?;
+        self.write_event(PERF_RECORD_MMAP, PERF_RECORD_MISC_KERNEL, &data, &[])
+    }
+
+    /// Write a `FINISHED_ROUND` record if any records were written since the
+    /// last one. Call this after every pass over all perf event ring buffers.
+    pub fn finish_round(&mut self) -> io::Result<()> {
+        if !self.round_has_records {
+            return Ok(());
+        }
+        self.write_event(PERF_RECORD_FINISHED_ROUND, 0, &[], &[])?;
+        self.round_has_records = false;
+        Ok(())
+    }
+
+    /// Write the feature sections, the attrs and the file header, and return
+    /// the underlying writer. `attrs` must contain the attrs and IDs of all perf
+    /// events whose records were written, with the main event first.
+    pub fn finish(mut self, attrs: &[PerfDataAttr], host_info: &PerfDataHostInfo) -> io::Result<W> {
+        self.finish_round()?;
+
+        let mut features: Vec<(Feature, Vec<u8>)> = Vec::new();
+        let build_id_section = self.build_id_section()?;
+        if !build_id_section.is_empty() {
+            features.push((Feature::BUILD_ID, build_id_section));
+        }
+        if let Some(hostname) = &host_info.hostname {
+            features.push((Feature::HOSTNAME, header_string(hostname)?));
+        }
+        if let Some(os_release) = &host_info.os_release {
+            features.push((Feature::OSRELEASE, header_string(os_release)?));
+        }
+        if let Some(arch) = &host_info.arch {
+            features.push((Feature::ARCH, header_string(arch)?));
+        }
+        let mut cmdline = Vec::new();
+        cmdline.write_u32::<NativeEndian>(host_info.cmdline.len() as u32)?;
+        for arg in &host_info.cmdline {
+            cmdline.extend_from_slice(&header_string(arg)?);
+        }
+        features.push((Feature::CMDLINE, cmdline));
+        features.push((Feature::EVENT_DESC, event_desc_section(attrs)?));
+
+        // The table of feature sections has to come directly after the data section,
+        // with one entry per feature, ordered by feature bit.
+        let data_offset = FILE_HEADER_SIZE;
+        let mut offset = data_offset + self.data_size + FILE_SECTION_SIZE * features.len() as u64;
+        let mut feature_set = [0; 4];
+        for (feature, data) in &features {
+            feature_set[feature.0 as usize / 64] |= 1 << (feature.0 % 64);
+            self.writer.write_u64::<NativeEndian>(offset)?;
+            self.writer.write_u64::<NativeEndian>(data.len() as u64)?;
+            offset += data.len() as u64;
+        }
+        for (_, data) in &features {
+            self.writer.write_all(data)?;
+        }
+
+        // The IDs of each attr, followed by the attr section.
+        let mut id_sections = Vec::new();
+        for attr in attrs {
+            id_sections.push((offset, attr.ids.len() as u64 * 8));
+            for id in &attr.ids {
+                self.writer.write_u64::<NativeEndian>(*id)?;
+            }
+            offset += attr.ids.len() as u64 * 8;
+        }
+        let attr_size = attrs.first().map_or(0, |attr| attr.raw_attr.len() as u64);
+        let attr_section_offset = offset;
+        for (attr, (ids_offset, ids_size)) in attrs.iter().zip(id_sections) {
+            self.writer.write_all(&attr.raw_attr)?;
+            self.writer.write_u64::<NativeEndian>(ids_offset)?;
+            self.writer.write_u64::<NativeEndian>(ids_size)?;
+        }
+        let file_attr_size = attr_size + FILE_SECTION_SIZE;
+
+        self.writer.seek(SeekFrom::Start(0))?;
+        // Readers detect the endianness of the file from the byte order of the magic.
+        self.writer
+            .write_u64::<NativeEndian>(u64::from_le_bytes(*b"PERFILE2"))?;
+        self.writer.write_u64::<NativeEndian>(FILE_HEADER_SIZE)?;
+        self.writer.write_u64::<NativeEndian>(file_attr_size)?;
+        self.writer.write_u64::<NativeEndian>(attr_section_offset)?;
+        self.writer
+            .write_u64::<NativeEndian>(file_attr_size * attrs.len() as u64)?;
+        self.writer.write_u64::<NativeEndian>(data_offset)?;
+        self.writer.write_u64::<NativeEndian>(self.data_size)?;
+        // The event_types section is unused.
+        self.writer.write_u64::<NativeEndian>(0)?;
+        self.writer.write_u64::<NativeEndian>(0)?;
+        for bits in feature_set {
+            self.writer.write_u64::<NativeEndian>(bits)?;
+        }
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write_event(
+        &mut self,
+        record_type: u32,
+        misc: u16,
+        data: &[u8],
+        data2: &[u8],
+    ) -> io::Result<()> {
+        let size = EVENT_HEADER_SIZE + data.len() + data2.len();
+        let size = u16::try_from(size)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "record is too large"))?;
+        self.writer.write_u32::<NativeEndian>(record_type)?;
+        self.writer.write_u16::<NativeEndian>(misc)?;
+        self.writer.write_u16::<NativeEndian>(size)?;
+        self.writer.write_all(data)?;
+        self.writer.write_all(data2)?;
+        self.data_size += u64::from(size);
+        self.round_has_records = true;
+        Ok(())
+    }
+
+    /// Append the `sample_id` fields that the kernel appends to non-sample records
+    /// if `sample_id_all` is set.
+    fn write_sample_id(&self, data: &mut Vec<u8>, pid: i32, tid: i32) -> io::Result<()> {
+        let attr = &self.synthesized_record_attr;
+        if !attr.flags.contains(AttrFlags::SAMPLE_ID_ALL) {
+            return Ok(());
+        }
+        let format = attr.sample_format;
+        let id = self.synthesized_record_id;
+        if format.contains(SampleFormat::TID) {
+            data.write_i32::<NativeEndian>(pid)?;
+            data.write_i32::<NativeEndian>(tid)?;
+        }
+        if format.contains(SampleFormat::TIME) {
+            data.write_u64::<NativeEndian>(0)?;
+        }
+        if format.contains(SampleFormat::ID) {
+            data.write_u64::<NativeEndian>(id)?;
+        }
+        if format.contains(SampleFormat::STREAM_ID) {
+            data.write_u64::<NativeEndian>(id)?;
+        }
+        if format.contains(SampleFormat::CPU) {
+            data.write_u32::<NativeEndian>(0)?;
+            data.write_u32::<NativeEndian>(0)?;
+        }
+        if format.contains(SampleFormat::IDENTIFIER) {
+            data.write_u64::<NativeEndian>(id)?;
+        }
+        Ok(())
+    }
+
+    fn note_mapped_file(&mut self, cpu_mode: CpuMode, path: RawData) {
+        let path = path.as_slice();
+        // Skip anonymous and special mappings such as [vdso].
+        if cpu_mode == CpuMode::User && path.starts_with(b"/") {
+            self.mapped_files.insert(path.into_owned());
+        }
+    }
+
+    fn build_id_section(&self) -> io::Result<Vec<u8>> {
+        let mut section = Vec::new();
+        if let Some(build_id) = &self.kernel_build_id {
+            write_build_id_event(
+                &mut section,
+                PERF_RECORD_MISC_KERNEL,
+                build_id,
+                KERNEL_BUILD_ID_NAME,
+            )?;
+        }
+        for path in &self.mapped_files {
+            let build_id = match build_id_for_file(Path::new(OsStr::from_bytes(path))) {
+                Some(build_id) => build_id,
+                None => continue,
+            };
+            write_build_id_event(&mut section, PERF_RECORD_MISC_USER, &build_id, path)?;
+        }
+        Ok(section)
+    }
+}
+
+fn cpu_mode_misc(cpu_mode: CpuMode) -> u16 {
+    match cpu_mode {
+        CpuMode::Kernel => PERF_RECORD_MISC_KERNEL,
+        _ => PERF_RECORD_MISC_USER,
+    }
+}
+
+/// Append `s` with a nul terminator, padded with zeros to a multiple of `align`.
+fn write_padded_string(data: &mut Vec<u8>, s: &[u8], align: usize) {
+    let padded_len = (s.len() + 1 + align - 1) / align * align;
+    data.extend_from_slice(s);
+    data.resize(data.len() + padded_len - s.len(), 0);
+}
+
+/// Serialize a `perf_header_string`.
+fn header_string(s: &str) -> io::Result<Vec<u8>> {
+    let mut string = Vec::new();
+    write_padded_string(&mut string, s.as_bytes(), NAME_ALIGN);
+    let mut data = Vec::new();
+    data.write_u32::<NativeEndian>(string.len() as u32)?;
+    data.extend_from_slice(&string);
+    Ok(data)
+}
+
+/// Serialize a `build_id_event` for the build ID section.
+fn write_build_id_event(
+    section: &mut Vec<u8>,
+    misc: u16,
+    build_id: &[u8],
+    path: &[u8],
+) -> io::Result<()> {
+    let mut path_bytes = Vec::new();
+    write_padded_string(&mut path_bytes, path, NAME_ALIGN);
+    let size = EVENT_HEADER_SIZE + 4 + 24 + path_bytes.len();
+    section.write_u32::<NativeEndian>(PERF_RECORD_HEADER_BUILD_ID)?;
+    section.write_u16::<NativeEndian>(misc | PERF_RECORD_MISC_BUILD_ID_SIZE)?;
+    section.write_u16::<NativeEndian>(size as u16)?;
+    // The pid of the machine, -1 for the host.
+    section.write_i32::<NativeEndian>(-1)?;
+    let mut build_id_bytes = [0; 24];
+    let len = build_id.len().min(20);
+    build_id_bytes[..len].copy_from_slice(&build_id[..len]);
+    build_id_bytes[20] = len as u8;
+    section.extend_from_slice(&build_id_bytes);
+    section.extend_from_slice(&path_bytes);
+    Ok(())
+}
+
+/// Serialize the `HEADER_EVENT_DESC` section, which has the names of the events.
+fn event_desc_section(attrs: &[PerfDataAttr]) -> io::Result<Vec<u8>> {
+    let attr_size = attrs.first().map_or(0, |attr| attr.raw_attr.len());
+    let mut section = Vec::new();
+    section.write_u32::<NativeEndian>(attrs.len() as u32)?;
+    section.write_u32::<NativeEndian>(attr_size as u32)?;
+    for attr in attrs {
+        section.extend_from_slice(&attr.raw_attr);
+        section.write_u32::<NativeEndian>(attr.ids.len() as u32)?;
+        section.extend_from_slice(&header_string(&attr.name)?);
+        for id in &attr.ids {
+            section.write_u64::<NativeEndian>(*id)?;
+        }
+    }
+    Ok(section)
+}
+
+fn build_id_for_file(path: &Path) -> Option<Vec<u8>> {
+    let file = File::open(path).ok()?;
+    let mmap = unsafe { memmap2::MmapOptions::new().map(&file) }.ok()?;
+    let object = object::File::parse(&mmap[..]).ok()?;
+    let build_id = object.build_id().ok()??;
+    Some(build_id.to_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use linux_perf_data::{PerfFileReader, PerfFileRecord};
+    use linux_perf_event_reader::constants::PERF_RECORD_MISC_USER;
+    use linux_perf_event_reader::{Endianness, Mmap2InodeAndVersion, RecordParseInfo};
+
+    use super::super::sys::{
+        PERF_ATTR_FLAG_FREQ, PERF_ATTR_FLAG_SAMPLE_ID_ALL, PERF_ATTR_FLAG_USE_CLOCKID,
+        PERF_COUNT_SW_CPU_CLOCK, PERF_SAMPLE_CPU, PERF_SAMPLE_IDENTIFIER, PERF_SAMPLE_IP,
+        PERF_SAMPLE_PERIOD, PERF_SAMPLE_TID, PERF_SAMPLE_TIME, PERF_TYPE_SOFTWARE,
+    };
+    use super::*;
+
+    const SAMPLE_TYPE: u64 = PERF_SAMPLE_IP
+        | PERF_SAMPLE_TID
+        | PERF_SAMPLE_TIME
+        | PERF_SAMPLE_CPU
+        | PERF_SAMPLE_PERIOD
+        | PERF_SAMPLE_IDENTIFIER;
+
+    fn make_attr(name: &str, id: u64) -> PerfDataAttr {
+        let mut raw_attr = Vec::new();
+        raw_attr
+            .write_u32::<NativeEndian>(PERF_TYPE_SOFTWARE)
+            .unwrap();
+        raw_attr.write_u32::<NativeEndian>(96).unwrap();
+        raw_attr
+            .write_u64::<NativeEndian>(PERF_COUNT_SW_CPU_CLOCK)
+            .unwrap();
+        raw_attr.write_u64::<NativeEndian>(1000).unwrap();
+        raw_attr.write_u64::<NativeEndian>(SAMPLE_TYPE).unwrap();
+        raw_attr.write_u64::<NativeEndian>(0).unwrap();
+        let flags = PERF_ATTR_FLAG_FREQ | PERF_ATTR_FLAG_SAMPLE_ID_ALL | PERF_ATTR_FLAG_USE_CLOCKID;
+        raw_attr.write_u64::<NativeEndian>(flags).unwrap();
+        raw_attr.resize(96, 0);
+        PerfDataAttr {
+            raw_attr,
+            name: name.to_string(),
+            ids: vec![id],
+        }
+    }
+
+    fn write_sample<W: Write + Seek>(
+        writer: &mut PerfDataWriter<W>,
+        attr: &PerfDataAttr,
+        tid: i32,
+        timestamp: u64,
+    ) {
+        let mut data = Vec::new();
+        data.write_u64::<NativeEndian>(attr.ids[0]).unwrap();
+        data.write_u64::<NativeEndian>(0x1234).unwrap();
+        data.write_i32::<NativeEndian>(123).unwrap();
+        data.write_i32::<NativeEndian>(tid).unwrap();
+        data.write_u64::<NativeEndian>(timestamp).unwrap();
+        data.write_u32::<NativeEndian>(0).unwrap();
+        data.write_u32::<NativeEndian>(0).unwrap();
+        data.write_u64::<NativeEndian>(1).unwrap();
+        let perf_attr = PerfEventAttr::parse::<_, NativeEndian>(&attr.raw_attr[..], None).unwrap();
+        let parse_info = RecordParseInfo::new(&perf_attr, Endianness::NATIVE);
+        let record = RawEventRecord::new(
+            RecordType::SAMPLE,
+            PERF_RECORD_MISC_USER,
+            RawData::Single(&data),
+            parse_info,
+        );
+        writer.write_record(&record).unwrap();
+    }
+
+    #[test]
+    fn round_trip() {
+        let main_attr = make_attr("cycles", 10);
+        let probe_attr = make_attr("probe_samply:malloc", 20);
+        let binary_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("..")
+            .join("fixtures")
+            .join("other")
+            .join("example-linux");
+        let binary_path = binary_path.canonicalize().unwrap();
+        let binary_path = binary_path.as_os_str().as_bytes();
+
+        let mut writer = PerfDataWriter::new(Cursor::new(Vec::new()), &main_attr).unwrap();
+        writer.write_comm(123, 124, b"worker").unwrap();
+        writer
+            .write_mmap2(&Mmap2Record {
+                pid: 123,
+                tid: 123,
+                address: 0x10000,
+                length: 0x2000,
+                page_offset: 0,
+                file_id: Mmap2FileId::InodeAndVersion(Mmap2InodeAndVersion {
+                    major: 8,
+                    minor: 1,
+                    inode: 42,
+                    inode_generation: 0,
+                }),
+                protection: (libc::PROT_READ | libc::PROT_EXEC) as u32,
+                flags: libc::MAP_PRIVATE as u32,
+                cpu_mode: CpuMode::User,
+                path: RawData::Single(binary_path),
+            })
+            .unwrap();
+        // Out of order within the round, like records from different CPUs.
+        write_sample(&mut writer, &main_attr, 124, 2000);
+        write_sample(&mut writer, &probe_attr, 123, 1000);
+        writer.finish_round().unwrap();
+        write_sample(&mut writer, &main_attr, 123, 3000);
+        let host_info = PerfDataHostInfo {
+            hostname: Some("test-host".to_string()),
+            os_release: Some("6.1.0".to_string()),
+            arch: Some("x86_64".to_string()),
+            cmdline: vec!["samply".to_string(), "record".to_string()],
+        };
+        let cursor = writer.finish(&[main_attr, probe_attr], &host_info).unwrap();
+
+        let PerfFileReader {
+            mut perf_file,
+            mut record_iter,
+        } = PerfFileReader::parse_file(Cursor::new(cursor.into_inner())).unwrap();
+        assert_eq!(perf_file.hostname().unwrap(), Some("test-host"));
+        assert_eq!(perf_file.os_release().unwrap(), Some("6.1.0"));
+        assert_eq!(perf_file.arch().unwrap(), Some("x86_64"));
+        assert_eq!(perf_file.cmdline().unwrap(), Some(vec!["samply", "record"]));
+        let names: Vec<_> = perf_file
+            .event_attributes()
+            .iter()
+            .map(|attr| attr.name())
+            .collect();
+        assert_eq!(names, [Some("cycles"), Some("probe_samply:malloc")]);
+        let build_ids = perf_file.build_ids().unwrap();
+        let dso_info = build_ids.values().next().unwrap();
+        assert_eq!(build_ids.len(), 1);
+        assert_eq!(dso_info.path, binary_path);
+        assert_eq!(
+            dso_info.build_id,
+            b"\x6c\x97\x4e\xbe\x52\x32\xee\x46\x9d\x6b\x78\x47\xa6\x70\xb2\xa9\x56\xf8\xae\xde"
+        );
+
+        let mut records = Vec::new();
+        while let Some(record) = record_iter.next_record(&mut perf_file).unwrap() {
+            let (attr_index, record) = match record {
+                PerfFileRecord::EventRecord { attr_index, record } => (attr_index, record),
+                PerfFileRecord::UserRecord(_) => panic!("unexpected user record"),
+            };
+            match record.parse().unwrap() {
+                EventRecord::Comm(e) => {
+                    assert_eq!((e.pid, e.tid), (123, 124));
+                    assert_eq!(&e.name.as_slice()[..], b"worker");
+                    records.push(("comm", attr_index, 0));
+                }
+                EventRecord::Mmap2(e) => {
+                    assert_eq!(e.address, 0x10000);
+                    assert_eq!(&e.path.as_slice()[..], binary_path);
+                    records.push(("mmap2", attr_index, 0));
+                }
+                EventRecord::Sample(e) => {
+                    assert_eq!(e.pid, Some(123));
+                    records.push(("sample", attr_index, e.timestamp.unwrap()));
+                }
+                other => panic!("unexpected record {:?}", other),
+            }
+        }
+        assert_eq!(
+            records,
+            [
+                ("comm", 0, 0),
+                ("mmap2", 0, 0),
+                ("sample", 1, 1000),
+                ("sample", 0, 2000),
+                ("sample", 0, 3000),
+            ]
+        );
+    }
+}
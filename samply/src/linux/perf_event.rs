@@ -1,4 +1,5 @@
 use std::cmp::max;
+use std::ffi::CString;
 use std::fmt;
 use std::io;
 use std::mem;
@@ -83,6 +84,7 @@ pub struct Perf {
     fd: RawFd,
     position: u64,
     parse_info: RecordParseInfo,
+    raw_attr: Vec<u8>,
 }
 
 impl Drop for Perf {
@@ -141,30 +143,81 @@ fn next_raw_event(
     Some(raw_event_location)
 }
 
-#[derive(Copy, Clone, PartialEq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum EventSource {
     HwCpuCycles,
     SwCpuClock,
+    /// A generic hardware event, with one of the `PERF_COUNT_HW_*` values as the config.
+    Hardware(u64),
+    /// A software event, with one of the `PERF_COUNT_SW_*` values as the config.
+    Software(u64),
+    /// A raw PMU event, with a CPU-specific config.
+    Raw(u64),
+    /// A tracepoint, with the tracepoint id from tracefs as the config.
+    Tracepoint(u64),
+}
+
+/// How often an event is sampled.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Sampling {
+    /// Sample at roughly this many samples per second. The kernel adjusts the
+    /// period dynamically.
+    Frequency(u64),
+    /// Sample every time the event has occurred this many times.
+    Period(u64),
+}
+
+/// An event source, together with the rate at which it is sampled.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct SampledEvent {
+    pub source: EventSource,
+    pub sampling: Sampling,
+}
+
+/// A uprobe or uretprobe, i.e. a breakpoint at an offset in an executable file.
+/// See the "uprobe" PMU in `/sys/bus/event_source/devices/uprobe`.
+#[derive(Clone, Debug)]
+pub struct Uprobe {
+    /// The PMU type of the uprobe PMU, from `/sys/bus/event_source/devices/uprobe/type`.
+    pub pmu_type: u32,
+    /// The config bit which turns the probe into a uretprobe, from
+    /// `/sys/bus/event_source/devices/uprobe/format/retprobe`.
+    pub retprobe_bit: u32,
+    pub path: CString,
+    pub file_offset: u64,
+    pub is_return: bool,
 }
 
 #[derive(Clone, Debug)]
 pub struct PerfBuilder {
-    pid: u32,
+    pid: Option<u32>,
     cpu: Option<u32>,
-    frequency: u64,
+    sampling: Sampling,
     stack_size: u32,
     reg_mask: u64,
+    callchain: bool,
+    raw: bool,
     event_source: EventSource,
     inherit: bool,
     start_disabled: bool,
     enable_on_exec: bool,
     exclude_kernel: bool,
     gather_context_switches: bool,
+    sideband: bool,
+    uprobe: Option<Uprobe>,
 }
 
 impl PerfBuilder {
     pub fn pid(mut self, pid: u32) -> Self {
-        self.pid = pid;
+        self.pid = Some(pid);
+        self
+    }
+
+    /// Observe all processes and threads. This needs to be combined with
+    /// `only_cpu`, because the kernel doesn't support events which are neither
+    /// restricted to a process nor to a CPU.
+    pub fn any_pid(mut self) -> Self {
+        self.pid = None;
         self
     }
 
@@ -178,8 +231,8 @@ impl PerfBuilder {
         self
     }
 
-    pub fn frequency(mut self, frequency: u64) -> Self {
-        self.frequency = frequency;
+    pub fn sampling(mut self, sampling: Sampling) -> Self {
+        self.sampling = sampling;
         self
     }
 
@@ -193,6 +246,19 @@ impl PerfBuilder {
         self
     }
 
+    /// Record the callchain, which the kernel obtains by walking the stack with
+    /// frame pointers.
+    pub fn sample_callchain(mut self) -> Self {
+        self.callchain = true;
+        self
+    }
+
+    /// Record the raw data of the event, i.e. the fields of a tracepoint.
+    pub fn sample_raw(mut self) -> Self {
+        self.raw = true;
+        self
+    }
+
     /// Turns on the kernel measurements. This requires the `/proc/sys/kernel/perf_event_paranoid` to be less than `2`.
     pub fn sample_kernel(mut self) -> Self {
         self.exclude_kernel = false;
@@ -224,12 +290,27 @@ impl PerfBuilder {
         self
     }
 
+    /// Don't report mmap, comm and task records. This is used if another event
+    /// already reports them.
+    pub fn no_sideband(mut self) -> Self {
+        self.sideband = false;
+        self
+    }
+
+    /// Sample on the uprobe instead of on the event source.
+    pub fn uprobe(mut self, uprobe: Uprobe) -> Self {
+        self.uprobe = Some(uprobe);
+        self
+    }
+
     pub fn open(self) -> io::Result<Perf> {
-        let pid = self.pid;
+        let pid: pid_t = self.pid.map(|pid| pid as pid_t).unwrap_or(-1);
         let cpu = self.cpu.map(|cpu| cpu as i32).unwrap_or(-1);
-        let frequency = self.frequency;
+        let sampling = self.sampling;
         let stack_size = self.stack_size;
         let reg_mask = self.reg_mask;
+        let callchain = self.callchain;
+        let raw = self.raw;
         let event_source = self.event_source;
         let inherit = self.inherit;
         let start_disabled = self.start_disabled;
@@ -237,10 +318,10 @@ impl PerfBuilder {
         let gather_context_switches = self.gather_context_switches;
 
         // debug!(
-        //     "Opening perf events; pid={}, cpu={}, frequency={}, stack_size={}, reg_mask=0x{:016X}, event_source={:?}, inherit={}, start_disabled={}...",
+        //     "Opening perf events; pid={}, cpu={}, sampling={:?}, stack_size={}, reg_mask=0x{:016X}, event_source={:?}, inherit={}, start_disabled={}...",
         //     pid,
         //     cpu,
-        //     frequency,
+        //     sampling,
         //     stack_size,
         //     reg_mask,
         //     event_source,
@@ -251,7 +332,7 @@ impl PerfBuilder {
         let max_sample_rate = Perf::max_sample_rate();
         if let Some(max_sample_rate) = max_sample_rate {
             // debug!("Maximum sample rate: {}", max_sample_rate);
-            if frequency > max_sample_rate {
+            if matches!(sampling, Sampling::Frequency(frequency) if frequency > max_sample_rate) {
                 let message = format!( "frequency can be at most {max_sample_rate} as configured in /proc/sys/kernel/perf_event_max_sample_rate" );
                 return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
             }
@@ -283,22 +364,48 @@ impl PerfBuilder {
         let mut attr: PerfEventAttr = unsafe { mem::zeroed() };
         attr.size = mem::size_of::<PerfEventAttr>() as u32;
 
-        match event_source {
-            EventSource::HwCpuCycles => {
+        match (&self.uprobe, event_source) {
+            (Some(uprobe), _) => {
+                attr.kind = uprobe.pmu_type;
+                if uprobe.is_return {
+                    attr.config = 1 << uprobe.retprobe_bit;
+                }
+                // The kernel reads the path during the perf_event_open call.
+                attr.bp_addr_or_config = uprobe.path.as_ptr() as u64;
+                attr.bp_len_or_config = uprobe.file_offset;
+            }
+            (None, EventSource::HwCpuCycles) => {
                 attr.kind = PERF_TYPE_HARDWARE;
                 attr.config = PERF_COUNT_HW_CPU_CYCLES;
             }
-            EventSource::SwCpuClock => {
+            (None, EventSource::SwCpuClock) => {
                 attr.kind = PERF_TYPE_SOFTWARE;
                 attr.config = PERF_COUNT_SW_CPU_CLOCK;
             }
+            (None, EventSource::Hardware(config)) => {
+                attr.kind = PERF_TYPE_HARDWARE;
+                attr.config = config;
+            }
+            (None, EventSource::Software(config)) => {
+                attr.kind = PERF_TYPE_SOFTWARE;
+                attr.config = config;
+            }
+            (None, EventSource::Raw(config)) => {
+                attr.kind = PERF_TYPE_RAW;
+                attr.config = config;
+            }
+            (None, EventSource::Tracepoint(id)) => {
+                attr.kind = PERF_TYPE_TRACEPOINT;
+                attr.config = id;
+            }
         }
 
         attr.sample_type = PERF_SAMPLE_IP
             | PERF_SAMPLE_TID
             | PERF_SAMPLE_TIME
             | PERF_SAMPLE_CPU
-            | PERF_SAMPLE_PERIOD;
+            | PERF_SAMPLE_PERIOD
+            | PERF_SAMPLE_IDENTIFIER;
 
         if reg_mask != 0 {
             attr.sample_type |= PERF_SAMPLE_REGS_USER;
@@ -308,18 +415,41 @@ impl PerfBuilder {
             attr.sample_type |= PERF_SAMPLE_STACK_USER;
         }
 
+        if callchain {
+            attr.sample_type |= PERF_SAMPLE_CALLCHAIN;
+        }
+
+        if raw {
+            attr.sample_type |= PERF_SAMPLE_RAW;
+        }
+
         attr.sample_regs_user = reg_mask;
         attr.sample_stack_user = stack_size;
-        attr.sample_period_or_freq = frequency;
 
-        attr.flags = PERF_ATTR_FLAG_DISABLED
-            | PERF_ATTR_FLAG_MMAP
-            | PERF_ATTR_FLAG_MMAP2
-            | PERF_ATTR_FLAG_MMAP_DATA
-            | PERF_ATTR_FLAG_COMM
-            | PERF_ATTR_FLAG_FREQ
-            | PERF_ATTR_FLAG_TASK
-            | PERF_ATTR_FLAG_SAMPLE_ID_ALL;
+        attr.flags =
+            PERF_ATTR_FLAG_DISABLED | PERF_ATTR_FLAG_SAMPLE_ID_ALL | PERF_ATTR_FLAG_USE_CLOCKID;
+
+        match sampling {
+            Sampling::Frequency(frequency) => {
+                attr.sample_period_or_freq = frequency;
+                attr.flags |= PERF_ATTR_FLAG_FREQ;
+            }
+            Sampling::Period(period) => {
+                attr.sample_period_or_freq = period;
+            }
+        }
+
+        if self.sideband {
+            attr.flags |= PERF_ATTR_FLAG_MMAP
+                | PERF_ATTR_FLAG_MMAP2
+                | PERF_ATTR_FLAG_MMAP_DATA
+                | PERF_ATTR_FLAG_COMM
+                | PERF_ATTR_FLAG_TASK;
+        }
+
+        // Use the same clock as JIT runtimes use for their jitdump records,
+        // so that we can order jitdump records and samples correctly.
+        attr.clock_id = libc::CLOCK_MONOTONIC;
 
         if self.enable_on_exec {
             attr.flags |= PERF_ATTR_FLAG_ENABLE_ON_EXEC;
@@ -337,7 +467,7 @@ impl PerfBuilder {
             attr.flags |= PERF_ATTR_FLAG_CONTEX_SWITCH;
         }
 
-        let fd = sys_perf_event_open(&attr, pid as pid_t, cpu as _, -1, PERF_FLAG_FD_CLOEXEC);
+        let fd = sys_perf_event_open(&attr, pid, cpu as _, -1, PERF_FLAG_FD_CLOEXEC);
         if fd < 0 {
             let err = io::Error::from_raw_os_error(-fd);
             // eprintln!(
@@ -393,6 +523,7 @@ impl PerfBuilder {
             attr_bytes, None,
         )
         .unwrap();
+        let raw_attr = attr_bytes.to_vec();
         let parse_info = RecordParseInfo::new(&attr2, Endianness::NATIVE);
 
         // debug!("Perf events open with fd={}", fd);
@@ -403,6 +534,7 @@ impl PerfBuilder {
             fd,
             position: 0,
             parse_info,
+            raw_attr,
         };
 
         if !start_disabled {
@@ -421,17 +553,21 @@ impl Perf {
 
     pub fn build() -> PerfBuilder {
         PerfBuilder {
-            pid: 0,
+            pid: Some(0),
             cpu: None,
-            frequency: 0,
+            sampling: Sampling::Frequency(0),
             stack_size: 0,
             reg_mask: 0,
+            callchain: false,
+            raw: false,
             event_source: EventSource::SwCpuClock,
             inherit: false,
             start_disabled: false,
             enable_on_exec: false,
             exclude_kernel: true,
             gather_context_switches: false,
+            sideband: true,
+            uprobe: None,
         }
     }
 
@@ -452,12 +588,97 @@ impl Perf {
         self.fd
     }
 
+    /// The ID which the kernel assigned to this perf event. Records carry this ID
+    /// in their `PERF_SAMPLE_IDENTIFIER` field.
+    pub fn id(&self) -> io::Result<u64> {
+        let mut id: u64 = 0;
+        let result = unsafe { libc::ioctl(self.fd, PERF_EVENT_IOC_ID as _, &mut id as *mut u64) };
+        if result == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(id)
+    }
+
+    /// The bytes of the `perf_event_attr` struct which this perf event was opened with.
+    pub fn raw_attr(&self) -> &[u8] {
+        &self.raw_attr
+    }
+
     #[inline]
     pub fn iter(&mut self) -> EventIter {
         EventIter::new(self)
     }
 }
 
+#[cfg(test)]
+impl Perf {
+    /// Create a perf event whose ring buffer already contains the given records,
+    /// as if the kernel had written them. Each record is given as its type and
+    /// the data after the header. Samples start with the `PERF_SAMPLE_IDENTIFIER`
+    /// and `PERF_SAMPLE_TIME` fields, and other records end with them.
+    pub fn with_records(records: &[(u32, Vec<u8>)]) -> Perf {
+        use std::os::unix::io::IntoRawFd;
+
+        let mut attr: PerfEventAttr = unsafe { mem::zeroed() };
+        attr.size = mem::size_of::<PerfEventAttr>() as u32;
+        attr.sample_type = PERF_SAMPLE_IDENTIFIER | PERF_SAMPLE_TIME;
+        attr.flags = PERF_ATTR_FLAG_SAMPLE_ID_ALL;
+        let attr_bytes = unsafe {
+            slice::from_raw_parts(
+                &attr as *const PerfEventAttr as *const u8,
+                mem::size_of::<PerfEventAttr>(),
+            )
+        };
+        let attr2 = linux_perf_event_reader::PerfEventAttr::parse::<_, byteorder::NativeEndian>(
+            attr_bytes, None,
+        )
+        .unwrap();
+        let parse_info = RecordParseInfo::new(&attr2, Endianness::NATIVE);
+
+        let size = 16 * 4096;
+        let buffer = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                (size + 4096) as usize,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        assert_ne!(buffer, libc::MAP_FAILED);
+        let buffer = buffer as *mut u8;
+
+        let mut data = Vec::new();
+        for (kind, record_data) in records {
+            let header_size = mem::size_of::<PerfEventHeader>();
+            data.extend_from_slice(&kind.to_ne_bytes());
+            data.extend_from_slice(&0u16.to_ne_bytes());
+            data.extend_from_slice(&((header_size + record_data.len()) as u16).to_ne_bytes());
+            data.extend_from_slice(record_data);
+        }
+        assert!(data.len() as u64 <= size);
+        unsafe {
+            ptr::copy_nonoverlapping(data.as_ptr(), buffer.offset(4096), data.len());
+            let page = &mut *(buffer as *mut PerfEventMmapPage);
+            page.data_head = data.len() as u64;
+        }
+
+        // The fd is only used to identify the member in its group.
+        let fd = std::fs::File::open("/dev/null").unwrap().into_raw_fd();
+        #[allow(clippy::arc_with_non_send_sync)]
+        Perf {
+            event_ref_state: Arc::new(Mutex::new(EventRefState::new(buffer, size))),
+            buffer,
+            size,
+            fd,
+            position: 0,
+            parse_info,
+            raw_attr: attr_bytes.to_vec(),
+        }
+    }
+}
+
 #[derive(Debug)]
 struct EventRefState {
     buffer: *mut u8,
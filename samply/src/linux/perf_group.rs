@@ -4,7 +4,9 @@ use std::ops::{Deref, DerefMut};
 use std::os::unix::io::RawFd;
 use std::{fs, io, vec};
 
-use super::perf_event::{EventRef, EventSource, Perf};
+use super::perf_data_writer::PerfDataAttr;
+use super::perf_event::{EventRef, EventSource, Perf, PerfBuilder, SampledEvent, Sampling, Uprobe};
+use crate::linux_shared::AllocationProbe;
 
 struct StoppedProcess(u32);
 
@@ -32,13 +34,15 @@ impl Drop for StoppedProcess {
 struct Member {
     perf: Perf,
     is_closed: Cell<bool>,
+    event: MemberEvent,
 }
 
 impl Member {
-    fn new(perf: Perf) -> Self {
+    fn new(perf: Perf, event: MemberEvent) -> Self {
         Member {
             perf,
             is_closed: Cell::new(false),
+            event,
         }
     }
 }
@@ -56,17 +60,80 @@ impl DerefMut for Member {
     }
 }
 
+/// What a member samples on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemberEvent {
+    /// The event at this index in `PerfGroup::events`.
+    Sampled(usize),
+    /// The `sched:sched_switch` tracepoint, which fires with the stack of the
+    /// thread that is about to go off-CPU.
+    SchedSwitch,
+    /// The `sched:sched_waking` or `sched:sched_wakeup` tracepoint.
+    SchedWakeup,
+    /// The `raw_syscalls:sys_enter` tracepoint.
+    SyscallEnter,
+    /// The `raw_syscalls:sys_exit` tracepoint, which records the callchain of
+    /// the syscall.
+    SyscallExit,
+    /// A uprobe on an allocation function.
+    AllocationProbe(AllocationProbe),
+}
+
+/// The attr of the members which sample on `event`, and the IDs of these members.
+struct OpenedAttr {
+    event: MemberEvent,
+    raw_attr: Vec<u8>,
+    ids: Vec<u64>,
+}
+
 pub struct PerfGroup {
-    event_buffer: Vec<EventRef>,
+    /// The events drained from the members, with the timestamp they're sorted by.
+    event_buffer: Vec<(u64, MemberEvent, EventRef)>,
     members: BTreeMap<RawFd, Member>,
+    /// The attrs of all members that were ever opened. Members are removed once
+    /// their perf event is closed, but the perf.data file still needs their IDs.
+    opened_attrs: Vec<OpenedAttr>,
     poll_fds: Vec<libc::pollfd>,
-    frequency: u32,
-    stack_size: u32,
-    regs_mask: u64,
-    event_source: EventSource,
+    /// The events to sample on. The first event also reports the mmap, comm, task
+    /// and context switch records.
+    events: Vec<SampledEvent>,
+    sched_tracepoints: Option<SchedTracepoints>,
+    syscall_tracepoints: Option<SyscallTracepoints>,
+    stack_sampling: StackSampling,
     stopped_processes: Vec<StoppedProcess>,
 }
 
+/// The scheduler tracepoints which are observed in addition to the sampled
+/// events.
+#[derive(Debug, Clone, Copy)]
+pub struct SchedTracepoints {
+    /// The id of `sched:sched_switch`, for off-CPU stacks.
+    pub switch: u64,
+    /// The id and the name of `sched_waking` or `sched_wakeup`, for thread
+    /// state markers. If set, the tracepoint fields of both tracepoints are
+    /// recorded.
+    pub wakeup: Option<(u64, &'static str)>,
+}
+
+/// The ids of the `raw_syscalls:sys_enter` and `raw_syscalls:sys_exit`
+/// tracepoints, for syscall markers.
+#[derive(Debug, Clone, Copy)]
+pub struct SyscallTracepoints {
+    pub enter: u64,
+    pub exit: u64,
+}
+
+/// What the kernel records about the stack of each sample.
+#[derive(Debug, Clone, Copy)]
+pub struct StackSampling {
+    /// The number of bytes of the user stack to copy, for DWARF unwinding.
+    pub stack_size: u32,
+    /// The user registers to record, for DWARF unwinding.
+    pub regs_mask: u64,
+    /// Whether to record the callchain, which the kernel walks with frame pointers.
+    pub callchain: bool,
+}
+
 fn poll_events<'a, I>(poll_fds: &mut Vec<libc::pollfd>, iter: I)
 where
     I: IntoIterator<Item = &'a Member>,
@@ -110,6 +177,27 @@ fn get_threads(pid: u32) -> Result<Vec<u32>, io::Error> {
     Ok(tids)
 }
 
+/// The ids of the online CPUs. These aren't necessarily contiguous, and they
+/// can include CPUs which the profiler's own affinity mask excludes.
+fn online_cpus() -> Vec<u32> {
+    fs::read_to_string("/sys/devices/system/cpu/online")
+        .ok()
+        .and_then(|cpu_list| parse_cpu_list(&cpu_list))
+        .unwrap_or_else(|| (0..num_cpus::get() as u32).collect())
+}
+
+/// Parse a CPU list in the kernel's format, e.g. `0-3,6,8-11`.
+fn parse_cpu_list(cpu_list: &str) -> Option<Vec<u32>> {
+    let mut cpus = Vec::new();
+    for range in cpu_list.trim().split(',') {
+        match range.split_once('-') {
+            Some((first, last)) => cpus.extend(first.parse::<u32>().ok()?..=last.parse().ok()?),
+            None => cpus.push(range.parse().ok()?),
+        }
+    }
+    Some(cpus)
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AttachMode {
     AttachWithEnableOnExec,
@@ -117,32 +205,189 @@ pub enum AttachMode {
 }
 
 impl PerfGroup {
-    pub fn new(frequency: u32, stack_size: u32, regs_mask: u64, event_source: EventSource) -> Self {
+    pub fn new(
+        events: Vec<SampledEvent>,
+        sched_tracepoints: Option<SchedTracepoints>,
+        syscall_tracepoints: Option<SyscallTracepoints>,
+        stack_sampling: StackSampling,
+    ) -> Self {
         PerfGroup {
             event_buffer: Vec::new(),
             members: Default::default(),
+            opened_attrs: Vec::new(),
             poll_fds: Vec::new(),
-            frequency,
-            stack_size,
-            event_source,
-            regs_mask,
+            events,
+            sched_tracepoints,
+            syscall_tracepoints,
+            stack_sampling,
             stopped_processes: Vec::new(),
         }
     }
 
     pub fn open(
         pid: u32,
-        frequency: u32,
-        stack_size: u32,
-        event_source: EventSource,
-        regs_mask: u64,
+        events: Vec<SampledEvent>,
+        sched_tracepoints: Option<SchedTracepoints>,
+        syscall_tracepoints: Option<SyscallTracepoints>,
+        stack_sampling: StackSampling,
         attach_mode: AttachMode,
     ) -> Result<Self, io::Error> {
-        let mut group = PerfGroup::new(frequency, stack_size, regs_mask, event_source);
+        let mut group = PerfGroup::new(
+            events,
+            sched_tracepoints,
+            syscall_tracepoints,
+            stack_sampling,
+        );
         group.open_process(pid, attach_mode)?;
         Ok(group)
     }
 
+    /// Open one perf event per CPU and sampled event which observes all processes
+    /// on the system. The events start out disabled.
+    pub fn open_all_cpus(
+        events: Vec<SampledEvent>,
+        sched_tracepoints: Option<SchedTracepoints>,
+        syscall_tracepoints: Option<SyscallTracepoints>,
+        stack_sampling: StackSampling,
+    ) -> Result<Self, io::Error> {
+        let mut group = PerfGroup::new(
+            events,
+            sched_tracepoints,
+            syscall_tracepoints,
+            stack_sampling,
+        );
+        let cpus = online_cpus();
+        for event in group.member_events() {
+            for &cpu in &cpus {
+                let perf = group
+                    .event_builder(event, true)
+                    .any_pid()
+                    .only_cpu(cpu as _)
+                    .open()?;
+                group.insert_member(Member::new(perf, event))?;
+            }
+        }
+        Ok(group)
+    }
+
+    /// The scheduler tracepoints which are observed, if any.
+    pub fn sched_tracepoints(&self) -> Option<SchedTracepoints> {
+        self.sched_tracepoints
+    }
+
+    /// The syscall tracepoints which are observed, if any.
+    pub fn syscall_tracepoints(&self) -> Option<SyscallTracepoints> {
+        self.syscall_tracepoints
+    }
+
+    /// The events which are opened for every observed process or CPU: the
+    /// sampled events, followed by the scheduler and syscall tracepoints if
+    /// requested.
+    fn member_events(&self) -> Vec<MemberEvent> {
+        let mut member_events: Vec<MemberEvent> =
+            (0..self.events.len()).map(MemberEvent::Sampled).collect();
+        if let Some(sched_tracepoints) = self.sched_tracepoints {
+            member_events.push(MemberEvent::SchedSwitch);
+            if sched_tracepoints.wakeup.is_some() {
+                member_events.push(MemberEvent::SchedWakeup);
+            }
+        }
+        if self.syscall_tracepoints.is_some() {
+            member_events.push(MemberEvent::SyscallEnter);
+            member_events.push(MemberEvent::SyscallExit);
+        }
+        member_events
+    }
+
+    /// Create a builder for a sampled event or for a tracepoint. Only
+    /// the first sampled event reports sideband records, so that we don't get
+    /// duplicate records if more than one event is sampled.
+    ///
+    /// `sched:sched_switch` records the callchain (kernel plus user) even when
+    /// unwinding with DWARF, so that the blocking stack is still known if DWARF
+    /// unwinding fails. Wakeups and syscall entries are recorded without stacks.
+    ///
+    /// `sys_exit` only records the callchain, without a copy of the user stack:
+    /// Only long syscalls get a stack in the profile, and copying the stack on
+    /// every syscall would overflow the ring buffers of syscall-heavy threads.
+    fn event_builder(&self, event: MemberEvent, gather_context_switches: bool) -> PerfBuilder {
+        let sched_tracepoints = || {
+            self.sched_tracepoints
+                .expect("The sched tracepoints were not requested")
+        };
+        let (sampled_event, sample_stack, callchain, raw) = match event {
+            MemberEvent::Sampled(event_index) => (
+                self.events[event_index],
+                true,
+                self.stack_sampling.callchain,
+                false,
+            ),
+            MemberEvent::SchedSwitch => {
+                let sched_tracepoints = sched_tracepoints();
+                let sampled_event = SampledEvent {
+                    source: EventSource::Tracepoint(sched_tracepoints.switch),
+                    sampling: Sampling::Period(1),
+                };
+                (
+                    sampled_event,
+                    true,
+                    true,
+                    sched_tracepoints.wakeup.is_some(),
+                )
+            }
+            MemberEvent::SchedWakeup => {
+                let (tracepoint, _) = sched_tracepoints()
+                    .wakeup
+                    .expect("The wakeup tracepoint was not requested");
+                let sampled_event = SampledEvent {
+                    source: EventSource::Tracepoint(tracepoint),
+                    sampling: Sampling::Period(1),
+                };
+                (sampled_event, false, false, true)
+            }
+            MemberEvent::SyscallEnter | MemberEvent::SyscallExit => {
+                let syscall_tracepoints = self
+                    .syscall_tracepoints
+                    .expect("The syscall tracepoints were not requested");
+                let (tracepoint, callchain) = match event {
+                    MemberEvent::SyscallEnter => (syscall_tracepoints.enter, false),
+                    _ => (syscall_tracepoints.exit, true),
+                };
+                let sampled_event = SampledEvent {
+                    source: EventSource::Tracepoint(tracepoint),
+                    sampling: Sampling::Period(1),
+                };
+                (sampled_event, false, callchain, true)
+            }
+            MemberEvent::AllocationProbe(_) => {
+                panic!("Allocation probes are opened by open_allocation_probes")
+            }
+        };
+        let mut builder = Perf::build()
+            .sampling(sampled_event.sampling)
+            .sample_kernel()
+            .event_source(sampled_event.source)
+            .start_disabled();
+        if sample_stack {
+            builder = builder
+                .sample_user_stack(self.stack_sampling.stack_size)
+                .sample_user_regs(self.stack_sampling.regs_mask);
+        }
+        if callchain {
+            builder = builder.sample_callchain();
+        }
+        if raw {
+            builder = builder.sample_raw();
+        }
+        if event != MemberEvent::Sampled(0) {
+            builder.no_sideband()
+        } else if gather_context_switches {
+            builder.gather_context_switches()
+        } else {
+            builder
+        }
+    }
+
     pub fn open_process(&mut self, pid: u32, attach_mode: AttachMode) -> Result<(), io::Error> {
         if attach_mode == AttachMode::StopAttachEnableResume {
             self.stopped_processes.push(StoppedProcess::new(pid)?);
@@ -150,78 +395,154 @@ impl PerfGroup {
         let mut perf_events = Vec::new();
         let threads = get_threads(pid)?;
 
-        let cpu_count = num_cpus::get();
-        for cpu in 0..cpu_count as u32 {
-            let mut builder = Perf::build()
-                .pid(pid)
-                .only_cpu(cpu as _)
-                .frequency(self.frequency as u64)
-                .sample_user_stack(self.stack_size)
-                .sample_user_regs(self.regs_mask)
-                .sample_kernel()
-                .gather_context_switches()
-                .event_source(self.event_source)
-                .inherit_to_children()
-                .start_disabled();
-
-            if attach_mode == AttachMode::AttachWithEnableOnExec {
-                builder = builder.enable_on_exec();
-            }
-
-            let perf = builder.open()?;
-
-            perf_events.push((Some(cpu), perf));
-        }
+        let cpus = online_cpus();
+        for event in self.member_events() {
+            for &cpu in &cpus {
+                let mut builder = self
+                    .event_builder(event, true)
+                    .pid(pid)
+                    .only_cpu(cpu as _)
+                    .inherit_to_children();
 
-        if cpu_count * (threads.len() + 1) >= 1000 {
-            for &tid in &threads {
-                let mut builder = Perf::build()
-                    .pid(tid)
-                    .any_cpu()
-                    .frequency(self.frequency as u64)
-                    .sample_user_stack(self.stack_size)
-                    .sample_user_regs(self.regs_mask)
-                    .sample_kernel()
-                    .event_source(self.event_source)
-                    .start_disabled();
                 if attach_mode == AttachMode::AttachWithEnableOnExec {
                     builder = builder.enable_on_exec();
                 }
+
                 let perf = builder.open()?;
 
-                perf_events.push((None, perf));
+                perf_events.push((event, perf));
             }
-        } else {
-            for cpu in 0..cpu_count as u32 {
+
+            if cpus.len() * (threads.len() + 1) >= 1000 {
                 for &tid in &threads {
-                    let mut builder = Perf::build()
-                        .pid(tid)
-                        .only_cpu(cpu as _)
-                        .frequency(self.frequency as u64)
-                        .sample_user_stack(self.stack_size)
-                        .sample_user_regs(self.regs_mask)
-                        .sample_kernel()
-                        .gather_context_switches()
-                        .event_source(self.event_source)
-                        .inherit_to_children()
-                        .start_disabled();
+                    let mut builder = self.event_builder(event, false).pid(tid).any_cpu();
                     if attach_mode == AttachMode::AttachWithEnableOnExec {
                         builder = builder.enable_on_exec();
                     }
                     let perf = builder.open()?;
 
-                    perf_events.push((Some(cpu), perf));
+                    perf_events.push((event, perf));
+                }
+            } else {
+                for &cpu in &cpus {
+                    for &tid in &threads {
+                        let mut builder = self
+                            .event_builder(event, true)
+                            .pid(tid)
+                            .only_cpu(cpu as _)
+                            .inherit_to_children();
+                        if attach_mode == AttachMode::AttachWithEnableOnExec {
+                            builder = builder.enable_on_exec();
+                        }
+                        let perf = builder.open()?;
+
+                        perf_events.push((event, perf));
+                    }
                 }
             }
         }
 
-        for (_cpu, perf) in perf_events {
-            self.members.insert(perf.fd(), Member::new(perf));
+        for (event, perf) in perf_events {
+            self.insert_member(Member::new(perf, event))?;
+        }
+
+        Ok(())
+    }
+
+    /// Open the uprobes on the allocation functions, for the launched process `pid`
+    /// and all the processes it spawns. `call_regs_mask` selects the registers which
+    /// hold the function arguments and the return value.
+    pub fn open_allocation_probes(
+        &mut self,
+        pid: u32,
+        probes: Vec<(AllocationProbe, Uprobe)>,
+        call_regs_mask: u64,
+    ) -> Result<(), io::Error> {
+        let cpus = online_cpus();
+        for (allocation_probe, uprobe) in probes {
+            for &cpu in &cpus {
+                let mut builder = Perf::build()
+                    .pid(pid)
+                    .only_cpu(cpu as _)
+                    .uprobe(uprobe.clone())
+                    .sampling(Sampling::Period(1))
+                    .no_sideband()
+                    .sample_user_stack(self.stack_sampling.stack_size)
+                    .sample_user_regs(self.stack_sampling.regs_mask | call_regs_mask)
+                    .inherit_to_children()
+                    .start_disabled()
+                    .enable_on_exec();
+                if self.stack_sampling.callchain {
+                    builder = builder.sample_callchain();
+                }
+                let perf = builder.open()?;
+                let event = MemberEvent::AllocationProbe(allocation_probe);
+                self.insert_member(Member::new(perf, event))?;
+            }
         }
+        Ok(())
+    }
 
+    fn insert_member(&mut self, member: Member) -> Result<(), io::Error> {
+        let event = member.event;
+        let id = member.id()?;
+        match self
+            .opened_attrs
+            .iter_mut()
+            .find(|attr| attr.event == event)
+        {
+            Some(attr) => attr.ids.push(id),
+            None => self.opened_attrs.push(OpenedAttr {
+                event,
+                raw_attr: member.raw_attr().to_owned(),
+                ids: vec![id],
+            }),
+        }
+        self.members.insert(member.fd(), member);
         Ok(())
     }
 
+    /// The attrs for the perf.data file, with the sampled events first, followed
+    /// by the scheduler and syscall tracepoints and the allocation probes. Members
+    /// which sample on the same event share an attr, even if their attrs differ
+    /// in details like the context switch flag, because their records have the
+    /// same layout.
+    pub fn perf_data_attrs(&self, event_name: &str) -> Vec<PerfDataAttr> {
+        let mut opened_attrs: Vec<&OpenedAttr> = self.opened_attrs.iter().collect();
+        opened_attrs.sort_by_key(|attr| match attr.event {
+            MemberEvent::Sampled(event_index) => (0, event_index),
+            MemberEvent::SchedSwitch => (1, 0),
+            MemberEvent::SchedWakeup => (2, 0),
+            MemberEvent::SyscallEnter => (3, 0),
+            MemberEvent::SyscallExit => (4, 0),
+            MemberEvent::AllocationProbe(_) => (5, 0),
+        });
+        opened_attrs
+            .into_iter()
+            .map(|attr| {
+                let name = match attr.event {
+                    MemberEvent::Sampled(_) => event_name.to_string(),
+                    MemberEvent::SchedSwitch => "sched:sched_switch".to_string(),
+                    MemberEvent::SchedWakeup => {
+                        let (_, name) = self.sched_tracepoints().unwrap().wakeup.unwrap();
+                        format!("sched:{name}")
+                    }
+                    MemberEvent::SyscallEnter => "raw_syscalls:sys_enter".to_string(),
+                    MemberEvent::SyscallExit => "raw_syscalls:sys_exit".to_string(),
+                    MemberEvent::AllocationProbe(probe) => {
+                        let suffix = if probe.is_return { "__return" } else { "" };
+                        format!("probe_samply:{}{suffix}", probe.function.symbol_name())
+                    }
+                };
+                PerfDataAttr {
+                    raw_attr: attr.raw_attr.clone(),
+                    name,
+                    ids: attr.ids.clone(),
+                }
+            })
+            .collect()
+    }
+
     pub fn is_empty(&self) -> bool {
         self.members.is_empty()
     }
@@ -244,7 +565,15 @@ impl PerfGroup {
         poll_events(&mut self.poll_fds, self.members.values());
     }
 
-    pub fn iter(&mut self) -> vec::Drain<EventRef> {
+    /// Drain the pending events of all members, together with the event of the
+    /// member they came from. The events are sorted by time, because the state
+    /// changes of a thread can be spread over several members, e.g. the entry
+    /// and return probes of an allocation function, or a `sched_switch` sample
+    /// and the context switch record of its next switch-in.
+    ///
+    /// Events without a timestamp are sorted as if they had the timestamp of the
+    /// previous event from the same member, so that they stay behind it.
+    pub fn iter(&mut self) -> impl ExactSizeIterator<Item = (MemberEvent, EventRef)> + '_ {
         self.event_buffer.clear();
 
         let mut fds_to_remove = Vec::new();
@@ -259,13 +588,166 @@ impl PerfGroup {
                 continue;
             }
 
-            self.event_buffer.extend(perf.iter());
+            let member_event = member.event;
+            let mut timestamp = 0;
+            self.event_buffer.extend(perf.iter().map(|event| {
+                timestamp = event.get().timestamp().unwrap_or(timestamp);
+                (timestamp, member_event, event)
+            }));
         }
 
         for fd in fds_to_remove {
             self.members.remove(&fd);
         }
 
-        self.event_buffer.drain(..)
+        // The sort is stable, so events with the same timestamp keep the order
+        // in which their member reported them.
+        self.event_buffer
+            .sort_by_key(|(timestamp, _, _)| *timestamp);
+
+        self.event_buffer
+            .drain(..)
+            .map(|(_, member_event, event)| (member_event, event))
+    }
+}
+
+#[test]
+fn test_parse_cpu_list() {
+    assert_eq!(parse_cpu_list("0\n"), Some(vec![0]));
+    assert_eq!(parse_cpu_list("0-3\n"), Some(vec![0, 1, 2, 3]));
+    assert_eq!(parse_cpu_list("0-1,4,6-7\n"), Some(vec![0, 1, 4, 6, 7]));
+    assert_eq!(parse_cpu_list(""), None);
+    assert_eq!(parse_cpu_list("0-x"), None);
+}
+
+/// A record type and the record's data after the header.
+#[cfg(test)]
+type TestRecord = (u32, Vec<u8>);
+
+/// Drain the given records of each member through `PerfGroup::iter`, and return
+/// the member event and the record type of each drained event.
+#[cfg(test)]
+fn drain_test_group(members: Vec<(MemberEvent, Vec<TestRecord>)>) -> Vec<(MemberEvent, u32)> {
+    let mut group = PerfGroup::new(
+        Vec::new(),
+        None,
+        None,
+        StackSampling {
+            stack_size: 0,
+            regs_mask: 0,
+            callchain: false,
+        },
+    );
+    for (event, records) in members {
+        let perf = Perf::with_records(&records);
+        group.members.insert(perf.fd(), Member::new(perf, event));
     }
+    group
+        .iter()
+        .map(|(member_event, event)| (member_event, event.get().record_type.0))
+        .collect()
+}
+
+/// A sample record with `PERF_SAMPLE_IDENTIFIER` and `PERF_SAMPLE_TIME`.
+#[cfg(test)]
+fn test_sample(timestamp: u64) -> TestRecord {
+    let mut data = 0u64.to_ne_bytes().to_vec();
+    data.extend_from_slice(&timestamp.to_ne_bytes());
+    (super::sys::PERF_RECORD_SAMPLE, data)
+}
+
+/// A context switch record, whose `sample_id` has the timestamp and the identifier.
+#[cfg(test)]
+fn test_switch(timestamp: u64) -> TestRecord {
+    let mut data = timestamp.to_ne_bytes().to_vec();
+    data.extend_from_slice(&0u64.to_ne_bytes());
+    (super::sys::PERF_RECORD_SWITCH, data)
+}
+
+#[test]
+fn test_iter_sorts_allocation_probes() {
+    use super::sys::PERF_RECORD_SAMPLE;
+    use crate::linux_shared::AllocationFunction;
+
+    // Two malloc calls, whose entry probe's member is drained before the
+    // return probe's member.
+    let entry = MemberEvent::AllocationProbe(AllocationProbe {
+        function: AllocationFunction::Malloc,
+        is_return: false,
+    });
+    let ret = MemberEvent::AllocationProbe(AllocationProbe {
+        function: AllocationFunction::Malloc,
+        is_return: true,
+    });
+    let events = drain_test_group(vec![
+        (entry, vec![test_sample(10), test_sample(30)]),
+        (ret, vec![test_sample(20), test_sample(40)]),
+    ]);
+    assert_eq!(
+        events,
+        vec![
+            (entry, PERF_RECORD_SAMPLE),
+            (ret, PERF_RECORD_SAMPLE),
+            (entry, PERF_RECORD_SAMPLE),
+            (ret, PERF_RECORD_SAMPLE),
+        ]
+    );
+}
+
+#[test]
+fn test_iter_sorts_sched_switch_before_switch_in() {
+    use super::sys::{PERF_RECORD_SAMPLE, PERF_RECORD_SWITCH};
+
+    // The sampled member reports the context switch records, and the off-CPU
+    // sample at the switch-in uses the stack of the preceding sched_switch
+    // sample from the tracepoint's member. Both blocking periods of the thread
+    // are in the same batch.
+    let sampled = MemberEvent::Sampled(0);
+    let sched_switch = MemberEvent::SchedSwitch;
+    let events = drain_test_group(vec![
+        (
+            sampled,
+            vec![
+                test_switch(11),
+                test_switch(20),
+                test_switch(31),
+                test_switch(40),
+            ],
+        ),
+        (sched_switch, vec![test_sample(10), test_sample(30)]),
+    ]);
+    assert_eq!(
+        events,
+        vec![
+            (sched_switch, PERF_RECORD_SAMPLE),
+            (sampled, PERF_RECORD_SWITCH),
+            (sampled, PERF_RECORD_SWITCH),
+            (sched_switch, PERF_RECORD_SAMPLE),
+            (sampled, PERF_RECORD_SWITCH),
+            (sampled, PERF_RECORD_SWITCH),
+        ]
+    );
+}
+
+#[test]
+fn test_iter_keeps_events_without_timestamp_behind_their_predecessor() {
+    use super::sys::PERF_RECORD_SAMPLE;
+
+    // Record types from 64 on are user types, which have no timestamp.
+    let user_record = (64, Vec::new());
+    let sampled = MemberEvent::Sampled(0);
+    let sched_switch = MemberEvent::SchedSwitch;
+    let events = drain_test_group(vec![
+        (sampled, vec![test_sample(10), user_record, test_sample(30)]),
+        (sched_switch, vec![test_sample(20)]),
+    ]);
+    assert_eq!(
+        events,
+        vec![
+            (sampled, PERF_RECORD_SAMPLE),
+            (sampled, 64),
+            (sched_switch, PERF_RECORD_SAMPLE),
+            (sampled, PERF_RECORD_SAMPLE),
+        ]
+    );
 }
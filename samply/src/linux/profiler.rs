@@ -1,6 +1,6 @@
 use linux_perf_data::linux_perf_event_reader::EventRecord;
 use linux_perf_data::linux_perf_event_reader::{
-    CpuMode, Mmap2FileId, Mmap2InodeAndVersion, Mmap2Record, RawData,
+    CpuMode, Mmap2FileId, Mmap2InodeAndVersion, Mmap2Record, RawData, RecordType,
 };
 
 use std::collections::HashMap;
@@ -12,13 +12,21 @@ use std::process::ExitStatus;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use super::perf_event::EventSource;
-use super::perf_group::{AttachMode, PerfGroup};
+use super::allocation_probes::allocation_uprobes;
+use super::event_spec::{tracepoint_format, tracepoint_id, EventSpec};
+use super::perf_data_writer::{PerfDataHostInfo, PerfDataWriter};
+use super::perf_group::{
+    AttachMode, MemberEvent, PerfGroup, SchedTracepoints, StackSampling, SyscallTracepoints,
+};
 use super::proc_maps;
 use super::process::SuspendedLaunchedProcess;
-use crate::linux_shared::{ConvertRegs, Converter, EventInterpretation};
+use crate::linux_shared::{
+    lost_samples_count, ConvertRegs, Converter, EventInterpretation, KernelSymbols,
+    SchedTracepointFormats, SyscallTracepointFormats,
+};
+use crate::recording_props::{RecordingProps, UnwindMode};
 use crate::server::{start_server_main, ServerProps};
 
 #[cfg(target_arch = "x86_64")]
@@ -27,12 +35,21 @@ pub type ConvertRegsNative = crate::linux_shared::ConvertRegsX86_64;
 #[cfg(target_arch = "aarch64")]
 pub type ConvertRegsNative = crate::linux_shared::ConvertRegsAarch64;
 
+type ConverterNative =
+    Converter<framehop::UnwinderNative<Vec<u8>, framehop::MayAllocateDuringUnwind>>;
+
+/// The perf.data file which the raw records are saved to.
+struct PerfDataOutput {
+    writer: PerfDataWriter<BufWriter<File>>,
+    /// The name of the sampled event, for the event descriptions in the file.
+    event_name: String,
+}
+
 pub fn start_recording(
     output_file: &Path,
     command_name: OsString,
     command_args: &[OsString],
-    time_limit: Option<Duration>,
-    interval: Duration,
+    recording_props: RecordingProps,
     server_props: Option<ServerProps>,
 ) -> Result<ExitStatus, ()> {
     // Ignore SIGINT while the subcommand is running. The signal still reaches the process
@@ -63,28 +80,53 @@ pub fn start_recording(
         let product = command_name_copy;
 
         // Create the perf events, setting ENABLE_ON_EXEC.
-        let (perf_group, converter) =
-            init_profiler(interval, pid, AttachMode::AttachWithEnableOnExec, &product);
+        let (mut perf_group, converter, perf_data) = init_profiler(
+            &recording_props,
+            ProfilingTarget::Pid(pid, AttachMode::AttachWithEnableOnExec),
+            &product,
+        );
+
+        if recording_props.allocations {
+            // The uprobes get enabled together with the sampling events once the child execs.
+            if let Err(err) = allocation_uprobes().and_then(|probes| {
+                perf_group.open_allocation_probes(pid, probes, ConvertRegsNative::call_regs_mask())
+            }) {
+                eprintln!("Failed to set up allocation profiling: {err}");
+                eprintln!(
+                    "Allocation profiling uses uprobes, which usually requires root privileges."
+                );
+                std::process::exit(1);
+            }
+        }
 
         // Tell the main thread to tell the child process to begin executing.
         s.send(()).unwrap();
         drop(s);
 
         // Create a stop flag which always stays false. We won't stop profiling until the
-        // child process is done.
+        // child process is done, or until the time limit has elapsed.
         // If Ctrl+C is pressed, it will reach the child process, and the child process
         // will act on it and maybe terminate. If it does, profiling stops too because
         // the main thread's wait() call below will exit.
         let stop_flag = Arc::new(AtomicBool::new(false));
 
         // Start profiling the process.
-        run_profiler(
+        let time_limit_expired = run_profiler(
             perf_group,
             converter,
+            perf_data,
             &output_file_copy,
-            time_limit,
+            recording_props.time_limit,
             stop_flag,
         );
+
+        // The main thread is waiting for the child to quit. If we stopped because
+        // of the time limit, the child is still running, so kill it.
+        if time_limit_expired {
+            unsafe {
+                libc::kill(pid as i32, libc::SIGKILL);
+            }
+        }
     });
 
     // We're on the main thread here and the observer thread has just been launched.
@@ -114,7 +156,7 @@ pub fn start_recording(
 
     // Now wait for the observer thread to quit. It will keep running until all
     // perf events are closed, which happens if all processes which the events
-    // are attached to have quit.
+    // are attached to have quit, or until the time limit has elapsed.
     observer_thread
         .join()
         .expect("couldn't join observer thread");
@@ -129,37 +171,92 @@ pub fn start_recording(
 pub fn start_profiling_pid(
     output_file: &Path,
     pid: u32,
-    time_limit: Option<Duration>,
-    interval: Duration,
+    recording_props: RecordingProps,
+    server_props: Option<ServerProps>,
+) {
+    profile_existing_processes(
+        output_file,
+        ProfilingTarget::Pid(pid, AttachMode::StopAttachEnableResume),
+        recording_props,
+        server_props,
+    )
+}
+
+pub fn start_profiling_all_cpus(
+    output_file: &Path,
+    recording_props: RecordingProps,
+    server_props: Option<ServerProps>,
+) {
+    profile_existing_processes(
+        output_file,
+        ProfilingTarget::AllCpus,
+        recording_props,
+        server_props,
+    )
+}
+
+/// What to attach the perf events to.
+#[derive(Debug, Clone, Copy)]
+enum ProfilingTarget {
+    /// A single process and all its threads and child processes.
+    Pid(u32, AttachMode),
+    /// Every process on the system, with one perf event per CPU.
+    AllCpus,
+}
+
+impl ProfilingTarget {
+    fn description(&self) -> String {
+        match self {
+            ProfilingTarget::Pid(pid, _) => format!("process with PID {pid}"),
+            ProfilingTarget::AllCpus => "all CPUs".to_string(),
+        }
+    }
+}
+
+fn profile_existing_processes(
+    output_file: &Path,
+    target: ProfilingTarget,
+    recording_props: RecordingProps,
     server_props: Option<ServerProps>,
 ) {
-    // When the first Ctrl+C is received, stop recording.
+    let time_limit = recording_props.time_limit;
+    // When the first Ctrl+C (or SIGTERM) is received, stop recording.
     // The server launches after the recording finishes. On the second Ctrl+C, terminate the server.
     let stop = Arc::new(AtomicBool::new(false));
     #[cfg(unix)]
-    signal_hook::flag::register_conditional_default(signal_hook::consts::SIGINT, stop.clone())
-        .expect("cannot register signal handler");
-    #[cfg(unix)]
-    signal_hook::flag::register(signal_hook::consts::SIGINT, stop.clone())
-        .expect("cannot register signal handler");
+    for signal in [signal_hook::consts::SIGINT, signal_hook::consts::SIGTERM] {
+        signal_hook::flag::register_conditional_default(signal, stop.clone())
+            .expect("cannot register signal handler");
+        signal_hook::flag::register(signal, stop.clone()).expect("cannot register signal handler");
+    }
 
     // Create a channel for the observer thread to notify the main thread once
     // profiling has been initialized.
     let (s, r) = crossbeam_channel::bounded(1);
 
     let output_file_copy = output_file.to_owned();
-    let product = format!("PID {pid}");
+    let product = match target {
+        ProfilingTarget::Pid(pid, _) => format!("PID {pid}"),
+        ProfilingTarget::AllCpus => "All processes".to_string(),
+    };
     let observer_thread = thread::spawn({
         let stop = stop.clone();
         move || {
-            let (perf_group, converter) =
-                init_profiler(interval, pid, AttachMode::StopAttachEnableResume, &product);
+            let (perf_group, converter, perf_data) =
+                init_profiler(&recording_props, target, &product);
 
             // Tell the main thread that we are now executing.
             s.send(()).unwrap();
             drop(s);
 
-            run_profiler(perf_group, converter, &output_file_copy, time_limit, stop)
+            run_profiler(
+                perf_group,
+                converter,
+                perf_data,
+                &output_file_copy,
+                time_limit,
+                stop,
+            );
         }
     });
 
@@ -170,11 +267,19 @@ pub fn start_profiling_pid(
     drop(r);
 
     // Now that we know that profiler initialization has succeeded, tell the user about it.
-    eprintln!("Recording process with PID {pid} until Ctrl+C...");
+    let description = target.description();
+    match time_limit {
+        Some(time_limit) => eprintln!(
+            "Recording {description} for {:.3}s or until Ctrl+C...",
+            time_limit.as_secs_f64()
+        ),
+        None => eprintln!("Recording {description} until Ctrl+C..."),
+    }
 
     // Now wait for the observer thread to quit. It will keep running until the
-    // stop flag has been set to true by Ctrl+C, or until all perf events are closed,
-    // which happens if all processes which the events are attached to have quit.
+    // stop flag has been set to true by Ctrl+C or SIGTERM, until the time limit
+    // has elapsed, or until all perf events are closed, which happens if all
+    // processes which the events are attached to have quit.
     observer_thread
         .join()
         .expect("couldn't join observer thread");
@@ -195,32 +300,149 @@ fn paranoia_level() -> Option<u32> {
 }
 
 fn init_profiler(
-    interval: Duration,
-    pid: u32,
-    attach_mode: AttachMode,
+    recording_props: &RecordingProps,
+    target: ProfilingTarget,
     product_name: &str,
-) -> (
-    PerfGroup,
-    Converter<framehop::UnwinderNative<Vec<u8>, framehop::MayAllocateDuringUnwind>>,
-) {
-    let interval_nanos = if interval.as_nanos() > 0 {
-        interval.as_nanos() as u64
+) -> (PerfGroup, Option<ConverterNative>, Option<PerfDataOutput>) {
+    let interval_nanos = if recording_props.interval.as_nanos() > 0 {
+        recording_props.interval.as_nanos() as u64
     } else {
         1_000_000 // 1 million nano seconds = 1 milli second
     };
 
-    let frequency = (1_000_000_000 / interval_nanos) as u32;
-    let stack_size = 32000;
+    let frequency = 1_000_000_000 / interval_nanos;
     let regs_mask = ConvertRegsNative::regs_mask();
+    let stack_sampling = match recording_props.unwind.unwrap_or(UnwindMode::Dwarf) {
+        UnwindMode::Fp => StackSampling {
+            stack_size: 0,
+            regs_mask: 0,
+            callchain: true,
+        },
+        UnwindMode::Dwarf => StackSampling {
+            stack_size: recording_props.stack_size.unwrap_or(32000),
+            regs_mask,
+            callchain: false,
+        },
+        UnwindMode::Hybrid => StackSampling {
+            stack_size: recording_props.stack_size.unwrap_or(4096),
+            regs_mask,
+            callchain: true,
+        },
+    };
+
+    let explicit_event_spec = recording_props
+        .event
+        .as_deref()
+        .map(|event| match EventSpec::parse(event, frequency) {
+            Ok(event_spec) => event_spec,
+            Err(error) => {
+                eprintln!("Invalid event {event:?}: {error}");
+                std::process::exit(1);
+            }
+        });
+
+    // Off-CPU stacks come from the sched:sched_switch tracepoint, and thread
+    // states also need sched:sched_waking. Reading their ids from tracefs usually
+    // requires root privileges; without them, neither is recorded.
+    let sched_tracepoints = match tracepoint_id("sched", "sched_switch") {
+        Ok(switch) => {
+            let wakeup = if recording_props.sched_states {
+                let wakeup = ["sched_waking", "sched_wakeup"]
+                    .iter()
+                    .find_map(|name| Some((tracepoint_id("sched", name).ok()?, *name)));
+                if wakeup.is_none() {
+                    eprintln!("Thread states will not be recorded: Could not find the sched_waking or sched_wakeup tracepoint.");
+                }
+                wakeup
+            } else {
+                None
+            };
+            Some(SchedTracepoints { switch, wakeup })
+        }
+        Err(error) => {
+            if recording_props.sched_states {
+                eprintln!("Thread states will not be recorded: {error}");
+            }
+            None
+        }
+    };
+
+    // Syscall markers come from the raw_syscalls tracepoints.
+    let syscall_tracepoints = if recording_props.syscalls {
+        let ids = tracepoint_id("raw_syscalls", "sys_enter")
+            .and_then(|enter| Ok((enter, tracepoint_id("raw_syscalls", "sys_exit")?)));
+        match ids {
+            Ok((enter, exit)) => Some(SyscallTracepoints { enter, exit }),
+            Err(error) => {
+                eprintln!("Syscalls will not be recorded: {error}");
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let open_perf_group_with =
+        |event_spec: &EventSpec, sched_tracepoints, syscall_tracepoints| match target {
+            ProfilingTarget::Pid(pid, attach_mode) => PerfGroup::open(
+                pid,
+                event_spec.events.clone(),
+                sched_tracepoints,
+                syscall_tracepoints,
+                stack_sampling,
+                attach_mode,
+            ),
+            ProfilingTarget::AllCpus => PerfGroup::open_all_cpus(
+                event_spec.events.clone(),
+                sched_tracepoints,
+                syscall_tracepoints,
+                stack_sampling,
+            ),
+        };
+    let open_perf_group = |event_spec: &EventSpec| {
+        // Don't observe sched:sched_switch twice if it is the sampled event.
+        let sched_tracepoints =
+            sched_tracepoints.filter(|_| event_spec.name != "sched:sched_switch");
+        let error = match open_perf_group_with(event_spec, sched_tracepoints, syscall_tracepoints) {
+            Err(error) if sched_tracepoints.is_some() || syscall_tracepoints.is_some() => error,
+            result => return result,
+        };
+
+        // Find out which of the tracepoints couldn't be opened, and only drop those.
+        let sched_failed = |sched_tracepoints: SchedTracepoints| {
+            let names = match sched_tracepoints.wakeup {
+                Some((_, wakeup)) => format!("sched:sched_switch and sched:{wakeup} tracepoints"),
+                None => "sched:sched_switch tracepoint".to_string(),
+            };
+            eprintln!("Could not open the {names}, off-CPU stacks and thread states will not be recorded: {error}");
+        };
+        let syscalls_failed = || {
+            eprintln!("Could not open the raw_syscalls:sys_enter and raw_syscalls:sys_exit tracepoints, syscalls will not be recorded: {error}");
+        };
+        if let (Some(sched), Some(_)) = (sched_tracepoints, syscall_tracepoints) {
+            if let Ok(perf) = open_perf_group_with(event_spec, Some(sched), None) {
+                syscalls_failed();
+                return Ok(perf);
+            }
+            if let Ok(perf) = open_perf_group_with(event_spec, None, syscall_tracepoints) {
+                sched_failed(sched);
+                return Ok(perf);
+            }
+        }
+        let perf = open_perf_group_with(event_spec, None, None)?;
+        if let Some(sched) = sched_tracepoints {
+            sched_failed(sched);
+        }
+        if syscall_tracepoints.is_some() {
+            syscalls_failed();
+        }
+        Ok(perf)
+    };
 
-    let perf = PerfGroup::open(
-        pid,
-        frequency,
-        stack_size,
-        EventSource::HwCpuCycles,
-        regs_mask,
-        attach_mode,
-    );
+    let mut event_spec = explicit_event_spec
+        .clone()
+        .unwrap_or_else(|| EventSpec::cycles(frequency));
+    let perf = open_perf_group(&event_spec);
 
     let mut perf = match perf {
         Ok(perf) => perf,
@@ -238,19 +460,18 @@ fn init_profiler(
                     eprintln!();
                     std::process::exit(1);
                 }
+                _ if explicit_event_spec.is_some() => {
+                    eprintln!("Failed to start profiling: {error}");
+                    eprintln!("The event {:?} may not be supported on this machine, or it may require root privileges.", event_spec.name);
+                    std::process::exit(1);
+                }
                 _ => {
                     // Permission denied even though parania was probably not the reason.
                     // Another reason for the error could be the type of perf event:
                     // The "Hardware CPU cycles" event is not supported in some contexts, for example in VMs.
                     // Try a different event type.
-                    let perf = PerfGroup::open(
-                        pid,
-                        frequency,
-                        stack_size,
-                        EventSource::SwCpuClock,
-                        regs_mask,
-                        attach_mode,
-                    );
+                    event_spec = EventSpec::cpu_clock(frequency);
+                    let perf = open_perf_group(&event_spec);
                     match perf {
                         Ok(perf) => perf, // Success!
                         Err(error) => {
@@ -267,20 +488,70 @@ fn init_profiler(
         }
     };
 
+    let mut perf_data = recording_props.save_perf_data.as_deref().map(|path| {
+        let attrs = perf.perf_data_attrs(&event_spec.name);
+        let mut writer = match PerfDataWriter::create(path, &attrs[0]) {
+            Ok(writer) => writer,
+            Err(error) => {
+                eprintln!("Could not create the perf.data file {path:?}: {error}");
+                std::process::exit(1);
+            }
+        };
+        if let Ok(kernel_symbols) = KernelSymbols::new_for_running_kernel() {
+            writer
+                .write_kernel_mmap(kernel_symbols.base_avma, &kernel_symbols.build_id)
+                .expect("Couldn't write to the perf.data file");
+        }
+        PerfDataOutput {
+            writer,
+            event_name: event_spec.name.clone(),
+        }
+    });
+
     let first_sample_time = 0;
 
     let little_endian = cfg!(target_endian = "little");
     let machine_info = uname::uname().ok();
+    let sched_state_formats = perf
+        .sched_tracepoints()
+        .and_then(|sched_tracepoints| sched_tracepoints.wakeup)
+        .map(|(_, wakeup_name)| {
+            let mut formats = SchedTracepointFormats::default();
+            if let Ok(format) = tracepoint_format("sched", "sched_switch") {
+                formats.set_switch_format(&format);
+            }
+            if let Ok(format) = tracepoint_format("sched", wakeup_name) {
+                formats.set_wakeup_format(&format);
+            }
+            formats
+        });
+    let mut syscall_formats = SyscallTracepointFormats::default();
+    if perf.syscall_tracepoints().is_some() {
+        if let Ok(format) = tracepoint_format("raw_syscalls", "sys_enter") {
+            syscall_formats.set_enter_format(&format);
+        }
+        if let Ok(format) = tracepoint_format("raw_syscalls", "sys_exit") {
+            syscall_formats.set_exit_format(&format);
+        }
+    }
     let interpretation = EventInterpretation {
         main_event_attr_index: 0,
-        main_event_name: "cycles".to_string(),
-        sampling_is_time_based: Some(interval_nanos),
+        main_event_name: event_spec.name.clone(),
+        sampling_is_time_based: event_spec.sampling_interval_nanos(),
         have_context_switches: true,
         sched_switch_attr_index: None,
+        sched_wakeup_attr_index: None,
+        sched_state_formats,
+        syscall_enter_attr_index: None,
+        syscall_exit_attr_index: None,
+        syscall_formats,
+        clock_is_monotonic: true,
     };
 
-    let mut converter =
-        Converter::<framehop::UnwinderNative<Vec<u8>, framehop::MayAllocateDuringUnwind>>::new(
+    let mut converter = if recording_props.perf_data_only {
+        None
+    } else {
+        Some(ConverterNative::new(
             product_name,
             None,
             HashMap::new(),
@@ -290,25 +561,84 @@ fn init_profiler(
             framehop::CacheNative::new(),
             None,
             interpretation,
-        );
+        ))
+    };
+
+    match target {
+        ProfilingTarget::Pid(pid, _) => {
+            // TODO: Gather threads / processes recursively, here and in PerfGroup setup.
+            add_existing_process(
+                converter.as_mut(),
+                perf_data.as_mut().map(|perf_data| &mut perf_data.writer),
+                pid,
+            )
+            .expect("couldn't read the process information from /proc");
+        }
+        ProfilingTarget::AllCpus => {
+            for entry in std::fs::read_dir("/proc")
+                .expect("couldn't read /proc")
+                .flatten()
+            {
+                let pid: u32 = match entry.file_name().to_string_lossy().parse() {
+                    Ok(pid) => pid,
+                    Err(_) => continue,
+                };
+                // Processes can exit while we're looking at them, and we may not be
+                // allowed to read the maps of some processes. Skip those.
+                let _ = add_existing_process(
+                    converter.as_mut(),
+                    perf_data.as_mut().map(|perf_data| &mut perf_data.writer),
+                    pid,
+                );
+            }
+        }
+    }
+
+    // eprintln!("Enabling perf events...");
+    match target {
+        ProfilingTarget::Pid(_, AttachMode::StopAttachEnableResume) | ProfilingTarget::AllCpus => {
+            perf.enable()
+        }
+        ProfilingTarget::Pid(_, AttachMode::AttachWithEnableOnExec) => {
+            // The perf event will get enabled automatically once the forked child process execs.
+        }
+    }
+
+    (perf, converter, perf_data)
+}
 
-    // TODO: Gather threads / processes recursively, here and in PerfGroup setup.
-    for entry in std::fs::read_dir(format!("/proc/{pid}/task"))
-        .unwrap()
-        .flatten()
-    {
-        let tid: u32 = entry.file_name().to_string_lossy().parse().unwrap();
+/// Tell the converter about the threads and the memory mappings of a process
+/// which was already running when profiling started, and synthesize the
+/// corresponding records in the perf.data file.
+fn add_existing_process(
+    mut converter: Option<&mut ConverterNative>,
+    mut perf_data: Option<&mut PerfDataWriter<BufWriter<File>>>,
+    pid: u32,
+) -> std::io::Result<()> {
+    let maps = read_string_lossy(format!("/proc/{pid}/maps"))?;
+
+    for entry in std::fs::read_dir(format!("/proc/{pid}/task"))?.flatten() {
+        let tid: u32 = match entry.file_name().to_string_lossy().parse() {
+            Ok(tid) => tid,
+            Err(_) => continue,
+        };
         let comm_path = format!("/proc/{pid}/task/{tid}/comm");
         if let Ok(buffer) = std::fs::read(comm_path) {
             let length = memchr::memchr(b'\0', &buffer).unwrap_or(buffer.len());
-            let name = std::str::from_utf8(&buffer[..length]).unwrap().trim_end();
-            converter.set_thread_name(pid as i32, tid as i32, name, true);
+            let name = String::from_utf8_lossy(&buffer[..length]);
+            let name = name.trim_end();
+            if let Some(perf_data) = perf_data.as_mut() {
+                perf_data
+                    .write_comm(pid as i32, tid as i32, name.as_bytes())
+                    .expect("Couldn't write to the perf.data file");
+            }
+            if let Some(converter) = converter.as_mut() {
+                converter.set_thread_name(pid as i32, tid as i32, name, true);
+            }
         }
     }
 
-    let maps = read_string_lossy(format!("/proc/{pid}/maps")).expect("couldn't read proc maps");
     let maps = proc_maps::parse(&maps);
-
     for region in maps {
         let mut protection = 0;
         if region.is_read {
@@ -328,7 +658,7 @@ fn init_profiler(
             flags |= libc::MAP_PRIVATE;
         }
 
-        converter.handle_mmap2(Mmap2Record {
+        let record = Mmap2Record {
             pid: pid as i32,
             tid: pid as i32,
             address: region.start,
@@ -342,39 +672,52 @@ fn init_profiler(
             }),
             protection: protection as _,
             flags: flags as _,
-            path: RawData::Single(&region.name.into_bytes()),
+            path: RawData::Single(region.name.as_bytes()),
             cpu_mode: CpuMode::User,
-        });
-    }
-
-    // eprintln!("Enabling perf events...");
-    match attach_mode {
-        AttachMode::StopAttachEnableResume => perf.enable(),
-        AttachMode::AttachWithEnableOnExec => {
-            // The perf event will get enabled automatically once the forked child process execs.
+        };
+        if let Some(perf_data) = perf_data.as_mut() {
+            perf_data
+                .write_mmap2(&record)
+                .expect("Couldn't write to the perf.data file");
+        }
+        if let Some(converter) = converter.as_mut() {
+            converter.handle_mmap2(record);
         }
     }
 
-    (perf, converter)
+    Ok(())
 }
 
+/// Returns whether recording stopped because the time limit expired.
 fn run_profiler(
     mut perf: PerfGroup,
-    mut converter: Converter<framehop::UnwinderNative<Vec<u8>, framehop::MayAllocateDuringUnwind>>,
+    mut converter: Option<ConverterNative>,
+    mut perf_data: Option<PerfDataOutput>,
     output_filename: &Path,
-    _time_limit: Option<Duration>,
+    time_limit: Option<Duration>,
     stop: Arc<AtomicBool>,
-) {
+) -> bool {
     // eprintln!("Running...");
 
+    // perf.wait() returns at least once per second, so the time limit is
+    // honored with roughly that granularity.
+    let stop_time = time_limit.map(|time_limit| Instant::now() + time_limit);
+
     let mut wait = false;
-    let mut pending_lost_events = 0;
     let mut total_lost_events = 0;
+    let mut time_limit_expired = false;
     loop {
         if stop.load(Ordering::SeqCst) || perf.is_empty() {
             break;
         }
 
+        if let Some(stop_time) = stop_time {
+            if Instant::now() >= stop_time {
+                time_limit_expired = true;
+                break;
+            }
+        }
+
         if wait {
             wait = false;
             perf.wait();
@@ -386,19 +729,42 @@ fn run_profiler(
             continue;
         }
 
-        for event_ref in iter {
+        for (member_event, event_ref) in iter {
             let record = event_ref.get();
+            if let Some(perf_data) = perf_data.as_mut() {
+                perf_data
+                    .writer
+                    .write_record(&record)
+                    .expect("Couldn't write to the perf.data file");
+            }
+            let converter = match converter.as_mut() {
+                Some(converter) => converter,
+                None => continue,
+            };
             let parsed_record = record.parse().unwrap();
             // debug!("Recording parsed_record: {:#?}", parsed_record);
 
             match parsed_record {
-                EventRecord::Sample(e) => {
-                    converter.handle_sample::<ConvertRegsNative>(e);
-                    /*
-                    } else if interpretation.sched_switch_attr_index == Some(attr_index) {
-                        converter.handle_sched_switch::<C>(e);
-                    }*/
-                }
+                EventRecord::Sample(e) => match member_event {
+                    MemberEvent::Sampled(_) => {
+                        converter.handle_sample::<ConvertRegsNative>(e);
+                    }
+                    MemberEvent::SchedSwitch => {
+                        converter.handle_sched_switch::<ConvertRegsNative>(e);
+                    }
+                    MemberEvent::SchedWakeup => {
+                        converter.handle_sched_wakeup(e);
+                    }
+                    MemberEvent::SyscallEnter => {
+                        converter.handle_syscall_enter(e);
+                    }
+                    MemberEvent::SyscallExit => {
+                        converter.handle_syscall_exit::<ConvertRegsNative>(e);
+                    }
+                    MemberEvent::AllocationProbe(allocation_probe) => {
+                        converter.handle_allocation_probe::<ConvertRegsNative>(allocation_probe, e);
+                    }
+                },
                 EventRecord::Fork(e) => {
                     converter.handle_thread_start(e);
                 }
@@ -421,18 +787,37 @@ fn run_profiler(
                     };
                     converter.handle_context_switch(e, common);
                 }
-                EventRecord::Lost(event) => {
-                    pending_lost_events += event.count;
-                    total_lost_events += event.count;
-                    continue;
+                EventRecord::Lost(e) => {
+                    total_lost_events += e.count;
+                    if let Ok(common) = record.common_data() {
+                        converter.handle_lost_events(e.count, common);
+                    }
+                }
+                EventRecord::Throttle(e) => {
+                    if let Ok(common) = record.common_data() {
+                        converter.handle_throttle(e, common);
+                    }
+                }
+                EventRecord::Unthrottle(e) => {
+                    converter.handle_unthrottle(e);
+                }
+                EventRecord::Raw(raw) if raw.record_type == RecordType::LOST_SAMPLES => {
+                    if let (Some(count), Ok(common)) =
+                        (lost_samples_count(&raw), record.common_data())
+                    {
+                        total_lost_events += count;
+                        converter.handle_lost_events(count, common);
+                    }
                 }
                 _ => {}
             }
+        }
 
-            if pending_lost_events > 0 {
-                // eprintln!("Pending lost events: {pending_lost_events}");
-                pending_lost_events = 0;
-            }
+        if let Some(perf_data) = perf_data.as_mut() {
+            perf_data
+                .writer
+                .finish_round()
+                .expect("Couldn't write to the perf.data file");
         }
     }
 
@@ -440,11 +825,22 @@ fn run_profiler(
         eprintln!("Lost {total_lost_events} events.");
     }
 
-    let profile = converter.finish();
+    if let Some(PerfDataOutput { writer, event_name }) = perf_data {
+        let attrs = perf.perf_data_attrs(&event_name);
+        writer
+            .finish(&attrs, &PerfDataHostInfo::for_this_machine())
+            .expect("Couldn't write the perf.data file");
+    }
+
+    if let Some(converter) = converter {
+        let profile = converter.finish();
+
+        let output_file = File::create(output_filename).unwrap();
+        let writer = BufWriter::new(output_file);
+        serde_json::to_writer(writer, &profile).expect("Couldn't write JSON");
+    }
 
-    let output_file = File::create(output_filename).unwrap();
-    let writer = BufWriter::new(output_file);
-    serde_json::to_writer(writer, &profile).expect("Couldn't write JSON");
+    time_limit_expired
 }
 
 pub fn read_string_lossy<P: AsRef<Path>>(path: P) -> std::io::Result<String> {
@@ -22,6 +22,7 @@ pub const PERF_FLAG_FD_CLOEXEC: c_ulong = 1 << 3;
 pub const PERF_TYPE_HARDWARE: u32 = 0;
 pub const PERF_TYPE_SOFTWARE: u32 = 1;
 pub const PERF_TYPE_TRACEPOINT: u32 = 2;
+pub const PERF_TYPE_RAW: u32 = 4;
 
 pub const PERF_ATTR_FLAG_DISABLED: u64 = flag!(0);
 pub const PERF_ATTR_FLAG_INHERIT: u64 = flag!(1);
@@ -50,11 +51,25 @@ pub const PERF_ATTR_FLAG_USE_CLOCKID: u64 = flag!(25);
 pub const PERF_ATTR_FLAG_CONTEX_SWITCH: u64 = flag!(26);
 
 pub const PERF_COUNT_HW_CPU_CYCLES: u64 = 0;
+pub const PERF_COUNT_HW_INSTRUCTIONS: u64 = 1;
+pub const PERF_COUNT_HW_CACHE_REFERENCES: u64 = 2;
+pub const PERF_COUNT_HW_CACHE_MISSES: u64 = 3;
+pub const PERF_COUNT_HW_BRANCH_INSTRUCTIONS: u64 = 4;
+pub const PERF_COUNT_HW_BRANCH_MISSES: u64 = 5;
+pub const PERF_COUNT_HW_BUS_CYCLES: u64 = 6;
+pub const PERF_COUNT_HW_STALLED_CYCLES_FRONTEND: u64 = 7;
+pub const PERF_COUNT_HW_STALLED_CYCLES_BACKEND: u64 = 8;
 pub const PERF_COUNT_HW_REF_CPU_CYCLES: u64 = 9;
 
 pub const PERF_COUNT_SW_CPU_CLOCK: u64 = 0;
 pub const PERF_COUNT_SW_TASK_CLOCK: u64 = 1;
 pub const PERF_COUNT_SW_PAGE_FAULTS: u64 = 2;
+pub const PERF_COUNT_SW_CONTEXT_SWITCHES: u64 = 3;
+pub const PERF_COUNT_SW_CPU_MIGRATIONS: u64 = 4;
+pub const PERF_COUNT_SW_PAGE_FAULTS_MIN: u64 = 5;
+pub const PERF_COUNT_SW_PAGE_FAULTS_MAJ: u64 = 6;
+pub const PERF_COUNT_SW_ALIGNMENT_FAULTS: u64 = 7;
+pub const PERF_COUNT_SW_EMULATION_FAULTS: u64 = 8;
 pub const PERF_COUNT_SW_DUMMY: u64 = 9;
 
 pub const PERF_RECORD_LOST: u32 = 2;
@@ -221,6 +236,7 @@ mod ioctl {
         pub const IOC_SIZEBITS: c_ulong = 14;
         pub const IOC_DIRBITS: c_ulong = 2;
         pub const IOC_NONE: c_ulong = 0;
+        pub const IOC_READ: c_ulong = 2;
     }
 
     #[cfg(any(
@@ -235,6 +251,7 @@ mod ioctl {
         pub const IOC_SIZEBITS: c_ulong = 13;
         pub const IOC_DIRBITS: c_ulong = 3;
         pub const IOC_NONE: c_ulong = 1;
+        pub const IOC_READ: c_ulong = 2;
     }
 
     pub use self::arch::*;
@@ -262,8 +279,15 @@ macro_rules! io {
     };
 }
 
+macro_rules! ior {
+    ($kind:expr, $nr:expr, $size:expr) => {
+        ioc!(ioctl::IOC_READ, $kind, $nr, $size)
+    };
+}
+
 pub const PERF_EVENT_IOC_ENABLE: c_ulong = io!(b'$', 0);
 pub const PERF_EVENT_IOC_DISABLE: c_ulong = io!(b'$', 1);
+pub const PERF_EVENT_IOC_ID: c_ulong = ior!(b'$', 7, std::mem::size_of::<*mut u64>() as c_ulong);
 
 #[repr(C)]
 pub struct PerfEventAttr {
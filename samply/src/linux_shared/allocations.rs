@@ -0,0 +1,235 @@
+use fxprof_processed_profile::{CategoryPairHandle, Frame};
+
+use std::collections::HashMap;
+
+/// One of the libc functions which are observed for allocation profiling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AllocationFunction {
+    Malloc,
+    Calloc,
+    Realloc,
+    Free,
+}
+
+impl AllocationFunction {
+    pub const ALL: [AllocationFunction; 4] = [
+        AllocationFunction::Malloc,
+        AllocationFunction::Calloc,
+        AllocationFunction::Realloc,
+        AllocationFunction::Free,
+    ];
+
+    pub fn symbol_name(&self) -> &'static str {
+        match self {
+            AllocationFunction::Malloc => "malloc",
+            AllocationFunction::Calloc => "calloc",
+            AllocationFunction::Realloc => "realloc",
+            AllocationFunction::Free => "free",
+        }
+    }
+
+    /// Whether we need to see the return value of this function. `free` doesn't
+    /// return anything, so it only needs a probe at the function entry.
+    pub fn needs_return_probe(&self) -> bool {
+        !matches!(self, AllocationFunction::Free)
+    }
+}
+
+/// Identifies a uprobe on an allocation function: either at the function's
+/// entry, where the arguments are known, or at its return, where the address
+/// of the new allocation is known.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllocationProbe {
+    pub function: AllocationFunction,
+    pub is_return: bool,
+}
+
+/// An allocation or a deallocation, with the stack of the call to the allocation
+/// function. Deallocations have a negative size.
+#[derive(Debug, Clone)]
+pub struct AllocationSample {
+    pub address: u64,
+    pub size: i64,
+    pub stack: Vec<(Frame, CategoryPairHandle)>,
+}
+
+/// A call to an allocation function whose return probe hasn't been hit yet.
+#[derive(Debug)]
+struct PendingCall {
+    function: AllocationFunction,
+    size: u64,
+    old_address: u64,
+    stack: Vec<(Frame, CategoryPairHandle)>,
+}
+
+/// Matches up the entry and return probe hits of the allocation functions and
+/// turns them into allocation samples.
+///
+/// The sizes of live allocations are remembered so that deallocations can be
+/// given the (negative) size of the allocation they free. Allocations which
+/// happened before profiling started are unknown, and freeing them doesn't
+/// produce a sample.
+#[derive(Debug, Default)]
+pub struct AllocationTracker {
+    /// The calls which are currently executing, per tid, innermost call last.
+    pending_calls: HashMap<i32, Vec<PendingCall>>,
+    /// The size of each live allocation, keyed by pid and address.
+    live_allocations: HashMap<(i32, u64), u64>,
+}
+
+impl AllocationTracker {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Handle a hit of an entry probe. `args` are the first two integer
+    /// arguments of the call.
+    pub fn handle_call(
+        &mut self,
+        pid: i32,
+        tid: i32,
+        function: AllocationFunction,
+        args: (u64, u64),
+        stack: Vec<(Frame, CategoryPairHandle)>,
+    ) -> Vec<AllocationSample> {
+        let pending_calls = self.pending_calls.entry(tid).or_default();
+        if function == AllocationFunction::Free {
+            // Calls from inside another allocation function, e.g. realloc calling
+            // free, are accounted for by the outer call.
+            if !pending_calls.is_empty() {
+                return Vec::new();
+            }
+            return self.deallocation(pid, args.0, stack).into_iter().collect();
+        }
+
+        let (size, old_address) = match function {
+            AllocationFunction::Malloc => (args.0, 0),
+            AllocationFunction::Calloc => (args.0.saturating_mul(args.1), 0),
+            AllocationFunction::Realloc => (args.1, args.0),
+            AllocationFunction::Free => unreachable!(),
+        };
+        pending_calls.push(PendingCall {
+            function,
+            size,
+            old_address,
+            stack,
+        });
+        Vec::new()
+    }
+
+    /// Handle a hit of a return probe. `return_value` is the address of the
+    /// new allocation, or zero if the allocation failed.
+    pub fn handle_return(
+        &mut self,
+        pid: i32,
+        tid: i32,
+        return_value: u64,
+    ) -> Vec<AllocationSample> {
+        let pending_calls = match self.pending_calls.get_mut(&tid) {
+            Some(pending_calls) => pending_calls,
+            None => return Vec::new(),
+        };
+        let call = match pending_calls.pop() {
+            Some(call) => call,
+            None => return Vec::new(),
+        };
+        if !pending_calls.is_empty() {
+            // This is a nested call, e.g. realloc calling malloc.
+            return Vec::new();
+        }
+
+        let mut samples = Vec::new();
+        if call.function == AllocationFunction::Realloc && call.old_address != 0 {
+            if return_value == 0 && call.size != 0 {
+                // The reallocation failed, and the old allocation is still alive.
+                return samples;
+            }
+            samples.extend(self.deallocation(pid, call.old_address, call.stack.clone()));
+        }
+        if return_value != 0 {
+            self.live_allocations.insert((pid, return_value), call.size);
+            samples.push(AllocationSample {
+                address: return_value,
+                size: call.size as i64,
+                stack: call.stack,
+            });
+        }
+        samples
+    }
+
+    /// Forget the state of a thread which has exited. If it was the main thread,
+    /// forget the allocations of its process.
+    pub fn handle_thread_end(&mut self, pid: i32, tid: i32) {
+        self.pending_calls.remove(&tid);
+        if pid == tid {
+            self.live_allocations.retain(|(p, _), _| *p != pid);
+        }
+    }
+
+    fn deallocation(
+        &mut self,
+        pid: i32,
+        address: u64,
+        stack: Vec<(Frame, CategoryPairHandle)>,
+    ) -> Option<AllocationSample> {
+        let size = self.live_allocations.remove(&(pid, address))?;
+        Some(AllocationSample {
+            address,
+            size: -(size as i64),
+            stack,
+        })
+    }
+}
+
+#[test]
+fn test_allocation_tracker() {
+    let mut tracker = AllocationTracker::new();
+    let (pid, tid) = (10, 11);
+    let sizes = |samples: Vec<AllocationSample>| -> Vec<(u64, i64)> {
+        samples.iter().map(|s| (s.address, s.size)).collect()
+    };
+
+    // malloc(100) = 0x1000
+    assert!(
+        sizes(tracker.handle_call(pid, tid, AllocationFunction::Malloc, (100, 0), vec![]))
+            .is_empty()
+    );
+    assert_eq!(
+        sizes(tracker.handle_return(pid, tid, 0x1000)),
+        vec![(0x1000, 100)]
+    );
+
+    // realloc(0x1000, 200) = 0x2000, which calls malloc and free internally.
+    tracker.handle_call(pid, tid, AllocationFunction::Realloc, (0x1000, 200), vec![]);
+    tracker.handle_call(pid, tid, AllocationFunction::Malloc, (200, 0), vec![]);
+    assert!(sizes(tracker.handle_return(pid, tid, 0x2000)).is_empty());
+    assert!(
+        sizes(tracker.handle_call(pid, tid, AllocationFunction::Free, (0x1000, 0), vec![]))
+            .is_empty()
+    );
+    assert_eq!(
+        sizes(tracker.handle_return(pid, tid, 0x2000)),
+        vec![(0x1000, -100), (0x2000, 200)]
+    );
+
+    // calloc(4, 8) = 0x3000
+    tracker.handle_call(pid, tid, AllocationFunction::Calloc, (4, 8), vec![]);
+    assert_eq!(
+        sizes(tracker.handle_return(pid, tid, 0x3000)),
+        vec![(0x3000, 32)]
+    );
+
+    // free(0x2000), and free of an unknown address.
+    assert_eq!(
+        sizes(tracker.handle_call(pid, tid, AllocationFunction::Free, (0x2000, 0), vec![])),
+        vec![(0x2000, -200)]
+    );
+    assert!(
+        sizes(tracker.handle_call(pid, tid, AllocationFunction::Free, (0x9000, 0), vec![]))
+            .is_empty()
+    );
+
+    // A failed malloc doesn't produce a sample.
+    tracker.handle_call(pid, tid, AllocationFunction::Malloc, (1 << 60, 0), vec![]);
+    assert!(sizes(tracker.handle_return(pid, tid, 0)).is_empty());
+}
@@ -16,6 +16,9 @@ impl JitCategoryManager {
         ("Ion: ", "Ion", CategoryColor::Green),
         ("IC: ", "IC", CategoryColor::Brown),
         ("Trampoline: ", "Trampoline", CategoryColor::DarkGray),
+        ("LazyCompile:~", "Interpreter", CategoryColor::Red),
+        ("LazyCompile:*", "Turbofan", CategoryColor::Green),
+        ("py::", "Python", CategoryColor::Yellow),
         ("", "JIT", CategoryColor::Purple), // Generic fallback category for JIT code
     ];
 
@@ -0,0 +1,399 @@
+use debugid::DebugId;
+use framehop::{Module, ModuleSvmaInfo, ModuleUnwindData, TextByteData, Unwinder};
+use fxprof_processed_profile::{LibraryInfo, ProcessHandle, Profile, Symbol, SymbolTable};
+use samply_symbols::{JitDumpHeader, JitDumpRecord};
+use wholesym::samply_symbols;
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::jit_category_manager::JitCategoryManager;
+use super::{open_file_with_fallback, read_appended_data, JitFunction, JitFunctions};
+
+/// Returns whether the mapped file is a jitdump file, i.e. whether its
+/// filename has the form `jit-<pid>.dump`.
+///
+/// JIT runtimes mmap the jitdump file so that the mapping shows up in the perf
+/// event stream; the mapping itself doesn't contain any code.
+pub fn is_jitdump_path(path: &[u8]) -> bool {
+    let filename = match path.iter().rposition(|b| *b == b'/') {
+        Some(pos) => &path[pos + 1..],
+        None => path,
+    };
+    match filename
+        .strip_prefix(b"jit-")
+        .and_then(|rest| rest.strip_suffix(b".dump"))
+    {
+        Some(pid) => !pid.is_empty() && pid.iter().all(u8::is_ascii_digit),
+        None => false,
+    }
+}
+
+/// Keeps track of the jitdump files of a single process, and turns their
+/// records into libraries in the profile.
+#[derive(Debug, Default)]
+pub struct JitDumpManager {
+    processors: Vec<SingleJitDumpProcessor>,
+}
+
+impl JitDumpManager {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn add_jitdump_path(&mut self, path: &Path, fallback_dir: Option<&Path>) {
+        if self.processors.iter().any(|p| p.original_path == path) {
+            return;
+        }
+        match open_file_with_fallback(path, fallback_dir) {
+            Ok((file, actual_path)) => {
+                self.processors.push(SingleJitDumpProcessor::new(
+                    path.to_owned(),
+                    actual_path,
+                    file,
+                ));
+            }
+            Err(err) => {
+                eprintln!("Could not open jitdump file {path:?}: {err}");
+            }
+        }
+    }
+
+    /// Process all records with a timestamp up to and including `timestamp`.
+    /// If `timestamp` is `None`, all records which have been written to the file
+    /// so far are processed.
+    #[allow(clippy::too_many_arguments)]
+    pub fn process_pending_records<U>(
+        &mut self,
+        timestamp: Option<u64>,
+        process_handle: ProcessHandle,
+        profile: &mut Profile,
+        jit_category_manager: &mut JitCategoryManager,
+        jit_functions: &mut JitFunctions,
+        unwinder: &mut U,
+    ) where
+        U: Unwinder<Module = Module<Vec<u8>>>,
+    {
+        for processor in &mut self.processors {
+            processor.process_pending_records(
+                timestamp,
+                process_handle,
+                profile,
+                jit_category_manager,
+                jit_functions,
+                unwinder,
+            );
+        }
+    }
+}
+
+#[derive(Debug)]
+struct SingleJitDumpProcessor {
+    /// The path from the mmap record.
+    original_path: PathBuf,
+    /// The path we opened, which is used as the path of the libraries in the profile.
+    path: String,
+    name: String,
+    file: File,
+    /// The file contents which have been read so far.
+    data: Vec<u8>,
+    /// The number of bytes read from `file` so far.
+    read_len: u64,
+    header: Option<JitDumpHeader>,
+    /// The file offset of the next unprocessed record.
+    offset: u64,
+    /// Set once we've encountered a parse error, after which we stop reading the file.
+    is_broken: bool,
+    /// Keyed by code_index.
+    functions: HashMap<u64, JitDumpFunction>,
+    pending_unwind_info: Option<PendingUnwindInfo>,
+}
+
+#[derive(Debug, Clone)]
+struct JitDumpFunction {
+    /// The file offset of the code bytes, which we use as the relative address.
+    relative_address: u64,
+    code_addr: u64,
+    code_size: u64,
+    name: String,
+    /// Kept so that the unwinder module can be re-created when the code moves.
+    unwind_info: Option<PendingUnwindInfo>,
+}
+
+#[derive(Debug, Clone)]
+struct PendingUnwindInfo {
+    eh_frame: Vec<u8>,
+    eh_frame_hdr: Vec<u8>,
+}
+
+impl SingleJitDumpProcessor {
+    fn new(original_path: PathBuf, path: PathBuf, file: File) -> Self {
+        let name = path
+            .file_name()
+            .map_or("<jitdump>".into(), |f| f.to_string_lossy().to_string());
+        Self {
+            original_path,
+            path: path.to_string_lossy().to_string(),
+            name,
+            file,
+            data: Vec::new(),
+            read_len: 0,
+            header: None,
+            offset: 0,
+            is_broken: false,
+            functions: HashMap::new(),
+            pending_unwind_info: None,
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn process_pending_records<U>(
+        &mut self,
+        timestamp: Option<u64>,
+        process_handle: ProcessHandle,
+        profile: &mut Profile,
+        jit_category_manager: &mut JitCategoryManager,
+        jit_functions: &mut JitFunctions,
+        unwinder: &mut U,
+    ) where
+        U: Unwinder<Module = Module<Vec<u8>>>,
+    {
+        if self.is_broken {
+            return;
+        }
+
+        // Read anything that has been appended to the file since we last looked.
+        if let Err(err) = read_appended_data(&mut self.file, &mut self.read_len, &mut self.data) {
+            eprintln!("Could not read jitdump file {}: {err}", self.path);
+            self.is_broken = true;
+            return;
+        }
+
+        let header = match &self.header {
+            Some(header) => header.clone(),
+            None => match JitDumpHeader::parse(&self.data) {
+                Ok(header) if header.uses_arch_timestamps() => {
+                    // We'd need the TSC conversion parameters from the perf
+                    // event mmap page to compare these with the sample times.
+                    eprintln!(
+                        "Ignoring jitdump file {} because its timestamps don't use CLOCK_MONOTONIC",
+                        self.path
+                    );
+                    self.is_broken = true;
+                    return;
+                }
+                Ok(header) => {
+                    self.header = Some(header.clone());
+                    header
+                }
+                Err(_) if self.data.len() < JitDumpHeader::SIZE => return,
+                Err(err) => {
+                    eprintln!("Could not parse jitdump file {}: {err}", self.path);
+                    self.is_broken = true;
+                    return;
+                }
+            },
+        };
+        let debug_id = header.debug_id();
+
+        let data = std::mem::take(&mut self.data);
+        let mut records = header.records(&data, self.offset);
+        loop {
+            let record_offset = records.offset();
+            let (record_timestamp, record) = match records.next() {
+                Some(Ok(record)) => record,
+                Some(Err(err)) => {
+                    eprintln!("Could not parse jitdump file {}: {err}", self.path);
+                    self.is_broken = true;
+                    break;
+                }
+                None => break,
+            };
+            if matches!(timestamp, Some(timestamp) if record_timestamp > timestamp) {
+                // Leave this record for later.
+                self.offset = record_offset;
+                break;
+            }
+            self.offset = records.offset();
+
+            match record {
+                JitDumpRecord::CodeLoad(load) => {
+                    let function = JitDumpFunction {
+                        relative_address: load.code_bytes_offset,
+                        code_addr: load.code_addr,
+                        code_size: load.code_bytes.len() as u64,
+                        name: String::from_utf8_lossy(load.function_name).into_owned(),
+                        unwind_info: self.pending_unwind_info.take(),
+                    };
+                    self.add_function(
+                        &function,
+                        debug_id,
+                        process_handle,
+                        profile,
+                        jit_category_manager,
+                        jit_functions,
+                    );
+                    add_unwinder_module(unwinder, &function, load.code_bytes);
+                    self.functions.insert(load.code_index, function);
+                }
+                JitDumpRecord::CodeMove(code_move) => {
+                    let function = match self.functions.get(&code_move.code_index) {
+                        Some(function) => function.clone(),
+                        None => continue,
+                    };
+                    if let Some(base_avma) =
+                        function.code_addr.checked_sub(function.relative_address)
+                    {
+                        profile.unload_lib(process_handle, base_avma);
+                    }
+                    unwinder.remove_module(function.code_addr);
+                    let moved_function = JitDumpFunction {
+                        code_addr: code_move.new_code_addr,
+                        ..function
+                    };
+                    self.add_function(
+                        &moved_function,
+                        debug_id,
+                        process_handle,
+                        profile,
+                        jit_category_manager,
+                        jit_functions,
+                    );
+                    // The code bytes are still in the file, at the relative address.
+                    let code_start = usize::try_from(moved_function.relative_address).ok();
+                    let code_size = usize::try_from(moved_function.code_size).ok();
+                    let code_bytes = code_start
+                        .zip(code_size)
+                        .and_then(|(start, size)| data.get(start..start.checked_add(size)?));
+                    if let Some(code_bytes) = code_bytes {
+                        add_unwinder_module(unwinder, &moved_function, code_bytes);
+                    }
+                    self.functions.insert(code_move.code_index, moved_function);
+                }
+                JitDumpRecord::CodeUnwindingInfo(unwind_info) => {
+                    self.pending_unwind_info = Some(PendingUnwindInfo {
+                        eh_frame: unwind_info.eh_frame().to_owned(),
+                        eh_frame_hdr: unwind_info.eh_frame_hdr().to_owned(),
+                    });
+                }
+                JitDumpRecord::CodeDebugInfo(_) => {
+                    // Line information is obtained from the jitdump file during
+                    // symbolication, see samply_symbols::JitDumpSymbolMap.
+                }
+                JitDumpRecord::CodeClose | JitDumpRecord::Other(_) => {}
+            }
+        }
+        self.data = data;
+    }
+
+    /// Add a library for a single JIT function to the profile.
+    ///
+    /// All functions from the same jitdump file share the same path and debug ID,
+    /// so that they are symbolicated with the same symbol map. The relative address
+    /// of each function is the file offset of its code bytes, so each function
+    /// has its own base address.
+    #[allow(clippy::too_many_arguments)]
+    fn add_function(
+        &self,
+        function: &JitDumpFunction,
+        debug_id: DebugId,
+        process_handle: ProcessHandle,
+        profile: &mut Profile,
+        jit_category_manager: &mut JitCategoryManager,
+        jit_functions: &mut JitFunctions,
+    ) {
+        let start_avma = function.code_addr;
+        let end_avma = function.code_addr + function.code_size;
+
+        let category = jit_category_manager.get_category(Some(&function.name), profile);
+        jit_functions.insert(JitFunction {
+            start_address: start_avma,
+            end_address: end_avma,
+            category,
+        });
+
+        let base_avma = match start_avma.checked_sub(function.relative_address) {
+            Some(base_avma) => base_avma,
+            None => return,
+        };
+        let symbol_table = SymbolTable::new(vec![Symbol {
+            address: function.relative_address,
+            size: Some(function.code_size),
+            name: function.name.clone(),
+        }]);
+        profile.add_lib(
+            process_handle,
+            LibraryInfo {
+                base_avma,
+                avma_range: start_avma..end_avma,
+                debug_id,
+                code_id: None,
+                path: self.path.clone(),
+                debug_path: self.path.clone(),
+                debug_name: self.name.clone(),
+                name: self.name.clone(),
+                arch: None,
+                symbol_table: Some(Arc::new(symbol_table)),
+            },
+        );
+    }
+}
+
+/// Tell the unwinder about the code of a JIT function.
+///
+/// The unwinding info from a `JIT_CODE_UNWINDING_INFO` record is laid out the
+/// same way as in the ELF files created by `perf inject --jit`: the `.eh_frame`
+/// section directly follows the code, and the `.eh_frame_hdr` section directly
+/// follows the `.eh_frame` section.
+fn add_unwinder_module<U>(unwinder: &mut U, function: &JitDumpFunction, code_bytes: &[u8])
+where
+    U: Unwinder<Module = Module<Vec<u8>>>,
+{
+    let code_size = function.code_size;
+    let avma_range = function.code_addr..function.code_addr + code_size;
+    let (unwind_data, eh_frame_svma, eh_frame_hdr_svma) = match function.unwind_info.clone() {
+        Some(PendingUnwindInfo {
+            eh_frame,
+            eh_frame_hdr,
+        }) => {
+            let eh_frame_end = code_size + eh_frame.len() as u64;
+            let eh_frame_hdr_end = eh_frame_end + eh_frame_hdr.len() as u64;
+            (
+                ModuleUnwindData::EhFrameHdrAndEhFrame(eh_frame_hdr, eh_frame),
+                Some(code_size..eh_frame_end),
+                Some(eh_frame_end..eh_frame_hdr_end),
+            )
+        }
+        None => (ModuleUnwindData::None, None, None),
+    };
+    let module = Module::new(
+        function.name.clone(),
+        avma_range.clone(),
+        function.code_addr,
+        ModuleSvmaInfo {
+            base_svma: 0,
+            text: Some(0..code_size),
+            text_env: None,
+            stubs: None,
+            stub_helper: None,
+            eh_frame: eh_frame_svma,
+            eh_frame_hdr: eh_frame_hdr_svma,
+            got: None,
+        },
+        unwind_data,
+        Some(TextByteData::new(code_bytes.to_owned(), avma_range)),
+    );
+    unwinder.add_module(module);
+}
+
+#[test]
+fn test_is_jitdump_path() {
+    assert!(is_jitdump_path(b"/tmp/jit-1234.dump"));
+    assert!(is_jitdump_path(b"jit-5.dump"));
+    assert!(!is_jitdump_path(b"/tmp/jit-.dump"));
+    assert!(!is_jitdump_path(b"/tmp/jit-12a.dump"));
+    assert!(!is_jitdump_path(b"/tmp/jitted-1234-5.so"));
+}
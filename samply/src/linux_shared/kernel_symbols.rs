@@ -17,9 +17,6 @@ pub enum KernelSymbolsError {
 
     #[error("Did not find a _text symbol in the kernel symbol list")]
     NoTextSymbol,
-
-    #[error("Relative address {0:#x} does not fit into u32")]
-    RelativeAddressTooLarge(u64),
 }
 
 #[derive(Debug, Clone)]
@@ -110,12 +107,8 @@ pub fn parse_kallsyms(data: &[u8]) -> Result<(u64, SymbolTable), KernelSymbolsEr
                 });
             }
             (Some(text_addr), _) if absolute_addr >= text_addr => {
-                let relative_address = absolute_addr - text_addr;
-                use std::convert::TryFrom;
-                let relative_address = u32::try_from(relative_address)
-                    .map_err(|_| KernelSymbolsError::RelativeAddressTooLarge(relative_address))?;
                 symbols.push(Symbol {
-                    address: relative_address,
+                    address: absolute_addr - text_addr,
                     size: None,
                     name: String::from_utf8_lossy(symbol_name).to_string(),
                 });
@@ -0,0 +1,211 @@
+use fxprof_processed_profile::{
+    MarkerDynamicField, MarkerFieldFormat, MarkerLocation, MarkerSchema, MarkerSchemaField,
+    ProfilerMarker,
+};
+use serde_json::json;
+
+/// Marks a time range in which the kernel dropped events because the ring
+/// buffer was full.
+#[derive(Debug, Clone)]
+pub struct SamplesLostMarker {
+    pub count: u64,
+}
+
+impl ProfilerMarker for SamplesLostMarker {
+    const MARKER_TYPE_NAME: &'static str = "SamplesLost";
+
+    fn json_marker_data(&self) -> serde_json::Value {
+        json!({
+            "type": Self::MARKER_TYPE_NAME,
+            "count": self.count,
+        })
+    }
+
+    fn schema() -> MarkerSchema {
+        MarkerSchema {
+            type_name: Self::MARKER_TYPE_NAME,
+            locations: vec![
+                MarkerLocation::MarkerChart,
+                MarkerLocation::MarkerTable,
+                MarkerLocation::TimelineOverview,
+            ],
+            chart_label: Some("{marker.data.count} lost"),
+            tooltip_label: Some("{marker.data.count} events lost"),
+            table_label: Some("{marker.name} - {marker.data.count} events"),
+            fields: vec![MarkerSchemaField::Dynamic(MarkerDynamicField {
+                key: "count",
+                label: "Lost events",
+                format: MarkerFieldFormat::Integer,
+                searchable: None,
+            })],
+        }
+    }
+}
+
+/// Marks a time range in which the kernel throttled sampling, because the
+/// sampling interrupts were taking up too much CPU time.
+#[derive(Debug, Clone)]
+pub struct SamplingThrottledMarker;
+
+impl ProfilerMarker for SamplingThrottledMarker {
+    const MARKER_TYPE_NAME: &'static str = "SamplingThrottled";
+
+    fn json_marker_data(&self) -> serde_json::Value {
+        json!({
+            "type": Self::MARKER_TYPE_NAME,
+        })
+    }
+
+    fn schema() -> MarkerSchema {
+        MarkerSchema {
+            type_name: Self::MARKER_TYPE_NAME,
+            locations: vec![
+                MarkerLocation::MarkerChart,
+                MarkerLocation::MarkerTable,
+                MarkerLocation::TimelineOverview,
+            ],
+            chart_label: None,
+            tooltip_label: None,
+            table_label: None,
+            fields: vec![],
+        }
+    }
+}
+
+/// Marks a time range in which a thread was in one scheduling state: running,
+/// runnable (waiting for a CPU), sleeping, or blocked on I/O. The marker name
+/// is the state.
+#[derive(Debug, Clone)]
+pub struct ThreadStateMarker {
+    pub cpu: Option<u32>,
+    /// The thread which woke this thread up, for the runnable state.
+    pub waker_tid: Option<i32>,
+    /// How long the woken thread waited for a CPU, in milliseconds, for the
+    /// runnable state.
+    pub latency_ms: Option<f64>,
+}
+
+impl ProfilerMarker for ThreadStateMarker {
+    const MARKER_TYPE_NAME: &'static str = "ThreadState";
+
+    fn json_marker_data(&self) -> serde_json::Value {
+        json!({
+            "type": Self::MARKER_TYPE_NAME,
+            "cpu": self.cpu,
+            "waker": self.waker_tid,
+            "latency": self.latency_ms,
+        })
+    }
+
+    fn schema() -> MarkerSchema {
+        MarkerSchema {
+            type_name: Self::MARKER_TYPE_NAME,
+            locations: vec![MarkerLocation::MarkerChart, MarkerLocation::MarkerTable],
+            chart_label: Some("{marker.name}"),
+            tooltip_label: Some("{marker.name} on CPU {marker.data.cpu}"),
+            table_label: Some("{marker.name} - CPU {marker.data.cpu}"),
+            fields: vec![
+                MarkerSchemaField::Dynamic(MarkerDynamicField {
+                    key: "cpu",
+                    label: "CPU",
+                    format: MarkerFieldFormat::Integer,
+                    searchable: None,
+                }),
+                MarkerSchemaField::Dynamic(MarkerDynamicField {
+                    key: "waker",
+                    label: "Woken up by thread",
+                    format: MarkerFieldFormat::Integer,
+                    searchable: Some(true),
+                }),
+                MarkerSchemaField::Dynamic(MarkerDynamicField {
+                    key: "latency",
+                    label: "Scheduling latency",
+                    format: MarkerFieldFormat::Duration,
+                    searchable: None,
+                }),
+            ],
+        }
+    }
+}
+
+/// Marks the duration of a syscall. The marker name is the syscall name.
+#[derive(Debug, Clone)]
+pub struct SyscallMarker {
+    pub args: String,
+    pub ret: i64,
+}
+
+impl ProfilerMarker for SyscallMarker {
+    const MARKER_TYPE_NAME: &'static str = "Syscall";
+
+    fn json_marker_data(&self) -> serde_json::Value {
+        json!({
+            "type": Self::MARKER_TYPE_NAME,
+            "args": self.args,
+            "ret": self.ret,
+        })
+    }
+
+    fn schema() -> MarkerSchema {
+        MarkerSchema {
+            type_name: Self::MARKER_TYPE_NAME,
+            locations: vec![MarkerLocation::MarkerChart, MarkerLocation::MarkerTable],
+            chart_label: Some("{marker.name}"),
+            tooltip_label: Some("{marker.name}({marker.data.args}) = {marker.data.ret}"),
+            table_label: Some("{marker.name}({marker.data.args}) = {marker.data.ret}"),
+            fields: syscall_marker_fields(),
+        }
+    }
+}
+
+/// Marks the duration of a syscall which works with files, such as `read` or
+/// `openat`. These markers are also shown in the file I/O track.
+#[derive(Debug, Clone)]
+pub struct FileIoSyscallMarker {
+    pub args: String,
+    pub ret: i64,
+}
+
+impl ProfilerMarker for FileIoSyscallMarker {
+    const MARKER_TYPE_NAME: &'static str = "FileIOSyscall";
+
+    fn json_marker_data(&self) -> serde_json::Value {
+        json!({
+            "type": Self::MARKER_TYPE_NAME,
+            "args": self.args,
+            "ret": self.ret,
+        })
+    }
+
+    fn schema() -> MarkerSchema {
+        MarkerSchema {
+            type_name: Self::MARKER_TYPE_NAME,
+            locations: vec![
+                MarkerLocation::MarkerChart,
+                MarkerLocation::MarkerTable,
+                MarkerLocation::TimelineFileIO,
+            ],
+            chart_label: Some("{marker.name}"),
+            tooltip_label: Some("{marker.name}({marker.data.args}) = {marker.data.ret}"),
+            table_label: Some("{marker.name}({marker.data.args}) = {marker.data.ret}"),
+            fields: syscall_marker_fields(),
+        }
+    }
+}
+
+fn syscall_marker_fields() -> Vec<MarkerSchemaField> {
+    vec![
+        MarkerSchemaField::Dynamic(MarkerDynamicField {
+            key: "args",
+            label: "Arguments",
+            format: MarkerFieldFormat::String,
+            searchable: Some(true),
+        }),
+        MarkerSchemaField::Dynamic(MarkerDynamicField {
+            key: "ret",
+            label: "Return value",
+            format: MarkerFieldFormat::Integer,
+            searchable: None,
+        }),
+    ]
+}
@@ -1,29 +1,45 @@
+mod allocations;
 mod context_switch;
 mod jit_category_manager;
+mod jitdump_manager;
 mod kernel_symbols;
+mod markers;
 mod object_rewriter;
-
-use byteorder::LittleEndian;
+mod perf_map_manager;
+mod sched_states;
+mod syscall_names;
+mod syscalls;
+mod tracepoint_format;
+
+pub use allocations::{AllocationFunction, AllocationProbe};
+pub use kernel_symbols::KernelSymbols;
+pub use sched_states::SchedTracepointFormats;
+pub use syscalls::SyscallTracepointFormats;
+
+use byteorder::{BigEndian, LittleEndian};
 use context_switch::{ContextSwitchHandler, OffCpuSampleGroup, ThreadContextSwitchData};
 use debugid::{CodeId, DebugId};
 use framehop::aarch64::UnwindRegsAarch64;
 use framehop::x86_64::UnwindRegsX86_64;
 use framehop::{FrameAddress, Module, ModuleSvmaInfo, ModuleUnwindData, TextByteData, Unwinder};
 use fxprof_processed_profile::{
-    CategoryColor, CategoryPairHandle, CpuDelta, Frame, LibraryInfo, ProcessHandle, Profile,
-    ReferenceTimestamp, SamplingInterval, ThreadHandle, Timestamp,
+    CategoryColor, CategoryPairHandle, CounterHandle, CpuDelta, Frame, LibraryInfo, MarkerTiming,
+    PausedRangeReason, ProcessHandle, Profile, ProfilerMarker, ReferenceTimestamp,
+    SamplingInterval, ThreadHandle, Timestamp,
 };
 use linux_perf_data::linux_perf_event_reader;
 use linux_perf_data::{AttributeDescription, DsoInfo, DsoKey};
 use linux_perf_event_reader::constants::{
     PERF_CONTEXT_GUEST, PERF_CONTEXT_GUEST_KERNEL, PERF_CONTEXT_GUEST_USER, PERF_CONTEXT_KERNEL,
     PERF_CONTEXT_MAX, PERF_CONTEXT_USER, PERF_REG_ARM64_LR, PERF_REG_ARM64_PC, PERF_REG_ARM64_SP,
-    PERF_REG_ARM64_X29, PERF_REG_X86_BP, PERF_REG_X86_IP, PERF_REG_X86_SP,
+    PERF_REG_ARM64_X0, PERF_REG_ARM64_X1, PERF_REG_ARM64_X29, PERF_REG_X86_AX, PERF_REG_X86_BP,
+    PERF_REG_X86_DI, PERF_REG_X86_IP, PERF_REG_X86_SI, PERF_REG_X86_SP,
 };
 use linux_perf_event_reader::{
-    AttrFlags, CommOrExecRecord, CommonData, ContextSwitchRecord, CpuMode, ForkOrExitRecord,
-    Mmap2FileId, Mmap2Record, MmapRecord, PerfEventType, RawDataU64, Regs, SampleRecord,
-    SamplingPolicy, SoftwareCounterType,
+    AttrFlags, ClockId, CommOrExecRecord, CommonData, ContextSwitchRecord, CpuMode, Endianness,
+    ForkOrExitRecord, HardwareEventId, Mmap2FileId, Mmap2Record, MmapRecord, PerfClock,
+    PerfEventType, RawDataU64, RawEventRecord, Regs, SampleFormat, SampleRecord, SamplingPolicy,
+    SoftwareCounterType, ThrottleRecord,
 };
 use memmap2::Mmap;
 use object::pe::{ImageNtHeaders32, ImageNtHeaders64};
@@ -34,22 +50,44 @@ use object::{
 use samply_symbols::{debug_id_for_object, DebugIdExt};
 use wholesym::samply_symbols;
 
+use std::borrow::Cow;
 use std::collections::{BTreeMap, HashMap};
 use std::convert::TryFrom;
 use std::ffi::OsStr;
 use std::fmt::Debug;
+use std::io::Read;
 use std::os::unix::ffi::OsStrExt;
 use std::path::PathBuf;
 use std::time::SystemTime;
 use std::{ops::Range, path::Path};
 
+use self::allocations::AllocationTracker;
 use self::jit_category_manager::JitCategoryManager;
-use self::kernel_symbols::KernelSymbols;
+use self::jitdump_manager::JitDumpManager;
+use self::markers::{
+    FileIoSyscallMarker, SamplesLostMarker, SamplingThrottledMarker, SyscallMarker,
+    ThreadStateMarker,
+};
+use self::perf_map_manager::PerfMapManager;
+use self::sched_states::{SchedStateInterval, SchedStateTracker, ThreadSchedState};
+use self::syscalls::{format_syscall_args, is_file_io_syscall, SyscallEnter};
 
 pub trait ConvertRegs {
     type UnwindRegs;
     fn convert_regs(regs: &Regs) -> (u64, u64, Self::UnwindRegs);
     fn regs_mask() -> u64;
+
+    /// The first two integer arguments of a function call, at the entry of the function.
+    fn call_args(regs: &Regs) -> (u64, u64);
+
+    /// The integer return value of a function call, at the return from the function.
+    fn return_value(regs: &Regs) -> u64;
+
+    /// The registers which are needed by `call_args` and `return_value`.
+    fn call_regs_mask() -> u64;
+
+    /// The name of the system call with the number `id`.
+    fn syscall_name(id: u64) -> Option<&'static str>;
 }
 
 pub struct ConvertRegsX86_64;
@@ -66,6 +104,24 @@ impl ConvertRegs for ConvertRegsX86_64 {
     fn regs_mask() -> u64 {
         1 << PERF_REG_X86_IP | 1 << PERF_REG_X86_SP | 1 << PERF_REG_X86_BP
     }
+
+    fn call_args(regs: &Regs) -> (u64, u64) {
+        let di = regs.get(PERF_REG_X86_DI).unwrap();
+        let si = regs.get(PERF_REG_X86_SI).unwrap();
+        (di, si)
+    }
+
+    fn return_value(regs: &Regs) -> u64 {
+        regs.get(PERF_REG_X86_AX).unwrap()
+    }
+
+    fn call_regs_mask() -> u64 {
+        1 << PERF_REG_X86_DI | 1 << PERF_REG_X86_SI | 1 << PERF_REG_X86_AX
+    }
+
+    fn syscall_name(id: u64) -> Option<&'static str> {
+        syscall_names::x86_64_syscall_name(id)
+    }
 }
 
 pub struct ConvertRegsAarch64;
@@ -86,16 +142,69 @@ impl ConvertRegs for ConvertRegsAarch64 {
             | 1 << PERF_REG_ARM64_SP
             | 1 << PERF_REG_ARM64_X29
     }
+
+    fn call_args(regs: &Regs) -> (u64, u64) {
+        let x0 = regs.get(PERF_REG_ARM64_X0).unwrap();
+        let x1 = regs.get(PERF_REG_ARM64_X1).unwrap();
+        (x0, x1)
+    }
+
+    fn return_value(regs: &Regs) -> u64 {
+        regs.get(PERF_REG_ARM64_X0).unwrap()
+    }
+
+    fn call_regs_mask() -> u64 {
+        1 << PERF_REG_ARM64_X0 | 1 << PERF_REG_ARM64_X1
+    }
+
+    fn syscall_name(id: u64) -> Option<&'static str> {
+        syscall_names::aarch64_syscall_name(id)
+    }
+}
+
+/// If the samples of an event are spaced out by time, the sampling interval in
+/// nanoseconds. This is the case for the clock events, and for CPU cycles sampled
+/// at a frequency. Samples of other events, e.g. cache misses, are weighted by
+/// their period, even if the period was chosen by the kernel to hit a frequency.
+fn sampling_interval_nanos(event_type: PerfEventType, policy: SamplingPolicy) -> Option<u64> {
+    let is_clock = matches!(
+        event_type,
+        PerfEventType::Software(SoftwareCounterType::CpuClock | SoftwareCounterType::TaskClock)
+    );
+    let is_cycles = matches!(
+        event_type,
+        PerfEventType::Hardware(HardwareEventId::CpuCycles, _)
+    );
+    match policy {
+        SamplingPolicy::NoSampling => panic!("Can only convert profiles with sampled events"),
+        SamplingPolicy::Frequency(freq) if is_clock || is_cycles => Some(1_000_000_000 / freq),
+        // Assume that we're using a nanosecond clock. TODO: Check how we can know this for sure
+        SamplingPolicy::Period(period) if is_clock => Some(u64::from(period)),
+        SamplingPolicy::Frequency(_) | SamplingPolicy::Period(_) => None,
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct EventInterpretation {
     pub main_event_attr_index: usize,
-    #[allow(unused)]
     pub main_event_name: String,
     pub sampling_is_time_based: Option<u64>,
     pub have_context_switches: bool,
     pub sched_switch_attr_index: Option<usize>,
+    /// The attr of `sched:sched_waking`, or of `sched:sched_wakeup` if waking
+    /// wasn't recorded.
+    pub sched_wakeup_attr_index: Option<usize>,
+    /// Set if the `sched_switch` samples contain the tracepoint fields, which
+    /// are turned into thread state markers.
+    pub sched_state_formats: Option<SchedTracepointFormats>,
+    /// The attrs of `raw_syscalls:sys_enter` and `raw_syscalls:sys_exit`.
+    pub syscall_enter_attr_index: Option<usize>,
+    pub syscall_exit_attr_index: Option<usize>,
+    /// The layout of the `raw_syscalls` tracepoints.
+    pub syscall_formats: SyscallTracepointFormats,
+    /// Whether the sample timestamps come from `CLOCK_MONOTONIC`, which is the
+    /// clock that JIT runtimes use for the timestamps in jitdump files.
+    pub clock_is_monotonic: bool,
 }
 
 impl EventInterpretation {
@@ -106,30 +215,31 @@ impl EventInterpretation {
             .as_deref()
             .unwrap_or("<unnamed event>")
             .to_string();
-        let sampling_is_time_based = match (attrs[0].attr.type_, attrs[0].attr.sampling_policy) {
-            (_, SamplingPolicy::NoSampling) => {
-                panic!("Can only convert profiles with sampled events")
-            }
-            (_, SamplingPolicy::Frequency(freq)) => {
-                let nanos = 1_000_000_000 / freq;
-                Some(nanos)
-            }
-            (
-                PerfEventType::Software(
-                    SoftwareCounterType::CpuClock | SoftwareCounterType::TaskClock,
-                ),
-                SamplingPolicy::Period(period),
-            ) => {
-                // Assume that we're using a nanosecond clock. TODO: Check how we can know this for sure
-                let nanos = u64::from(period);
-                Some(nanos)
-            }
-            (_, SamplingPolicy::Period(_)) => None,
-        };
+        let sampling_is_time_based =
+            sampling_interval_nanos(attrs[0].attr.type_, attrs[0].attr.sampling_policy);
         let have_context_switches = attrs[0].attr.flags.contains(AttrFlags::CONTEXT_SWITCH);
         let sched_switch_attr_index = attrs
             .iter()
             .position(|attr_desc| attr_desc.name.as_deref() == Some("sched:sched_switch"));
+        let sched_wakeup_attr_index = ["sched:sched_waking", "sched:sched_wakeup"]
+            .iter()
+            .find_map(|name| {
+                attrs
+                    .iter()
+                    .position(|attr_desc| attr_desc.name.as_deref() == Some(name))
+            });
+        let sched_state_formats = sched_switch_attr_index
+            .filter(|index| attrs[*index].attr.sample_format.contains(SampleFormat::RAW))
+            .map(|_| SchedTracepointFormats::default());
+        let attr_index_by_name = |name: &str| {
+            attrs
+                .iter()
+                .position(|attr_desc| attr_desc.name.as_deref() == Some(name))
+        };
+        let syscall_enter_attr_index = attr_index_by_name("raw_syscalls:sys_enter");
+        let syscall_exit_attr_index = attr_index_by_name("raw_syscalls:sys_exit");
+        let clock_is_monotonic =
+            matches!(attrs[0].attr.clock, PerfClock::ClockId(ClockId::Monotonic));
 
         Self {
             main_event_attr_index,
@@ -137,6 +247,12 @@ impl EventInterpretation {
             sampling_is_time_based,
             have_context_switches,
             sched_switch_attr_index,
+            sched_wakeup_attr_index,
+            sched_state_formats,
+            syscall_enter_attr_index,
+            syscall_exit_attr_index,
+            syscall_formats: SyscallTracepointFormats::default(),
+            clock_is_monotonic,
         }
     }
 }
@@ -169,8 +285,12 @@ where
     linux_version: Option<String>,
     extra_binary_artifact_dir: Option<PathBuf>,
     context_switch_handler: ContextSwitchHandler,
-    off_cpu_weight_per_sample: i32,
+    off_cpu_weight_per_sample: i64,
     have_context_switches: bool,
+    /// Whether the main event is sampled based on time. If not, e.g. for cache
+    /// misses, the sample period is used as the sample weight.
+    sampling_is_time_based: bool,
+    clock_is_monotonic: bool,
     kernel_symbols: Option<KernelSymbols>,
 
     /// Mapping of start address to potential mapped PE binaries.
@@ -178,10 +298,26 @@ where
     suspected_pe_mappings: BTreeMap<u64, SuspectedPeMapping>,
 
     jit_category_manager: JitCategoryManager,
+    allocation_tracker: AllocationTracker,
+
+    /// The start time and the pid of each currently throttled event, keyed by event id.
+    throttled_events: HashMap<u64, (u64, Option<i32>)>,
+
+    /// Present if thread state markers are created from the sched tracepoints.
+    sched_state_tracker: Option<SchedStateTracker>,
+
+    /// The layout of the `raw_syscalls` tracepoints.
+    syscall_formats: SyscallTracepointFormats,
+
+    /// The start time and the arguments of each thread's current syscall, keyed by tid.
+    pending_syscalls: HashMap<i32, (u64, SyscallEnter)>,
 }
 
 const DEFAULT_OFF_CPU_SAMPLING_INTERVAL_NS: u64 = 1_000_000; // 1ms
 
+/// Syscall markers which are at least this long get the stack of the syscall.
+const SYSCALL_STACK_THRESHOLD_NS: u64 = 1_000_000; // 1ms
+
 impl<U> Converter<U>
 where
     U: Unwinder<Module = Module<Vec<u8>>> + Default,
@@ -207,6 +343,7 @@ where
             ReferenceTimestamp::from_system_time(SystemTime::now()),
             interval,
         );
+        profile.set_sampled_event_name(&interpretation.main_event_name);
         let user_category = profile.add_category("User", CategoryColor::Yellow).into();
         let kernel_category = profile.add_category("Kernel", CategoryColor::Orange).into();
         let (off_cpu_sampling_interval_ns, off_cpu_weight_per_sample) =
@@ -241,9 +378,18 @@ where
             off_cpu_weight_per_sample,
             context_switch_handler: ContextSwitchHandler::new(off_cpu_sampling_interval_ns),
             have_context_switches: interpretation.have_context_switches,
+            sampling_is_time_based: interpretation.sampling_is_time_based.is_some(),
+            clock_is_monotonic: interpretation.clock_is_monotonic,
             kernel_symbols,
             suspected_pe_mappings: BTreeMap::new(),
             jit_category_manager: JitCategoryManager::new(),
+            allocation_tracker: AllocationTracker::new(),
+            throttled_events: HashMap::new(),
+            sched_state_tracker: interpretation
+                .sched_state_formats
+                .map(SchedStateTracker::new),
+            syscall_formats: interpretation.syscall_formats,
+            pending_syscalls: HashMap::new(),
         }
     }
 
@@ -261,6 +407,8 @@ where
 
         let profile_timestamp = self.timestamp_converter.convert_time(timestamp);
 
+        self.process_jit_info(pid, timestamp);
+
         let is_main = pid == tid;
         let process = self.processes.get_by_pid(pid, &mut self.profile);
 
@@ -304,28 +452,105 @@ where
                 self.context_switch_handler
                     .consume_cpu_delta(&mut thread.context_switch_data),
             )
-        } else if let Some(period) = e.period {
-            // If the observed perf event is one of the clock time events, or cycles, then we should convert it to a CpuDelta.
-            // TODO: Detect event type
+        } else if let (true, Some(period)) = (self.sampling_is_time_based, e.period) {
+            // The observed perf event is a clock time event, or cycles sampled at a
+            // frequency, so we treat the period as the CPU time since the previous sample.
             CpuDelta::from_nanos(period)
         } else {
             CpuDelta::from_nanos(0)
         };
 
+        // For events which aren't time-based, each sample stands for `period` events.
+        let weight = match e.period {
+            Some(period) if !self.sampling_is_time_based => {
+                i64::try_from(period).unwrap_or(i64::MAX)
+            }
+            _ => 1,
+        };
+
         let frames = self
             .stack_converter
             .convert_stack(stack, &process.jit_functions);
         self.profile
-            .add_sample(thread_handle, profile_timestamp, frames, cpu_delta, 1);
+            .add_sample(thread_handle, profile_timestamp, frames, cpu_delta, weight);
         thread.last_sample_timestamp = Some(timestamp);
     }
 
+    /// Handle a hit of one of the uprobes on the allocation functions, see
+    /// `samply record --allocations`.
+    pub fn handle_allocation_probe<C: ConvertRegs<UnwindRegs = U::UnwindRegs>>(
+        &mut self,
+        probe: AllocationProbe,
+        e: SampleRecord,
+    ) {
+        let pid = e.pid.expect("Can't handle samples without pids");
+        let tid = e.tid.expect("Can't handle samples without tids");
+        let timestamp = e
+            .timestamp
+            .expect("Can't handle samples without timestamps");
+        let regs = match &e.user_regs {
+            Some(regs) => regs,
+            None => return,
+        };
+
+        self.process_jit_info(pid, timestamp);
+        let is_main = pid == tid;
+        let process = self.processes.get_by_pid(pid, &mut self.profile);
+
+        let samples = if probe.is_return {
+            self.allocation_tracker
+                .handle_return(pid, tid, C::return_value(regs))
+        } else {
+            let mut stack = Vec::new();
+            Self::get_sample_stack::<C>(&e, &process.unwinder, &mut self.cache, &mut stack);
+            let stack = self
+                .stack_converter
+                .convert_stack_no_kernel(&stack, &process.jit_functions)
+                .collect();
+            self.allocation_tracker
+                .handle_call(pid, tid, probe.function, C::call_args(regs), stack)
+        };
+        if samples.is_empty() {
+            return;
+        }
+
+        let process_handle = process.profile_process;
+        let profile = &mut self.profile;
+        let counter = *process.allocation_counter.get_or_insert_with(|| {
+            profile.add_counter(
+                process_handle,
+                "malloc",
+                "Memory",
+                "Amount of allocated memory",
+            )
+        });
+        let thread_handle = self
+            .threads
+            .get_by_tid(tid, process_handle, is_main, &mut self.profile)
+            .profile_thread;
+        let profile_timestamp = self.timestamp_converter.convert_time(timestamp);
+        for sample in samples {
+            self.profile
+                .add_counter_sample(counter, profile_timestamp, sample.size as f64, 1);
+            self.profile.add_allocation_sample(
+                thread_handle,
+                profile_timestamp,
+                sample.stack.into_iter(),
+                sample.address,
+                sample.size,
+            );
+        }
+    }
+
     pub fn handle_sched_switch<C: ConvertRegs<UnwindRegs = U::UnwindRegs>>(
         &mut self,
         e: SampleRecord,
     ) {
         let pid = e.pid.expect("Can't handle samples without pids");
         let tid = e.tid.expect("Can't handle samples without tids");
+        if let Some(timestamp) = e.timestamp {
+            self.process_jit_info(pid, timestamp);
+        }
         let is_main = pid == tid;
         let process = self.processes.get_by_pid(pid, &mut self.profile);
 
@@ -341,6 +566,194 @@ where
             self.threads
                 .get_by_tid(tid, process.profile_process, is_main, &mut self.profile);
         thread.off_cpu_stack = Some(stack);
+
+        let tracker = match self.sched_state_tracker.as_mut() {
+            Some(tracker) => tracker,
+            None => return,
+        };
+        let (raw, timestamp) = match (&e.raw, e.timestamp) {
+            (Some(raw), Some(timestamp)) => (raw, timestamp),
+            _ => return,
+        };
+        let switch = match tracker.formats.parse_switch(raw, self.little_endian) {
+            Some(switch) => switch,
+            None => return,
+        };
+        let prev_interval =
+            tracker.handle_switch_out(switch.prev_tid, timestamp, e.cpu, switch.prev_state);
+        let next_interval = tracker.handle_switch_in(switch.next_tid, timestamp, e.cpu);
+        for interval in prev_interval.into_iter().chain(next_interval) {
+            self.add_thread_state_marker(interval);
+        }
+    }
+
+    /// Called for a `sched:sched_waking` or `sched:sched_wakeup` sample. The
+    /// sample's thread is the thread which does the waking.
+    pub fn handle_sched_wakeup(&mut self, e: SampleRecord) {
+        let tracker = match self.sched_state_tracker.as_mut() {
+            Some(tracker) => tracker,
+            None => return,
+        };
+        let (raw, timestamp) = match (&e.raw, e.timestamp) {
+            (Some(raw), Some(timestamp)) => (raw, timestamp),
+            _ => return,
+        };
+        let wakeup = match tracker.formats.parse_wakeup(raw, self.little_endian) {
+            Some(wakeup) => wakeup,
+            None => return,
+        };
+        if let Some(interval) =
+            tracker.handle_wakeup(wakeup.tid, timestamp, wakeup.target_cpu, e.tid)
+        {
+            self.add_thread_state_marker(interval);
+        }
+    }
+
+    /// Called for a `raw_syscalls:sys_enter` sample.
+    pub fn handle_syscall_enter(&mut self, e: SampleRecord) {
+        let (tid, timestamp, raw) = match (e.tid, e.timestamp, &e.raw) {
+            (Some(tid), Some(timestamp), Some(raw)) => (tid, timestamp, raw),
+            _ => return,
+        };
+        if let Some(enter) = self.syscall_formats.parse_enter(raw, self.little_endian) {
+            self.pending_syscalls.insert(tid, (timestamp, enter));
+        }
+    }
+
+    /// Called for a `raw_syscalls:sys_exit` sample. This adds a marker for the
+    /// syscall, with the sample's stack if the syscall took a long time. The
+    /// user stack at the exit of the syscall is the same as at its entry.
+    pub fn handle_syscall_exit<C: ConvertRegs<UnwindRegs = U::UnwindRegs>>(
+        &mut self,
+        e: SampleRecord,
+    ) {
+        let (pid, tid, timestamp, raw) = match (e.pid, e.tid, e.timestamp, &e.raw) {
+            (Some(pid), Some(tid), Some(timestamp), Some(raw)) => (pid, tid, timestamp, raw),
+            _ => return,
+        };
+        let exit = match self.syscall_formats.parse_exit(raw, self.little_endian) {
+            Some(exit) => exit,
+            None => return,
+        };
+        let (start, enter) = match self.pending_syscalls.remove(&tid) {
+            Some((start, enter)) if enter.id == exit.id && start <= timestamp => (start, enter),
+            _ => return,
+        };
+
+        let is_main = pid == tid;
+        let process = self.processes.get_by_pid(pid, &mut self.profile);
+        let stack = if timestamp - start >= SYSCALL_STACK_THRESHOLD_NS {
+            let mut stack = Vec::new();
+            Self::get_sample_stack::<C>(&e, &process.unwinder, &mut self.cache, &mut stack);
+            let stack: Vec<_> = self
+                .stack_converter
+                .convert_stack_no_kernel(&stack, &process.jit_functions)
+                .collect();
+            Some(stack)
+        } else {
+            None
+        };
+        let thread_handle = self
+            .threads
+            .get_by_tid(tid, process.profile_process, is_main, &mut self.profile)
+            .profile_thread;
+
+        let name = match C::syscall_name(exit.id) {
+            Some(name) => Cow::Borrowed(name),
+            None => Cow::Owned(format!("syscall {}", exit.id)),
+        };
+        let timing = MarkerTiming::Interval(
+            self.timestamp_converter.convert_time(start),
+            self.timestamp_converter.convert_time(timestamp),
+        );
+        let args = format_syscall_args(&enter.args);
+        if is_file_io_syscall(&name) {
+            let marker = FileIoSyscallMarker {
+                args,
+                ret: exit.ret,
+            };
+            self.add_marker_with_optional_stack(thread_handle, &name, marker, timing, stack);
+        } else {
+            let marker = SyscallMarker {
+                args,
+                ret: exit.ret,
+            };
+            self.add_marker_with_optional_stack(thread_handle, &name, marker, timing, stack);
+        }
+    }
+
+    fn add_marker_with_optional_stack<T: ProfilerMarker>(
+        &mut self,
+        thread: ThreadHandle,
+        name: &str,
+        marker: T,
+        timing: MarkerTiming,
+        stack: Option<Vec<(Frame, CategoryPairHandle)>>,
+    ) {
+        match stack {
+            Some(stack) => {
+                self.profile
+                    .add_marker_with_stack(thread, name, marker, timing, stack.into_iter())
+            }
+            None => self.profile.add_marker(thread, name, marker, timing),
+        }
+    }
+
+    /// Add a thread state marker to the interval's thread. Nothing is added for
+    /// threads we haven't seen any other records for, e.g. threads of other
+    /// processes which were woken up by an observed thread.
+    fn add_thread_state_marker(&mut self, interval: SchedStateInterval) {
+        let thread = match self.threads.0.get(&interval.tid) {
+            Some(thread) => thread,
+            None => return,
+        };
+        let latency_ms = match interval.state {
+            ThreadSchedState::Runnable => {
+                Some((interval.end - interval.start) as f64 / 1_000_000.0)
+            }
+            _ => None,
+        };
+        let timing = MarkerTiming::Interval(
+            self.timestamp_converter.convert_time(interval.start),
+            self.timestamp_converter.convert_time(interval.end),
+        );
+        let marker = ThreadStateMarker {
+            cpu: interval.cpu,
+            waker_tid: interval.waker_tid,
+            latency_ms,
+        };
+        self.profile
+            .add_marker(thread.profile_thread, interval.state.name(), marker, timing);
+    }
+
+    /// Add the JIT functions from the process's jitdump files which were
+    /// created up until `timestamp`, and any new entries from its perf map.
+    fn process_jit_info(&mut self, pid: i32, timestamp: u64) {
+        let process = self.processes.get_by_pid(pid, &mut self.profile);
+        process.perf_map_manager.process_new_entries(
+            timestamp,
+            self.extra_binary_artifact_dir.as_deref(),
+            process.profile_process,
+            &mut self.profile,
+            &mut self.jit_category_manager,
+            &mut process.jit_functions,
+        );
+
+        // If the perf event timestamps use a different clock than the jitdump
+        // records, we can't order them, so we just consume all available records.
+        let timestamp = if self.clock_is_monotonic {
+            Some(timestamp)
+        } else {
+            None
+        };
+        process.jitdump_manager.process_pending_records(
+            timestamp,
+            process.profile_process,
+            &mut self.profile,
+            &mut self.jit_category_manager,
+            &mut process.jit_functions,
+            &mut process.unwinder,
+        );
     }
 
     /// Get the stack contained in this sample, and put it into `stack`.
@@ -371,6 +784,7 @@ where
         // CpuMode::from_misc(e.raw.misc)
 
         // Get the first fragment of the stack from e.callchain.
+        let mut user_callchain_start = None;
         if let Some(callchain) = e.callchain {
             let mut is_first_frame = true;
             let mut mode = StackMode::from(e.cpu_mode);
@@ -383,6 +797,9 @@ where
                     continue;
                 }
 
+                if matches!(mode, StackMode::User) && user_callchain_start.is_none() {
+                    user_callchain_start = Some(stack.len());
+                }
                 let stack_frame = match is_first_frame {
                     true => StackFrame::InstructionPointer(address, mode),
                     false => StackFrame::ReturnAddress(address, mode),
@@ -395,6 +812,15 @@ where
 
         // Append the user stack with the help of DWARF unwinding.
         if let (Some(regs), Some((user_stack, _))) = (&e.user_regs, e.user_stack) {
+            // If the callchain has user frames too, the sample was recorded in "hybrid"
+            // mode: The user stack copy only covers the leaf frames, and the frame
+            // pointer walk in the callchain covers the rest of the stack.
+            let user_callchain = match user_callchain_start {
+                Some(start) => stack.split_off(start),
+                None => Vec::new(),
+            };
+            let dwarf_start = stack.len();
+
             let ustack_bytes = RawDataU64::from_raw_data::<LittleEndian>(user_stack);
             let (pc, sp, regs) = C::convert_regs(regs);
             let mut read_stack = |addr: u64| {
@@ -411,7 +837,9 @@ where
                     Ok(Some(frame)) => frame,
                     Ok(None) => break,
                     Err(_) => {
-                        stack.push(StackFrame::TruncatedStackMarker);
+                        if !continue_with_user_callchain(stack, dwarf_start, &user_callchain) {
+                            stack.push(StackFrame::TruncatedStackMarker);
+                        }
                         break;
                     }
                 };
@@ -485,11 +913,29 @@ where
         }
     }
 
+    /// Check whether the mapped file is a jitdump file, and if so, remember
+    /// it so that its records are processed as we encounter samples.
+    fn check_for_jitdump_mapping(&mut self, pid: i32, path_slice: &[u8]) -> bool {
+        if !jitdump_manager::is_jitdump_path(path_slice) {
+            return false;
+        }
+        let process = self.processes.get_by_pid(pid, &mut self.profile);
+        let path = Path::new(OsStr::from_bytes(path_slice));
+        process
+            .jitdump_manager
+            .add_jitdump_path(path, self.extra_binary_artifact_dir.as_deref());
+        true
+    }
+
     pub fn handle_mmap(&mut self, e: MmapRecord) {
         if e.page_offset == 0 {
             self.check_for_pe_mapping(&e.path.as_slice(), e.address);
         }
 
+        if e.pid != -1 && self.check_for_jitdump_mapping(e.pid, &e.path.as_slice()) {
+            return;
+        }
+
         if !e.is_executable {
             return;
         }
@@ -531,6 +977,10 @@ where
             self.check_for_pe_mapping(&e.path.as_slice(), e.address);
         }
 
+        if self.check_for_jitdump_mapping(e.pid, &e.path.as_slice()) {
+            return;
+        }
+
         const PROT_EXEC: u32 = 0b100;
         if e.protection & PROT_EXEC == 0 {
             // Ignore non-executable mappings.
@@ -602,6 +1052,91 @@ where
                     .handle_switch_out(timestamp, &mut thread.context_switch_data);
             }
         }
+
+        // Switch-outs are handled in handle_sched_switch, which knows the new
+        // state of the thread.
+        if let (ContextSwitchRecord::In { .. }, Some(tracker)) =
+            (&e, self.sched_state_tracker.as_mut())
+        {
+            if let Some(interval) = tracker.handle_switch_in(tid, timestamp, common.cpu) {
+                self.add_thread_state_marker(interval);
+            }
+        }
+    }
+
+    /// Called for a LOST or LOST_SAMPLES record, when the kernel had to drop
+    /// events because the ring buffer was full. The events were lost at some
+    /// point between the previous sample and this record.
+    pub fn handle_lost_events(&mut self, count: u64, common: CommonData) {
+        let end = common.timestamp.unwrap_or(self.current_sample_time);
+        let start = self.current_sample_time.min(end);
+        let timing = MarkerTiming::Interval(
+            self.timestamp_converter.convert_time(start),
+            self.timestamp_converter.convert_time(end),
+        );
+        self.add_paused_range(&timing);
+        self.add_process_marker(
+            common.pid,
+            "Samples lost",
+            SamplesLostMarker { count },
+            timing,
+        );
+    }
+
+    /// Called for a THROTTLE record. Sampling of this event is paused until the
+    /// matching UNTHROTTLE record.
+    pub fn handle_throttle(&mut self, e: ThrottleRecord, common: CommonData) {
+        self.throttled_events
+            .entry(e.id)
+            .or_insert((e.timestamp, common.pid));
+    }
+
+    /// Called for an UNTHROTTLE record.
+    pub fn handle_unthrottle(&mut self, e: ThrottleRecord) {
+        let (start, pid) = match self.throttled_events.remove(&e.id) {
+            Some(throttled) => throttled,
+            None => return,
+        };
+        let timing = MarkerTiming::Interval(
+            self.timestamp_converter.convert_time(start),
+            self.timestamp_converter
+                .convert_time(e.timestamp.max(start)),
+        );
+        self.add_paused_range(&timing);
+        self.add_process_marker(pid, "Sampling throttled", SamplingThrottledMarker, timing);
+    }
+
+    /// Mark the range as paused because data was not collected. The reason why
+    /// is shown by the marker which accompanies it.
+    fn add_paused_range(&mut self, timing: &MarkerTiming) {
+        if let MarkerTiming::Interval(start, end) = timing {
+            self.profile
+                .add_paused_range(*start, *end, PausedRangeReason::CollectorPaused);
+        }
+    }
+
+    /// Add a marker to the main thread of the process with the given pid. Nothing
+    /// is added if the pid is unknown, or if it's the idle task.
+    fn add_process_marker<T: ProfilerMarker>(
+        &mut self,
+        pid: Option<i32>,
+        name: &str,
+        marker: T,
+        timing: MarkerTiming,
+    ) {
+        let pid = match pid {
+            Some(pid) if pid > 0 => pid,
+            _ => return,
+        };
+        let process_handle = self
+            .processes
+            .get_by_pid(pid, &mut self.profile)
+            .profile_process;
+        let thread_handle = self
+            .threads
+            .get_by_tid(pid, process_handle, true, &mut self.profile)
+            .profile_thread;
+        self.profile.add_marker(thread_handle, name, marker, timing);
     }
 
     /// Called for a FORK record.
@@ -660,6 +1195,7 @@ where
         let thread_handle = thread.profile_thread;
         self.profile.set_thread_end_time(thread_handle, end_time);
         self.threads.0.remove(&e.tid);
+        self.allocation_tracker.handle_thread_end(e.pid, e.tid);
         if is_main {
             self.profile.set_process_end_time(process_handle, end_time);
             self.processes.0.remove(&e.pid);
@@ -1069,7 +1605,7 @@ fn process_off_cpu_sample_group(
     thread_handle: ThreadHandle,
     cpu_delta_ns: u64,
     timestamp_converter: &TimestampConverter,
-    off_cpu_weight_per_sample: i32,
+    off_cpu_weight_per_sample: i64,
     off_cpu_stack: &[(Frame, CategoryPairHandle)],
     profile: &mut Profile,
 ) {
@@ -1090,7 +1626,7 @@ fn process_off_cpu_sample_group(
     if sample_count > 1 {
         // Emit a "rest sample" with a CPU delta of zero covering the rest of the paused range.
         let cpu_delta = CpuDelta::from_nanos(0);
-        let weight = i32::try_from(sample_count - 1).unwrap_or(0) * off_cpu_weight_per_sample;
+        let weight = i64::try_from(sample_count - 1).unwrap_or(0) * off_cpu_weight_per_sample;
         let frames = off_cpu_stack.iter().cloned();
         let profile_timestamp = timestamp_converter.convert_time(end_timestamp);
         profile.add_sample(thread_handle, profile_timestamp, frames, cpu_delta, weight);
@@ -1162,6 +1698,16 @@ impl StackConverter {
     }
 }
 
+/// Read the number of lost samples from a LOST_SAMPLES record. These records
+/// aren't parsed by linux-perf-event-reader, so they arrive as raw records.
+pub fn lost_samples_count(record: &RawEventRecord) -> Option<u64> {
+    let mut data = record.data;
+    match record.parse_info.endian {
+        Endianness::LittleEndian => data.read_u64::<LittleEndian>().ok(),
+        Endianness::BigEndian => data.read_u64::<BigEndian>().ok(),
+    }
+}
+
 struct Processes<U>(HashMap<i32, Process<U>>)
 where
     U: Unwinder<Module = Module<Vec<u8>>> + Default;
@@ -1182,6 +1728,9 @@ where
                 profile_process: handle,
                 unwinder: U::default(),
                 jit_functions: JitFunctions(Vec::new()),
+                jitdump_manager: JitDumpManager::new(),
+                perf_map_manager: PerfMapManager::new(pid),
+                allocation_counter: None,
                 name: None,
             }
         })
@@ -1228,6 +1777,10 @@ struct Process<U> {
     pub profile_process: ProcessHandle,
     pub unwinder: U,
     pub jit_functions: JitFunctions,
+    pub jitdump_manager: JitDumpManager,
+    pub perf_map_manager: PerfMapManager,
+    /// The "malloc" memory counter, created on the first allocation sample.
+    pub allocation_counter: Option<CounterHandle>,
     pub name: Option<String>,
 }
 
@@ -1264,14 +1817,14 @@ struct JitFunction {
     pub category: CategoryPairHandle,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum StackFrame {
     InstructionPointer(u64, StackMode),
     ReturnAddress(u64, StackMode),
     TruncatedStackMarker,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum StackMode {
     User,
     Kernel,
@@ -1301,6 +1854,39 @@ impl From<CpuMode> for StackMode {
     }
 }
 
+/// Continue the DWARF-unwound leaf frames in `stack[dwarf_start..]` with the
+/// frame pointer callchain, after the deepest return address that both have in
+/// common. Returns false if there is no callchain to continue with.
+fn continue_with_user_callchain(
+    stack: &mut Vec<StackFrame>,
+    dwarf_start: usize,
+    user_callchain: &[StackFrame],
+) -> bool {
+    if user_callchain.is_empty() {
+        return false;
+    }
+    for dwarf_index in (dwarf_start..stack.len()).rev() {
+        let address = match stack[dwarf_index] {
+            StackFrame::ReturnAddress(address, _) => address,
+            _ => continue,
+        };
+        let callchain_index = user_callchain.iter().position(|frame| match frame {
+            StackFrame::InstructionPointer(a, _) | StackFrame::ReturnAddress(a, _) => *a == address,
+            StackFrame::TruncatedStackMarker => false,
+        });
+        if let Some(callchain_index) = callchain_index {
+            stack.truncate(dwarf_index + 1);
+            stack.extend_from_slice(&user_callchain[callchain_index + 1..]);
+            return true;
+        }
+    }
+
+    // The frames have nothing in common. The callchain is the more complete stack.
+    stack.truncate(dwarf_start);
+    stack.extend_from_slice(user_callchain);
+    true
+}
+
 fn open_file_with_fallback(
     path: &Path,
     extra_dir: Option<&Path>,
@@ -1315,6 +1901,22 @@ fn open_file_with_fallback(
     }
 }
 
+/// Append everything which has been written to `file` since the last call to
+/// `data`. `read_len` is the number of bytes which have been read so far.
+///
+/// This is called for every sample, so the file is only read if its size has
+/// changed.
+fn read_appended_data(
+    file: &mut std::fs::File,
+    read_len: &mut u64,
+    data: &mut Vec<u8>,
+) -> std::io::Result<()> {
+    if file.metadata()?.len() > *read_len {
+        *read_len += file.read_to_end(data)? as u64;
+    }
+    Ok(())
+}
+
 // A file range in an object file, such as a segment or a section,
 // for which we know the corresponding Stated Virtual Memory Address (SVMA).
 #[derive(Clone)]
@@ -1504,6 +2106,116 @@ fn compute_vma_bias_impl(
     Some(bias)
 }
 
+#[test]
+fn test_continue_with_user_callchain() {
+    use StackFrame::{InstructionPointer, ReturnAddress};
+    use StackMode::User;
+
+    // The DWARF unwinder got as far as the return address 0x300, and then
+    // failed. The callchain continues after the 0x300 frame.
+    let callchain = vec![
+        InstructionPointer(0x100, User),
+        ReturnAddress(0x200, User),
+        ReturnAddress(0x300, User),
+        ReturnAddress(0x400, User),
+        ReturnAddress(0x500, User),
+    ];
+    let mut stack = vec![
+        InstructionPointer(0xffff_0000, StackMode::Kernel),
+        InstructionPointer(0x100, User),
+        ReturnAddress(0x200, User),
+        ReturnAddress(0x300, User),
+        ReturnAddress(0x1234, User),
+    ];
+    assert!(continue_with_user_callchain(&mut stack, 1, &callchain));
+    assert_eq!(
+        stack,
+        vec![
+            InstructionPointer(0xffff_0000, StackMode::Kernel),
+            InstructionPointer(0x100, User),
+            ReturnAddress(0x200, User),
+            ReturnAddress(0x300, User),
+            ReturnAddress(0x400, User),
+            ReturnAddress(0x500, User),
+        ]
+    );
+
+    // Without a common return address, the callchain replaces the DWARF frames.
+    let mut stack = vec![
+        InstructionPointer(0xffff_0000, StackMode::Kernel),
+        InstructionPointer(0x100, User),
+        ReturnAddress(0x1234, User),
+    ];
+    assert!(continue_with_user_callchain(&mut stack, 1, &callchain[1..]));
+    assert_eq!(
+        stack,
+        vec![
+            InstructionPointer(0xffff_0000, StackMode::Kernel),
+            ReturnAddress(0x200, User),
+            ReturnAddress(0x300, User),
+            ReturnAddress(0x400, User),
+            ReturnAddress(0x500, User),
+        ]
+    );
+
+    // Without a callchain, the stack is left alone.
+    let mut stack = vec![InstructionPointer(0x100, User), ReturnAddress(0x200, User)];
+    assert!(!continue_with_user_callchain(&mut stack, 0, &[]));
+    assert_eq!(
+        stack,
+        vec![InstructionPointer(0x100, User), ReturnAddress(0x200, User)]
+    );
+}
+
+#[test]
+fn test_sampling_interval_nanos() {
+    use linux_perf_event_reader::{
+        HardwareCacheId, HardwareCacheOp, HardwareCacheOpResult, PmuTypeId,
+    };
+    use std::num::NonZeroU64;
+
+    let cpu_clock = PerfEventType::Software(SoftwareCounterType::CpuClock);
+    let cycles = PerfEventType::Hardware(HardwareEventId::CpuCycles, PmuTypeId(0));
+    let cache_misses = PerfEventType::Hardware(HardwareEventId::CacheMisses, PmuTypeId(0));
+    let l1d_read_misses = PerfEventType::HwCache(
+        HardwareCacheId::L1d,
+        HardwareCacheOp::Read,
+        HardwareCacheOpResult::Miss,
+        PmuTypeId(0),
+    );
+    assert_eq!(
+        sampling_interval_nanos(cpu_clock, SamplingPolicy::Frequency(1000)),
+        Some(1_000_000)
+    );
+    assert_eq!(
+        sampling_interval_nanos(
+            cpu_clock,
+            SamplingPolicy::Period(NonZeroU64::new(250_000).unwrap())
+        ),
+        Some(250_000)
+    );
+    assert_eq!(
+        sampling_interval_nanos(cycles, SamplingPolicy::Frequency(4000)),
+        Some(250_000)
+    );
+    assert_eq!(
+        sampling_interval_nanos(
+            cycles,
+            SamplingPolicy::Period(NonZeroU64::new(100_000).unwrap())
+        ),
+        None
+    );
+    // `perf record -e cache-misses` samples at a frequency by default.
+    assert_eq!(
+        sampling_interval_nanos(cache_misses, SamplingPolicy::Frequency(4000)),
+        None
+    );
+    assert_eq!(
+        sampling_interval_nanos(l1d_read_misses, SamplingPolicy::Frequency(4000)),
+        None
+    );
+}
+
 #[test]
 fn test_compute_base_avma_impl() {
     // From a local build of the Spidermonkey shell ("js")
@@ -0,0 +1,295 @@
+use debugid::DebugId;
+use fxprof_processed_profile::{LibraryInfo, ProcessHandle, Profile, Symbol, SymbolTable};
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::jit_category_manager::JitCategoryManager;
+use super::{open_file_with_fallback, read_appended_data, JitFunction, JitFunctions};
+
+/// Reads the `/tmp/perf-<pid>.map` file of a single process, and turns its
+/// entries into a library in the profile.
+///
+/// Each line of a perf map has the form `START SIZE NAME`, with START and SIZE
+/// in hex. The file is written to by the JIT runtime while the process is
+/// running, so we keep the file open and pick up new lines as they appear.
+///
+/// All entries go into a single library with a base address of zero, whose
+/// address range and symbol table grow as new entries are added.
+#[derive(Debug)]
+pub struct PerfMapManager {
+    path: PathBuf,
+    name: String,
+    file: Option<File>,
+    /// The timestamp of the last failed attempt at opening the file.
+    last_open_attempt: Option<u64>,
+    /// The number of bytes read from `file` so far.
+    read_len: u64,
+    /// The part of the file after the last newline, which we'll parse once
+    /// the line is complete.
+    incomplete_line: Vec<u8>,
+    /// The symbols for all entries so far, keyed by start address. Later
+    /// entries for the same address replace earlier ones.
+    symbols: BTreeMap<u64, Symbol>,
+    /// The address range covered by the library in the profile, or `None` if
+    /// the library hasn't been added yet.
+    avma_range: Option<Range<u64>>,
+}
+
+impl PerfMapManager {
+    /// How long to wait before we check again whether the perf map file exists,
+    /// in nanoseconds. Runtimes create the file lazily, e.g. when the first
+    /// function is compiled.
+    const OPEN_RETRY_INTERVAL_NS: u64 = 1_000_000_000;
+
+    pub fn new(pid: i32) -> Self {
+        let name = format!("perf-{pid}.map");
+        Self {
+            path: Path::new("/tmp").join(&name),
+            name,
+            file: None,
+            last_open_attempt: None,
+            read_len: 0,
+            incomplete_line: Vec::new(),
+            symbols: BTreeMap::new(),
+            avma_range: None,
+        }
+    }
+
+    /// Add the entries which have been appended to the perf map since the last
+    /// call to the perf map's library.
+    pub fn process_new_entries(
+        &mut self,
+        timestamp: u64,
+        fallback_dir: Option<&Path>,
+        process_handle: ProcessHandle,
+        profile: &mut Profile,
+        jit_category_manager: &mut JitCategoryManager,
+        jit_functions: &mut JitFunctions,
+    ) {
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => {
+                if matches!(self.last_open_attempt, Some(last) if timestamp < last + Self::OPEN_RETRY_INTERVAL_NS)
+                {
+                    return;
+                }
+                match open_file_with_fallback(&self.path, fallback_dir) {
+                    Ok((file, path)) => {
+                        self.path = path;
+                        self.file.insert(file)
+                    }
+                    Err(_) => {
+                        self.last_open_attempt = Some(timestamp);
+                        return;
+                    }
+                }
+            }
+        };
+
+        let mut data = std::mem::take(&mut self.incomplete_line);
+        let data_len_before = data.len();
+        if let Err(err) = read_appended_data(file, &mut self.read_len, &mut data) {
+            eprintln!("Could not read perf map file {:?}: {err}", self.path);
+            self.file = None;
+            self.last_open_attempt = Some(timestamp);
+            return;
+        }
+        if data.len() == data_len_before {
+            self.incomplete_line = data;
+            return;
+        }
+
+        let complete_len = match data.iter().rposition(|b| *b == b'\n') {
+            Some(pos) => pos + 1,
+            None => {
+                self.incomplete_line = data;
+                return;
+            }
+        };
+        self.incomplete_line = data[complete_len..].to_owned();
+
+        let mut avma_range = self.avma_range.clone();
+        for line in data[..complete_len].split(|b| *b == b'\n') {
+            let entry = match parse_perf_map_line(line) {
+                Some(entry) => entry,
+                None => continue,
+            };
+            let start_avma = entry.start;
+            let end_avma = entry.start.saturating_add(entry.size);
+
+            let category = jit_category_manager.get_category(Some(&entry.name), profile);
+            jit_functions.insert(JitFunction {
+                start_address: start_avma,
+                end_address: end_avma,
+                category,
+            });
+
+            avma_range = Some(match avma_range {
+                Some(range) => range.start.min(start_avma)..range.end.max(end_avma),
+                None => start_avma..end_avma,
+            });
+            self.symbols.insert(
+                start_avma,
+                Symbol {
+                    address: start_avma,
+                    size: Some(entry.size),
+                    name: entry.name,
+                },
+            );
+        }
+
+        let avma_range = match avma_range {
+            Some(avma_range) => avma_range,
+            None => return,
+        };
+        let symbol_table = Arc::new(SymbolTable::new(self.symbols.values().cloned().collect()));
+        if self.avma_range.is_some() {
+            profile.update_lib(process_handle, 0, avma_range.clone(), Some(symbol_table));
+        } else {
+            let path = self.path.to_string_lossy().to_string();
+            profile.add_lib(
+                process_handle,
+                LibraryInfo {
+                    base_avma: 0,
+                    avma_range: avma_range.clone(),
+                    debug_id: DebugId::nil(),
+                    code_id: None,
+                    path: path.clone(),
+                    debug_path: path,
+                    debug_name: self.name.clone(),
+                    name: self.name.clone(),
+                    arch: None,
+                    symbol_table: Some(symbol_table),
+                },
+            );
+        }
+        self.avma_range = Some(avma_range);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PerfMapEntry {
+    start: u64,
+    size: u64,
+    name: String,
+}
+
+/// Parse a line of the form `START SIZE NAME`. The name can contain spaces.
+fn parse_perf_map_line(line: &[u8]) -> Option<PerfMapEntry> {
+    let line = std::str::from_utf8(line).ok()?.trim_end_matches('\r');
+    let mut parts = line.splitn(3, ' ');
+    let start = parse_hex(parts.next()?)?;
+    let size = parse_hex(parts.next()?)?;
+    let name = parts.next()?.trim();
+    if size == 0 || name.is_empty() {
+        return None;
+    }
+    Some(PerfMapEntry {
+        start,
+        size,
+        name: name.to_owned(),
+    })
+}
+
+fn parse_hex(s: &str) -> Option<u64> {
+    let s = s.strip_prefix("0x").unwrap_or(s);
+    u64::from_str_radix(s, 16).ok()
+}
+
+#[test]
+fn test_parse_perf_map_line() {
+    assert_eq!(
+        parse_perf_map_line(b"3ef414c0 398 LazyCompile:~foo /home/user/foo.js:12:3"),
+        Some(PerfMapEntry {
+            start: 0x3ef414c0,
+            size: 0x398,
+            name: "LazyCompile:~foo /home/user/foo.js:12:3".into()
+        })
+    );
+    assert_eq!(
+        parse_perf_map_line(b"0x7f0a4c000000 0x40 py::main:/tmp/x.py"),
+        Some(PerfMapEntry {
+            start: 0x7f0a4c000000,
+            size: 0x40,
+            name: "py::main:/tmp/x.py".into()
+        })
+    );
+    assert_eq!(parse_perf_map_line(b""), None);
+    assert_eq!(parse_perf_map_line(b"1234 0 empty"), None);
+    assert_eq!(parse_perf_map_line(b"zz 10 bad"), None);
+}
+
+#[test]
+fn test_one_lib_per_perf_map() {
+    use fxprof_processed_profile::{
+        CategoryHandle, CpuDelta, Frame, ReferenceTimestamp, SamplingInterval, Timestamp,
+    };
+    use std::io::Write;
+
+    let path = std::env::temp_dir().join(format!("samply-test-perf-{}.map", std::process::id()));
+    let mut file = File::create(&path).unwrap();
+    let mut manager = PerfMapManager::new(1234);
+    manager.path = path.clone();
+
+    let mut profile = Profile::new(
+        "test",
+        ReferenceTimestamp::from_millis_since_unix_epoch(0.0),
+        SamplingInterval::from_millis(1),
+    );
+    let process = profile.add_process("test", 1234, Timestamp::from_millis_since_reference(0.0));
+    let thread = profile.add_thread(
+        process,
+        1234,
+        Timestamp::from_millis_since_reference(0.0),
+        true,
+    );
+    let mut jit_category_manager = JitCategoryManager::new();
+    let mut jit_functions = JitFunctions(Vec::new());
+    let mut process_and_sample = |profile: &mut Profile, address| {
+        manager.process_new_entries(
+            0,
+            None,
+            process,
+            profile,
+            &mut jit_category_manager,
+            &mut jit_functions,
+        );
+        profile.add_sample(
+            thread,
+            Timestamp::from_millis_since_reference(0.0),
+            std::iter::once((
+                Frame::InstructionPointer(address),
+                CategoryHandle::OTHER.into(),
+            )),
+            CpuDelta::ZERO,
+            1,
+        );
+    };
+
+    file.write_all(b"1000 10 first\n2000 10 sec").unwrap();
+    process_and_sample(&mut profile, 0x1004);
+    file.write_all(b"ond\n3000 10 third\n").unwrap();
+    process_and_sample(&mut profile, 0x2004);
+    process_and_sample(&mut profile, 0x3004);
+    std::fs::remove_file(&path).unwrap();
+
+    let json = serde_json::to_value(&profile).unwrap();
+    assert_eq!(json["libs"].as_array().unwrap().len(), 1);
+    let thread_json = &json["threads"][0];
+    let string_array = thread_json["stringArray"].as_array().unwrap();
+    let symbol_names: Vec<&str> = thread_json["nativeSymbols"]["name"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|index| {
+            string_array[index.as_u64().unwrap() as usize]
+                .as_str()
+                .unwrap()
+        })
+        .collect();
+    assert_eq!(symbol_names, ["first", "second", "third"]);
+}
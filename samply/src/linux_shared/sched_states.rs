@@ -0,0 +1,396 @@
+use linux_perf_data::linux_perf_event_reader::RawData;
+
+use std::collections::HashMap;
+
+use super::tracepoint_format::{find_format, parse_format_fields, update_field, TracepointField};
+
+/// The locations of the fields which we need from the `sched:sched_switch` and
+/// `sched:sched_waking` / `sched:sched_wakeup` tracepoints.
+///
+/// The layout of tracepoints is described by their format files in tracefs, and
+/// can change between kernel versions. The defaults are the layout on 64-bit
+/// kernels, which has been stable for a long time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchedTracepointFormats {
+    switch_prev_pid: TracepointField,
+    switch_prev_state: TracepointField,
+    switch_next_pid: TracepointField,
+    wakeup_pid: TracepointField,
+    wakeup_target_cpu: TracepointField,
+}
+
+impl Default for SchedTracepointFormats {
+    fn default() -> Self {
+        SchedTracepointFormats {
+            switch_prev_pid: TracepointField::new(24, 4),
+            switch_prev_state: TracepointField::new(32, 8),
+            switch_next_pid: TracepointField::new(56, 4),
+            wakeup_pid: TracepointField::new(24, 4),
+            wakeup_target_cpu: TracepointField::new(32, 4),
+        }
+    }
+}
+
+impl SchedTracepointFormats {
+    /// Update the field locations from the format description of `sched_switch`.
+    pub fn set_switch_format(&mut self, format: &str) {
+        let fields = parse_format_fields(format);
+        update_field(&mut self.switch_prev_pid, &fields, "prev_pid");
+        update_field(&mut self.switch_prev_state, &fields, "prev_state");
+        update_field(&mut self.switch_next_pid, &fields, "next_pid");
+    }
+
+    /// Update the field locations from the format description of `sched_waking`
+    /// or `sched_wakeup`, which share their layout.
+    pub fn set_wakeup_format(&mut self, format: &str) {
+        let fields = parse_format_fields(format);
+        update_field(&mut self.wakeup_pid, &fields, "pid");
+        update_field(&mut self.wakeup_target_cpu, &fields, "target_cpu");
+    }
+
+    /// Update the field locations from the TRACING_DATA section of a perf.data
+    /// file, which contains the format descriptions of the recorded tracepoints
+    /// as text.
+    pub fn set_tracing_data(&mut self, tracing_data: &[u8]) {
+        if let Some(format) = find_format(tracing_data, "sched_switch") {
+            self.set_switch_format(&format);
+        }
+        let wakeup_format = find_format(tracing_data, "sched_waking")
+            .or_else(|| find_format(tracing_data, "sched_wakeup"));
+        if let Some(format) = wakeup_format {
+            self.set_wakeup_format(&format);
+        }
+    }
+
+    pub fn parse_switch(&self, raw: &RawData, little_endian: bool) -> Option<SchedSwitch> {
+        Some(SchedSwitch {
+            prev_tid: self.switch_prev_pid.read(raw, little_endian)? as i32,
+            prev_state: self.switch_prev_state.read(raw, little_endian)?,
+            next_tid: self.switch_next_pid.read(raw, little_endian)? as i32,
+        })
+    }
+
+    pub fn parse_wakeup(&self, raw: &RawData, little_endian: bool) -> Option<SchedWakeup> {
+        Some(SchedWakeup {
+            tid: self.wakeup_pid.read(raw, little_endian)? as i32,
+            target_cpu: self.wakeup_target_cpu.read(raw, little_endian)? as u32,
+        })
+    }
+}
+
+/// The fields of a `sched:sched_switch` sample.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SchedSwitch {
+    pub prev_tid: i32,
+    pub prev_state: u64,
+    pub next_tid: i32,
+}
+
+/// The fields of a `sched:sched_waking` or `sched:sched_wakeup` sample.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SchedWakeup {
+    pub tid: i32,
+    pub target_cpu: u32,
+}
+
+/// The scheduling state of a thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadSchedState {
+    /// On a CPU.
+    Running,
+    /// Ready to run, but waiting for a CPU.
+    Runnable,
+    /// Waiting for something, in an interruptible sleep.
+    Sleeping,
+    /// Waiting in an uninterruptible sleep, which usually means disk I/O.
+    BlockedIo,
+}
+
+impl ThreadSchedState {
+    pub fn name(self) -> &'static str {
+        match self {
+            ThreadSchedState::Running => "Running",
+            ThreadSchedState::Runnable => "Runnable",
+            ThreadSchedState::Sleeping => "Sleeping",
+            ThreadSchedState::BlockedIo => "Blocked (I/O)",
+        }
+    }
+
+    /// The state of a thread after it was switched out, from the `prev_state`
+    /// field of `sched_switch`. Returns `None` if the thread has exited.
+    pub fn from_prev_state(prev_state: u64) -> Option<Self> {
+        const TASK_UNINTERRUPTIBLE: u64 = 0x2;
+        const EXIT_DEAD: u64 = 0x10;
+        const EXIT_ZOMBIE: u64 = 0x20;
+        // Older kernels report idle kernel threads as TASK_UNINTERRUPTIBLE | TASK_NOLOAD.
+        const TASK_NOLOAD: u64 = 0x400;
+
+        if prev_state & 0xff == 0 {
+            // TASK_RUNNING, possibly with the flag for preemption in the higher bits.
+            Some(ThreadSchedState::Runnable)
+        } else if prev_state & (EXIT_DEAD | EXIT_ZOMBIE) != 0 {
+            None
+        } else if prev_state & TASK_UNINTERRUPTIBLE != 0 && prev_state & TASK_NOLOAD == 0 {
+            Some(ThreadSchedState::BlockedIo)
+        } else {
+            // TASK_INTERRUPTIBLE, or one of the rarer states like stopped or parked.
+            Some(ThreadSchedState::Sleeping)
+        }
+    }
+}
+
+/// A time range in which a thread was in one scheduling state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchedStateInterval {
+    pub tid: i32,
+    pub state: ThreadSchedState,
+    pub start: u64,
+    pub end: u64,
+    /// The CPU which the thread ran on, was woken up on, or was switched out on.
+    pub cpu: Option<u32>,
+    /// The thread which woke this thread up, for the runnable state.
+    pub waker_tid: Option<i32>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CurrentState {
+    state: ThreadSchedState,
+    start: u64,
+    cpu: Option<u32>,
+    waker_tid: Option<i32>,
+}
+
+impl CurrentState {
+    fn end(self, tid: i32, timestamp: u64) -> SchedStateInterval {
+        SchedStateInterval {
+            tid,
+            state: self.state,
+            start: self.start,
+            end: timestamp.max(self.start),
+            cpu: self.cpu,
+            waker_tid: self.waker_tid,
+        }
+    }
+}
+
+/// Follows the scheduling state of each thread through switch-ins, switch-outs
+/// and wakeups, and returns a [`SchedStateInterval`] whenever a state ends.
+///
+/// Switch-ins come from context switch records or from the `next_pid` of
+/// `sched_switch`, whichever is observed. Switch-outs come from `sched_switch`,
+/// because only its `prev_state` tells us why the thread stopped running.
+pub struct SchedStateTracker {
+    pub formats: SchedTracepointFormats,
+    threads: HashMap<i32, CurrentState>,
+}
+
+impl SchedStateTracker {
+    pub fn new(formats: SchedTracepointFormats) -> Self {
+        SchedStateTracker {
+            formats,
+            threads: HashMap::new(),
+        }
+    }
+
+    pub fn handle_switch_in(
+        &mut self,
+        tid: i32,
+        timestamp: u64,
+        cpu: Option<u32>,
+    ) -> Option<SchedStateInterval> {
+        if let Some(current) = self.threads.get(&tid) {
+            if current.state == ThreadSchedState::Running {
+                return None;
+            }
+        }
+        self.set_state(tid, ThreadSchedState::Running, timestamp, cpu, None)
+    }
+
+    pub fn handle_switch_out(
+        &mut self,
+        tid: i32,
+        timestamp: u64,
+        cpu: Option<u32>,
+        prev_state: u64,
+    ) -> Option<SchedStateInterval> {
+        match ThreadSchedState::from_prev_state(prev_state) {
+            Some(state) => self.set_state(tid, state, timestamp, cpu, None),
+            None => Some(self.threads.remove(&tid)?.end(tid, timestamp)),
+        }
+    }
+
+    /// Wakeups of threads which are already running or runnable are ignored.
+    pub fn handle_wakeup(
+        &mut self,
+        tid: i32,
+        timestamp: u64,
+        target_cpu: u32,
+        waker_tid: Option<i32>,
+    ) -> Option<SchedStateInterval> {
+        if let Some(current) = self.threads.get(&tid) {
+            if matches!(
+                current.state,
+                ThreadSchedState::Running | ThreadSchedState::Runnable
+            ) {
+                return None;
+            }
+        }
+        self.set_state(
+            tid,
+            ThreadSchedState::Runnable,
+            timestamp,
+            Some(target_cpu),
+            waker_tid,
+        )
+    }
+
+    fn set_state(
+        &mut self,
+        tid: i32,
+        state: ThreadSchedState,
+        timestamp: u64,
+        cpu: Option<u32>,
+        waker_tid: Option<i32>,
+    ) -> Option<SchedStateInterval> {
+        let new_state = CurrentState {
+            state,
+            start: timestamp,
+            cpu,
+            waker_tid,
+        };
+        let previous_state = self.threads.insert(tid, new_state)?;
+        Some(previous_state.end(tid, timestamp))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SCHED_SWITCH_FORMAT: &str = "name: sched_switch
+ID: 316
+format:
+	field:unsigned short common_type;	offset:0;	size:2;	signed:0;
+	field:unsigned char common_flags;	offset:2;	size:1;	signed:0;
+	field:unsigned char common_preempt_count;	offset:3;	size:1;	signed:0;
+	field:int common_pid;	offset:4;	size:4;	signed:1;
+
+	field:char prev_comm[16];	offset:8;	size:16;	signed:0;
+	field:pid_t prev_pid;	offset:24;	size:4;	signed:1;
+	field:int prev_prio;	offset:28;	size:4;	signed:1;
+	field:long prev_state;	offset:32;	size:8;	signed:1;
+	field:char next_comm[16];	offset:40;	size:16;	signed:0;
+	field:pid_t next_pid;	offset:56;	size:4;	signed:1;
+	field:int next_prio;	offset:60;	size:4;	signed:1;
+";
+
+    #[test]
+    fn test_parse_formats() {
+        let mut formats = SchedTracepointFormats::default();
+        formats.set_switch_format(SCHED_SWITCH_FORMAT);
+        assert_eq!(formats, SchedTracepointFormats::default());
+
+        // A 32-bit kernel, where prev_state is only 4 bytes.
+        let format = SCHED_SWITCH_FORMAT
+            .replace(
+                "long prev_state;\toffset:32;\tsize:8",
+                "long prev_state;\toffset:32;\tsize:4",
+            )
+            .replace("offset:40;", "offset:36;")
+            .replace("offset:56;", "offset:52;");
+        let mut tracing_data = b"\x17\x08\x44tracing0.6\0".to_vec();
+        tracing_data.extend_from_slice(format.as_bytes());
+        tracing_data.extend_from_slice(b"\nprint fmt: \"prev_comm=%s\"\n");
+        formats.set_tracing_data(&tracing_data);
+        assert_eq!(formats.switch_prev_state, TracepointField::new(32, 4));
+        assert_eq!(formats.switch_next_pid, TracepointField::new(52, 4));
+        assert_eq!(formats.wakeup_pid, TracepointField::new(24, 4));
+
+        let mut raw = vec![0; 64];
+        raw[24..28].copy_from_slice(&1234i32.to_le_bytes());
+        raw[32..36].copy_from_slice(&2u32.to_le_bytes());
+        raw[52..56].copy_from_slice(&5678i32.to_le_bytes());
+        assert_eq!(
+            formats.parse_switch(&RawData::Single(&raw), true),
+            Some(SchedSwitch {
+                prev_tid: 1234,
+                prev_state: 2,
+                next_tid: 5678,
+            })
+        );
+        assert_eq!(
+            formats.parse_switch(&RawData::Single(&raw[..40]), true),
+            None
+        );
+    }
+
+    #[test]
+    fn test_prev_state() {
+        use ThreadSchedState::*;
+        assert_eq!(ThreadSchedState::from_prev_state(0), Some(Runnable));
+        assert_eq!(ThreadSchedState::from_prev_state(0x100), Some(Runnable));
+        assert_eq!(ThreadSchedState::from_prev_state(0x1), Some(Sleeping));
+        assert_eq!(ThreadSchedState::from_prev_state(0x2), Some(BlockedIo));
+        assert_eq!(ThreadSchedState::from_prev_state(0x402), Some(Sleeping));
+        assert_eq!(ThreadSchedState::from_prev_state(0x80), Some(Sleeping));
+        assert_eq!(ThreadSchedState::from_prev_state(0x20), None);
+    }
+
+    #[test]
+    fn test_sched_state_tracker() {
+        let mut tracker = SchedStateTracker::new(SchedTracepointFormats::default());
+        let tid = 10;
+        assert_eq!(tracker.handle_switch_in(tid, 100, Some(1)), None);
+        // Duplicate switch-ins, e.g. from both a context switch record and
+        // sched_switch, are ignored.
+        assert_eq!(tracker.handle_switch_in(tid, 101, Some(1)), None);
+        assert_eq!(
+            tracker.handle_switch_out(tid, 150, Some(1), 0x1),
+            Some(SchedStateInterval {
+                tid,
+                state: ThreadSchedState::Running,
+                start: 100,
+                end: 150,
+                cpu: Some(1),
+                waker_tid: None,
+            })
+        );
+        assert_eq!(
+            tracker.handle_wakeup(tid, 200, 3, Some(11)),
+            Some(SchedStateInterval {
+                tid,
+                state: ThreadSchedState::Sleeping,
+                start: 150,
+                end: 200,
+                cpu: Some(1),
+                waker_tid: None,
+            })
+        );
+        // A second wakeup doesn't change who woke the thread up.
+        assert_eq!(tracker.handle_wakeup(tid, 210, 3, Some(12)), None);
+        assert_eq!(
+            tracker.handle_switch_in(tid, 230, Some(3)),
+            Some(SchedStateInterval {
+                tid,
+                state: ThreadSchedState::Runnable,
+                start: 200,
+                end: 230,
+                cpu: Some(3),
+                waker_tid: Some(11),
+            })
+        );
+        // The thread exits.
+        assert_eq!(
+            tracker.handle_switch_out(tid, 260, Some(3), 0x20),
+            Some(SchedStateInterval {
+                tid,
+                state: ThreadSchedState::Running,
+                start: 230,
+                end: 260,
+                cpu: Some(3),
+                waker_tid: None,
+            })
+        );
+        assert_eq!(tracker.handle_switch_in(tid, 300, Some(3)), None);
+    }
+}
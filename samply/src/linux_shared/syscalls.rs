@@ -0,0 +1,235 @@
+use linux_perf_data::linux_perf_event_reader::RawData;
+
+use super::tracepoint_format::{find_format, parse_format_fields, update_field, TracepointField};
+
+/// The locations of the fields of the `raw_syscalls:sys_enter` and
+/// `raw_syscalls:sys_exit` tracepoints.
+///
+/// The defaults are the layout on 64-bit kernels. On 32-bit kernels, the
+/// syscall number, the arguments and the return value are only 4 bytes each.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyscallTracepointFormats {
+    enter_id: TracepointField,
+    enter_args: TracepointField,
+    exit_id: TracepointField,
+    exit_ret: TracepointField,
+}
+
+impl Default for SyscallTracepointFormats {
+    fn default() -> Self {
+        SyscallTracepointFormats {
+            enter_id: TracepointField::new(8, 8),
+            enter_args: TracepointField::new(16, 48),
+            exit_id: TracepointField::new(8, 8),
+            exit_ret: TracepointField::new(16, 8),
+        }
+    }
+}
+
+impl SyscallTracepointFormats {
+    /// Update the field locations from the format description of `sys_enter`.
+    pub fn set_enter_format(&mut self, format: &str) {
+        let fields = parse_format_fields(format);
+        update_field(&mut self.enter_id, &fields, "id");
+        update_field(&mut self.enter_args, &fields, "args");
+    }
+
+    /// Update the field locations from the format description of `sys_exit`.
+    pub fn set_exit_format(&mut self, format: &str) {
+        let fields = parse_format_fields(format);
+        update_field(&mut self.exit_id, &fields, "id");
+        update_field(&mut self.exit_ret, &fields, "ret");
+    }
+
+    /// Update the field locations from the TRACING_DATA section of a perf.data
+    /// file.
+    pub fn set_tracing_data(&mut self, tracing_data: &[u8]) {
+        if let Some(format) = find_format(tracing_data, "sys_enter") {
+            self.set_enter_format(&format);
+        }
+        if let Some(format) = find_format(tracing_data, "sys_exit") {
+            self.set_exit_format(&format);
+        }
+    }
+
+    pub fn parse_enter(&self, raw: &RawData, little_endian: bool) -> Option<SyscallEnter> {
+        let mut args = [0; 6];
+        for (i, arg) in args.iter_mut().enumerate() {
+            *arg = self.enter_args.read_element(raw, little_endian, i, 6)?;
+        }
+        Some(SyscallEnter {
+            id: self.enter_id.read(raw, little_endian)?,
+            args,
+        })
+    }
+
+    pub fn parse_exit(&self, raw: &RawData, little_endian: bool) -> Option<SyscallExit> {
+        Some(SyscallExit {
+            id: self.exit_id.read(raw, little_endian)?,
+            ret: self.exit_ret.read_signed(raw, little_endian)?,
+        })
+    }
+}
+
+/// The fields of a `raw_syscalls:sys_enter` sample.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyscallEnter {
+    pub id: u64,
+    pub args: [u64; 6],
+}
+
+/// The fields of a `raw_syscalls:sys_exit` sample.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyscallExit {
+    pub id: u64,
+    /// The return value, or the negated errno if the syscall failed.
+    pub ret: i64,
+}
+
+/// Format the syscall arguments for the marker. We don't know how many
+/// arguments each syscall takes, so all six are shown.
+pub fn format_syscall_args(args: &[u64; 6]) -> String {
+    args.iter()
+        .map(|arg| format!("{arg:#x}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Whether the syscall reads or writes files, or works with file descriptors
+/// or paths. These syscalls are shown in the file I/O track.
+pub fn is_file_io_syscall(name: &str) -> bool {
+    matches!(
+        name,
+        "read"
+            | "write"
+            | "pread64"
+            | "pwrite64"
+            | "readv"
+            | "writev"
+            | "preadv"
+            | "pwritev"
+            | "preadv2"
+            | "pwritev2"
+            | "open"
+            | "openat"
+            | "openat2"
+            | "creat"
+            | "close"
+            | "close_range"
+            | "lseek"
+            | "fsync"
+            | "fdatasync"
+            | "sync_file_range"
+            | "fallocate"
+            | "fadvise64"
+            | "truncate"
+            | "ftruncate"
+            | "stat"
+            | "fstat"
+            | "lstat"
+            | "newfstatat"
+            | "statx"
+            | "access"
+            | "faccessat"
+            | "faccessat2"
+            | "readlink"
+            | "readlinkat"
+            | "getdents64"
+            | "mkdir"
+            | "mkdirat"
+            | "rmdir"
+            | "unlink"
+            | "unlinkat"
+            | "rename"
+            | "renameat"
+            | "renameat2"
+            | "sendfile"
+            | "splice"
+            | "copy_file_range"
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SYS_EXIT_FORMAT: &str = "name: sys_exit
+ID: 22
+format:
+\tfield:unsigned short common_type;\toffset:0;\tsize:2;\tsigned:0;
+\tfield:unsigned char common_flags;\toffset:2;\tsize:1;\tsigned:0;
+\tfield:unsigned char common_preempt_count;\toffset:3;\tsize:1;\tsigned:0;
+\tfield:int common_pid;\toffset:4;\tsize:4;\tsigned:1;
+
+\tfield:long id;\toffset:8;\tsize:8;\tsigned:1;
+\tfield:long ret;\toffset:16;\tsize:8;\tsigned:1;
+";
+
+    #[test]
+    fn test_parse_syscall_records() {
+        let formats = SyscallTracepointFormats::default();
+        let mut raw = vec![0; 64];
+        raw[8..16].copy_from_slice(&257u64.to_le_bytes());
+        raw[16..24].copy_from_slice(&(-100i64).to_le_bytes());
+        raw[24..32].copy_from_slice(&0x7ffd_1234u64.to_le_bytes());
+        let enter = formats.parse_enter(&RawData::Single(&raw), true).unwrap();
+        assert_eq!(enter.id, 257);
+        assert_eq!(
+            format_syscall_args(&enter.args),
+            "0xffffffffffffff9c, 0x7ffd1234, 0x0, 0x0, 0x0, 0x0"
+        );
+        assert_eq!(
+            formats.parse_enter(&RawData::Single(&raw[..40]), true),
+            None
+        );
+
+        let exit = formats
+            .parse_exit(&RawData::Single(&raw[..24]), true)
+            .unwrap();
+        assert_eq!(exit, SyscallExit { id: 257, ret: -100 });
+    }
+
+    #[test]
+    fn test_parse_formats() {
+        let mut formats = SyscallTracepointFormats::default();
+        formats.set_exit_format(SYS_EXIT_FORMAT);
+        assert_eq!(formats, SyscallTracepointFormats::default());
+
+        // A 32-bit kernel, where id, args and ret are 4 bytes each.
+        let exit_format = SYS_EXIT_FORMAT
+            .replace("long id;\toffset:8;\tsize:8", "long id;\toffset:8;\tsize:4")
+            .replace(
+                "long ret;\toffset:16;\tsize:8",
+                "long ret;\toffset:12;\tsize:4",
+            );
+        let enter_format = SYS_EXIT_FORMAT
+            .replace("name: sys_exit", "name: sys_enter")
+            .replace("long id;\toffset:8;\tsize:8", "long id;\toffset:8;\tsize:4")
+            .replace(
+                "long ret;\toffset:16;\tsize:8;\tsigned:1",
+                "unsigned long args[6];\toffset:12;\tsize:24;\tsigned:0",
+            );
+        let mut tracing_data = Vec::new();
+        tracing_data.extend_from_slice(enter_format.as_bytes());
+        tracing_data.extend_from_slice(b"\nprint fmt: \"NR %ld\"\n");
+        tracing_data.extend_from_slice(exit_format.as_bytes());
+        tracing_data.extend_from_slice(b"\nprint fmt: \"NR %ld = %ld\"\n");
+        formats.set_tracing_data(&tracing_data);
+
+        let mut raw = vec![0; 36];
+        raw[8..12].copy_from_slice(&5u32.to_le_bytes());
+        raw[12..16].copy_from_slice(&(-2i32).to_le_bytes());
+        raw[16..20].copy_from_slice(&0x1000u32.to_le_bytes());
+        assert_eq!(
+            formats.parse_enter(&RawData::Single(&raw), true),
+            Some(SyscallEnter {
+                id: 5,
+                args: [0xffff_fffe, 0x1000, 0, 0, 0, 0],
+            })
+        );
+        assert_eq!(
+            formats.parse_exit(&RawData::Single(&raw[..16]), true),
+            Some(SyscallExit { id: 5, ret: -2 })
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use byteorder::{BigEndian, LittleEndian};
+use linux_perf_data::linux_perf_event_reader::RawData;
+
+use std::collections::HashMap;
+
+/// The location of a field in the raw data of a tracepoint sample.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TracepointField {
+    offset: usize,
+    size: usize,
+}
+
+impl TracepointField {
+    pub const fn new(offset: usize, size: usize) -> Self {
+        TracepointField { offset, size }
+    }
+
+    pub fn read(&self, raw: &RawData, little_endian: bool) -> Option<u64> {
+        let mut value = raw.get(self.offset..self.offset.checked_add(self.size)?)?;
+        match (self.size, little_endian) {
+            (8, true) => value.read_u64::<LittleEndian>().ok(),
+            (8, false) => value.read_u64::<BigEndian>().ok(),
+            (4, true) => value.read_u32::<LittleEndian>().ok().map(u64::from),
+            (4, false) => value.read_u32::<BigEndian>().ok().map(u64::from),
+            _ => None,
+        }
+    }
+
+    /// Read a signed field, such as a `long` which is only 4 bytes on 32-bit
+    /// kernels.
+    pub fn read_signed(&self, raw: &RawData, little_endian: bool) -> Option<i64> {
+        let value = self.read(raw, little_endian)?;
+        let shift = 64 - 8 * self.size as u32;
+        Some(((value << shift) as i64) >> shift)
+    }
+
+    /// Read the element at `index` of an array field with `len` elements, such
+    /// as `unsigned long args[6]`.
+    pub fn read_element(
+        &self,
+        raw: &RawData,
+        little_endian: bool,
+        index: usize,
+        len: usize,
+    ) -> Option<u64> {
+        let element_size = self.size / len;
+        TracepointField::new(self.offset + index * element_size, element_size)
+            .read(raw, little_endian)
+    }
+}
+
+/// Parse the `field:` lines of a tracepoint format description, such as
+/// `field:pid_t prev_pid; offset:24; size:4; signed:1;`, whose parts are separated by tabs.
+pub fn parse_format_fields(format: &str) -> HashMap<&str, TracepointField> {
+    let mut fields = HashMap::new();
+    for line in format.lines() {
+        let mut parts = line.trim().split(';').map(str::trim);
+        let declaration = match parts.next().and_then(|part| part.strip_prefix("field:")) {
+            Some(declaration) => declaration,
+            None => continue,
+        };
+        // Strip the array length from declarations like `char prev_comm[16]`.
+        let name = declaration.rsplit(' ').next().unwrap_or_default();
+        let name = name.split('[').next().unwrap_or_default();
+        let mut offset = None;
+        let mut size = None;
+        for part in parts {
+            if let Some(value) = part.strip_prefix("offset:") {
+                offset = value.parse().ok();
+            } else if let Some(value) = part.strip_prefix("size:") {
+                size = value.parse().ok();
+            }
+        }
+        if let (Some(offset), Some(size)) = (offset, size) {
+            fields.insert(name, TracepointField { offset, size });
+        }
+    }
+    fields
+}
+
+pub fn update_field(
+    field: &mut TracepointField,
+    fields: &HashMap<&str, TracepointField>,
+    name: &str,
+) {
+    if let Some(new_field) = fields.get(name) {
+        *field = *new_field;
+    }
+}
+
+/// Find the format description of a tracepoint in perf's tracing data.
+pub fn find_format(tracing_data: &[u8], name: &str) -> Option<String> {
+    let header = format!("name: {name}\n");
+    let start = memchr::memmem::find(tracing_data, header.as_bytes())?;
+    let format = &tracing_data[start..];
+    let end = memchr::memmem::find(format, b"\nprint fmt:").unwrap_or(format.len());
+    Some(String::from_utf8_lossy(&format[..end]).into_owned())
+}
+
+#[test]
+fn test_read_fields() {
+    let mut raw = vec![0; 16];
+    raw[0..4].copy_from_slice(&(-38i32).to_le_bytes());
+    raw[4..8].copy_from_slice(&7u32.to_le_bytes());
+    raw[8..16].copy_from_slice(&(-2i64).to_le_bytes());
+    let raw = RawData::Single(&raw);
+    assert_eq!(
+        TracepointField::new(0, 4).read(&raw, true),
+        Some(0xffff_ffda)
+    );
+    assert_eq!(
+        TracepointField::new(0, 4).read_signed(&raw, true),
+        Some(-38)
+    );
+    assert_eq!(TracepointField::new(8, 8).read_signed(&raw, true), Some(-2));
+    assert_eq!(
+        TracepointField::new(0, 8).read_element(&raw, true, 1, 2),
+        Some(7)
+    );
+    assert_eq!(TracepointField::new(12, 8).read(&raw, true), None);
+    assert_eq!(TracepointField::new(usize::MAX, 8).read(&raw, true), None);
+}
@@ -14,13 +14,13 @@ use std::time::{Duration, Instant};
 use super::error::SamplingError;
 use super::process_launcher::{MachError, TaskAccepter};
 use super::sampler::{Sampler, TaskInit};
+use crate::recording_props::RecordingProps;
 use crate::server::{start_server_main, ServerProps};
 
 pub fn start_profiling_pid(
     _output_file: &Path,
     _pid: u32,
-    _time_limit: Option<Duration>,
-    _interval: Duration,
+    _recording_props: RecordingProps,
     _server_props: Option<ServerProps>,
 ) {
     eprintln!("Profiling existing processes is currently not supported on macOS.");
@@ -28,14 +28,53 @@ pub fn start_profiling_pid(
     std::process::exit(1)
 }
 
+pub fn start_profiling_all_cpus(
+    _output_file: &Path,
+    _recording_props: RecordingProps,
+    _server_props: Option<ServerProps>,
+) {
+    eprintln!("System-wide profiling is currently not supported on macOS.");
+    eprintln!("You can only profile processes which you launch via samply.");
+    std::process::exit(1)
+}
+
 pub fn start_recording(
     output_file: &Path,
     command_name: OsString,
     command_args: &[OsString],
-    time_limit: Option<Duration>,
-    interval: Duration,
+    recording_props: RecordingProps,
     server_props: Option<ServerProps>,
 ) -> Result<ExitStatus, MachError> {
+    if recording_props.allocations {
+        eprintln!("Allocation profiling is currently only supported on Linux.");
+        std::process::exit(1)
+    }
+    if recording_props.event.is_some() {
+        eprintln!("Choosing the sampled event is currently only supported on Linux.");
+        std::process::exit(1)
+    }
+    if recording_props.unwind.is_some() || recording_props.stack_size.is_some() {
+        eprintln!("Choosing the unwinding mode is currently only supported on Linux.");
+        std::process::exit(1)
+    }
+    if recording_props.sched_states {
+        eprintln!("Recording thread states is currently only supported on Linux.");
+        std::process::exit(1)
+    }
+    if recording_props.syscalls {
+        eprintln!("Recording syscalls is currently only supported on Linux.");
+        std::process::exit(1)
+    }
+    if recording_props.save_perf_data.is_some() {
+        eprintln!("Saving perf.data files is currently only supported on Linux.");
+        std::process::exit(1)
+    }
+    let RecordingProps {
+        time_limit,
+        interval,
+        ..
+    } = recording_props;
+
     let (task_sender, task_receiver) = unbounded();
     let command_name_copy = command_name.to_string_lossy().to_string();
     let sampler_thread = thread::spawn(move || {
@@ -6,7 +6,10 @@ mod linux;
 
 mod import;
 mod linux_shared;
+mod recording_props;
 mod server;
+mod symbolicate;
+mod symbols_config;
 
 use clap::{Args, Parser, Subcommand};
 use tempfile::NamedTempFile;
@@ -25,6 +28,10 @@ use linux::profiler;
 use mac::profiler;
 
 use server::{start_server_main, PortSelection, ServerProps};
+use symbols_config::{SymbolServer, SymbolsConfig};
+
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+use recording_props::{RecordingProps, UnwindMode};
 
 #[derive(Debug, Parser)]
 #[command(
@@ -43,12 +50,28 @@ EXAMPLES:
     # On Linux, you can also profile existing processes by pid:
     samply record -p 12345 # Linux only
 
+    # On Linux, you can also profile all processes on the system:
+    samply record -a # Linux only
+
     # Alternative usage: Save profile to file for later viewing, and then load it.
     samply record --save-only -o prof.json -- ./yourcommand yourargs
     samply load prof.json # Opens in the browser and supplies symbols
 
     # Import perf.data files from Linux perf:
     samply load perf.data
+
+    # Save a copy of a profile with all symbols resolved, for sharing:
+    samply symbolicate prof.json -o prof-symbolicated.json
+
+    # Get symbols from a Breakpad symbol server:
+    samply load prof.json --breakpad-symbol-server https://symbols.example.com/
+
+SYMBOL CONFIGURATION:
+    Symbol directories and servers can also be configured in the [symbols]
+    table of a samply.toml file, in the samply directory of the user's config
+    directory (e.g. ~/.config/samply/samply.toml) or in the current directory.
+    Command line flags take precedence over the current directory's file,
+    which takes precedence over the user's file.
 "#
 )]
 struct Opt {
@@ -61,6 +84,10 @@ enum Action {
     /// Load a profile from a file and display it.
     Load(LoadArgs),
 
+    /// Resolve all symbols in a profile and save a self-contained copy, which
+    /// can be viewed without a symbol server.
+    Symbolicate(SymbolicateArgs),
+
     #[cfg(any(target_os = "macos", target_os = "linux"))]
     /// Record a profile and display it.
     Record(RecordArgs),
@@ -75,6 +102,23 @@ struct LoadArgs {
     server_args: ServerArgs,
 }
 
+#[derive(Debug, Args)]
+struct SymbolicateArgs {
+    /// Path to the profile that should be symbolicated.
+    file: PathBuf,
+
+    /// Output filename. Defaults to the input filename with a "-symbolicated" suffix.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Print debugging output.
+    #[arg(short, long)]
+    verbose: bool,
+
+    #[command(flatten)]
+    symbol_args: SymbolArgs,
+}
+
 #[cfg(any(target_os = "macos", target_os = "linux"))]
 #[derive(Debug, Args)]
 struct RecordArgs {
@@ -99,16 +143,66 @@ struct RecordArgs {
 
     /// Profile the execution of this command.
     #[arg(
-        required_unless_present = "pid",
-        conflicts_with = "pid",
+        required_unless_present_any = ["pid", "all_cpus"],
+        conflicts_with_all = ["pid", "all_cpus"],
         allow_hyphen_values = true,
         trailing_var_arg = true
     )]
     command: Vec<std::ffi::OsString>,
 
     /// Process ID of existing process to attach to (Linux only).
-    #[arg(short, long)]
+    #[arg(short, long, conflicts_with = "all_cpus")]
     pid: Option<u32>,
+
+    /// Profile all processes on the system, on all CPUs (Linux only).
+    #[arg(short, long)]
+    all_cpus: bool,
+
+    /// Also record the native allocations of the launched command, by observing
+    /// the malloc, calloc, realloc and free functions of libc (Linux only).
+    #[arg(long, conflicts_with_all = ["pid", "all_cpus"])]
+    allocations: bool,
+
+    /// The event to sample on, in the syntax of `perf record -e`, for example
+    /// `instructions`, `cache-misses/period=1000/` or `sched:sched_switch`.
+    /// Defaults to CPU cycles (Linux only).
+    #[arg(short, long)]
+    event: Option<String>,
+
+    /// How to obtain the stack of each sample. `fp` has the lowest overhead but
+    /// needs frame pointers, `dwarf` works without frame pointers, and `hybrid`
+    /// uses DWARF only for the leaf frames. Defaults to `dwarf` (Linux only).
+    #[arg(long, value_enum)]
+    unwind: Option<UnwindMode>,
+
+    /// The number of bytes of the user stack which are copied for each sample,
+    /// for `dwarf` and `hybrid` unwinding. Must be a multiple of 8 and at most
+    /// 65528. Defaults to 32000 for `dwarf` and 4096 for `hybrid` (Linux only).
+    #[arg(long, value_name = "BYTES")]
+    stack_size: Option<u32>,
+
+    /// Also record the scheduling state of each thread (running, runnable,
+    /// sleeping or blocked on I/O) and which thread woke it up, as markers.
+    /// This usually requires root privileges (Linux only).
+    #[arg(long)]
+    sched_states: bool,
+
+    /// Also record the syscalls of each thread as markers, with their arguments,
+    /// return values and durations. Syscalls which take at least 1ms get a stack,
+    /// which is walked with frame pointers. This usually requires root privileges
+    /// (Linux only).
+    #[arg(long)]
+    syscalls: bool,
+
+    /// Also save the raw perf events to this file, in the perf.data format which
+    /// `samply load` and `perf report` can read (Linux only).
+    #[arg(long, value_name = "PATH")]
+    save_perf_data: Option<PathBuf>,
+
+    /// Only save the perf.data file, and don't turn the recording into a profile.
+    /// Implies --save-only (Linux only).
+    #[arg(long, requires = "save_perf_data")]
+    perf_data_only: bool,
 }
 
 #[derive(Debug, Args)]
@@ -124,6 +218,37 @@ struct ServerArgs {
     /// Print debugging output.
     #[arg(short, long)]
     verbose: bool,
+
+    #[command(flatten)]
+    symbol_args: SymbolArgs,
+}
+
+/// Where to find symbols, in addition to the servers and directories from samply.toml.
+#[derive(Debug, Args)]
+struct SymbolArgs {
+    /// Extra directory with Breakpad .sym files, in the symbol store layout.
+    #[arg(long = "breakpad-symbol-dir", value_name = "DIR")]
+    breakpad_symbol_dirs: Vec<PathBuf>,
+
+    /// Extra Breakpad symbol server URL.
+    #[arg(long = "breakpad-symbol-server", value_name = "URL")]
+    breakpad_symbol_servers: Vec<String>,
+
+    /// Where to cache the symindex files for Breakpad .sym files.
+    #[arg(long, value_name = "DIR")]
+    breakpad_symindex_cache_dir: Option<PathBuf>,
+
+    /// Extra Windows symbol server URL, for pdb, exe and dll files.
+    #[arg(long = "windows-symbol-server", value_name = "URL")]
+    windows_symbol_servers: Vec<String>,
+
+    /// Extra debuginfod server URL, for ELF debug info and executables.
+    #[arg(long = "debuginfod-server", value_name = "URL")]
+    debuginfod_servers: Vec<String>,
+
+    /// The directory in which files downloaded from symbol servers are cached.
+    #[arg(long, value_name = "DIR")]
+    symbol_cache_dir: Option<PathBuf>,
 }
 
 fn main() {
@@ -145,11 +270,28 @@ fn main() {
             start_server_main(filename, load_args.server_args.server_props());
         }
 
+        Action::Symbolicate(symbolicate_args) => {
+            let output_file = match symbolicate_args.output {
+                Some(output_file) => output_file,
+                None => symbolicate::default_output_path(&symbolicate_args.file),
+            };
+            if let Err(err) = symbolicate::symbolicate_profile_file(
+                &symbolicate_args.file,
+                &output_file,
+                symbolicate_args.verbose,
+                &symbolicate_args.symbol_args.symbols_config(),
+            ) {
+                eprintln!("Could not symbolicate {:?}: {}", symbolicate_args.file, err);
+                std::process::exit(1)
+            }
+            eprintln!("Saved the symbolicated profile to {output_file:?}.");
+        }
+
         #[cfg(any(target_os = "macos", target_os = "linux"))]
         Action::Record(record_args) => {
             use std::time::Duration;
 
-            let server_props = if record_args.save_only {
+            let server_props = if record_args.save_only || record_args.perf_data_only {
                 None
             } else {
                 Some(record_args.server_args.server_props())
@@ -164,13 +306,42 @@ fn main() {
                 std::process::exit(1);
             }
             let interval = Duration::from_secs_f64(1.0 / record_args.rate);
+            if let Some(stack_size) = record_args.stack_size {
+                if record_args.unwind == Some(UnwindMode::Fp) {
+                    eprintln!("Error: --stack-size has no effect with --unwind fp");
+                    std::process::exit(1);
+                }
+                if stack_size == 0 || stack_size % 8 != 0 || stack_size > 65528 {
+                    eprintln!(
+                        "Error: the stack size must be a non-zero multiple of 8 and at most 65528, got {stack_size}"
+                    );
+                    std::process::exit(1);
+                }
+            }
+            let recording_props = RecordingProps {
+                time_limit,
+                interval,
+                allocations: record_args.allocations,
+                event: record_args.event,
+                unwind: record_args.unwind,
+                stack_size: record_args.stack_size,
+                sched_states: record_args.sched_states,
+                syscalls: record_args.syscalls,
+                save_perf_data: record_args.save_perf_data,
+                perf_data_only: record_args.perf_data_only,
+            };
 
-            if let Some(pid) = record_args.pid {
+            if record_args.all_cpus {
+                profiler::start_profiling_all_cpus(
+                    &record_args.output,
+                    recording_props,
+                    server_props,
+                );
+            } else if let Some(pid) = record_args.pid {
                 profiler::start_profiling_pid(
                     &record_args.output,
                     pid,
-                    time_limit,
-                    interval,
+                    recording_props,
                     server_props,
                 );
             } else {
@@ -178,8 +349,7 @@ fn main() {
                     &record_args.output,
                     record_args.command[0].clone(),
                     &record_args.command[1..],
-                    time_limit,
-                    interval,
+                    recording_props,
                     server_props,
                 ) {
                     Ok(exit_status) => exit_status,
@@ -211,6 +381,41 @@ impl ServerArgs {
             port_selection,
             verbose: self.verbose,
             open_in_browser,
+            symbols_config: self.symbol_args.symbols_config(),
+        }
+    }
+}
+
+impl SymbolArgs {
+    /// The symbol configuration from the config files, overridden by the
+    /// command line flags.
+    pub fn symbols_config(&self) -> SymbolsConfig {
+        let file_config = match SymbolsConfig::from_config_files() {
+            Ok(config) => config,
+            Err(err) => {
+                eprintln!("{err}");
+                std::process::exit(1)
+            }
+        };
+        self.to_config().overriding(file_config)
+    }
+
+    fn to_config(&self) -> SymbolsConfig {
+        let servers = |urls: &[String]| -> Vec<SymbolServer> {
+            urls.iter()
+                .map(|url| SymbolServer {
+                    url: url.clone(),
+                    cache_dir: None,
+                })
+                .collect()
+        };
+        SymbolsConfig {
+            cache_dir: self.symbol_cache_dir.clone(),
+            breakpad_symbol_dirs: self.breakpad_symbol_dirs.clone(),
+            breakpad_symindex_cache_dir: self.breakpad_symindex_cache_dir.clone(),
+            breakpad_symbol_servers: servers(&self.breakpad_symbol_servers),
+            windows_symbol_servers: servers(&self.windows_symbol_servers),
+            debuginfod_servers: servers(&self.debuginfod_servers),
         }
     }
 }
@@ -252,4 +457,108 @@ fn verify_cli() {
     // Make sure you can't pass both a pid and a command name at the same time.
     let opt_res = Opt::try_parse_from(["samply", "record", "-p", "1234", "rustup"]);
     assert!(opt_res.is_err());
+
+    let opt = Opt::parse_from(["samply", "record", "--all-cpus"]);
+    assert!(
+        matches!(opt.action, Action::Record(record_args) if record_args.all_cpus && record_args.command.is_empty())
+    );
+
+    // --all-cpus can't be combined with a pid or a command.
+    let opt_res = Opt::try_parse_from(["samply", "record", "-a", "-p", "1234"]);
+    assert!(opt_res.is_err());
+    let opt_res = Opt::try_parse_from(["samply", "record", "-a", "rustup"]);
+    assert!(opt_res.is_err());
+
+    let opt = Opt::parse_from(["samply", "record", "--allocations", "rustup"]);
+    assert!(
+        matches!(opt.action, Action::Record(record_args) if record_args.allocations && record_args.command == ["rustup"])
+    );
+
+    // --allocations only works for launched commands.
+    let opt_res = Opt::try_parse_from(["samply", "record", "--allocations", "-p", "1234"]);
+    assert!(opt_res.is_err());
+
+    let opt = Opt::parse_from(["samply", "symbolicate", "prof.json", "-o", "out.json"]);
+    assert!(
+        matches!(opt.action, Action::Symbolicate(args) if args.file == Path::new("prof.json") && args.output.as_deref() == Some(Path::new("out.json")))
+    );
+
+    let opt = Opt::parse_from(["samply", "record", "-e", "cache-misses", "rustup"]);
+    assert!(
+        matches!(opt.action, Action::Record(record_args) if record_args.event.as_deref() == Some("cache-misses") && record_args.command == ["rustup"])
+    );
+
+    let opt = Opt::parse_from(["samply", "record", "-a", "--event", "sched:sched_switch"]);
+    assert!(
+        matches!(opt.action, Action::Record(record_args) if record_args.event.as_deref() == Some("sched:sched_switch") && record_args.all_cpus)
+    );
+
+    let opt = Opt::parse_from([
+        "samply",
+        "record",
+        "--unwind",
+        "hybrid",
+        "--stack-size",
+        "8192",
+        "rustup",
+    ]);
+    assert!(
+        matches!(opt.action, Action::Record(record_args) if record_args.unwind == Some(UnwindMode::Hybrid) && record_args.stack_size == Some(8192))
+    );
+
+    let opt_res = Opt::try_parse_from(["samply", "record", "--unwind", "lbr", "rustup"]);
+    assert!(opt_res.is_err());
+
+    let opt = Opt::parse_from([
+        "samply",
+        "record",
+        "--save-perf-data",
+        "out.data",
+        "--perf-data-only",
+        "rustup",
+    ]);
+    assert!(
+        matches!(opt.action, Action::Record(record_args) if record_args.save_perf_data.as_deref() == Some(Path::new("out.data")) && record_args.perf_data_only)
+    );
+
+    // --perf-data-only needs a perf.data file to save to.
+    let opt_res = Opt::try_parse_from(["samply", "record", "--perf-data-only", "rustup"]);
+    assert!(opt_res.is_err());
+
+    let opt = Opt::parse_from([
+        "samply",
+        "load",
+        "prof.json",
+        "--breakpad-symbol-server",
+        "https://a.example.com/",
+        "--breakpad-symbol-server",
+        "https://b.example.com/",
+        "--debuginfod-server",
+        "https://c.example.com/",
+        "--symbol-cache-dir",
+        "/cache",
+    ]);
+    let config = match opt.action {
+        Action::Load(load_args) => load_args.server_args.symbol_args.to_config(),
+        _ => panic!("expected a load action"),
+    };
+    let urls: Vec<&str> = config
+        .breakpad_symbol_servers
+        .iter()
+        .map(|server| server.url.as_str())
+        .collect();
+    assert_eq!(urls, ["https://a.example.com/", "https://b.example.com/"]);
+    assert_eq!(config.debuginfod_servers.len(), 1);
+    assert_eq!(config.cache_dir.as_deref(), Some(Path::new("/cache")));
+
+    let opt = Opt::parse_from([
+        "samply",
+        "symbolicate",
+        "prof.json",
+        "--breakpad-symbol-dir",
+        "/symbols",
+    ]);
+    assert!(
+        matches!(opt.action, Action::Symbolicate(args) if args.symbol_args.breakpad_symbol_dirs == [PathBuf::from("/symbols")])
+    );
 }
@@ -0,0 +1,44 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// The settings of `samply record` which control what gets recorded.
+#[derive(Debug, Clone)]
+pub struct RecordingProps {
+    /// Stop recording after this much time has elapsed.
+    pub time_limit: Option<Duration>,
+    /// The sampling interval.
+    pub interval: Duration,
+    /// Also record native allocations (Linux only).
+    pub allocations: bool,
+    /// The perf event to sample on, in `perf record -e` syntax (Linux only).
+    pub event: Option<String>,
+    /// How the stacks of samples are obtained (Linux only).
+    pub unwind: Option<UnwindMode>,
+    /// The number of bytes of the user stack which are copied for each sample,
+    /// for DWARF unwinding (Linux only).
+    pub stack_size: Option<u32>,
+    /// Also record the scheduling state of each thread, and which thread woke
+    /// it up, as markers (Linux only).
+    pub sched_states: bool,
+    /// Also record the syscalls of each thread as markers (Linux only).
+    pub syscalls: bool,
+    /// Also save the raw perf events to this perf.data file (Linux only).
+    pub save_perf_data: Option<PathBuf>,
+    /// Only save the perf.data file, without processing the recording into a
+    /// profile (Linux only).
+    pub perf_data_only: bool,
+}
+
+/// How the stack of each sample is obtained.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum UnwindMode {
+    /// The kernel walks the user stack with frame pointers. This has the lowest
+    /// overhead, but needs all code to be compiled with frame pointers.
+    Fp,
+    /// The kernel copies a piece of the user stack, which is then unwound with
+    /// DWARF unwind information.
+    Dwarf,
+    /// The leaf frames are unwound with DWARF from a small copy of the user stack,
+    /// and the rest of the stack is taken from the frame pointer walk.
+    Hybrid,
+}
@@ -22,11 +22,14 @@ use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Arc;
 
+use crate::symbols_config::SymbolsConfig;
+
 #[derive(Clone, Debug)]
 pub struct ServerProps {
     pub port_selection: PortSelection,
     pub verbose: bool,
     pub open_in_browser: bool,
+    pub symbols_config: SymbolsConfig,
 }
 
 #[tokio::main]
@@ -36,6 +39,7 @@ pub async fn start_server_main(file: &Path, props: ServerProps) {
         props.port_selection,
         props.verbose,
         props.open_in_browser,
+        &props.symbols_config,
     )
     .await;
 }
@@ -74,6 +78,7 @@ async fn start_server(
     port_selection: PortSelection,
     verbose: bool,
     open_in_browser: bool,
+    symbols_config: &SymbolsConfig,
 ) {
     let libinfo_map = if let Some(profile_filename) = profile_filename {
         // Read the profile.json file and parse it as JSON.
@@ -128,22 +133,7 @@ async fn start_server(
 
     let template_values = Arc::new(template_values);
 
-    let mut config = SymbolManagerConfig::new()
-        .verbose(verbose)
-        .respect_nt_symbol_path(true)
-        .default_nt_symbol_path("srv**https://msdl.microsoft.com/download/symbols")
-        .use_debuginfod(std::env::var("SAMPLY_USE_DEBUGINFOD").is_ok())
-        .use_spotlight(true);
-    if let Some(home_dir) = dirs::home_dir() {
-        config = config.debuginfod_cache_dir_if_not_installed(home_dir.join("sym"));
-    }
-    // TODO: Read breakpad symbol server config from some kind of config file, and call breakpad_symbols_server
-
-    let mut symbol_manager = SymbolManager::with_config(config);
-    for lib_info in libinfo_map.into_values() {
-        symbol_manager.add_known_library(lib_info);
-    }
-    let symbol_manager = Arc::new(symbol_manager);
+    let symbol_manager = Arc::new(create_symbol_manager(libinfo_map, verbose, symbols_config));
     let new_service = make_service_fn(move |_conn| {
         let symbol_manager = symbol_manager.clone();
         let profile_filename = profile_filename.map(PathBuf::from);
@@ -184,7 +174,33 @@ async fn start_server(
     }
 }
 
-fn parse_libinfo_map_from_profile(
+/// Create the `SymbolManager` which is used to symbolicate the profile, and
+/// tell it about the libraries in the profile. The symbol directories and
+/// servers from `symbols_config` are consulted in addition to the defaults.
+pub fn create_symbol_manager(
+    libinfo_map: HashMap<(String, DebugId), LibraryInfo>,
+    verbose: bool,
+    symbols_config: &SymbolsConfig,
+) -> SymbolManager {
+    let mut config = SymbolManagerConfig::new()
+        .verbose(verbose)
+        .respect_nt_symbol_path(true)
+        .default_nt_symbol_path("srv**https://msdl.microsoft.com/download/symbols")
+        .use_debuginfod(std::env::var("SAMPLY_USE_DEBUGINFOD").is_ok())
+        .use_spotlight(true);
+    if let Some(home_dir) = dirs::home_dir() {
+        config = config.debuginfod_cache_dir_if_not_installed(home_dir.join("sym"));
+    }
+    let config = symbols_config.apply(config);
+
+    let mut symbol_manager = SymbolManager::with_config(config);
+    for lib_info in libinfo_map.into_values() {
+        symbol_manager.add_known_library(lib_info);
+    }
+    symbol_manager
+}
+
+pub fn parse_libinfo_map_from_profile(
     reader: impl std::io::Read,
 ) -> Result<HashMap<(String, DebugId), LibraryInfo>, std::io::Error> {
     let profile: ProfileJsonProcess = serde_json::from_reader(reader)?;
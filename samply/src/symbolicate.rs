@@ -0,0 +1,664 @@
+use flate2::read::GzDecoder;
+use serde_json::{json, Map, Value};
+use wholesym::debugid::DebugId;
+use wholesym::{FrameDebugInfo, FramesLookupResult, SourceFilePath, SymbolInfo, SymbolManager};
+
+use std::collections::{BTreeSet, HashMap};
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{BufWriter, Read};
+use std::path::{Path, PathBuf};
+
+use crate::server::{create_symbol_manager, parse_libinfo_map_from_profile};
+use crate::symbols_config::SymbolsConfig;
+
+#[derive(thiserror::Error, Debug)]
+pub enum SymbolicationError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("The profile is not in the processed profile format: {0}")]
+    InvalidProfile(&'static str),
+}
+
+/// The symbol information for one address in a library.
+#[derive(Debug, Clone)]
+struct AddressSymbolication {
+    /// The symbol of the outer function.
+    symbol: SymbolInfo,
+    /// The frames at this address from the debug info, outer function first.
+    /// Empty if no debug info was found.
+    frames: Vec<FrameDebugInfo>,
+}
+
+/// Load the processed profile at `input_file`, look up the symbols for all
+/// frame addresses, and save a profile with symbolicated func, frame and
+/// native symbol tables at `output_file`. The saved profile is self-contained:
+/// it can be viewed without a symbol server.
+#[tokio::main]
+pub async fn symbolicate_profile_file(
+    input_file: &Path,
+    output_file: &Path,
+    verbose: bool,
+    symbols_config: &SymbolsConfig,
+) -> Result<(), SymbolicationError> {
+    let mut bytes = Vec::new();
+    let mut file = File::open(input_file)?;
+    if input_file.extension() == Some("gz".as_ref()) {
+        GzDecoder::new(file).read_to_end(&mut bytes)?;
+    } else {
+        file.read_to_end(&mut bytes)?;
+    }
+
+    let libinfo_map = parse_libinfo_map_from_profile(&bytes[..])?;
+    let symbol_manager = create_symbol_manager(libinfo_map, verbose, symbols_config);
+    let mut profile: Value = serde_json::from_slice(&bytes)?;
+    drop(bytes);
+
+    let lookups = look_up_addresses(&profile, &symbol_manager, verbose).await?;
+    let threads = profile
+        .get_mut("threads")
+        .and_then(Value::as_array_mut)
+        .ok_or(SymbolicationError::InvalidProfile("missing threads"))?;
+    for thread in threads {
+        symbolicate_thread(thread, &lookups)?;
+    }
+    profile["meta"]["symbolicated"] = json!(true);
+
+    let writer = BufWriter::new(File::create(output_file)?);
+    serde_json::to_writer(writer, &profile)?;
+    Ok(())
+}
+
+/// The default output path for `samply symbolicate`: the input path with a
+/// `-symbolicated` suffix, e.g. `profile.json.gz` becomes `profile-symbolicated.json`.
+pub fn default_output_path(input_file: &Path) -> PathBuf {
+    let file_name = input_file
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let stem = file_name
+        .strip_suffix(".gz")
+        .unwrap_or(&file_name)
+        .trim_end_matches(".json");
+    input_file.with_file_name(format!("{stem}-symbolicated.json"))
+}
+
+/// Look up all frame addresses of all threads, grouped by library index.
+async fn look_up_addresses(
+    profile: &Value,
+    symbol_manager: &SymbolManager,
+    verbose: bool,
+) -> Result<HashMap<(usize, u64), AddressSymbolication>, SymbolicationError> {
+    let mut addresses_per_lib: HashMap<usize, BTreeSet<u64>> = HashMap::new();
+    let threads = profile["threads"]
+        .as_array()
+        .ok_or(SymbolicationError::InvalidProfile("missing threads"))?;
+    for thread in threads {
+        let tables = ThreadTables::from_thread(thread)?;
+        for frame in 0..tables.frames.len() {
+            if let Some((lib, address)) = tables.lib_address_for_frame(frame) {
+                addresses_per_lib.entry(lib).or_default().insert(address);
+            }
+        }
+    }
+
+    let libs = profile["libs"]
+        .as_array()
+        .ok_or(SymbolicationError::InvalidProfile("missing libs"))?;
+    let mut lookups = HashMap::new();
+    for (lib_index, addresses) in addresses_per_lib {
+        let lib = match libs.get(lib_index) {
+            Some(lib) => lib,
+            None => return Err(SymbolicationError::InvalidProfile("invalid lib index")),
+        };
+        let debug_name = lib["debugName"].as_str().unwrap_or_default();
+        let debug_id = match lib["breakpadId"]
+            .as_str()
+            .and_then(|id| DebugId::from_breakpad(id).ok())
+        {
+            Some(debug_id) => debug_id,
+            None => continue,
+        };
+        let symbol_map = match symbol_manager.load_symbol_map(debug_name, debug_id).await {
+            Ok(symbol_map) => symbol_map,
+            Err(err) => {
+                if verbose {
+                    eprintln!("Could not obtain symbols for {debug_name} {debug_id}: {err}");
+                }
+                continue;
+            }
+        };
+        for address in addresses {
+            let address_info = match symbol_map.lookup_relative_address(address) {
+                Some(address_info) => address_info,
+                None => continue,
+            };
+            let mut frames = match address_info.frames {
+                FramesLookupResult::Available(frames) => frames,
+                FramesLookupResult::External(external) => symbol_manager
+                    .lookup_external(&symbol_map.symbol_file_origin(), &external)
+                    .await
+                    .unwrap_or_default(),
+                FramesLookupResult::Unavailable => Vec::new(),
+            };
+            frames.reverse();
+            lookups.insert(
+                (lib_index, address),
+                AddressSymbolication {
+                    symbol: address_info.symbol,
+                    frames,
+                },
+            );
+        }
+    }
+    Ok(lookups)
+}
+
+/// A table in the processed profile format: an object with one array per
+/// column, and a `length` property.
+#[derive(Debug, Clone)]
+struct Table {
+    length: usize,
+    columns: Map<String, Value>,
+}
+
+impl Table {
+    fn from_value(value: &Value, error: &'static str) -> Result<Self, SymbolicationError> {
+        let columns = value
+            .as_object()
+            .ok_or(SymbolicationError::InvalidProfile(error))?;
+        let length = columns
+            .get("length")
+            .and_then(Value::as_u64)
+            .ok_or(SymbolicationError::InvalidProfile(error))? as usize;
+        let columns = columns
+            .iter()
+            .filter(|(_, column)| column.is_array())
+            .map(|(name, column)| (name.clone(), column.clone()))
+            .collect();
+        Ok(Self { length, columns })
+    }
+
+    /// Create an empty table with the same columns, plus the `extra_columns`.
+    fn empty_like(&self, extra_columns: &[&str]) -> Self {
+        let mut columns: Map<String, Value> = self
+            .columns
+            .keys()
+            .map(|name| (name.clone(), json!([])))
+            .collect();
+        for name in extra_columns {
+            columns.insert(name.to_string(), json!([]));
+        }
+        Self { length: 0, columns }
+    }
+
+    fn len(&self) -> usize {
+        self.length
+    }
+
+    fn get(&self, column: &str, row: usize) -> &Value {
+        self.columns
+            .get(column)
+            .and_then(|column| column.get(row))
+            .unwrap_or(&Value::Null)
+    }
+
+    fn get_index(&self, column: &str, row: usize) -> Option<usize> {
+        self.get(column, row).as_u64().map(|index| index as usize)
+    }
+
+    /// Append a copy of the given row of `other`, and return the new row index.
+    fn push_copy(&mut self, other: &Table, row: usize) -> usize {
+        for (name, column) in self.columns.iter_mut() {
+            if let Value::Array(column) = column {
+                column.push(other.get(name, row).clone());
+            }
+        }
+        self.length += 1;
+        self.length - 1
+    }
+
+    fn set(&mut self, column: &str, row: usize, value: Value) {
+        if let Some(Value::Array(column)) = self.columns.get_mut(column) {
+            if let Some(cell) = column.get_mut(row) {
+                *cell = value;
+            }
+        }
+    }
+
+    fn into_value(self) -> Value {
+        let mut map = self.columns;
+        map.insert("length".to_string(), json!(self.length));
+        Value::Object(map)
+    }
+}
+
+/// The tables of a thread which are involved in symbolication.
+struct ThreadTables {
+    frames: Table,
+    funcs: Table,
+    resources: Table,
+    native_symbols: Table,
+    stacks: Table,
+    strings: Vec<String>,
+}
+
+impl ThreadTables {
+    fn from_thread(thread: &Value) -> Result<Self, SymbolicationError> {
+        let strings = thread["stringArray"]
+            .as_array()
+            .ok_or(SymbolicationError::InvalidProfile("missing stringArray"))?
+            .iter()
+            .map(|s| s.as_str().unwrap_or_default().to_owned())
+            .collect();
+        Ok(Self {
+            frames: Table::from_value(&thread["frameTable"], "invalid frameTable")?,
+            funcs: Table::from_value(&thread["funcTable"], "invalid funcTable")?,
+            resources: Table::from_value(&thread["resourceTable"], "invalid resourceTable")?,
+            native_symbols: Table::from_value(&thread["nativeSymbols"], "invalid nativeSymbols")?,
+            stacks: Table::from_value(&thread["stackTable"], "invalid stackTable")?,
+            strings,
+        })
+    }
+
+    /// The library index and the relative address of a frame, if the frame
+    /// has an address in a library.
+    fn lib_address_for_frame(&self, frame: usize) -> Option<(usize, u64)> {
+        let address = self.frames.get("address", frame).as_i64()?;
+        let address = u64::try_from(address).ok()?;
+        let func = self.frames.get_index("func", frame)?;
+        let resource = self.funcs.get_index("resource", func)?;
+        let lib = self.resources.get_index("lib", resource)?;
+        Some((lib, address))
+    }
+}
+
+/// Rewrite the frame, func, native symbol and stack tables of a thread so that
+/// every frame with a known address has a function name. Inlined calls become
+/// separate frames with increasing `inlineDepth`, and stacks are expanded
+/// accordingly. Columns which refer to stacks are updated to the new stack
+/// indexes.
+fn symbolicate_thread(
+    thread: &mut Value,
+    lookups: &HashMap<(usize, u64), AddressSymbolication>,
+) -> Result<(), SymbolicationError> {
+    let old_tables = ThreadTables::from_thread(thread)?;
+    let mut native_symbols = old_tables.native_symbols.clone();
+    let mut strings = old_tables.strings.clone();
+
+    let mut string_indexes: HashMap<String, usize> = strings
+        .iter()
+        .enumerate()
+        .map(|(index, s)| (s.clone(), index))
+        .collect();
+    let mut string_index = |s: &str| -> usize {
+        *string_indexes.entry(s.to_owned()).or_insert_with(|| {
+            strings.push(s.to_owned());
+            strings.len() - 1
+        })
+    };
+
+    let mut native_symbol_indexes: HashMap<(u64, u64), usize> = HashMap::new();
+    for row in 0..native_symbols.len() {
+        if let (Some(lib), Some(address)) = (
+            native_symbols.get("libIndex", row).as_u64(),
+            native_symbols.get("address", row).as_u64(),
+        ) {
+            native_symbol_indexes.insert((lib, address), row);
+        }
+    }
+
+    let mut new_frames = old_tables
+        .frames
+        .empty_like(&["inlineDepth", "line", "column"]);
+    let mut new_funcs = old_tables
+        .funcs
+        .empty_like(&["fileName", "lineNumber", "columnNumber"]);
+    let mut copied_funcs: HashMap<usize, usize> = HashMap::new();
+    let mut symbolicated_funcs: HashMap<(usize, Option<usize>, Option<usize>), usize> =
+        HashMap::new();
+
+    // For each old frame, the new frames, outer function first.
+    let mut frame_map: Vec<Vec<usize>> = Vec::with_capacity(old_tables.frames.len());
+    for frame in 0..old_tables.frames.len() {
+        let old_func = old_tables
+            .frames
+            .get_index("func", frame)
+            .ok_or(SymbolicationError::InvalidProfile("frame without func"))?;
+        let info = old_tables
+            .lib_address_for_frame(frame)
+            .and_then(|lib_address| Some((lib_address.0, lookups.get(&lib_address)?)));
+        let (lib, info) = match info {
+            Some(info) => info,
+            None => {
+                let func = *copied_funcs
+                    .entry(old_func)
+                    .or_insert_with(|| new_funcs.push_copy(&old_tables.funcs, old_func));
+                let new_frame = new_frames.push_copy(&old_tables.frames, frame);
+                new_frames.set("func", new_frame, json!(func));
+                frame_map.push(vec![new_frame]);
+                continue;
+            }
+        };
+
+        let symbol = &info.symbol;
+        let native_symbol = *native_symbol_indexes
+            .entry((lib as u64, symbol.address))
+            .or_insert_with(|| {
+                let row = native_symbols.len();
+                for (name, value) in [
+                    ("libIndex", json!(lib)),
+                    ("address", json!(symbol.address)),
+                    ("name", json!(string_index(&symbol.name))),
+                    ("functionSize", json!(symbol.size)),
+                ] {
+                    if let Some(Value::Array(column)) = native_symbols.columns.get_mut(name) {
+                        column.push(value);
+                    }
+                }
+                native_symbols.length += 1;
+                row
+            });
+
+        let resource = old_tables.funcs.get_index("resource", old_func);
+        let inline_frames: Vec<(String, Option<String>, Option<u32>)> = if info.frames.is_empty() {
+            vec![(symbol.name.clone(), None, None)]
+        } else {
+            info.frames
+                .iter()
+                .map(|frame| {
+                    (
+                        frame
+                            .function
+                            .clone()
+                            .unwrap_or_else(|| symbol.name.clone()),
+                        frame.file_path.as_ref().map(file_path_string),
+                        frame.line_number,
+                    )
+                })
+                .collect()
+        };
+
+        let mut new_frames_for_frame = Vec::with_capacity(inline_frames.len());
+        for (depth, (name, file, line)) in inline_frames.into_iter().enumerate() {
+            let name = string_index(&name);
+            let file = file.map(|file| string_index(&file));
+            let func = *symbolicated_funcs
+                .entry((name, resource, file))
+                .or_insert_with(|| {
+                    let func = new_funcs.push_copy(&old_tables.funcs, old_func);
+                    new_funcs.set("name", func, json!(name));
+                    new_funcs.set("fileName", func, json!(file));
+                    new_funcs.set("lineNumber", func, Value::Null);
+                    new_funcs.set("columnNumber", func, Value::Null);
+                    func
+                });
+            let new_frame = new_frames.push_copy(&old_tables.frames, frame);
+            new_frames.set("func", new_frame, json!(func));
+            new_frames.set("nativeSymbol", new_frame, json!(native_symbol));
+            new_frames.set("inlineDepth", new_frame, json!(depth));
+            new_frames.set("line", new_frame, json!(line));
+            new_frames_for_frame.push(new_frame);
+        }
+        frame_map.push(new_frames_for_frame);
+    }
+
+    // Expand each stack into one stack per inline frame.
+    let old_stacks = &old_tables.stacks;
+    let mut new_stacks = old_stacks.empty_like(&[]);
+    let mut stack_map: Vec<usize> = Vec::with_capacity(old_stacks.len());
+    for stack in 0..old_stacks.len() {
+        let mut prefix = match old_stacks.get_index("prefix", stack) {
+            Some(prefix) => Some(
+                *stack_map
+                    .get(prefix)
+                    .ok_or(SymbolicationError::InvalidProfile("invalid stack prefix"))?,
+            ),
+            None => None,
+        };
+        let frame = old_stacks
+            .get_index("frame", stack)
+            .and_then(|frame| frame_map.get(frame))
+            .ok_or(SymbolicationError::InvalidProfile("invalid stack frame"))?;
+        for new_frame in frame {
+            let new_stack = new_stacks.push_copy(old_stacks, stack);
+            new_stacks.set("prefix", new_stack, json!(prefix));
+            new_stacks.set("frame", new_stack, json!(new_frame));
+            prefix = Some(new_stack);
+        }
+        stack_map.push(prefix.ok_or(SymbolicationError::InvalidProfile("empty stack"))?);
+    }
+
+    let map_stack = |stack: &mut Value| -> Result<(), SymbolicationError> {
+        if let Some(old_stack) = stack.as_u64() {
+            let new_stack = stack_map
+                .get(old_stack as usize)
+                .ok_or(SymbolicationError::InvalidProfile("invalid stack index"))?;
+            *stack = json!(new_stack);
+        }
+        Ok(())
+    };
+    for table in ["samples", "nativeAllocations", "jsAllocations"] {
+        if let Some(Value::Array(column)) = thread.get_mut(table).and_then(|t| t.get_mut("stack")) {
+            for stack in column.iter_mut() {
+                map_stack(stack)?;
+            }
+        }
+    }
+    // Markers with a stack have it in their data, e.g. `{ "cause": { "stack": 5 } }`.
+    if let Some(Value::Array(data)) = thread.get_mut("markers").and_then(|m| m.get_mut("data")) {
+        for marker_data in data.iter_mut() {
+            if let Some(stack) = marker_data
+                .get_mut("cause")
+                .and_then(|cause| cause.get_mut("stack"))
+            {
+                map_stack(stack)?;
+            }
+        }
+    }
+
+    thread["frameTable"] = new_frames.into_value();
+    thread["funcTable"] = new_funcs.into_value();
+    thread["nativeSymbols"] = native_symbols.into_value();
+    thread["stackTable"] = new_stacks.into_value();
+    thread["stringArray"] = json!(strings);
+    Ok(())
+}
+
+/// Paths which can be mapped to a source code URL are written in their special
+/// path form, e.g. `git:github.com/rust-lang/rust:library/core/src/ptr/mod.rs:<rev>`,
+/// so that the profiler can fetch the source.
+fn file_path_string(path: &SourceFilePath) -> String {
+    match path.mapped_path() {
+        Some(mapped_path) => mapped_path.to_special_path_str(),
+        None => path.raw_path().to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use fxprof_processed_profile::{
+        CategoryHandle, CpuDelta, Frame, LibraryInfo, MarkerLocation, MarkerSchema, MarkerTiming,
+        Profile, ProfilerMarker, ReferenceTimestamp, SamplingInterval, Timestamp,
+    };
+
+    #[test]
+    fn test_default_output_path() {
+        assert_eq!(
+            default_output_path(Path::new("/tmp/profile.json")),
+            Path::new("/tmp/profile-symbolicated.json")
+        );
+        assert_eq!(
+            default_output_path(Path::new("profile.json.gz")),
+            Path::new("profile-symbolicated.json")
+        );
+    }
+
+    #[test]
+    fn test_symbolicate_thread() {
+        let mut profile = Profile::new(
+            "test",
+            ReferenceTimestamp::from_millis_since_unix_epoch(0.0),
+            SamplingInterval::from_millis(1),
+        );
+        let process = profile.add_process("test", 1, Timestamp::from_millis_since_reference(0.0));
+        let thread = profile.add_thread(
+            process,
+            1,
+            Timestamp::from_millis_since_reference(0.0),
+            true,
+        );
+        profile.add_lib(
+            process,
+            LibraryInfo {
+                name: "libtest.so".to_string(),
+                debug_name: "libtest.so".to_string(),
+                path: "/libtest.so".to_string(),
+                debug_path: "/libtest.so".to_string(),
+                debug_id: DebugId::nil(),
+                code_id: None,
+                arch: None,
+                base_avma: 0x10000,
+                avma_range: 0x10000..0x20000,
+                symbol_table: None,
+            },
+        );
+        let category = CategoryHandle::OTHER.into();
+        // Stack: 0x1100 (main) -> 0x1200 (inlined helper in caller)
+        profile.add_sample(
+            thread,
+            Timestamp::from_millis_since_reference(1.0),
+            vec![
+                (Frame::InstructionPointer(0x11100), category),
+                (Frame::InstructionPointer(0x11200), category),
+            ]
+            .into_iter(),
+            CpuDelta::ZERO,
+            1,
+        );
+        profile.add_marker_with_stack(
+            thread,
+            "Syscall",
+            TestMarker,
+            MarkerTiming::Instant(Timestamp::from_millis_since_reference(2.0)),
+            vec![
+                (Frame::InstructionPointer(0x11100), category),
+                (Frame::InstructionPointer(0x11200), category),
+            ]
+            .into_iter(),
+        );
+        let mut json = serde_json::to_value(&profile).unwrap();
+
+        let symbol = |address, name: &str| SymbolInfo {
+            address,
+            size: Some(0x100),
+            name: name.to_string(),
+        };
+        let debug_frame = |function: &str, line| FrameDebugInfo {
+            function: Some(function.to_string()),
+            file_path: Some(SourceFilePath::new("/src/test.c".to_string(), None)),
+            line_number: Some(line),
+        };
+        let mut lookups = HashMap::new();
+        lookups.insert(
+            (0, 0x1100),
+            AddressSymbolication {
+                symbol: symbol(0x1100, "main"),
+                frames: vec![],
+            },
+        );
+        lookups.insert(
+            (0, 0x1200),
+            AddressSymbolication {
+                symbol: symbol(0x1200, "caller"),
+                frames: vec![debug_frame("caller", 10), debug_frame("helper", 20)],
+            },
+        );
+
+        let thread = &mut json["threads"][0];
+        symbolicate_thread(thread, &lookups).unwrap();
+
+        let strings = thread["stringArray"].clone();
+        let string = |index: &Value| strings[index.as_u64().unwrap() as usize].clone();
+        let frame_table = &thread["frameTable"];
+        assert_eq!(frame_table["length"], json!(3));
+        assert_eq!(frame_table["inlineDepth"], json!([0, 0, 1]));
+        assert_eq!(frame_table["line"], json!([null, 10, 20]));
+        assert_eq!(frame_table["address"], json!([0x1100, 0x1200, 0x1200]));
+        assert_eq!(frame_table["nativeSymbol"], json!([0, 1, 1]));
+        let func_table = &thread["funcTable"];
+        let names: Vec<Value> = frame_table["func"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|func| string(&func_table["name"][func.as_u64().unwrap() as usize]))
+            .collect();
+        assert_eq!(names, vec![json!("main"), json!("caller"), json!("helper")]);
+        assert_eq!(string(&func_table["fileName"][2]), json!("/src/test.c"));
+        assert_eq!(thread["nativeSymbols"]["length"], json!(2));
+
+        let stack_table = &thread["stackTable"];
+        assert_eq!(stack_table["length"], json!(3));
+        assert_eq!(stack_table["prefix"], json!([null, 0, 1]));
+        assert_eq!(stack_table["frame"], json!([0, 1, 2]));
+        assert_eq!(thread["samples"]["stack"], json!([2]));
+        assert_eq!(thread["markers"]["data"][0]["cause"]["stack"], json!(2));
+    }
+
+    #[test]
+    fn test_symbolicate_thread_rejects_invalid_marker_stack() {
+        let mut profile = Profile::new(
+            "test",
+            ReferenceTimestamp::from_millis_since_unix_epoch(0.0),
+            SamplingInterval::from_millis(1),
+        );
+        let process = profile.add_process("test", 1, Timestamp::from_millis_since_reference(0.0));
+        let thread = profile.add_thread(
+            process,
+            1,
+            Timestamp::from_millis_since_reference(0.0),
+            true,
+        );
+        let label = profile.intern_string("label");
+        profile.add_marker_with_stack(
+            thread,
+            "Syscall",
+            TestMarker,
+            MarkerTiming::Instant(Timestamp::from_millis_since_reference(2.0)),
+            std::iter::once((Frame::Label(label), CategoryHandle::OTHER.into())),
+        );
+        let mut json = serde_json::to_value(&profile).unwrap();
+        let thread = &mut json["threads"][0];
+        thread["markers"]["data"][0]["cause"]["stack"] = json!(7);
+        assert!(matches!(
+            symbolicate_thread(thread, &HashMap::new()),
+            Err(SymbolicationError::InvalidProfile("invalid stack index"))
+        ));
+    }
+
+    struct TestMarker;
+
+    impl ProfilerMarker for TestMarker {
+        const MARKER_TYPE_NAME: &'static str = "Test";
+
+        fn json_marker_data(&self) -> Value {
+            json!({ "type": Self::MARKER_TYPE_NAME })
+        }
+
+        fn schema() -> MarkerSchema {
+            MarkerSchema {
+                type_name: Self::MARKER_TYPE_NAME,
+                locations: vec![MarkerLocation::MarkerChart],
+                chart_label: None,
+                tooltip_label: None,
+                table_label: None,
+                fields: vec![],
+            }
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use serde_derive::Deserialize;
+use wholesym::SymbolManagerConfig;
+
+use std::path::{Path, PathBuf};
+
+/// The name of the configuration file. samply reads it from the user's config
+/// directory (e.g. `~/.config/samply/samply.toml` on Linux), and from the current
+/// directory, whose file takes precedence.
+pub const CONFIG_FILE_NAME: &str = "samply.toml";
+
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigError {
+    #[error("Could not read {0:?}: {1}")]
+    Io(PathBuf, std::io::Error),
+
+    #[error("Could not parse {0:?}: {1}")]
+    Toml(PathBuf, toml::de::Error),
+}
+
+/// Where to look for symbol files, in addition to the local files referenced by
+/// the profile. Read from the `[symbols]` table of `samply.toml`:
+///
+/// ```toml
+/// [symbols]
+/// breakpad_symbol_dirs = ["/path/to/symbols"]
+/// breakpad_symbol_servers = [{ url = "https://symbols.example.com/" }]
+/// debuginfod_servers = [{ url = "https://debuginfod.example.com/", cache_dir = "~/debuginfod-cache" }]
+/// ```
+///
+/// Servers without a `cache_dir` cache their files in a subdirectory of `cache_dir`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SymbolsConfig {
+    /// The directory in which downloaded symbol files are cached by default.
+    pub cache_dir: Option<PathBuf>,
+    /// Local directories with Breakpad .sym files, in the symbol store layout.
+    #[serde(default)]
+    pub breakpad_symbol_dirs: Vec<PathBuf>,
+    /// Where to cache the symindex files for Breakpad .sym files.
+    pub breakpad_symindex_cache_dir: Option<PathBuf>,
+    #[serde(default)]
+    pub breakpad_symbol_servers: Vec<SymbolServer>,
+    /// Servers for Windows pdb / exe / dll files, in addition to `_NT_SYMBOL_PATH`.
+    #[serde(default)]
+    pub windows_symbol_servers: Vec<SymbolServer>,
+    /// Servers for ELF debug info and executables, in addition to `DEBUGINFOD_URLS`.
+    #[serde(default)]
+    pub debuginfod_servers: Vec<SymbolServer>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SymbolServer {
+    pub url: String,
+    pub cache_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ConfigFile {
+    #[serde(default)]
+    symbols: SymbolsConfig,
+}
+
+impl SymbolsConfig {
+    /// Read the user's config file and the config file in the current directory,
+    /// if they exist.
+    pub fn from_config_files() -> Result<Self, ConfigError> {
+        let mut config = SymbolsConfig::default();
+        let user_config_path =
+            dirs::config_dir().map(|dir| dir.join("samply").join(CONFIG_FILE_NAME));
+        let project_config_path = Some(PathBuf::from(CONFIG_FILE_NAME));
+        for path in user_config_path.into_iter().chain(project_config_path) {
+            if path.is_file() {
+                config = Self::from_file(&path)?.overriding(config);
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let contents =
+            std::fs::read_to_string(path).map_err(|err| ConfigError::Io(path.to_owned(), err))?;
+        Self::parse(&contents).map_err(|err| ConfigError::Toml(path.to_owned(), err))
+    }
+
+    fn parse(contents: &str) -> Result<Self, toml::de::Error> {
+        let file: ConfigFile = toml::from_str(contents)?;
+        Ok(file.symbols)
+    }
+
+    /// Combine two configs. The values in `self` take precedence: They replace
+    /// single values in `base`, and list entries in `self` are searched before
+    /// those in `base`.
+    pub fn overriding(self, base: SymbolsConfig) -> SymbolsConfig {
+        fn concat<T>(mut first: Vec<T>, second: Vec<T>) -> Vec<T> {
+            first.extend(second);
+            first
+        }
+        SymbolsConfig {
+            cache_dir: self.cache_dir.or(base.cache_dir),
+            breakpad_symbol_dirs: concat(self.breakpad_symbol_dirs, base.breakpad_symbol_dirs),
+            breakpad_symindex_cache_dir: self
+                .breakpad_symindex_cache_dir
+                .or(base.breakpad_symindex_cache_dir),
+            breakpad_symbol_servers: concat(
+                self.breakpad_symbol_servers,
+                base.breakpad_symbol_servers,
+            ),
+            windows_symbol_servers: concat(
+                self.windows_symbol_servers,
+                base.windows_symbol_servers,
+            ),
+            debuginfod_servers: concat(self.debuginfod_servers, base.debuginfod_servers),
+        }
+    }
+
+    /// Add the configured directories and servers to the `SymbolManagerConfig`.
+    pub fn apply(&self, mut config: SymbolManagerConfig) -> SymbolManagerConfig {
+        let cache_dir = match &self.cache_dir {
+            Some(cache_dir) => expand_home(cache_dir),
+            None => dirs::cache_dir()
+                .unwrap_or_else(std::env::temp_dir)
+                .join("samply"),
+        };
+        let server_cache_dir = |server: &SymbolServer, kind: &str| match &server.cache_dir {
+            Some(dir) => expand_home(dir),
+            None => cache_dir.join(kind),
+        };
+
+        for dir in &self.breakpad_symbol_dirs {
+            config = config.breakpad_symbols_dir(expand_home(dir));
+        }
+        for server in &self.breakpad_symbol_servers {
+            config =
+                config.breakpad_symbols_server(&server.url, server_cache_dir(server, "breakpad"));
+        }
+        if !self.breakpad_symbol_servers.is_empty() || !self.breakpad_symbol_dirs.is_empty() {
+            let symindex_dir = match &self.breakpad_symindex_cache_dir {
+                Some(dir) => expand_home(dir),
+                None => cache_dir.join("breakpad-symindex"),
+            };
+            config = config.breakpad_symindex_cache_dir(symindex_dir);
+        }
+        for server in &self.windows_symbol_servers {
+            config =
+                config.windows_symbols_server(&server.url, server_cache_dir(server, "windows"));
+        }
+        for server in &self.debuginfod_servers {
+            config =
+                config.extra_debuginfod_server(&server.url, server_cache_dir(server, "debuginfod"));
+        }
+        config
+    }
+}
+
+/// Expand a leading `~` to the home directory.
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home_dir)) => home_dir.join(rest),
+        _ => path.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_config_file() {
+        let config = SymbolsConfig::parse(
+            r#"
+            [symbols]
+            cache_dir = "/cache"
+            breakpad_symbol_dirs = ["/symbols"]
+            breakpad_symbol_servers = [{ url = "https://symbols.example.com/" }]
+            debuginfod_servers = [
+                { url = "https://debuginfod.example.com/", cache_dir = "/debuginfod" },
+            ]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.cache_dir.as_deref(), Some(Path::new("/cache")));
+        assert_eq!(config.breakpad_symbol_dirs, vec![PathBuf::from("/symbols")]);
+        assert_eq!(
+            config.breakpad_symbol_servers,
+            vec![SymbolServer {
+                url: "https://symbols.example.com/".to_string(),
+                cache_dir: None
+            }]
+        );
+        assert_eq!(
+            config.debuginfod_servers[0].cache_dir.as_deref(),
+            Some(Path::new("/debuginfod"))
+        );
+        assert!(config.windows_symbol_servers.is_empty());
+
+        assert_eq!(SymbolsConfig::parse("").unwrap(), SymbolsConfig::default());
+        assert!(SymbolsConfig::parse("[symbols]\nunknown_key = 1").is_err());
+    }
+
+    #[test]
+    fn overriding_config() {
+        let user = SymbolsConfig {
+            cache_dir: Some("/user-cache".into()),
+            breakpad_symindex_cache_dir: Some("/user-symindex".into()),
+            breakpad_symbol_dirs: vec!["/user-symbols".into()],
+            ..Default::default()
+        };
+        let project = SymbolsConfig {
+            cache_dir: Some("/project-cache".into()),
+            breakpad_symbol_dirs: vec!["/project-symbols".into()],
+            ..Default::default()
+        };
+        let config = project.overriding(user);
+        assert_eq!(
+            config.cache_dir.as_deref(),
+            Some(Path::new("/project-cache"))
+        );
+        assert_eq!(
+            config.breakpad_symindex_cache_dir.as_deref(),
+            Some(Path::new("/user-symindex"))
+        );
+        assert_eq!(
+            config.breakpad_symbol_dirs,
+            vec![
+                PathBuf::from("/project-symbols"),
+                PathBuf::from("/user-symbols")
+            ]
+        );
+    }
+}
@@ -1,5 +1,6 @@
 pub use samply_symbols::debugid;
 use samply_symbols::debugid::DebugId;
+use samply_symbols::object::{Architecture, BinaryFormat, Object};
 use samply_symbols::{
     self, CandidatePathInfo, CompactSymbolTable, Error, FileAndPathHelper, FileAndPathHelperResult,
     FileLocation, LibraryInfo, MultiArchDisambiguator, OptionallySendFuture, SymbolManager,
@@ -60,6 +61,52 @@ pub async fn get_table_for_binary(
     Ok(CompactSymbolTable::from_symbol_map(&symbol_map))
 }
 
+/// Create the contents of a Breakpad .sym file for the binary at `binary_path`.
+pub async fn get_breakpad_sym_for_binary(
+    binary_path: &Path,
+    debug_id: Option<DebugId>,
+) -> Result<Vec<u8>, Error> {
+    let helper = Helper {
+        symbol_directory: binary_path.parent().unwrap().to_path_buf(),
+    };
+    let symbol_manager = SymbolManager::with_helper(&helper);
+    let binary =
+        get_library_info_with_dyld_cache_fallback(&symbol_manager, binary_path, debug_id).await?;
+    let mut info = binary.library_info();
+    let object = binary.make_object();
+    let os = match object.format() {
+        BinaryFormat::MachO => "mac",
+        BinaryFormat::Pe | BinaryFormat::Coff => "windows",
+        _ => "Linux",
+    };
+    if info.arch.is_none() {
+        info.arch = breakpad_arch(object.architecture()).map(ToOwned::to_owned);
+    }
+    drop(object);
+    drop(binary);
+    let symbol_map = symbol_manager.load_symbol_map(&info).await?;
+    let mut sym = Vec::new();
+    symbol_manager
+        .write_breakpad_sym_file(&mut sym, &symbol_map, &info, os)
+        .await
+        .expect("writing to a Vec can't fail");
+    Ok(sym)
+}
+
+fn breakpad_arch(arch: Architecture) -> Option<&'static str> {
+    match arch {
+        Architecture::X86_64 => Some("x86_64"),
+        Architecture::I386 => Some("x86"),
+        Architecture::Aarch64 => Some("arm64"),
+        Architecture::Arm => Some("arm"),
+        Architecture::PowerPc => Some("ppc"),
+        Architecture::PowerPc64 => Some("ppc64"),
+        Architecture::Mips => Some("mips"),
+        Architecture::Mips64 => Some("mips64"),
+        _ => None,
+    }
+}
+
 pub async fn get_table_for_debug_name_and_id(
     debug_name: &str,
     debug_id: Option<DebugId>,
@@ -272,6 +319,29 @@ impl<'h> FileAndPathHelper<'h> for Helper {
 
         Ok(paths)
     }
+
+    fn get_candidate_paths_for_dwo_file(
+        &self,
+        _original_file_path: &FileLocationType,
+        comp_dir: Option<&str>,
+        dwo_path: &str,
+    ) -> FileAndPathHelperResult<Vec<FileLocationType>> {
+        let mut paths = vec![];
+        if let Some(comp_dir) = comp_dir {
+            paths.push(FileLocationType(Path::new(comp_dir).join(dwo_path)));
+        }
+        paths.push(FileLocationType(self.symbol_directory.join(dwo_path)));
+        Ok(paths)
+    }
+
+    fn get_candidate_paths_for_dwp_file(
+        &self,
+        original_file_path: &FileLocationType,
+    ) -> FileAndPathHelperResult<Vec<FileLocationType>> {
+        let mut dwp_path = original_file_path.0.clone().into_os_string();
+        dwp_path.push(".dwp");
+        Ok(vec![FileLocationType(dwp_path.into())])
+    }
 }
 
 #[derive(Clone)]
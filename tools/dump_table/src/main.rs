@@ -1,8 +1,9 @@
 use clap::Parser;
 use samply_symbols::{debugid::DebugId, Error};
+use std::io::Write;
 use std::path::{Path, PathBuf};
 
-use dump_table::{dump_table, get_table_for_binary};
+use dump_table::{dump_table, get_breakpad_sym_for_binary, get_table_for_binary};
 
 #[derive(Parser)]
 #[command(
@@ -19,12 +20,20 @@ struct Opt {
     /// When specified, print the entire symbol table.
     #[arg(short, long)]
     full: bool,
+
+    /// Print a Breakpad .sym file for the binary instead of the symbol table.
+    #[arg(long)]
+    breakpad: bool,
 }
 
 fn main() -> anyhow::Result<()> {
     let opt = Opt::parse();
-    let result =
-        futures::executor::block_on(main_impl(&opt.binary_path, opt.breakpad_id, opt.full));
+    let result = futures::executor::block_on(main_impl(
+        &opt.binary_path,
+        opt.breakpad_id,
+        opt.full,
+        opt.breakpad,
+    ));
     match result {
         Ok(()) => Ok(()),
         Err(Error::NoDisambiguatorForFatArchive(members)) => {
@@ -46,10 +55,16 @@ async fn main_impl(
     binary_path: &Path,
     breakpad_id: Option<String>,
     full: bool,
+    breakpad: bool,
 ) -> Result<(), Error> {
     let debug_id = breakpad_id
         .as_deref()
         .and_then(|debug_id| DebugId::from_breakpad(debug_id).ok());
+    if breakpad {
+        let sym = get_breakpad_sym_for_binary(binary_path, debug_id).await?;
+        std::io::stdout().write_all(&sym).unwrap();
+        return Ok(());
+    }
     let table = get_table_for_binary(binary_path, debug_id).await?;
     dump_table(&mut std::io::stdout(), table, full).unwrap();
     Ok(())
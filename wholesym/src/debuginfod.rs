@@ -1,165 +1,529 @@
+//! A client for [debuginfod](https://sourceware.org/elfutils/Debuginfod.html) servers.
+//!
+//! This is a full reimplementation of the client side of the debuginfod protocol, so
+//! it works on all platforms and doesn't need elfutils to be installed. Downloaded
+//! files are stored in the same cache layout that elfutils' `libdebuginfod` uses,
+//! so the cache is shared with other debuginfod clients on the same machine,
+//! e.g. `gdb` or `debuginfod-find`.
+//!
+//! The following environment variables are respected, with the same meaning as
+//! for `libdebuginfod`:
+//!
+//!  - `DEBUGINFOD_URLS`: A whitespace-separated list of server base URLs.
+//!  - `DEBUGINFOD_CACHE_PATH`: The cache directory.
+//!  - `DEBUGINFOD_TIMEOUT`: The number of seconds to wait for a server to start
+//!    responding. Defaults to 90.
+//!  - `DEBUGINFOD_MAXSIZE`: The maximum size of a file that will be downloaded,
+//!    in bytes. Larger files are skipped.
+
 use std::path::{Path, PathBuf};
+use std::sync::Once;
+use std::time::{Duration, SystemTime};
 
 use symsrv::{memmap2, FileContents};
 
-pub struct DebuginfodSymbolCache(DebuginfodSymbolCacheInner);
+const DEFAULT_TIMEOUT_SECONDS: u64 = 90;
+
+/// The default values for the cache configuration files, matching `libdebuginfod`.
+const DEFAULT_CACHE_CLEAN_INTERVAL_SECONDS: u64 = 24 * 60 * 60;
+const DEFAULT_MAX_UNUSED_AGE_SECONDS: u64 = 7 * 24 * 60 * 60;
+const DEFAULT_CACHE_MISS_SECONDS: u64 = 10 * 60;
+
+/// A file that can be requested from a debuginfod server.
+#[derive(Debug, Clone, Copy)]
+pub enum DebuginfodArtifact<'a> {
+    /// The separate debug file, at `/buildid/<id>/debuginfo`.
+    DebugInfo,
+    /// The executable or shared library, at `/buildid/<id>/executable`.
+    Executable,
+    /// A source file, at `/buildid/<id>/source/<absolute path>`.
+    Source(&'a str),
+}
+
+impl<'a> DebuginfodArtifact<'a> {
+    /// The path of the artifact in the server URL, relative to `/buildid/<id>`.
+    fn url_path(&self) -> String {
+        match self {
+            Self::DebugInfo => "debuginfo".to_string(),
+            Self::Executable => "executable".to_string(),
+            Self::Source(path) => format!("source{}", percent_encode_path(path)),
+        }
+    }
+
+    /// The name of the file in the cache directory for the build ID.
+    ///
+    /// Source paths are escaped like `libdebuginfod` does it, so that
+    /// `/usr/include/stdio.h` is stored as `source##usr##include##stdio.h`.
+    fn cache_file_name(&self) -> String {
+        match self {
+            Self::DebugInfo => "debuginfo".to_string(),
+            Self::Executable => "executable".to_string(),
+            Self::Source(path) => {
+                let mut name = String::from("source");
+                for c in path.chars() {
+                    match c {
+                        '/' => name.push_str("##"),
+                        '#' => name.push_str("#_"),
+                        c => name.push(c),
+                    }
+                }
+                name
+            }
+        }
+    }
+}
+
+pub struct DebuginfodSymbolCache {
+    /// The servers to query, grouped by the cache directory that their
+    /// files are stored in.
+    caches: Vec<CacheWithServers>,
+    client: reqwest::Client,
+    timeout: Duration,
+    max_size: Option<u64>,
+    verbose: bool,
+}
 
-enum DebuginfodSymbolCacheInner {
-    #[allow(unused)]
-    Official(OfficialDebuginfodSymbolCache),
-    Manual(ManualDebuginfodSymbolCache),
+struct CacheWithServers {
+    cache: DebuginfodCache,
+    server_base_urls: Vec<String>,
 }
 
 impl DebuginfodSymbolCache {
+    /// Create a client for the servers in `DEBUGINFOD_URLS` (if `use_env_servers`
+    /// is true) and for the servers in `extra_servers_and_caches`.
+    ///
+    /// Files from the `DEBUGINFOD_URLS` servers are stored in the `libdebuginfod`
+    /// cache directory. `cache_dir_if_not_installed` is used instead if there is no
+    /// sign of an elfutils debuginfod installation on this machine.
     pub fn new(
-        debuginfod_cache_dir_if_not_installed: Option<PathBuf>,
-        mut servers_and_caches: Vec<(String, PathBuf)>,
+        use_env_servers: bool,
+        cache_dir_if_not_installed: Option<PathBuf>,
+        extra_servers_and_caches: Vec<(String, PathBuf)>,
         verbose: bool,
     ) -> Self {
-        let is_debuginfod_installed = false;
-        if is_debuginfod_installed {
-            todo!()
-        } else {
-            if let (Some(cache_dir), Ok(urls)) = (
-                debuginfod_cache_dir_if_not_installed,
+        let mut servers_and_caches = Vec::new();
+        if use_env_servers {
+            if let (Ok(urls), Some(cache_dir)) = (
                 std::env::var("DEBUGINFOD_URLS"),
+                env_cache_dir(cache_dir_if_not_installed),
             ) {
-                let mut servers_from_env = Vec::new();
                 for url in urls.split_ascii_whitespace() {
-                    servers_from_env.push((url.to_string(), cache_dir.clone()));
+                    servers_and_caches.push((url.to_string(), cache_dir.clone()));
                 }
-                let extra_servers = std::mem::replace(&mut servers_and_caches, servers_from_env);
-                servers_and_caches.extend(extra_servers.into_iter());
             }
-            Self(DebuginfodSymbolCacheInner::Manual(
-                ManualDebuginfodSymbolCache {
-                    servers_and_caches,
-                    verbose,
-                },
-            ))
         }
+        servers_and_caches.extend(extra_servers_and_caches);
+
+        let mut caches: Vec<CacheWithServers> = Vec::new();
+        for (url, cache_dir) in servers_and_caches {
+            let url = url.trim_end_matches('/').to_string();
+            match caches.iter_mut().find(|c| c.cache.root == cache_dir) {
+                Some(c) => c.server_base_urls.push(url),
+                None => caches.push(CacheWithServers {
+                    cache: DebuginfodCache::new(cache_dir),
+                    server_base_urls: vec![url],
+                }),
+            }
+        }
+
+        let timeout = std::env::var("DEBUGINFOD_TIMEOUT")
+            .ok()
+            .and_then(|s| s.trim().parse().ok())
+            .unwrap_or(DEFAULT_TIMEOUT_SECONDS);
+        let timeout = Duration::from_secs(timeout);
+        let max_size = std::env::var("DEBUGINFOD_MAXSIZE")
+            .ok()
+            .and_then(|s| s.trim().parse().ok())
+            .filter(|max_size| *max_size > 0);
+        let client = reqwest::Client::builder()
+            .connect_timeout(timeout)
+            .build()
+            .unwrap_or_default();
+
+        Self {
+            caches,
+            client,
+            timeout,
+            max_size,
+            verbose,
+        }
+    }
+
+    pub async fn get_file(&self, buildid: &str, file_type: &str) -> Option<FileContents> {
+        let artifact = match file_type {
+            "debuginfo" => DebuginfodArtifact::DebugInfo,
+            "executable" => DebuginfodArtifact::Executable,
+            _ => return None,
+        };
+        self.get_artifact(buildid, artifact).await
     }
 
-    #[allow(unused)]
-    pub async fn get_file_only_cached(
+    pub async fn get_source(&self, buildid: &str, source_path: &str) -> Option<FileContents> {
+        if !source_path.starts_with('/') {
+            // The protocol only supports absolute paths.
+            return None;
+        }
+        self.get_artifact(buildid, DebuginfodArtifact::Source(source_path))
+            .await
+    }
+
+    async fn get_artifact(
         &self,
         buildid: &str,
-        file_type: &str,
-    ) -> Option<symsrv::FileContents> {
-        match &self.0 {
-            DebuginfodSymbolCacheInner::Official(official) => {
-                official.get_file_only_cached(buildid, file_type).await
+        artifact: DebuginfodArtifact<'_>,
+    ) -> Option<FileContents> {
+        if !is_valid_build_id(buildid) {
+            return None;
+        }
+
+        for CacheWithServers {
+            cache,
+            server_base_urls,
+        } in &self.caches
+        {
+            cache
+                .clean_check
+                .call_once(|| cache.clean_if_needed(self.verbose));
+
+            let cached_file_path = cache.path_for(buildid, &artifact);
+            match cache.lookup(&cached_file_path, self.verbose) {
+                CacheLookupResult::Hit(contents) => return Some(contents),
+                CacheLookupResult::NegativeHit => continue,
+                CacheLookupResult::Miss => {}
+            }
+
+            let mut all_servers_said_not_found = true;
+            for server_base_url in server_base_urls {
+                let url = format!(
+                    "{server_base_url}/buildid/{buildid}/{}",
+                    artifact.url_path()
+                );
+                match self.download(&url, &cached_file_path).await {
+                    Ok(contents) => return Some(contents),
+                    Err(DownloadError::NotFound) => {}
+                    Err(DownloadError::Other(e)) => {
+                        if self.verbose {
+                            eprintln!("Downloading {url} failed: {e}");
+                        }
+                        all_servers_said_not_found = false;
+                    }
+                }
+            }
+
+            if all_servers_said_not_found {
+                // Remember that this file doesn't exist, so that we don't ask again
+                // until the negative cache entry expires.
+                cache.store_negative_entry(&cached_file_path, self.verbose);
             }
-            DebuginfodSymbolCacheInner::Manual(manual) => {
-                manual.get_file_only_cached(buildid, file_type).await
+        }
+        None
+    }
+
+    async fn download(&self, url: &str, dest_path: &Path) -> Result<FileContents, DownloadError> {
+        if self.verbose {
+            eprintln!("Downloading {url}...");
+        }
+        let mut request = self.client.get(url);
+        if let Some(max_size) = self.max_size {
+            request = request.header("X-DEBUGINFOD-MAXSIZE", max_size.to_string());
+        }
+        let response = tokio::time::timeout(self.timeout, request.send())
+            .await
+            .map_err(|_| DownloadError::Other("Timed out".into()))??;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(DownloadError::NotFound);
+        }
+        let response = response.error_for_status()?;
+        if let (Some(max_size), Some(len)) = (self.max_size, response.content_length()) {
+            if len > max_size {
+                return Err(DownloadError::Other(
+                    format!("File size {len} exceeds DEBUGINFOD_MAXSIZE").into(),
+                ));
             }
         }
+
+        let dir = dest_path
+            .parent()
+            .ok_or_else(|| DownloadError::Other("Invalid cache path".into()))?;
+        tokio::fs::create_dir_all(dir).await?;
+
+        // Download into a temporary file and rename it once the download is complete,
+        // so that other clients never see partial files.
+        let file_name = dest_path.file_name().unwrap_or_default().to_string_lossy();
+        let temp_path = dir.join(format!(".{file_name}.tmp.{}", std::process::id()));
+        if self.verbose {
+            eprintln!("Saving bytes to {temp_path:?}.");
+        }
+        let result = self.write_response_to_file(response, &temp_path).await;
+        if let Err(e) = result {
+            let _ = tokio::fs::remove_file(&temp_path).await;
+            return Err(e);
+        }
+        tokio::fs::rename(&temp_path, dest_path).await?;
+
+        if self.verbose {
+            eprintln!("Opening file {:?}", dest_path.to_string_lossy());
+        }
+        let file = std::fs::File::open(dest_path)?;
+        Ok(FileContents::Mmap(unsafe {
+            memmap2::MmapOptions::new().map(&file)?
+        }))
     }
 
-    pub async fn get_file(&self, buildid: &str, file_type: &str) -> Option<symsrv::FileContents> {
-        match &self.0 {
-            DebuginfodSymbolCacheInner::Official(official) => {
-                official.get_file(buildid, file_type).await
+    async fn write_response_to_file(
+        &self,
+        response: reqwest::Response,
+        path: &Path,
+    ) -> Result<(), DownloadError> {
+        use futures_util::StreamExt;
+        use tokio::io::AsyncWriteExt;
+
+        let file = tokio::fs::File::create(path).await?;
+        let mut writer = tokio::io::BufWriter::new(file);
+        let mut stream = response.bytes_stream();
+        let mut total_len = 0;
+        while let Some(item) = stream.next().await {
+            let item = item?;
+            total_len += item.len() as u64;
+            if matches!(self.max_size, Some(max_size) if total_len > max_size) {
+                return Err(DownloadError::Other(
+                    "Download exceeds DEBUGINFOD_MAXSIZE".into(),
+                ));
             }
-            DebuginfodSymbolCacheInner::Manual(manual) => manual.get_file(buildid, file_type).await,
+            writer.write_all(&item).await?;
         }
+        writer.flush().await?;
+        Ok(())
     }
 }
 
-/// Uses debuginfod-find on the shell maybe, not sure
-struct OfficialDebuginfodSymbolCache;
+#[derive(Debug)]
+enum DownloadError {
+    /// The server responded with 404 Not Found.
+    NotFound,
+    Other(Box<dyn std::error::Error + Send + Sync>),
+}
 
-impl OfficialDebuginfodSymbolCache {
-    pub async fn get_file_only_cached(
-        &self,
-        _buildid: &str,
-        _file_type: &str,
-    ) -> Option<symsrv::FileContents> {
-        None // TODO
+impl From<reqwest::Error> for DownloadError {
+    fn from(e: reqwest::Error) -> Self {
+        Self::Other(e.into())
     }
+}
 
-    pub async fn get_file(&self, _buildid: &str, _file_type: &str) -> Option<symsrv::FileContents> {
-        None // TODO
+impl From<std::io::Error> for DownloadError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Other(e.into())
     }
 }
 
-/// Full reimplementation of a `debuginfod` client, used on non-Linux platforms or on Linux if debuginfod is not installed.
-///
-/// Does not use the official debuginfod's cache directory because the cache directory structure is not a stable API.
-struct ManualDebuginfodSymbolCache {
-    servers_and_caches: Vec<(String, PathBuf)>,
-    verbose: bool,
+enum CacheLookupResult {
+    Hit(FileContents),
+    /// A previous download attempt found that no server has this file.
+    NegativeHit,
+    Miss,
 }
 
-impl ManualDebuginfodSymbolCache {
-    pub async fn get_file_only_cached(
-        &self,
-        buildid: &str,
-        file_type: &str,
-    ) -> Option<symsrv::FileContents> {
-        for (_server_base_url, cache_dir) in &self.servers_and_caches {
-            let cached_file_path = cache_dir.join(buildid).join(file_type);
-            if self.verbose {
-                eprintln!("Opening file {:?}", cached_file_path.to_string_lossy());
-            }
-            if let Ok(file) = std::fs::File::open(&cached_file_path) {
-                return Some(FileContents::Mmap(unsafe {
-                    memmap2::MmapOptions::new().map(&file).ok()?
-                }));
-            }
+/// A debuginfod cache directory, with the same layout as the one used by
+/// `libdebuginfod`: Files are stored at `<root>/<build id>/<file name>`, and
+/// the cache configuration is stored in files at the root.
+struct DebuginfodCache {
+    root: PathBuf,
+    /// Checking whether the cache needs to be cleaned reads several files, so
+    /// it is only done for the first lookup.
+    clean_check: Once,
+}
+
+impl DebuginfodCache {
+    fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            clean_check: Once::new(),
         }
-        None
     }
 
-    pub async fn get_file(&self, buildid: &str, file_type: &str) -> Option<symsrv::FileContents> {
-        if let Some(f) = self.get_file_only_cached(buildid, file_type).await {
-            return Some(f);
+    fn path_for(&self, buildid: &str, artifact: &DebuginfodArtifact) -> PathBuf {
+        self.root.join(buildid).join(artifact.cache_file_name())
+    }
+
+    fn lookup(&self, path: &Path, verbose: bool) -> CacheLookupResult {
+        let metadata = match std::fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(_) => return CacheLookupResult::Miss,
+        };
+        if metadata.len() == 0 {
+            // An empty file is a negative cache entry. It expires after `cache_miss_s` seconds.
+            let cache_miss_seconds =
+                self.read_config_value("cache_miss_s", DEFAULT_CACHE_MISS_SECONDS);
+            if age(metadata.modified()) < Duration::from_secs(cache_miss_seconds) {
+                if verbose {
+                    eprintln!("Found negative cache entry {:?}", path.to_string_lossy());
+                }
+                return CacheLookupResult::NegativeHit;
+            }
+            let _ = std::fs::remove_file(path);
+            return CacheLookupResult::Miss;
+        }
+
+        if verbose {
+            eprintln!("Opening file {:?}", path.to_string_lossy());
+        }
+        let mmap = std::fs::File::open(path)
+            .and_then(|file| unsafe { memmap2::MmapOptions::new().map(&file) });
+        match mmap {
+            Ok(mmap) => CacheLookupResult::Hit(FileContents::Mmap(mmap)),
+            Err(_) => CacheLookupResult::Miss,
         }
+    }
 
-        for (server_base_url, cache_dir) in &self.servers_and_caches {
-            if let Ok(file) = self
-                .get_file_from_server(buildid, file_type, server_base_url, cache_dir)
-                .await
+    fn store_negative_entry(&self, path: &Path, verbose: bool) {
+        if verbose {
+            eprintln!("Creating negative cache entry {:?}", path.to_string_lossy());
+        }
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        if std::fs::File::create(path).is_ok() {
+            // libdebuginfod creates negative cache entries without any permissions.
+            #[cfg(unix)]
             {
-                return Some(file);
+                use std::os::unix::fs::PermissionsExt;
+                let _ = std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o000));
             }
         }
-        None
     }
 
-    async fn get_file_from_server(
-        &self,
-        buildid: &str,
-        file_type: &str,
-        server_base_url: &str,
-        cache_dir: &Path,
-    ) -> Result<FileContents, Box<dyn std::error::Error>> {
-        let url = format!("{server_base_url}/buildid/{buildid}/{file_type}");
-        if self.verbose {
-            eprintln!("Downloading {url}...");
+    /// Delete files which haven't been accessed for `max_unused_age_s` seconds, if the
+    /// last cleanup was more than `cache_clean_interval_s` seconds ago. The time of the
+    /// last cleanup is the modification time of the `cache_clean_interval_s` file.
+    fn clean_if_needed(&self, verbose: bool) {
+        let interval_file_path = self.root.join("cache_clean_interval_s");
+        let interval = self.read_config_value(
+            "cache_clean_interval_s",
+            DEFAULT_CACHE_CLEAN_INTERVAL_SECONDS,
+        );
+        let max_unused_age = Duration::from_secs(
+            self.read_config_value("max_unused_age_s", DEFAULT_MAX_UNUSED_AGE_SECONDS),
+        );
+        let last_clean = std::fs::metadata(&interval_file_path).and_then(|m| m.modified());
+        if age(last_clean) < Duration::from_secs(interval) {
+            return;
         }
-        let sym_file_response = reqwest::get(&url).await?.error_for_status()?;
-        let mut stream = sym_file_response.bytes_stream();
-        let dest_path = cache_dir.join(buildid).join(file_type);
-        if let Some(dir) = dest_path.parent() {
-            tokio::fs::create_dir_all(dir).await?;
+        if verbose {
+            eprintln!(
+                "Cleaning debuginfod cache {:?}",
+                self.root.to_string_lossy()
+            );
         }
-        if self.verbose {
-            eprintln!("Saving bytes to {dest_path:?}.");
+
+        let build_id_dirs = match std::fs::read_dir(&self.root) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        for dir_entry in build_id_dirs.flatten() {
+            if !dir_entry.file_type().map_or(false, |t| t.is_dir()) {
+                continue;
+            }
+            let dir_path = dir_entry.path();
+            let files = match std::fs::read_dir(&dir_path) {
+                Ok(files) => files,
+                Err(_) => continue,
+            };
+            for file_entry in files.flatten() {
+                let last_use = file_entry
+                    .metadata()
+                    .and_then(|m| m.accessed().or_else(|_| m.modified()));
+                if age(last_use) >= max_unused_age {
+                    let _ = std::fs::remove_file(file_entry.path());
+                }
+            }
+            // This only succeeds if the directory is empty.
+            let _ = std::fs::remove_dir(&dir_path);
         }
-        let file = tokio::fs::File::create(&dest_path).await?;
-        let mut writer = tokio::io::BufWriter::new(file);
-        use futures_util::StreamExt;
-        while let Some(item) = stream.next().await {
-            tokio::io::copy(&mut item?.as_ref(), &mut writer).await?;
+
+        // Rewrite the interval file to record the time of this cleanup.
+        let _ = std::fs::write(&interval_file_path, format!("{interval}\n"));
+    }
+
+    /// Read a number from one of the configuration files at the cache root. If the
+    /// file doesn't exist, it is created with the default value.
+    fn read_config_value(&self, name: &str, default: u64) -> u64 {
+        let path = self.root.join(name);
+        match std::fs::read_to_string(&path) {
+            Ok(s) => s.trim().parse().unwrap_or(default),
+            Err(_) => {
+                if std::fs::create_dir_all(&self.root).is_ok() {
+                    let _ = std::fs::write(&path, format!("{default}\n"));
+                }
+                default
+            }
         }
-        drop(writer);
-        if self.verbose {
-            eprintln!("Opening file {:?}", dest_path.to_string_lossy());
+    }
+}
+
+/// The time since `time`, or `Duration::MAX` if the time isn't known.
+fn age(time: std::io::Result<SystemTime>) -> Duration {
+    time.ok()
+        .and_then(|time| time.elapsed().ok())
+        .unwrap_or(Duration::MAX)
+}
+
+fn is_valid_build_id(buildid: &str) -> bool {
+    !buildid.is_empty() && buildid.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Percent-encode all bytes of `path` which can't appear as-is in a URL path.
+fn percent_encode_path(path: &str) -> String {
+    let mut s = String::with_capacity(path.len());
+    for b in path.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                s.push(b as char)
+            }
+            b => s.push_str(&format!("%{b:02X}")),
         }
-        let file = std::fs::File::open(&dest_path)?;
-        Ok(FileContents::Mmap(unsafe {
-            memmap2::MmapOptions::new().map(&file)?
-        }))
+    }
+    s
+}
+
+/// The cache directory for servers from `DEBUGINFOD_URLS`.
+///
+/// This is the directory that `libdebuginfod` uses, unless elfutils' debuginfod
+/// doesn't seem to be installed on this machine and `cache_dir_if_not_installed`
+/// is set.
+fn env_cache_dir(cache_dir_if_not_installed: Option<PathBuf>) -> Option<PathBuf> {
+    if let Some(cache_path) = std::env::var_os("DEBUGINFOD_CACHE_PATH") {
+        return Some(PathBuf::from(cache_path));
+    }
+    let default_dir = default_libdebuginfod_cache_dir();
+    let is_installed =
+        default_dir.as_deref().map_or(false, Path::exists) || is_in_path("debuginfod-find");
+    match cache_dir_if_not_installed {
+        Some(cache_dir) if !is_installed => Some(cache_dir),
+        _ => default_dir,
+    }
+}
+
+/// `$XDG_CACHE_HOME/debuginfod_client`, or `~/.cache/debuginfod_client`. If the
+/// legacy directory `~/.debuginfod_client_cache` exists, that one is used instead.
+fn default_libdebuginfod_cache_dir() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    if let Some(home) = &home {
+        let legacy_dir = home.join(".debuginfod_client_cache");
+        if legacy_dir.exists() {
+            return Some(legacy_dir);
+        }
+    }
+    match std::env::var_os("XDG_CACHE_HOME") {
+        Some(xdg_cache_home) if !xdg_cache_home.is_empty() => {
+            Some(PathBuf::from(xdg_cache_home).join("debuginfod_client"))
+        }
+        _ => Some(home?.join(".cache").join("debuginfod_client")),
+    }
+}
+
+fn is_in_path(executable_name: &str) -> bool {
+    match std::env::var_os("PATH") {
+        Some(path) => std::env::split_paths(&path).any(|dir| dir.join(executable_name).exists()),
+        None => false,
     }
 }
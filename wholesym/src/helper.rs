@@ -29,6 +29,7 @@ pub enum WholesymFileLocation {
     BreakpadSymindexFile(String),
     DebuginfodDebugFile(ElfBuildId),
     DebuginfodExecutable(ElfBuildId),
+    DebuginfodSource(ElfBuildId, String),
 }
 
 impl FileLocation for WholesymFileLocation {
@@ -74,9 +75,17 @@ impl FileLocation for WholesymFileLocation {
                         .map(|base_path| Self::LocalFile(base_path.join(source_file_path)))
                 }
             }
-            Self::DebuginfodDebugFile(_build_id) | Self::DebuginfodExecutable(_build_id) => {
-                // TODO: load source file via debuginfod
-                None
+            Self::DebuginfodDebugFile(build_id) | Self::DebuginfodExecutable(build_id) => {
+                // debuginfod servers serve the sources for a build ID, but only
+                // for absolute paths.
+                if source_file_path.starts_with('/') {
+                    Some(Self::DebuginfodSource(
+                        build_id.clone(),
+                        source_file_path.to_string(),
+                    ))
+                } else {
+                    None
+                }
             }
             _ => {
                 // We don't have local source files for debug files from symbol servers.
@@ -181,15 +190,17 @@ impl Helper {
             Some(nt_symbol_path) => Some(SymbolCache::new(nt_symbol_path, config.verbose)),
             None => None,
         };
-        let debuginfod_symbol_cache = if config.use_debuginfod {
-            Some(DebuginfodSymbolCache::new(
-                config.debuginfod_cache_dir_if_not_installed.clone(),
-                config.debuginfod_servers.clone(),
-                config.verbose,
-            ))
-        } else {
-            None
-        };
+        let debuginfod_symbol_cache =
+            if config.use_debuginfod || !config.debuginfod_servers.is_empty() {
+                Some(DebuginfodSymbolCache::new(
+                    config.use_debuginfod,
+                    config.debuginfod_cache_dir_if_not_installed.clone(),
+                    config.debuginfod_servers.clone(),
+                    config.verbose,
+                ))
+            } else {
+                None
+            };
         Self {
             win_symbol_cache,
             debuginfod_symbol_cache,
@@ -290,9 +301,16 @@ impl Helper {
                 .debuginfod_symbol_cache
                 .as_ref()
                 .unwrap()
-                .get_file(&build_id.to_string(), "debuginfo")
+                .get_file(&build_id.to_string(), "executable")
                 .await
-                .ok_or_else(|| "Debuginfod could not find debuginfo".into()),
+                .ok_or_else(|| "Debuginfod could not find executable".into()),
+            WholesymFileLocation::DebuginfodSource(build_id, path) => self
+                .debuginfod_symbol_cache
+                .as_ref()
+                .unwrap()
+                .get_source(&build_id.to_string(), &path)
+                .await
+                .ok_or_else(|| "Debuginfod could not find source file".into()),
         }
     }
 
@@ -739,6 +757,51 @@ impl<'h> FileAndPathHelper<'h> for Helper {
 
         Ok(paths)
     }
+
+    fn get_candidate_paths_for_dwo_file(
+        &self,
+        original_file_path: &WholesymFileLocation,
+        comp_dir: Option<&str>,
+        dwo_path: &str,
+    ) -> FileAndPathHelperResult<Vec<WholesymFileLocation>> {
+        let mut paths = Vec::new();
+
+        // Like for supplementary files, only look for .dwo files next to local
+        // binaries.
+        if let WholesymFileLocation::LocalFile(original_file_path) = original_file_path {
+            let dwo_path = Path::new(dwo_path);
+            if dwo_path.is_absolute() {
+                paths.push(WholesymFileLocation::LocalFile(dwo_path.to_owned()));
+            } else if let Some(comp_dir) = comp_dir {
+                paths.push(WholesymFileLocation::LocalFile(
+                    Path::new(comp_dir).join(dwo_path),
+                ));
+            }
+            if let Some(parent_dir) = original_file_path.parent() {
+                if dwo_path.is_relative() {
+                    paths.push(WholesymFileLocation::LocalFile(parent_dir.join(dwo_path)));
+                }
+                if let Some(file_name) = dwo_path.file_name() {
+                    paths.push(WholesymFileLocation::LocalFile(parent_dir.join(file_name)));
+                }
+            }
+        }
+
+        Ok(paths)
+    }
+
+    fn get_candidate_paths_for_dwp_file(
+        &self,
+        original_file_path: &WholesymFileLocation,
+    ) -> FileAndPathHelperResult<Vec<WholesymFileLocation>> {
+        let mut paths = Vec::new();
+        if let WholesymFileLocation::LocalFile(original_file_path) = original_file_path {
+            let mut dwp_path = original_file_path.clone().into_os_string();
+            dwp_path.push(".dwp");
+            paths.push(WholesymFileLocation::LocalFile(dwp_path.into()));
+        }
+        Ok(paths)
+    }
 }
 
 fn get_dyld_shared_cache_paths(arch: Option<&str>) -> Vec<WholesymFileLocation> {
@@ -54,7 +54,7 @@ impl SymbolMap {
     /// A relative address is relative to the image base address. See
     /// [`relative_address_base`](https://docs.rs/samply-symbols/latest/samply_symbols/fn.relative_address_base.html)
     /// for more information.
-    pub fn lookup_relative_address(&self, address: u32) -> Option<AddressInfo> {
+    pub fn lookup_relative_address(&self, address: u64) -> Option<AddressInfo> {
         self.0.lookup_relative_address(address)
     }
 
@@ -96,7 +96,7 @@ impl SymbolMap {
     /// Iterate over all symbols in this `SymbolMap`.
     ///
     /// This iterator yields the relative address and the name of each symbol.
-    pub fn iter_symbols(&self) -> Box<dyn Iterator<Item = (u32, Cow<'_, str>)> + '_> {
+    pub fn iter_symbols(&self) -> Box<dyn Iterator<Item = (u64, Cow<'_, str>)> + '_> {
         self.0.iter_symbols()
     }
 }
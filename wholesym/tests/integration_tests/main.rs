@@ -169,6 +169,219 @@ fn dwz_symbolication() {
     assert_eq!(frames[1].function.as_ref().unwrap(), "gobble_file");
 }
 
+/// A minimal HTTP server which stands in for a debuginfod server. It serves the
+/// given files and records the paths of all requests.
+struct StandInDebuginfodServer {
+    url: String,
+    requested_paths: std::sync::Arc<std::sync::Mutex<Vec<String>>>,
+}
+
+impl StandInDebuginfodServer {
+    fn start(files: std::collections::HashMap<String, Vec<u8>>) -> Self {
+        use std::io::{BufRead, BufReader, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requested_paths = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let requested_paths_clone = requested_paths.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                // Skip the headers.
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                let path = request_line
+                    .split(' ')
+                    .nth(1)
+                    .unwrap_or_default()
+                    .to_string();
+                let (status, body) = match files.get(&path) {
+                    Some(body) => ("200 OK", body.as_slice()),
+                    None => ("404 Not Found", &[][..]),
+                };
+                requested_paths_clone.lock().unwrap().push(path);
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                );
+                let _ = stream.write_all(body);
+            }
+        });
+        Self {
+            url,
+            requested_paths,
+        }
+    }
+
+    fn request_count(&self) -> usize {
+        self.requested_paths.lock().unwrap().len()
+    }
+}
+
+#[test]
+fn debuginfod_stand_in_server() {
+    let build_id = "6c974ebe5232ee469d6b7847a670b2a956f8aede";
+    let missing_build_id = "0123456789abcdef0123456789abcdef01234567";
+    let source_path = "/home/njn/moz/fix-stacks/tests/example.c";
+    let binary_path = fixtures_dir().join("other").join("example-linux");
+
+    let mut files = std::collections::HashMap::new();
+    files.insert(
+        format!("/buildid/{build_id}/debuginfo"),
+        std::fs::read(&binary_path).unwrap(),
+    );
+    files.insert(
+        format!("/buildid/{build_id}/source{source_path}"),
+        b"int main() { return 0; }\n".to_vec(),
+    );
+    let server = StandInDebuginfodServer::start(files);
+
+    let cache_dir =
+        std::env::temp_dir().join(format!("wholesym-debuginfod-test-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&cache_dir);
+    let config =
+        wholesym::SymbolManagerConfig::default().extra_debuginfod_server(&server.url, &cache_dir);
+
+    let mut info = futures::executor::block_on(
+        wholesym::SymbolManager::library_info_for_binary_at_path(&binary_path, None),
+    )
+    .unwrap();
+    // Make sure that the debug info has to come from the server.
+    info.path = None;
+    let debug_name = info.debug_name.clone().unwrap();
+    let debug_id = info.debug_id.unwrap();
+    let missing_info = LibraryInfo {
+        debug_name: Some("missing".into()),
+        debug_id: Some(DebugId::from_breakpad("0123456789ABCDEF0123456789ABCDEF0").unwrap()),
+        code_id: CodeId::from_str(missing_build_id).ok(),
+        ..Default::default()
+    };
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    let mut symbol_manager = wholesym::SymbolManager::with_config(config.clone());
+    symbol_manager.add_known_library(info.clone());
+    symbol_manager.add_known_library(missing_info.clone());
+    runtime.block_on(async {
+        let symbol_map = symbol_manager
+            .load_symbol_map(&debug_name, debug_id)
+            .await
+            .unwrap();
+        let sym = symbol_map.lookup_relative_address(0x1156).unwrap();
+        assert_eq!(&sym.symbol.name, "main");
+        assert!(matches!(sym.frames, FramesLookupResult::Available(_)));
+
+        // The source file is requested from the server that had the debug info.
+        let response = symbol_manager
+            .query_json_api(
+                "/source/v1",
+                &format!(
+                    r#"{{"debugName":"{debug_name}","debugId":"{}","moduleOffset":"0x1156","file":"{source_path}"}}"#,
+                    debug_id.breakpad()
+                ),
+            )
+            .await;
+        assert!(response.contains(r#""source":"int main() { return 0; }\n""#), "{response}");
+
+        // Nobody has the file for the missing build ID.
+        assert!(symbol_manager
+            .load_symbol_map("missing", missing_info.debug_id.unwrap())
+            .await
+            .is_err());
+    });
+
+    // The downloaded files are stored in the libdebuginfod cache layout, and a
+    // negative cache entry is stored for the missing file.
+    let build_id_dir = cache_dir.join(build_id);
+    assert!(build_id_dir.join("debuginfo").exists());
+    assert!(build_id_dir
+        .join("source##home##njn##moz##fix-stacks##tests##example.c")
+        .exists());
+    let negative_entry = cache_dir.join(missing_build_id).join("debuginfo");
+    assert_eq!(std::fs::metadata(negative_entry).unwrap().len(), 0);
+    assert!(cache_dir.join("cache_clean_interval_s").exists());
+    assert!(cache_dir.join("max_unused_age_s").exists());
+
+    // A new symbol manager with the same cache finds everything in the cache,
+    // including the negative entry, and doesn't contact the server again.
+    let request_count = server.request_count();
+    let mut symbol_manager = wholesym::SymbolManager::with_config(config.clone());
+    symbol_manager.add_known_library(info.clone());
+    symbol_manager.add_known_library(missing_info.clone());
+    runtime.block_on(async {
+        assert!(symbol_manager
+            .load_symbol_map(&debug_name, debug_id)
+            .await
+            .is_ok());
+        assert!(symbol_manager
+            .load_symbol_map("missing", missing_info.debug_id.unwrap())
+            .await
+            .is_err());
+    });
+    assert_eq!(server.request_count(), request_count);
+
+    // DEBUGINFOD_MAXSIZE and DEBUGINFOD_TIMEOUT are read when the symbol manager
+    // is created. They are tested here rather than in their own tests so that
+    // they can't affect other tests which run in parallel.
+    let is_downloading = |build_id_dir: &std::path::Path| match std::fs::read_dir(build_id_dir) {
+        Ok(entries) => entries
+            .flatten()
+            .any(|entry| entry.file_name().to_string_lossy().contains(".tmp.")),
+        Err(_) => false,
+    };
+
+    // A file which is larger than DEBUGINFOD_MAXSIZE is not downloaded.
+    std::fs::remove_dir_all(&cache_dir).unwrap();
+    let file_size = std::fs::metadata(&binary_path).unwrap().len();
+    std::env::set_var("DEBUGINFOD_MAXSIZE", (file_size - 1).to_string());
+    let mut symbol_manager = wholesym::SymbolManager::with_config(config);
+    std::env::remove_var("DEBUGINFOD_MAXSIZE");
+    symbol_manager.add_known_library(info.clone());
+    let request_count = server.request_count();
+    runtime.block_on(async {
+        assert!(symbol_manager
+            .load_symbol_map(&debug_name, debug_id)
+            .await
+            .is_err());
+    });
+    assert!(server.request_count() > request_count);
+    // Nothing is cached, not even a negative entry, and no partial file is left behind.
+    assert!(!build_id_dir.join("debuginfo").exists());
+    assert!(!is_downloading(&build_id_dir));
+
+    // A server which doesn't respond within DEBUGINFOD_TIMEOUT seconds is skipped.
+    // The listener accepts connections but never answers.
+    let unresponsive_listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let unresponsive_url = format!("http://{}", unresponsive_listener.local_addr().unwrap());
+    let config = wholesym::SymbolManagerConfig::default()
+        .extra_debuginfod_server(&unresponsive_url, &cache_dir);
+    std::env::set_var("DEBUGINFOD_TIMEOUT", "1");
+    let mut symbol_manager = wholesym::SymbolManager::with_config(config);
+    std::env::remove_var("DEBUGINFOD_TIMEOUT");
+    symbol_manager.add_known_library(info);
+    let start = std::time::Instant::now();
+    runtime.block_on(async {
+        assert!(symbol_manager
+            .load_symbol_map(&debug_name, debug_id)
+            .await
+            .is_err());
+    });
+    assert!(start.elapsed() < std::time::Duration::from_secs(30));
+    assert!(!build_id_dir.join("debuginfo").exists());
+    assert!(!is_downloading(&build_id_dir));
+
+    let _ = std::fs::remove_dir_all(&cache_dir);
+}
+
 // This test only works on macOS 13.0.1.
 #[ignore]
 #[test]